@@ -0,0 +1,431 @@
+//! adaptive large neighborhood search: destroy part of the tour, repair it, repeat
+//!
+//! every iteration picks a destroy and a repair operator by roulette over their weights, removes
+//! some nodes and inserts them back. the result replaces the current tour like in simulated
+//! annealing, starting where 5% longer is accepted half the time and cooling 1000-fold over the
+//! run. every `segment` iterations the weights move towards how well the operators did in it:
+//! 33 points for a new best tour, 9 for improving the current one, 13 for an accepted longer one.
+//! destroy and repair are traits, so other operators plug into `tsp_alns` as they are
+
+use std::str::FromStr;
+
+use rand::rngs::StdRng;
+use rand::seq::{index, SliceRandom};
+use rand::Rng;
+
+use crate::cancel;
+use crate::insertion::cheapest_insertions;
+use crate::metric::Metric;
+use crate::Node;
+
+/// removes nodes from a tour
+pub trait Destroy: Send + Sync {
+    fn name(&self) -> &str;
+
+    /// takes `count` nodes out of the tour and returns them
+    fn destroy(
+        &self,
+        tour: &mut Vec<Node>,
+        count: usize,
+        metric: &dyn Metric,
+        rng: &mut StdRng,
+    ) -> Vec<Node>;
+}
+
+/// puts removed nodes back into a tour
+pub trait Repair: Send + Sync {
+    fn name(&self) -> &str;
+
+    /// inserts every node of `removed` into the tour
+    fn repair(
+        &self,
+        tour: &mut Vec<Node>,
+        removed: Vec<Node>,
+        metric: &dyn Metric,
+        rng: &mut StdRng,
+    );
+}
+
+/// takes the nodes at `positions` out of the tour
+fn remove_positions(tour: &mut Vec<Node>, positions: &[usize]) -> Vec<Node> {
+    let mut marked = vec![false; tour.len()];
+    for &p in positions {
+        marked[p] = true;
+    }
+    let removed = positions.iter().map(|&p| tour[p].clone()).collect();
+    let mut position = 0;
+    tour.retain(|_| {
+        position += 1;
+        !marked[position - 1]
+    });
+    removed
+}
+
+/// removes random nodes
+pub struct RandomRemoval;
+
+impl Destroy for RandomRemoval {
+    fn name(&self) -> &str {
+        "random removal"
+    }
+
+    fn destroy(
+        &self,
+        tour: &mut Vec<Node>,
+        count: usize,
+        _metric: &dyn Metric,
+        rng: &mut StdRng,
+    ) -> Vec<Node> {
+        let positions = index::sample(rng, tour.len(), count.min(tour.len())).into_vec();
+        remove_positions(tour, &positions)
+    }
+}
+
+/// removes the nodes that cost the most detour where they are, with some randomness so it
+/// doesn't pick the same ones every time
+pub struct WorstRemoval;
+
+impl Destroy for WorstRemoval {
+    fn name(&self) -> &str {
+        "worst removal"
+    }
+
+    fn destroy(
+        &self,
+        tour: &mut Vec<Node>,
+        count: usize,
+        metric: &dyn Metric,
+        rng: &mut StdRng,
+    ) -> Vec<Node> {
+        let n = tour.len();
+        let detour = |p: usize| {
+            let (prev, next) = (&tour[(p + n - 1) % n], &tour[(p + 1) % n]);
+            metric.distance(prev, &tour[p]) + metric.distance(&tour[p], next)
+                - metric.distance(prev, next)
+        };
+        let mut ranked: Vec<(f64, usize)> = (0..n).map(|p| (detour(p), p)).collect();
+        ranked.sort_by(|a, b| b.0.total_cmp(&a.0));
+        let mut positions = Vec::with_capacity(count);
+        while positions.len() < count.min(n) {
+            // y^3 leans strongly towards the front of the ranking
+            let pick = (rng.gen::<f64>().powi(3) * ranked.len() as f64) as usize;
+            positions.push(ranked.remove(pick.min(ranked.len() - 1)).1);
+        }
+        remove_positions(tour, &positions)
+    }
+}
+
+/// removes a run of consecutive nodes
+pub struct SegmentRemoval;
+
+impl Destroy for SegmentRemoval {
+    fn name(&self) -> &str {
+        "segment removal"
+    }
+
+    fn destroy(
+        &self,
+        tour: &mut Vec<Node>,
+        count: usize,
+        _metric: &dyn Metric,
+        rng: &mut StdRng,
+    ) -> Vec<Node> {
+        let n = tour.len();
+        let start = rng.gen_range(0..n);
+        let positions: Vec<usize> = (0..count.min(n)).map(|k| (start + k) % n).collect();
+        remove_positions(tour, &positions)
+    }
+}
+
+/// inserts the node that's cheapest to insert at its cheapest place, one after another
+pub struct GreedyInsertion;
+
+impl Repair for GreedyInsertion {
+    fn name(&self) -> &str {
+        "greedy insertion"
+    }
+
+    fn repair(
+        &self,
+        tour: &mut Vec<Node>,
+        mut removed: Vec<Node>,
+        metric: &dyn Metric,
+        rng: &mut StdRng,
+    ) {
+        // ties go to a random node
+        removed.shuffle(rng);
+        while !removed.is_empty() {
+            let (k, (_, position)) = removed
+                .iter()
+                .map(|node| cheapest_insertions(tour, node, metric)[0])
+                .enumerate()
+                .min_by(|a, b| a.1 .0.total_cmp(&b.1 .0))
+                .expect("there are nodes left");
+            tour.insert(position, removed.swap_remove(k));
+        }
+    }
+}
+
+/// regret-2 insertion: inserts the node that loses the most by not getting its cheapest place,
+/// the difference to its second cheapest, so awkward nodes go first while they still can
+pub struct RegretInsertion;
+
+impl Repair for RegretInsertion {
+    fn name(&self) -> &str {
+        "regret insertion"
+    }
+
+    fn repair(
+        &self,
+        tour: &mut Vec<Node>,
+        mut removed: Vec<Node>,
+        metric: &dyn Metric,
+        rng: &mut StdRng,
+    ) {
+        removed.shuffle(rng);
+        while !removed.is_empty() {
+            let (k, position) = removed
+                .iter()
+                .map(|node| {
+                    let [best, second] = cheapest_insertions(tour, node, metric);
+                    (second.0 - best.0, best.1)
+                })
+                .enumerate()
+                .max_by(|a, b| a.1 .0.total_cmp(&b.1 .0))
+                .map(|(k, (_, position))| (k, position))
+                .expect("there are nodes left");
+            tour.insert(position, removed.swap_remove(k));
+        }
+    }
+}
+
+/// random, worst and segment removal
+pub fn destroy_operators() -> Vec<Box<dyn Destroy>> {
+    vec![
+        Box::new(RandomRemoval),
+        Box::new(WorstRemoval),
+        Box::new(SegmentRemoval),
+    ]
+}
+
+/// greedy and regret-2 insertion
+pub fn repair_operators() -> Vec<Box<dyn Repair>> {
+    vec![Box::new(GreedyInsertion), Box::new(RegretInsertion)]
+}
+
+/// everything `tsp_alns` can be tuned with
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AlnsParams {
+    pub iterations: usize,
+    /// most of the tour one iteration removes, at least 2 nodes
+    pub removal: f64,
+    /// how far the weights move towards the last segment's scores
+    pub reaction: f64,
+    /// iterations between weight updates
+    pub segment: usize,
+}
+
+impl Default for AlnsParams {
+    fn default() -> Self {
+        AlnsParams {
+            iterations: 2000,
+            removal: 0.2,
+            reaction: 0.1,
+            segment: 100,
+        }
+    }
+}
+
+/// `key=value` pairs separated by `:` over the defaults, e.g. `iterations=5000:removal=0.3`
+impl FromStr for AlnsParams {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        fn value<T: FromStr>(key: &str, value: &str) -> Result<T, String>
+        where
+            T::Err: std::fmt::Display,
+        {
+            value
+                .parse()
+                .map_err(|err| format!("invalid {} '{}': {}", key, value, err))
+        }
+
+        let mut params = AlnsParams::default();
+        for pair in s.split(':').filter(|p| !p.is_empty()) {
+            let Some((key, v)) = pair.split_once('=') else {
+                return Err(format!("expected key=value, got '{}'", pair));
+            };
+            match key {
+                "iterations" => params.iterations = value(key, v)?,
+                "removal" => params.removal = value(key, v)?,
+                "reaction" => params.reaction = value(key, v)?,
+                "segment" => params.segment = value(key, v)?,
+                _ => {
+                    return Err(format!(
+                        "unknown parameter '{}', try iterations, removal, reaction or segment",
+                        key
+                    ))
+                }
+            }
+        }
+        if !(0.0..=1.0).contains(&params.removal) || !(0.0..=1.0).contains(&params.reaction) {
+            return Err("removal and reaction have to be between 0 and 1".to_string());
+        }
+        if params.segment == 0 {
+            return Err("segments need at least one iteration".to_string());
+        }
+        Ok(params)
+    }
+}
+
+/// points for an iteration whose tour was...
+const NEW_BEST: f64 = 33.0;
+/// ...shorter than the current one
+const IMPROVED: f64 = 9.0;
+/// ...longer but accepted
+const ACCEPTED: f64 = 13.0;
+
+/// an adaptively weighted set of operators
+struct Roulette {
+    weights: Vec<f64>,
+    scores: Vec<f64>,
+    uses: Vec<usize>,
+}
+
+impl Roulette {
+    fn new(count: usize) -> Self {
+        Roulette {
+            weights: vec![1.0; count],
+            scores: vec![0.0; count],
+            uses: vec![0; count],
+        }
+    }
+
+    fn pick(&mut self, rng: &mut StdRng) -> usize {
+        let mut ball = rng.gen::<f64>() * self.weights.iter().sum::<f64>();
+        let mut picked = self.weights.len() - 1;
+        for (k, &weight) in self.weights.iter().enumerate() {
+            if ball < weight {
+                picked = k;
+                break;
+            }
+            ball -= weight;
+        }
+        self.uses[picked] += 1;
+        picked
+    }
+
+    /// moves the weights towards the mean score per use since the last update
+    fn update(&mut self, reaction: f64) {
+        for k in 0..self.weights.len() {
+            if self.uses[k] > 0 {
+                let score = self.scores[k] / self.uses[k] as f64;
+                self.weights[k] = (1.0 - reaction) * self.weights[k] + reaction * score;
+            }
+        }
+        self.scores.fill(0.0);
+        self.uses.fill(0);
+    }
+}
+
+/// the shortest tour the search found from the given one, destroying and repairing with
+/// whichever of the operators works best so far
+pub fn tsp_alns(
+    nodes: &[Node],
+    metric: &dyn Metric,
+    destroy: &[Box<dyn Destroy>],
+    repair: &[Box<dyn Repair>],
+    params: &AlnsParams,
+    rng: &mut StdRng,
+) -> Vec<Node> {
+    let n = nodes.len();
+    if n < 4 || destroy.is_empty() || repair.is_empty() {
+        return nodes.to_vec();
+    }
+    let most = ((params.removal * n as f64) as usize).clamp(2, n - 2);
+
+    let mut current = (metric.tour_length(nodes), nodes.to_vec());
+    let mut best = current.clone();
+    let mut temperature = -0.05 * current.0 / 0.5f64.ln();
+    let cooling = 1000f64.powf(-1.0 / params.iterations.max(1) as f64);
+    let (mut destroyers, mut repairers) =
+        (Roulette::new(destroy.len()), Roulette::new(repair.len()));
+
+    for iteration in 0..params.iterations {
+        if cancel::is_cancelled() {
+            break;
+        }
+        let (d, r) = (destroyers.pick(rng), repairers.pick(rng));
+        let mut tour = current.1.clone();
+        let count = rng.gen_range(2..=most);
+        let removed = destroy[d].destroy(&mut tour, count, metric, rng);
+        repair[r].repair(&mut tour, removed, metric, rng);
+        let length = metric.tour_length(&tour);
+
+        let score = if length < best.0 {
+            best = (length, tour.clone());
+            current = (length, tour);
+            NEW_BEST
+        } else if length < current.0 {
+            current = (length, tour);
+            IMPROVED
+        } else if rng.gen::<f64>() < ((current.0 - length) / temperature).exp() {
+            current = (length, tour);
+            ACCEPTED
+        } else {
+            0.0
+        };
+        destroyers.scores[d] += score;
+        repairers.scores[r] += score;
+        temperature *= cooling;
+        if (iteration + 1) % params.segment == 0 {
+            destroyers.update(params.reaction);
+            repairers.update(params.reaction);
+        }
+    }
+    best.1
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::alns::*;
+    use crate::local_search::{local_search, TwoOpt};
+    use crate::metric::Euclidean;
+    use crate::random_nodes;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_operators_keep_every_node() {
+        let mut rng = StdRng::seed_from_u64(31);
+        let nodes = random_nodes(30, &mut rng);
+        for destroy in destroy_operators() {
+            for repair in repair_operators() {
+                let mut tour = nodes.clone();
+                let removed = destroy.destroy(&mut tour, 8, &Euclidean, &mut rng);
+                assert_eq!((tour.len(), removed.len()), (22, 8));
+                repair.repair(&mut tour, removed, &Euclidean, &mut rng);
+                let ids: Vec<usize> = tour.iter().map(|n| n.id).collect();
+                assert!(crate::tour::Tour::from_ids(&ids, &nodes).is_ok());
+            }
+        }
+        assert!("removal=2".parse::<AlnsParams>().is_err());
+    }
+
+    #[test]
+    fn test_beats_two_opt() {
+        let nodes = random_nodes(80, &mut StdRng::seed_from_u64(32));
+        let mut two = nodes.clone();
+        local_search(&mut two, &TwoOpt, &Euclidean);
+        let tour = tsp_alns(
+            &two,
+            &Euclidean,
+            &destroy_operators(),
+            &repair_operators(),
+            &AlnsParams::default(),
+            &mut StdRng::seed_from_u64(33),
+        );
+        let ids: Vec<usize> = tour.iter().map(|n| n.id).collect();
+        assert!(crate::tour::Tour::from_ids(&ids, &nodes).is_ok());
+        assert!(Euclidean.tour_length(&tour) < Euclidean.tour_length(&two));
+    }
+}
@@ -0,0 +1,679 @@
+//! parameters of simulated annealing, presets for different instance sizes
+//! and the rules deciding which moves it accepts
+//!
+//! the right amount of work and the right temperatures depend a lot on the
+//! size of the instance, constants tuned for 13 nodes are useless for 500
+
+use std::str::FromStr;
+
+use rand::{Rng, RngCore};
+
+/// decides whether simulated annealing takes a move, one instance per run
+///
+/// `current` and `candidate` are the tour lengths before and after the move,
+/// `temperature` is in the units of the lengths and cools down over the run
+pub trait Acceptance {
+    /// called once with the length of the starting tour
+    fn start(&mut self, _length: f64) {}
+
+    fn accept(
+        &mut self,
+        current: f64,
+        candidate: f64,
+        temperature: f64,
+        rng: &mut dyn RngCore,
+    ) -> bool;
+}
+
+/// takes longer tours with probability exp(-delta / t), shorter ones always
+pub struct Metropolis;
+
+impl Acceptance for Metropolis {
+    fn accept(
+        &mut self,
+        current: f64,
+        candidate: f64,
+        temperature: f64,
+        rng: &mut dyn RngCore,
+    ) -> bool {
+        let delta = candidate - current;
+        let probability = if delta > 0.0 {
+            f64::exp(-(delta / temperature))
+        } else {
+            1.0
+        };
+        // drawn for every move, improving or not, so runs stay what they've always been
+        rng.gen::<f64>() <= probability
+    }
+}
+
+/// logistic probability 1 / (1 + exp(delta / t)), even improving moves are only likely.
+/// a coin flip for moves that don't change the length
+pub struct Glauber;
+
+impl Acceptance for Glauber {
+    fn accept(
+        &mut self,
+        current: f64,
+        candidate: f64,
+        temperature: f64,
+        rng: &mut dyn RngCore,
+    ) -> bool {
+        let probability = 1.0 / (1.0 + f64::exp((candidate - current) / temperature));
+        rng.gen::<f64>() < probability
+    }
+}
+
+/// threshold accepting: deterministic, takes every move that's less than t longer
+pub struct Threshold;
+
+impl Acceptance for Threshold {
+    fn accept(
+        &mut self,
+        current: f64,
+        candidate: f64,
+        temperature: f64,
+        _rng: &mut dyn RngCore,
+    ) -> bool {
+        candidate - current < temperature
+    }
+}
+
+/// great deluge: takes every move no longer than a water level that only ever falls,
+/// down to the current tour plus t whenever that is lower. unlike `Threshold` the bar
+/// doesn't rise again with a worse tour
+pub struct GreatDeluge {
+    level: f64,
+}
+
+impl GreatDeluge {
+    pub fn new() -> Self {
+        GreatDeluge {
+            level: f64::INFINITY,
+        }
+    }
+}
+
+impl Default for GreatDeluge {
+    fn default() -> Self {
+        GreatDeluge::new()
+    }
+}
+
+impl Acceptance for GreatDeluge {
+    fn start(&mut self, _length: f64) {
+        self.level = f64::INFINITY;
+    }
+
+    fn accept(
+        &mut self,
+        current: f64,
+        candidate: f64,
+        temperature: f64,
+        _rng: &mut dyn RngCore,
+    ) -> bool {
+        self.level = self.level.min(current + temperature);
+        candidate <= self.level
+    }
+}
+
+/// late acceptance hill climbing: takes moves no longer than the current tour
+/// or the one `history.len()` moves ago, ignores the temperature
+pub struct LateAcceptance {
+    history: Vec<f64>,
+    moves: usize,
+}
+
+impl LateAcceptance {
+    pub fn new(history: usize) -> Self {
+        LateAcceptance {
+            history: vec![f64::INFINITY; history.max(1)],
+            moves: 0,
+        }
+    }
+}
+
+impl Acceptance for LateAcceptance {
+    fn start(&mut self, length: f64) {
+        self.history.fill(length);
+        self.moves = 0;
+    }
+
+    fn accept(
+        &mut self,
+        current: f64,
+        candidate: f64,
+        _temperature: f64,
+        _rng: &mut dyn RngCore,
+    ) -> bool {
+        let slot = self.moves % self.history.len();
+        let accepted = candidate <= current || candidate <= self.history[slot];
+        // the history remembers the tour each move started from
+        self.history[slot] = if accepted { candidate } else { current };
+        self.moves += 1;
+        accepted
+    }
+}
+
+/// the built in acceptance rules, picked per stage with `acceptance=`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AcceptanceRule {
+    Metropolis,
+    Glauber,
+    Threshold,
+    GreatDeluge,
+    LateAcceptance { history: usize },
+}
+
+/// late acceptance history length unless `history=` says otherwise
+pub const DEFAULT_HISTORY: usize = 1000;
+
+impl AcceptanceRule {
+    pub fn build(self) -> Box<dyn Acceptance> {
+        match self {
+            AcceptanceRule::Metropolis => Box::new(Metropolis),
+            AcceptanceRule::Glauber => Box::new(Glauber),
+            AcceptanceRule::Threshold => Box::new(Threshold),
+            AcceptanceRule::GreatDeluge => Box::new(GreatDeluge::new()),
+            AcceptanceRule::LateAcceptance { history } => Box::new(LateAcceptance::new(history)),
+        }
+    }
+}
+
+impl FromStr for AcceptanceRule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "metropolis" => Ok(AcceptanceRule::Metropolis),
+            "glauber" | "logistic" => Ok(AcceptanceRule::Glauber),
+            "threshold" => Ok(AcceptanceRule::Threshold),
+            "deluge" | "great-deluge" => Ok(AcceptanceRule::GreatDeluge),
+            "lahc" | "late-acceptance" => Ok(AcceptanceRule::LateAcceptance {
+                history: DEFAULT_HISTORY,
+            }),
+            _ => Err("try metropolis, glauber, threshold, deluge or lahc".to_string()),
+        }
+    }
+}
+
+/// the moves simulated annealing tries, picked per stage with `moves=`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MoveType {
+    /// exchange two nodes
+    Swap,
+    /// reverse the segment between two nodes, a random 2-opt move
+    Reversal,
+    /// take a node out and put it back somewhere else
+    Insertion,
+    /// reversals `reversal_share` of the time, swaps otherwise
+    Mixed,
+}
+
+impl FromStr for MoveType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "swap" => Ok(MoveType::Swap),
+            "reversal" | "2opt" => Ok(MoveType::Reversal),
+            "insertion" => Ok(MoveType::Insertion),
+            "mixed" => Ok(MoveType::Mixed),
+            _ => Err("try swap, reversal, insertion or mixed".to_string()),
+        }
+    }
+}
+
+/// how the temperature falls over the run, picked per stage with `schedule=`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Schedule {
+    /// multiplied by `cooling` after every move
+    Geometric,
+    /// falls in a straight line to where geometric cooling would end
+    Linear,
+    /// t0 * ln 2 / ln(k + 2) after k moves, falls fast at first and then hardly at all
+    Logarithmic,
+    /// raised or lowered after every move, so the share of moves taken follows a target
+    /// falling geometrically from a half at the start to one in ten thousand
+    Adaptive,
+}
+
+impl FromStr for Schedule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "geometric" => Ok(Schedule::Geometric),
+            "linear" => Ok(Schedule::Linear),
+            "logarithmic" | "log" => Ok(Schedule::Logarithmic),
+            "adaptive" => Ok(Schedule::Adaptive),
+            _ => Err("try geometric, linear, logarithmic or adaptive".to_string()),
+        }
+    }
+}
+
+/// the share of moves `Schedule::Adaptive` aims for at the end, relative to the start.
+/// with the presets, ten times that ended a few percent longer at 500 nodes
+const ADAPTIVE_END: f64 = 0.0002;
+
+/// how fast `Schedule::Adaptive` follows its target, the log of the change per move
+pub const ADAPTIVE_GAIN: f64 = 0.01;
+
+/// the temperature of a run, following its schedule
+#[derive(Debug, Clone)]
+pub struct Temperature {
+    schedule: Schedule,
+    start: f64,
+    /// where geometric cooling over all the iterations ends
+    end: f64,
+    cooling: f64,
+    /// moves the schedule runs over, and the ones made so far
+    length: u32,
+    moves: u32,
+    /// moves per temperature level, and the moves when the level last changed
+    epoch: u32,
+    level: u32,
+    /// log of the change `Schedule::Adaptive` makes at the end of the epoch
+    pending: f64,
+    current: f64,
+}
+
+impl Temperature {
+    pub fn new(params: &SaParams, start: f64) -> Self {
+        Temperature::over(params, start, params.iterations)
+    }
+
+    /// the schedule from `start` down to its end temperature in `moves` moves
+    /// instead of all of the iterations, for restarts
+    pub fn over(params: &SaParams, start: f64, moves: u32) -> Self {
+        let end = start * params.cooling.powf(params.iterations as f64);
+        Temperature {
+            schedule: params.schedule,
+            start,
+            end,
+            cooling: if moves == params.iterations {
+                params.cooling
+            } else {
+                (end / start).powf(1.0 / moves.max(1) as f64)
+            },
+            length: moves.max(1),
+            moves: 0,
+            epoch: params.epoch_length.max(1),
+            level: 0,
+            pending: 0.0,
+            current: start,
+        }
+    }
+
+    pub fn get(&self) -> f64 {
+        self.current
+    }
+
+    /// share of the schedule's moves still to come
+    pub fn remaining(&self) -> f64 {
+        1.0 - (self.moves as f64 / self.length as f64).min(1.0)
+    }
+
+    /// starts the schedule over from `temperature`, towards the same end in the moves left
+    pub fn reheat(&mut self, temperature: f64) {
+        let left = self.length.saturating_sub(self.moves).max(1);
+        self.cooling = (self.end / temperature).powf(1.0 / left as f64);
+        self.start = temperature;
+        self.length = left;
+        self.moves = 0;
+        self.level = 0;
+        self.pending = 0.0;
+        self.current = temperature;
+    }
+
+    /// after every move, whether or not it was taken. the temperature only changes
+    /// at the end of an epoch, by as much as it would have over its moves
+    pub fn update(&mut self, accepted: bool) {
+        self.moves += 1;
+        let progress = (self.moves as f64 / self.length as f64).min(1.0);
+        if let Schedule::Adaptive = self.schedule {
+            // a little hotter after every rejected move, a lot colder after an accepted one,
+            // which evens out when the share of moves taken is the target
+            let target = 0.5 * ADAPTIVE_END.powf(progress);
+            self.pending += ADAPTIVE_GAIN * (target - accepted as u8 as f64);
+        }
+        if !self.moves.is_multiple_of(self.epoch) && self.moves < self.length {
+            return;
+        }
+        let steps = self.moves - self.level;
+        self.level = self.moves;
+        match self.schedule {
+            Schedule::Geometric => self.current *= self.cooling.powi(steps as i32),
+            Schedule::Linear => self.current = self.start + (self.end - self.start) * progress,
+            Schedule::Logarithmic => {
+                self.current = self.start * std::f64::consts::LN_2 / ((self.moves + 2) as f64).ln();
+            }
+            Schedule::Adaptive => {
+                self.current *= self.pending.exp();
+                self.pending = 0.0;
+            }
+        }
+    }
+}
+
+/// everything `tsp_simulated_annealing` can be tuned with
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SaParams {
+    pub iterations: u32,
+    /// start temperature in units of the mean edge length of the starting tour,
+    /// so the same preset works for any coordinate scale
+    pub start_temperature: f64,
+    /// samples moves from the starting tour and starts where a longer one is taken with
+    /// this probability instead of at `start_temperature`
+    pub auto_temperature: Option<f64>,
+    /// the temperature is multiplied by this after every iteration, other schedules
+    /// end where that would
+    pub cooling: f64,
+    pub schedule: Schedule,
+    /// moves at every temperature before it changes
+    pub epoch_length: u32,
+    pub moves: MoveType,
+    /// share of `MoveType::Mixed` moves that reverse a segment of the tour, the rest swap two nodes
+    pub reversal_share: f64,
+    pub acceptance: AcceptanceRule,
+    /// reheats whenever less than this share of the last `REHEAT_WINDOW` moves were taken,
+    /// 0 never does
+    pub reheat: f64,
+    /// runs after the first, all of them share the iterations and start from the best
+    /// tour so far
+    pub restarts: u32,
+}
+
+/// `temperature=auto` aims for longer moves being taken this often at the start
+pub const AUTO_ACCEPTANCE: f64 = 0.8;
+
+/// moves sampled to find the start temperature
+pub const AUTO_SAMPLES: usize = 1000;
+
+/// moves over which the share of taken ones decides about reheating
+pub const REHEAT_WINDOW: u32 = 10_000;
+
+/// presets by the largest instance they are meant for
+///
+/// picked from sweeps over uniform random instances of 13, 50, 200 and 500 nodes,
+/// every preset cools down to 1/1000 of its start temperature over its iterations.
+/// segment reversals untangle crossing edges, which matters more the bigger the tour
+const PRESETS: [(usize, SaParams); 4] = [
+    (
+        15,
+        SaParams {
+            iterations: 10_000,
+            start_temperature: 0.3,
+            auto_temperature: None,
+            cooling: 0.999_309,
+            schedule: Schedule::Geometric,
+            epoch_length: 1,
+            moves: MoveType::Mixed,
+            reversal_share: 0.5,
+            acceptance: AcceptanceRule::Metropolis,
+            reheat: 0.0,
+            restarts: 0,
+        },
+    ),
+    (
+        100,
+        SaParams {
+            iterations: 100_000,
+            start_temperature: 0.3,
+            auto_temperature: None,
+            cooling: 0.999_930_9,
+            schedule: Schedule::Geometric,
+            epoch_length: 1,
+            moves: MoveType::Mixed,
+            reversal_share: 0.9,
+            acceptance: AcceptanceRule::Metropolis,
+            reheat: 0.0,
+            restarts: 0,
+        },
+    ),
+    (
+        1000,
+        SaParams {
+            iterations: 1_000_000,
+            start_temperature: 0.1,
+            auto_temperature: None,
+            cooling: 0.999_993_1,
+            schedule: Schedule::Geometric,
+            epoch_length: 1,
+            moves: MoveType::Mixed,
+            reversal_share: 0.95,
+            acceptance: AcceptanceRule::Metropolis,
+            reheat: 0.0,
+            restarts: 0,
+        },
+    ),
+    (
+        usize::MAX,
+        SaParams {
+            iterations: 2_000_000,
+            start_temperature: 0.05,
+            auto_temperature: None,
+            cooling: 0.999_996_5,
+            schedule: Schedule::Geometric,
+            epoch_length: 1,
+            moves: MoveType::Mixed,
+            reversal_share: 0.97,
+            acceptance: AcceptanceRule::Metropolis,
+            reheat: 0.0,
+            restarts: 0,
+        },
+    ),
+];
+
+impl SaParams {
+    /// preset of the smallest size class `nodes` fits into
+    pub fn preset(nodes: usize) -> SaParams {
+        PRESETS
+            .iter()
+            .find(|(max_nodes, _)| nodes <= *max_nodes)
+            .map(|(_, params)| *params)
+            .expect("the last preset takes any size")
+    }
+}
+
+/// parameters the user picked, the rest comes from the preset
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SaOverrides {
+    pub iterations: Option<u32>,
+    pub start_temperature: Option<f64>,
+    pub auto_temperature: Option<f64>,
+    pub cooling: Option<f64>,
+    pub schedule: Option<Schedule>,
+    pub epoch_length: Option<u32>,
+    pub moves: Option<MoveType>,
+    pub reversal_share: Option<f64>,
+    pub acceptance: Option<AcceptanceRule>,
+    pub reheat: Option<f64>,
+    pub restarts: Option<u32>,
+}
+
+impl SaOverrides {
+    pub fn apply(&self, mut params: SaParams) -> SaParams {
+        params.iterations = self.iterations.unwrap_or(params.iterations);
+        params.start_temperature = self.start_temperature.unwrap_or(params.start_temperature);
+        params.auto_temperature = self.auto_temperature.or(params.auto_temperature);
+        params.cooling = self.cooling.unwrap_or(params.cooling);
+        params.schedule = self.schedule.unwrap_or(params.schedule);
+        params.epoch_length = self.epoch_length.unwrap_or(params.epoch_length);
+        params.moves = self.moves.unwrap_or(params.moves);
+        params.reversal_share = self.reversal_share.unwrap_or(params.reversal_share);
+        params.acceptance = self.acceptance.unwrap_or(params.acceptance);
+        params.reheat = self.reheat.unwrap_or(params.reheat);
+        params.restarts = self.restarts.unwrap_or(params.restarts);
+        params
+    }
+}
+
+/// `key=value` pairs separated by `:`, e.g. `iterations=50000:cooling=0.9999`
+impl FromStr for SaOverrides {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        fn value<T: FromStr>(key: &str, value: &str) -> Result<Option<T>, String>
+        where
+            T::Err: std::fmt::Display,
+        {
+            value
+                .parse()
+                .map(Some)
+                .map_err(|err| format!("invalid {} '{}': {}", key, value, err))
+        }
+
+        let mut overrides = SaOverrides::default();
+        let mut history: Option<usize> = None;
+        for pair in s.split(':').filter(|p| !p.is_empty()) {
+            let Some((key, v)) = pair.split_once('=') else {
+                return Err(format!("expected key=value, got '{}'", pair));
+            };
+            match key {
+                "iterations" => overrides.iterations = value(key, v)?,
+                "temperature" if v == "auto" => overrides.auto_temperature = Some(AUTO_ACCEPTANCE),
+                "temperature" => overrides.start_temperature = value(key, v)?,
+                "cooling" => overrides.cooling = value(key, v)?,
+                "schedule" => overrides.schedule = value(key, v)?,
+                "epoch" => overrides.epoch_length = value(key, v)?,
+                "moves" => overrides.moves = value(key, v)?,
+                "reversals" => overrides.reversal_share = value(key, v)?,
+                "acceptance" => overrides.acceptance = value(key, v)?,
+                "history" => history = value(key, v)?,
+                "reheat" => overrides.reheat = value(key, v)?,
+                "restarts" => overrides.restarts = value(key, v)?,
+                _ => {
+                    return Err(format!(
+                        "unknown parameter '{}', try iterations, temperature, cooling, schedule, \
+                         epoch, moves, reversals, acceptance, history, reheat or restarts",
+                        key
+                    ))
+                }
+            }
+        }
+        if let Some(length) = history {
+            let Some(AcceptanceRule::LateAcceptance { history }) = &mut overrides.acceptance else {
+                return Err("history is the length of acceptance=lahc".to_string());
+            };
+            *history = length;
+        }
+        Ok(overrides)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::annealing::*;
+
+    #[test]
+    fn test_presets_and_overrides() {
+        assert_eq!(SaParams::preset(13), PRESETS[0].1);
+        assert_eq!(SaParams::preset(500), PRESETS[2].1);
+        assert_eq!(SaParams::preset(1_000_000), PRESETS[3].1);
+
+        let overrides: SaOverrides = "iterations=5:cooling=0.5".parse().unwrap();
+        let params = overrides.apply(SaParams::preset(13));
+        assert_eq!((params.iterations, params.cooling), (5, 0.5));
+        assert_eq!(params.start_temperature, PRESETS[0].1.start_temperature);
+        assert!("speed=11".parse::<SaOverrides>().is_err());
+        let overrides: SaOverrides = "moves=insertion".parse().unwrap();
+        assert_eq!(overrides.apply(params).moves, MoveType::Insertion);
+        assert!("moves=3opt".parse::<SaOverrides>().is_err());
+        let overrides: SaOverrides = "temperature=auto".parse().unwrap();
+        assert_eq!(overrides.auto_temperature, Some(AUTO_ACCEPTANCE));
+
+        let overrides: SaOverrides = "history=50:acceptance=lahc".parse().unwrap();
+        assert_eq!(
+            overrides.acceptance,
+            Some(AcceptanceRule::LateAcceptance { history: 50 })
+        );
+        assert!("acceptance=glauber:history=50"
+            .parse::<SaOverrides>()
+            .is_err());
+    }
+
+    #[test]
+    fn test_schedules() {
+        let mut params: SaOverrides = "iterations=1000:cooling=0.99".parse().unwrap();
+        for schedule in ["geometric", "linear", "log"] {
+            params.schedule = Some(schedule.parse().unwrap());
+            let mut temperature = Temperature::new(&params.apply(SaParams::preset(50)), 1.0);
+            for _ in 0..1000 {
+                temperature.update(true);
+            }
+            let end = if schedule == "log" {
+                std::f64::consts::LN_2 / 1002f64.ln()
+            } else {
+                0.99f64.powi(1000)
+            };
+            assert!((temperature.get() - end).abs() < 1e-9);
+        }
+
+        // epochs hold the temperature and then catch up on the cooling
+        params.epoch_length = Some(10);
+        params.schedule = Some(Schedule::Geometric);
+        let mut temperature = Temperature::new(&params.apply(SaParams::preset(50)), 1.0);
+        for _ in 0..9 {
+            temperature.update(true);
+        }
+        assert_eq!(temperature.get(), 1.0);
+        temperature.update(true);
+        assert!((temperature.get() - 0.99f64.powi(10)).abs() < 1e-12);
+        params.epoch_length = None;
+
+        // reheating halfway still ends where the schedule would have
+        params.schedule = Some(Schedule::Geometric);
+        let mut temperature = Temperature::new(&params.apply(SaParams::preset(50)), 1.0);
+        for _ in 0..500 {
+            temperature.update(true);
+        }
+        temperature.reheat(0.5);
+        assert_eq!(temperature.remaining(), 1.0);
+        for _ in 0..500 {
+            temperature.update(true);
+        }
+        assert!((temperature.get() - 0.99f64.powi(1000)).abs() < 1e-9);
+
+        // taking every other move is above the target, which falls from a half, so it cools
+        params.schedule = Some(Schedule::Adaptive);
+        let mut temperature = Temperature::new(&params.apply(SaParams::preset(50)), 1.0);
+        temperature.update(true);
+        assert!(temperature.get() < (-ADAPTIVE_GAIN / 2.0).exp());
+        for iteration in 1..500 {
+            temperature.update(iteration % 2 == 0);
+        }
+        let halfway = temperature.get();
+        assert!(halfway < 1.0);
+        // and rejecting everything heats it up again
+        for _ in 500..600 {
+            temperature.update(false);
+        }
+        assert!(temperature.get() > halfway);
+    }
+
+    #[test]
+    fn test_acceptance_rules() {
+        let mut rng = rand::thread_rng();
+        assert!(Threshold.accept(10.0, 10.5, 1.0, &mut rng));
+        assert!(!Threshold.accept(10.0, 11.5, 1.0, &mut rng));
+        assert!(Metropolis.accept(10.0, 9.0, 1e-9, &mut rng));
+        assert!(!Metropolis.accept(10.0, 11.0, 1e-9, &mut rng));
+        assert!(!Glauber.accept(10.0, 11.0, 1e-9, &mut rng));
+
+        // the level falls to 10.5 and stays there after a worse tour is taken
+        let mut deluge = GreatDeluge::new();
+        deluge.start(10.0);
+        assert!(deluge.accept(10.0, 10.4, 0.5, &mut rng));
+        assert!(!deluge.accept(10.4, 10.6, 0.5, &mut rng));
+        assert!(deluge.accept(10.4, 10.5, 0.5, &mut rng));
+
+        // a worse move passes as long as it beats the tour from 2 moves ago
+        let mut lahc = LateAcceptance::new(2);
+        lahc.start(10.0);
+        assert!(lahc.accept(10.0, 8.0, 0.0, &mut rng));
+        assert!(lahc.accept(8.0, 9.0, 0.0, &mut rng));
+        assert!(!lahc.accept(9.0, 9.5, 0.0, &mut rng));
+        assert!(lahc.accept(9.0, 9.0, 0.0, &mut rng));
+    }
+}
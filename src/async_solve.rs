@@ -0,0 +1,241 @@
+//! async entry points for embedding solves in servers and other async applications
+//!
+//! `solve_async` hands the pipeline to a pool of blocking worker threads and returns right away.
+//! the task is a future of the run and a stream of stage reports, works on any executor
+//! since it only needs wakers. cancelling or dropping the task cancels the solve,
+//! which then wraps up with the best tour so far like after ctrl-c
+
+use std::collections::VecDeque;
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::pin::Pin;
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::task::{Context, Poll, Waker};
+
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+use crate::cancel::{self, CancelToken};
+use crate::metric::Metric;
+use crate::pipeline::{Pipeline, PipelineRun, StageReport};
+use crate::Node;
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// queue of the worker threads, started with the first solve
+static POOL: OnceLock<Mutex<Sender<Job>>> = OnceLock::new();
+
+/// runs `job` on the pool, one worker per core, jobs beyond that wait their turn
+fn spawn_blocking(job: Job) {
+    let pool = POOL.get_or_init(|| {
+        let (sender, receiver) = mpsc::channel::<Job>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        let workers = std::thread::available_parallelism().map_or(1, |n| n.get());
+        for _ in 0..workers {
+            let receiver = Arc::clone(&receiver);
+            std::thread::spawn(move || loop {
+                // the lock is only held while waiting for the next job
+                let job = receiver.lock().unwrap_or_else(|p| p.into_inner()).recv();
+                match job {
+                    Ok(job) => job(),
+                    Err(_) => break,
+                }
+            });
+        }
+        Mutex::new(sender)
+    });
+    let sender = pool.lock().unwrap_or_else(|p| p.into_inner());
+    sender
+        .send(job)
+        .expect("pool workers live as long as the process");
+}
+
+#[derive(Default)]
+struct State {
+    progress: VecDeque<StageReport>,
+    /// Err if the solver panicked
+    result: Option<Result<PipelineRun, String>>,
+    /// the run was already handed out by the future
+    taken: bool,
+    result_waker: Option<Waker>,
+    progress_waker: Option<Waker>,
+}
+
+impl State {
+    fn wake(&mut self) {
+        for waker in [self.result_waker.take(), self.progress_waker.take()] {
+            waker.into_iter().for_each(Waker::wake);
+        }
+    }
+}
+
+/// a solve running on the pool, await it for the run
+#[must_use = "dropping the task cancels the solve"]
+pub struct SolveTask {
+    state: Arc<Mutex<State>>,
+    token: Arc<CancelToken>,
+}
+
+/// runs the pipeline from `nodes` on the blocking pool, randomness from `seed`
+pub fn solve_async<M: Metric + Send + 'static>(
+    pipeline: Pipeline,
+    nodes: Vec<Node>,
+    metric: M,
+    seed: u64,
+) -> SolveTask {
+    let state = Arc::new(Mutex::new(State::default()));
+    let token = Arc::new(CancelToken::new());
+    let task = SolveTask {
+        state: Arc::clone(&state),
+        token: Arc::clone(&token),
+    };
+    spawn_blocking(Box::new(move || {
+        let lock = || state.lock().unwrap_or_else(|p| p.into_inner());
+        let run = std::panic::catch_unwind(AssertUnwindSafe(|| {
+            cancel::with_token(Some(token), || {
+                let mut rng = StdRng::seed_from_u64(seed);
+                pipeline.run_with_progress(&nodes, &metric, &mut rng, |report| {
+                    let mut state = lock();
+                    state.progress.push_back(report.clone());
+                    state.wake();
+                })
+            })
+        }))
+        .map_err(|_| "solver panicked".to_string());
+        let mut state = lock();
+        state.result = Some(run);
+        state.wake();
+    }));
+    task
+}
+
+impl SolveTask {
+    /// asks the solve to wrap up, the run still arrives with the best tour so far
+    pub fn cancel(&self) {
+        self.token.cancel();
+    }
+
+    /// report of the next finished stage, None once all stages are reported
+    pub fn next_progress(&mut self) -> NextProgress<'_> {
+        NextProgress { task: self }
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|p| p.into_inner())
+    }
+}
+
+impl Drop for SolveTask {
+    fn drop(&mut self) {
+        // nobody is waiting for the run anymore
+        let state = self.state();
+        if state.result.is_none() && !state.taken {
+            self.token.cancel();
+        }
+    }
+}
+
+impl Future for SolveTask {
+    type Output = Result<PipelineRun, String>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self.state();
+        assert!(!state.taken, "SolveTask polled after it completed");
+        match state.result.take() {
+            Some(result) => {
+                state.taken = true;
+                Poll::Ready(result)
+            }
+            None => {
+                state.result_waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/// future of `SolveTask::next_progress`
+pub struct NextProgress<'a> {
+    task: &'a mut SolveTask,
+}
+
+impl Future for NextProgress<'_> {
+    type Output = Option<StageReport>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self.task.state();
+        if let Some(report) = state.progress.pop_front() {
+            return Poll::Ready(Some(report));
+        }
+        if state.result.is_some() || state.taken {
+            return Poll::Ready(None);
+        }
+        state.progress_waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::async_solve::*;
+    use crate::metric::Euclidean;
+    use crate::random_nodes;
+    use crate::solvers::{NearestNeighbor, SimulatedAnnealing};
+    use std::task::Wake;
+    use std::thread::Thread;
+
+    /// minimal executor, parks the thread until the waker unparks it
+    fn block_on<F: Future>(future: F) -> F::Output {
+        struct Unpark(Thread);
+        impl Wake for Unpark {
+            fn wake(self: Arc<Self>) {
+                self.0.unpark();
+            }
+        }
+        let waker = Waker::from(Arc::new(Unpark(std::thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        let mut future = std::pin::pin!(future);
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return output;
+            }
+            std::thread::park();
+        }
+    }
+
+    fn pipeline(iterations: &str) -> Pipeline {
+        Pipeline::new()
+            .then(NearestNeighbor)
+            .then(SimulatedAnnealing {
+                overrides: iterations.parse().unwrap(),
+            })
+    }
+
+    #[test]
+    fn test_progress_then_run() {
+        let nodes = random_nodes(30, &mut StdRng::seed_from_u64(0));
+        let mut task = solve_async(pipeline("iterations=5000"), nodes.clone(), Euclidean, 1);
+        let first = block_on(task.next_progress()).unwrap();
+        assert_eq!(first.name, "nearest neighbor");
+        let second = block_on(task.next_progress()).unwrap();
+        assert!(block_on(task.next_progress()).is_none());
+
+        let run = block_on(task).unwrap();
+        assert_eq!(run.stages.last().unwrap().length, second.length);
+        // same seed, same run as solving right here
+        let expected =
+            pipeline("iterations=5000").run(&nodes, &Euclidean, &mut StdRng::seed_from_u64(1));
+        assert_eq!(run.tour, expected.tour);
+    }
+
+    #[test]
+    fn test_cancel_wraps_up() {
+        let nodes = random_nodes(50, &mut StdRng::seed_from_u64(0));
+        let task = solve_async(pipeline("iterations=1000000000"), nodes, Euclidean, 1);
+        task.cancel();
+        let run = block_on(task).unwrap();
+        assert!(run.interrupted);
+        assert_eq!(run.tour.len(), 50);
+    }
+}
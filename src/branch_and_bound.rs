@@ -0,0 +1,223 @@
+//! exact branch and bound: depth first over partial tours from the first node,
+//! cutting every partial tour that can't end up shorter than the best tour so far
+//!
+//! the rest of a partial tour is a path from its last node through all unvisited nodes back to
+//! the first one, so it's at least the cheapest edge out of its last node, the cheapest one into
+//! the first and a minimum spanning tree of the unvisited nodes, by the cheaper way of every
+//! edge on asymmetric metrics. the first best tour is 2-opt after nearest neighbor,
+//! children are tried closest first to find good tours early
+
+use crate::cancel;
+use crate::metric::Metric;
+use crate::solvers::{shortest_direction, tsp_nearest_neighbor, tsp_two_opt};
+use crate::Node;
+
+/// optimal tour and how much of the search tree it took
+#[derive(Debug, Clone)]
+pub struct BranchAndBoundRun {
+    pub tour: Vec<Node>,
+    pub length: f64,
+    /// partial tours whose children were searched
+    pub expanded: u64,
+    /// partial tours cut by the bound
+    pub pruned: u64,
+}
+
+struct Search {
+    distance: Vec<Vec<f64>>,
+    /// whether edges cost the same both ways, the spanning tree doesn't know their direction
+    symmetric: bool,
+    path: Vec<usize>,
+    visited: Vec<bool>,
+    best: (f64, Vec<usize>),
+    expanded: u64,
+    pruned: u64,
+}
+
+impl Search {
+    /// no tour continuing the current path with `length` so far is shorter than this
+    fn bound(&self, length: f64) -> f64 {
+        let last = *self.path.last().expect("the path starts at the first node");
+        let remaining: Vec<usize> = (0..self.visited.len())
+            .filter(|&i| !self.visited[i])
+            .collect();
+        if remaining.is_empty() {
+            return length + self.distance[last][0];
+        }
+        let cheapest = |costs: &mut dyn Iterator<Item = f64>| costs.fold(f64::INFINITY, f64::min);
+        let out_of_last = cheapest(&mut remaining.iter().map(|&i| self.distance[last][i]));
+        let into_first = cheapest(&mut remaining.iter().map(|&i| self.distance[i][0]));
+        length + out_of_last + into_first + self.mst(&remaining)
+    }
+
+    /// the cheaper way between two nodes
+    fn undirected(&self, a: usize, b: usize) -> f64 {
+        if self.symmetric {
+            self.distance[a][b]
+        } else {
+            self.distance[a][b].min(self.distance[b][a])
+        }
+    }
+
+    /// prim's algorithm on the matrix
+    fn mst(&self, nodes: &[usize]) -> f64 {
+        let mut connection: Vec<f64> = nodes
+            .iter()
+            .map(|&i| self.undirected(nodes[0], i))
+            .collect();
+        let mut in_tree = vec![false; nodes.len()];
+        in_tree[0] = true;
+        let mut total = 0.0;
+        for _ in 1..nodes.len() {
+            let next = (0..nodes.len())
+                .filter(|&k| !in_tree[k])
+                .min_by(|&a, &b| connection[a].total_cmp(&connection[b]))
+                .expect("loop runs once per node not in the tree yet");
+            in_tree[next] = true;
+            total += connection[next];
+            for k in 0..nodes.len() {
+                connection[k] = connection[k].min(self.undirected(nodes[next], nodes[k]));
+            }
+        }
+        total
+    }
+
+    fn search(&mut self, length: f64) {
+        if cancel::is_cancelled() {
+            return;
+        }
+        let n = self.visited.len();
+        let last = *self.path.last().expect("the path starts at the first node");
+        if self.path.len() == n {
+            let total = length + self.distance[last][0];
+            if total < self.best.0 {
+                self.best = (total, self.path.clone());
+            }
+            return;
+        }
+        if self.bound(length) >= self.best.0 {
+            self.pruned += 1;
+            return;
+        }
+        self.expanded += 1;
+
+        let mut children: Vec<usize> = (0..n).filter(|&i| !self.visited[i]).collect();
+        children.sort_by(|&a, &b| self.distance[last][a].total_cmp(&self.distance[last][b]));
+        for child in children {
+            self.visited[child] = true;
+            self.path.push(child);
+            self.search(length + self.distance[last][child]);
+            self.path.pop();
+            self.visited[child] = false;
+        }
+    }
+}
+
+/// the optimal tour starting at the first node, or the best one found before an interrupt
+pub fn solve(nodes: &[Node], metric: &dyn Metric) -> BranchAndBoundRun {
+    let n = nodes.len();
+    if n <= 3 {
+        let tour = shortest_direction(nodes, metric);
+        return BranchAndBoundRun {
+            length: metric.tour_length(&tour),
+            tour,
+            expanded: 0,
+            pruned: 0,
+        };
+    }
+    let distance: Vec<Vec<f64>> = nodes
+        .iter()
+        .map(|a| nodes.iter().map(|b| metric.distance(a, b)).collect())
+        .collect();
+
+    // the heuristic's tour maps back to indices by the ids
+    let positions = crate::positions_by_id(nodes);
+    let mut start: Vec<usize> = tsp_two_opt(&tsp_nearest_neighbor(nodes, metric), metric)
+        .iter()
+        .map(|node| positions[node.id])
+        .collect();
+    let first = start
+        .iter()
+        .position(|&i| i == 0)
+        .expect("every node is in the tour");
+    start.rotate_left(first);
+
+    let mut visited = vec![false; n];
+    visited[0] = true;
+    let mut search = Search {
+        best: (metric.tour_length(&nodes_in_order(&start, nodes)), start),
+        distance,
+        symmetric: metric.is_symmetric(),
+        path: vec![0],
+        visited,
+        expanded: 0,
+        pruned: 0,
+    };
+    search.search(0.0);
+
+    let tour = nodes_in_order(&search.best.1, nodes);
+    BranchAndBoundRun {
+        length: metric.tour_length(&tour),
+        tour,
+        expanded: search.expanded,
+        pruned: search.pruned,
+    }
+}
+
+/// the nodes at the positions in `order`
+fn nodes_in_order(order: &[usize], nodes: &[Node]) -> Vec<Node> {
+    order.iter().map(|&i| nodes[i].clone()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::branch_and_bound::*;
+    use crate::metric::Euclidean;
+    use crate::random_nodes;
+    use crate::solvers::tsp_held_karp;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_matches_held_karp() {
+        let nodes = random_nodes(12, &mut StdRng::seed_from_u64(4));
+        let run = solve(&nodes, &Euclidean);
+        let (_, optimum) = tsp_held_karp(&nodes, &Euclidean);
+        assert!((run.length - optimum).abs() < 1e-9);
+        assert_eq!(run.tour[0], nodes[0]);
+        assert!(run.expanded > 0 && run.pruned > 0);
+        // far less than the 11! leaves of the full tree
+        assert!(run.expanded + run.pruned < 1_000_000);
+    }
+
+    #[test]
+    fn test_exact_on_directed_costs() {
+        use crate::explicit::ExplicitMatrix;
+        use crate::solvers::tsp_brute_force;
+        use rand::Rng;
+
+        let mut rng = StdRng::seed_from_u64(309);
+        for trial in 0..30 {
+            let n = 3 + trial % 6;
+            let rows: Vec<Vec<f64>> = (0..n)
+                .map(|i| {
+                    (0..n)
+                        .map(|j| {
+                            if i == j {
+                                0.0
+                            } else {
+                                rng.gen_range(1..100) as f64
+                            }
+                        })
+                        .collect()
+                })
+                .collect();
+            let matrix = ExplicitMatrix::directed(&rows).unwrap();
+            let nodes = matrix.nodes();
+            let (_, optimum) = tsp_held_karp(&nodes, &matrix);
+            let brute_force = matrix.tour_length(&tsp_brute_force(&nodes, &matrix));
+            assert_eq!(brute_force, optimum, "{} nodes", n);
+            assert_eq!(solve(&nodes, &matrix).length, optimum, "{} nodes", n);
+        }
+    }
+}
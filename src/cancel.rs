@@ -0,0 +1,100 @@
+//! cooperative cancellation of long running solvers
+//!
+//! solvers poll a token now and then and wrap up with what they have when it's cancelled,
+//! nothing gets killed in the middle of a move
+
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// exit status of runs stopped by ctrl-c, 128 + SIGINT like shells report it
+pub const INTERRUPTED_EXIT_CODE: i32 = 130;
+
+pub struct CancelToken {
+    cancelled: AtomicBool,
+}
+
+impl CancelToken {
+    pub const fn new() -> Self {
+        CancelToken {
+            cancelled: AtomicBool::new(false),
+        }
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// cheap enough to call every iteration
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+impl Default for CancelToken {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// cancelled by ctrl-c once `install_interrupt_handler` ran, never otherwise
+pub static INTERRUPT: CancelToken = CancelToken::new();
+
+thread_local! {
+    /// token of the solve running on this thread, see `with_token`
+    static CURRENT: RefCell<Option<Arc<CancelToken>>> = const { RefCell::new(None) };
+}
+
+/// whether the solve on this thread should wrap up, by ctrl-c or its own token.
+/// cheap enough to call every iteration
+pub fn is_cancelled() -> bool {
+    INTERRUPT.is_cancelled()
+        || CURRENT.with(|current| current.borrow().as_ref().is_some_and(|t| t.is_cancelled()))
+}
+
+/// the token `is_cancelled` checks on this thread, to hand on to helper threads
+pub fn current_token() -> Option<Arc<CancelToken>> {
+    CURRENT.with(|current| current.borrow().clone())
+}
+
+/// runs `f` with `token` cancelling it on this thread, on top of ctrl-c
+pub fn with_token<R>(token: Option<Arc<CancelToken>>, f: impl FnOnce() -> R) -> R {
+    // puts the previous token back even if f panics
+    struct Restore(Option<Arc<CancelToken>>);
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            CURRENT.with(|current| *current.borrow_mut() = self.0.take());
+        }
+    }
+    let _restore = Restore(CURRENT.with(|current| current.replace(token)));
+    f()
+}
+
+/// the first ctrl-c cancels `INTERRUPT`, a second one exits right away
+pub fn install_interrupt_handler() -> Result<(), String> {
+    ctrlc::set_handler(|| {
+        if INTERRUPT.is_cancelled() {
+            std::process::exit(INTERRUPTED_EXIT_CODE);
+        }
+        eprintln!("\ninterrupted, finishing up with the best tour so far (ctrl-c again to quit)");
+        INTERRUPT.cancel();
+    })
+    .map_err(|err| err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cancel::*;
+
+    #[test]
+    fn test_cancel_token() {
+        let token = CancelToken::new();
+        assert!(!token.is_cancelled());
+        token.cancel();
+        assert!(token.is_cancelled());
+        assert!(!INTERRUPT.is_cancelled());
+
+        assert!(with_token(Some(Arc::new(token)), is_cancelled));
+        assert!(!is_cancelled());
+    }
+}
@@ -0,0 +1,508 @@
+//! construction heuristics building a tour from scratch, next to nearest neighbor in `solvers`
+//!
+//! the order of the nodes they're given doesn't matter, only their positions
+
+use std::collections::HashMap;
+
+use crate::geometry::convex_hull;
+use crate::insertion::insertion_cost;
+use crate::metric::Metric;
+use crate::Node;
+
+/// disjoint sets with path halving and union by size
+pub struct UnionFind {
+    parent: Vec<usize>,
+    size: Vec<usize>,
+}
+
+impl UnionFind {
+    pub fn new(n: usize) -> Self {
+        UnionFind {
+            parent: (0..n).collect(),
+            size: vec![1; n],
+        }
+    }
+
+    pub fn find(&mut self, mut x: usize) -> usize {
+        while self.parent[x] != x {
+            self.parent[x] = self.parent[self.parent[x]];
+            x = self.parent[x];
+        }
+        x
+    }
+
+    /// merges the sets of `a` and `b`, false if they were one already
+    pub fn union(&mut self, a: usize, b: usize) -> bool {
+        let (mut a, mut b) = (self.find(a), self.find(b));
+        if a == b {
+            return false;
+        }
+        if self.size[a] < self.size[b] {
+            std::mem::swap(&mut a, &mut b);
+        }
+        self.parent[b] = a;
+        self.size[a] += self.size[b];
+        true
+    }
+}
+
+/// the tour through `nodes` along `neighbors`, the two tour neighbors of every node
+fn walk(nodes: &[Node], neighbors: &[Vec<usize>]) -> Vec<Node> {
+    let mut tour = Vec::with_capacity(nodes.len());
+    let (mut previous, mut current) = (usize::MAX, 0);
+    for _ in 0..nodes.len() {
+        tour.push(nodes[current].clone());
+        let next = if neighbors[current][0] != previous {
+            neighbors[current][0]
+        } else {
+            neighbors[current][1]
+        };
+        (previous, current) = (current, next);
+    }
+    tour
+}
+
+/// adds `edges` in order as long as `needed` aren't in yet, skipping every edge that would
+/// give a node a third neighbor or close a cycle. returns the neighbors of every node
+fn match_edges(
+    n: usize,
+    edges: impl IntoIterator<Item = (usize, usize)>,
+    needed: usize,
+) -> Vec<Vec<usize>> {
+    let mut neighbors: Vec<Vec<usize>> = vec![Vec::with_capacity(2); n];
+    let mut fragments = UnionFind::new(n);
+    let mut added = 0;
+    for (a, b) in edges {
+        if added == needed {
+            break;
+        }
+        if neighbors[a].len() < 2 && neighbors[b].len() < 2 && fragments.union(a, b) {
+            neighbors[a].push(b);
+            neighbors[b].push(a);
+            added += 1;
+        }
+    }
+    neighbors
+}
+
+/// greedy edge matching: takes the edges shortest first, skipping every edge that would
+/// give a node a third tour neighbor or close a cycle before all nodes are in it
+///
+/// sorts all n²/2 edges, so O(n² log n) time and memory for the edge list
+pub fn tsp_greedy_edge(nodes: &[Node], metric: &dyn Metric) -> Vec<Node> {
+    let n = nodes.len();
+    if n <= 3 {
+        return nodes.to_vec();
+    }
+    let mut edges: Vec<(f64, u32, u32)> = Vec::with_capacity(n * (n - 1) / 2);
+    for a in 0..n {
+        for b in a + 1..n {
+            edges.push((metric.distance(&nodes[a], &nodes[b]), a as u32, b as u32));
+        }
+    }
+    edges.sort_by(|x, y| x.0.total_cmp(&y.0));
+
+    let edges = edges.into_iter().map(|(_, a, b)| (a as usize, b as usize));
+    let mut neighbors = match_edges(n, edges, n - 1);
+    // a single path is left, its ends close the tour
+    let ends: Vec<usize> = (0..n).filter(|&i| neighbors[i].len() < 2).collect();
+    neighbors[ends[0]].push(ends[1]);
+    neighbors[ends[1]].push(ends[0]);
+    walk(nodes, &neighbors)
+}
+
+/// clarke-wright savings with the first node as the hub: every other node starts on its own
+/// route out of the hub and back, and routes are merged end to end by the largest savings
+/// d(hub, i) + d(hub, j) - d(i, j) of joining i and j directly instead of through the hub
+///
+/// the savings are computed on `threads` threads, sorting them is O(n² log n)
+pub fn tsp_savings(nodes: &[Node], metric: &dyn Metric, threads: usize) -> Vec<Node> {
+    let n = nodes.len();
+    if n <= 3 {
+        return nodes.to_vec();
+    }
+    let hub = &nodes[0];
+    let rows = crate::rng::parallel_map(n - 1, threads, |row| {
+        let i = row + 1;
+        let to_hub = metric.distance(hub, &nodes[i]);
+        (i + 1..n)
+            .map(|j| {
+                let saving = to_hub + metric.distance(hub, &nodes[j])
+                    - metric.distance(&nodes[i], &nodes[j]);
+                (saving, i as u32, j as u32)
+            })
+            .collect::<Vec<_>>()
+    });
+    let mut savings: Vec<(f64, u32, u32)> = rows.into_iter().flatten().collect();
+    savings.sort_by(|x, y| y.0.total_cmp(&x.0));
+
+    let edges = savings
+        .into_iter()
+        .map(|(_, a, b)| (a as usize, b as usize));
+    // all routes merge into one, a path through everything but the hub
+    let mut neighbors = match_edges(n, edges, n - 2);
+    let ends: Vec<usize> = (1..n).filter(|&i| neighbors[i].len() < 2).collect();
+    for &end in &ends {
+        neighbors[end].push(0);
+        neighbors[0].push(end);
+    }
+    walk(nodes, &neighbors)
+}
+
+/// cells per side of the grid the hilbert curve runs through
+const HILBERT_SIDE: u32 = 1 << 16;
+
+/// position of the cell (x, y) along the hilbert curve through a `HILBERT_SIDE` grid
+fn hilbert_index(mut x: u32, mut y: u32) -> u64 {
+    let mut index = 0u64;
+    let mut s = HILBERT_SIDE / 2;
+    while s > 0 {
+        let rx = u32::from(x & s > 0);
+        let ry = u32::from(y & s > 0);
+        index += u64::from(s) * u64::from(s) * u64::from((3 * rx) ^ ry);
+        // rotate the quadrant so the curve inside it starts where the last one ended
+        if ry == 0 {
+            if rx == 1 {
+                x = HILBERT_SIDE - 1 - x;
+                y = HILBERT_SIDE - 1 - y;
+            }
+            std::mem::swap(&mut x, &mut y);
+        }
+        s /= 2;
+    }
+    index
+}
+
+/// the nodes in the order a hilbert curve over their bounding box passes them, O(n log n)
+///
+/// nearby nodes are mostly close on the curve, so the tour is about 40% above the optimum on
+/// uniform instances, found in no time for millions of nodes. a start for local search
+pub fn tsp_space_filling_curve(nodes: &[Node]) -> Vec<Node> {
+    let (min_x, max_x, min_y, max_y) = nodes.iter().fold(
+        (
+            f64::INFINITY,
+            f64::NEG_INFINITY,
+            f64::INFINITY,
+            f64::NEG_INFINITY,
+        ),
+        |(min_x, max_x, min_y, max_y), n| {
+            (
+                min_x.min(n.x),
+                max_x.max(n.x),
+                min_y.min(n.y),
+                max_y.max(n.y),
+            )
+        },
+    );
+    // one scale for both axes keeps the curve's cells square
+    let width = (max_x - min_x).max(max_y - min_y).max(f64::MIN_POSITIVE);
+    let cell = |value: f64, min: f64| {
+        (((value - min) / width * HILBERT_SIDE as f64) as u32).min(HILBERT_SIDE - 1)
+    };
+    let mut tour = nodes.to_vec();
+    tour.sort_by_cached_key(|n| hilbert_index(cell(n.x, min_x), cell(n.y, min_y)));
+    tour
+}
+
+/// a cycle through some of the nodes, growing one insertion at a time.
+/// every node outside knows the edge it's cheapest to insert into, so insertions cost O(n)
+struct PartialTour<'a> {
+    nodes: &'a [Node],
+    metric: &'a dyn Metric,
+    /// indices into `nodes` in tour order
+    tour: Vec<usize>,
+    inside: Vec<bool>,
+    /// for nodes outside: (cost, a, b) of inserting between a and b, a followed by b
+    cheapest: Vec<(f64, usize, usize)>,
+    /// for nodes outside: distance to the closest node inside
+    distance_to_tour: Vec<f64>,
+}
+
+impl<'a> PartialTour<'a> {
+    fn new(nodes: &'a [Node], metric: &'a dyn Metric, start: Vec<usize>) -> Self {
+        let mut inside = vec![false; nodes.len()];
+        for &i in &start {
+            inside[i] = true;
+        }
+        let mut partial = PartialTour {
+            nodes,
+            metric,
+            tour: start,
+            inside,
+            cheapest: vec![(f64::INFINITY, 0, 0); nodes.len()],
+            distance_to_tour: vec![f64::INFINITY; nodes.len()],
+        };
+        for k in partial.outside() {
+            partial.cheapest[k] = partial.cheapest_edge(k);
+            partial.distance_to_tour[k] = partial
+                .tour
+                .iter()
+                .map(|&i| partial.d(i, k))
+                .fold(f64::INFINITY, f64::min);
+        }
+        partial
+    }
+
+    fn d(&self, a: usize, b: usize) -> f64 {
+        self.metric.distance(&self.nodes[a], &self.nodes[b])
+    }
+
+    /// how much longer the tour gets with `k` between a and b
+    fn cost(&self, a: usize, k: usize, b: usize) -> f64 {
+        insertion_cost(&self.nodes[a], &self.nodes[k], &self.nodes[b], self.metric)
+    }
+
+    fn outside(&self) -> Vec<usize> {
+        (0..self.nodes.len()).filter(|&i| !self.inside[i]).collect()
+    }
+
+    fn is_complete(&self) -> bool {
+        self.tour.len() == self.nodes.len()
+    }
+
+    fn cheapest_edge(&self, k: usize) -> (f64, usize, usize) {
+        let n = self.tour.len();
+        (0..n)
+            .map(|i| (self.tour[i], self.tour[(i + 1) % n]))
+            .map(|(a, b)| (self.cost(a, k, b), a, b))
+            .min_by(|x, y| x.0.total_cmp(&y.0))
+            .expect("the tour starts with at least one node")
+    }
+
+    /// inserts `k` where it's cheapest
+    fn insert(&mut self, k: usize) {
+        let (_, a, b) = self.cheapest[k];
+        let position = self
+            .tour
+            .iter()
+            .position(|&i| i == a)
+            .expect("the cheapest edge is in the tour");
+        self.tour.insert(position + 1, k);
+        self.inside[k] = true;
+
+        for j in self.outside() {
+            self.distance_to_tour[j] = self.distance_to_tour[j].min(self.d(j, k));
+            // the edge it wanted is gone, everything else only got the two new edges
+            self.cheapest[j] = if self.cheapest[j].1 == a && self.cheapest[j].2 == b {
+                self.cheapest_edge(j)
+            } else {
+                let candidates = [
+                    self.cheapest[j],
+                    (self.cost(a, j, k), a, k),
+                    (self.cost(k, j, b), k, b),
+                ];
+                candidates
+                    .into_iter()
+                    .min_by(|x, y| x.0.total_cmp(&y.0))
+                    .expect("there are candidates")
+            };
+        }
+    }
+
+    fn into_tour(self) -> Vec<Node> {
+        self.tour.iter().map(|&i| self.nodes[i].clone()).collect()
+    }
+}
+
+/// the first node and the node closest to it, a cycle of two to insert into
+fn closest_pair_start(nodes: &[Node], metric: &dyn Metric) -> Vec<usize> {
+    let closest = (1..nodes.len())
+        .min_by(|&a, &b| {
+            let d = |i: usize| metric.distance(&nodes[0], &nodes[i]);
+            d(a).total_cmp(&d(b))
+        })
+        .expect("there are at least two nodes");
+    vec![0, closest]
+}
+
+/// the first node and the node farthest from it
+fn farthest_pair_start(nodes: &[Node], metric: &dyn Metric) -> Vec<usize> {
+    let farthest = (1..nodes.len())
+        .max_by(|&a, &b| {
+            let d = |i: usize| metric.distance(&nodes[0], &nodes[i]);
+            d(a).total_cmp(&d(b))
+        })
+        .expect("there are at least two nodes");
+    vec![0, farthest]
+}
+
+/// cheapest insertion: from the first node and its closest one, always inserts the node
+/// that makes the tour the least longer, where it does so. within twice the optimum, O(n²)
+pub fn tsp_cheapest_insertion(nodes: &[Node], metric: &dyn Metric) -> Vec<Node> {
+    if nodes.len() <= 3 {
+        return nodes.to_vec();
+    }
+    let mut partial = PartialTour::new(nodes, metric, closest_pair_start(nodes, metric));
+    while !partial.is_complete() {
+        let k = partial
+            .outside()
+            .into_iter()
+            .min_by(|&a, &b| partial.cheapest[a].0.total_cmp(&partial.cheapest[b].0))
+            .expect("the tour isn't complete");
+        partial.insert(k);
+    }
+    partial.into_tour()
+}
+
+/// convex hull insertion: from the convex hull, whose order every optimal euclidean tour keeps,
+/// inserts the inner nodes by cheapest insertion. O(n²)
+pub fn tsp_hull_insertion(nodes: &[Node], metric: &dyn Metric) -> Vec<Node> {
+    if nodes.len() <= 3 {
+        return nodes.to_vec();
+    }
+    let index: HashMap<usize, usize> = nodes.iter().enumerate().map(|(i, n)| (n.id, i)).collect();
+    let mut start: Vec<usize> = convex_hull(nodes).iter().map(|id| index[id]).collect();
+    if start.len() < 2 {
+        // all nodes in one spot
+        start = closest_pair_start(nodes, metric);
+    }
+    let mut partial = PartialTour::new(nodes, metric, start);
+    while !partial.is_complete() {
+        let k = partial
+            .outside()
+            .into_iter()
+            .min_by(|&a, &b| partial.cheapest[a].0.total_cmp(&partial.cheapest[b].0))
+            .expect("the tour isn't complete");
+        partial.insert(k);
+    }
+    partial.into_tour()
+}
+
+/// farthest insertion: from the first node and the one farthest from it, always inserts the node
+/// farthest from the tour, where it's cheapest. the outline of the instance comes first and
+/// the rest fills in, usually better than cheapest insertion on euclidean instances, O(n²)
+pub fn tsp_farthest_insertion(nodes: &[Node], metric: &dyn Metric) -> Vec<Node> {
+    if nodes.len() <= 3 {
+        return nodes.to_vec();
+    }
+    let mut partial = PartialTour::new(nodes, metric, farthest_pair_start(nodes, metric));
+    while !partial.is_complete() {
+        let k = partial
+            .outside()
+            .into_iter()
+            .max_by(|&a, &b| partial.distance_to_tour[a].total_cmp(&partial.distance_to_tour[b]))
+            .expect("the tour isn't complete");
+        partial.insert(k);
+    }
+    partial.into_tour()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::construction::*;
+    use crate::metric::Euclidean;
+    use crate::random_nodes;
+    use crate::solvers::tsp_nearest_neighbor;
+    use crate::tour::Tour;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn assert_tour(tour: &[Node], nodes: &[Node]) {
+        let ids: Vec<usize> = tour.iter().map(|n| n.id).collect();
+        assert!(Tour::from_ids(&ids, nodes).is_ok());
+    }
+
+    #[test]
+    fn test_greedy_edge_beats_nearest_neighbor() {
+        let nodes = random_nodes(200, &mut StdRng::seed_from_u64(11));
+        let tour = tsp_greedy_edge(&nodes, &Euclidean);
+        assert_tour(&tour, &nodes);
+        let nn = tsp_nearest_neighbor(&nodes, &Euclidean);
+        assert!(Euclidean.tour_length(&tour) < Euclidean.tour_length(&nn));
+
+        let mut sets = UnionFind::new(4);
+        assert!(sets.union(0, 1) && sets.union(2, 3) && sets.union(1, 3));
+        assert!(!sets.union(0, 2));
+    }
+
+    #[test]
+    fn test_cheapest_insertion_tour() {
+        let nodes = random_nodes(150, &mut StdRng::seed_from_u64(12));
+        let tour = tsp_cheapest_insertion(&nodes, &Euclidean);
+        assert_tour(&tour, &nodes);
+        // at most twice the optimum, which is at most twice the mst
+        let mst = crate::geometry::mst_length(&nodes, &Euclidean);
+        assert!(Euclidean.tour_length(&tour) < 4.0 * mst);
+
+        // the cached cheapest edges agree with a fresh search
+        let mut partial = PartialTour::new(&nodes, &Euclidean, vec![0, 1]);
+        for k in 2..40 {
+            partial.insert(k);
+        }
+        for j in partial.outside() {
+            assert!((partial.cheapest[j].0 - partial.cheapest_edge(j).0).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn test_farthest_insertion_beats_cheapest() {
+        let nodes = random_nodes(300, &mut StdRng::seed_from_u64(13));
+        let tour = tsp_farthest_insertion(&nodes, &Euclidean);
+        assert_tour(&tour, &nodes);
+        let cheapest = tsp_cheapest_insertion(&nodes, &Euclidean);
+        assert!(Euclidean.tour_length(&tour) < Euclidean.tour_length(&cheapest));
+    }
+
+    #[test]
+    fn test_hull_insertion_keeps_hull_order() {
+        let nodes = random_nodes(200, &mut StdRng::seed_from_u64(14));
+        let tour = tsp_hull_insertion(&nodes, &Euclidean);
+        assert_tour(&tour, &nodes);
+        let hull = convex_hull(&nodes);
+        let on_hull: Vec<usize> = tour
+            .iter()
+            .map(|n| n.id)
+            .filter(|id| hull.contains(id))
+            .collect();
+        let first = on_hull.iter().position(|&id| id == hull[0]).unwrap();
+        let mut rotated = on_hull.clone();
+        rotated.rotate_left(first);
+        assert_eq!(rotated, hull);
+        assert!(
+            Euclidean.tour_length(&tour)
+                < Euclidean.tour_length(&tsp_cheapest_insertion(&nodes, &Euclidean))
+        );
+
+        // duplicates only, no hull to start from
+        let same = vec![
+            Node {
+                id: 0,
+                x: 0.5,
+                y: 0.5
+            };
+            5
+        ]
+        .into_iter()
+        .enumerate()
+        .map(|(id, n)| Node { id, ..n })
+        .collect::<Vec<_>>();
+        assert_tour(&tsp_hull_insertion(&same, &Euclidean), &same);
+    }
+
+    #[test]
+    fn test_savings_independent_of_threads() {
+        let nodes = random_nodes(150, &mut StdRng::seed_from_u64(15));
+        let tour = tsp_savings(&nodes, &Euclidean, 1);
+        assert_tour(&tour, &nodes);
+        assert_eq!(tour, tsp_savings(&nodes, &Euclidean, 4));
+        let nn = tsp_nearest_neighbor(&nodes, &Euclidean);
+        assert!(Euclidean.tour_length(&tour) < Euclidean.tour_length(&nn));
+    }
+
+    #[test]
+    fn test_hilbert_curve_order() {
+        // the first order curve visits the quadrants in a u
+        let quarter = HILBERT_SIDE / 2;
+        let corners: Vec<u64> = [(0, 0), (0, quarter), (quarter, quarter), (quarter, 0)]
+            .iter()
+            .map(|&(x, y)| hilbert_index(x, y))
+            .collect();
+        assert!(corners.windows(2).all(|w| w[0] < w[1]));
+
+        let nodes = random_nodes(2000, &mut StdRng::seed_from_u64(16));
+        let tour = tsp_space_filling_curve(&nodes);
+        assert_tour(&tour, &nodes);
+        let mst = crate::geometry::mst_length(&nodes, &Euclidean);
+        assert!(Euclidean.tour_length(&tour) < 2.0 * mst);
+    }
+}
@@ -0,0 +1,475 @@
+//! moving instances and tours between file formats
+//!
+//! the format is picked by extension: `.tsp`, `.atsp` and `.tour` are tsplib, `.csv` is csv,
+//! `.json` json, `.geojson` geojson, anything else the crate's own plain text. plain and csv files
+//! carry the metric in a `# metric: integer` comment, json in a `metric` field, tsplib in its
+//! EDGE_WEIGHT_TYPE
+
+use std::error::Error;
+use std::fmt::Write;
+use std::path::Path;
+
+use crate::formats;
+use crate::geo;
+use crate::json::{self, Json};
+use crate::metric::MetricKind;
+use crate::objective::NodeCosts;
+use crate::tsplib;
+use crate::Node;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Plain,
+    Csv,
+    Json,
+    Tsplib,
+    /// points on earth, read as their projection onto the plane in kilometers, see
+    /// `geo::project`. only read: planar coordinates have no latitude and longitude to write
+    GeoJson,
+}
+
+impl Format {
+    pub fn from_path(path: &str) -> Format {
+        match Path::new(path).extension().and_then(|ext| ext.to_str()) {
+            Some("tsp" | "atsp" | "tour") => Format::Tsplib,
+            Some("csv") => Format::Csv,
+            Some("json") => Format::Json,
+            Some("geojson") => Format::GeoJson,
+            _ => Format::Plain,
+        }
+    }
+}
+
+/// everything a format can say about an instance
+#[derive(Debug, Clone)]
+pub struct Instance {
+    pub name: String,
+    pub nodes: Vec<Node>,
+    pub costs: NodeCosts,
+    pub metric: MetricKind,
+}
+
+/// the `# metric: <kind>` comment of plain and csv files, euclidean without one
+fn metric_comment(text: &str) -> Result<MetricKind, String> {
+    for line in text.lines() {
+        let Some(comment) = line.trim().strip_prefix('#') else {
+            continue;
+        };
+        if let Some(kind) = comment.trim().strip_prefix("metric:") {
+            return kind.trim().parse();
+        }
+    }
+    Ok(MetricKind::Euclidean)
+}
+
+/// `id,x,y[,service][,weight]` rows under a header naming the columns, `#` lines are comments
+fn parse_csv_instance(text: &str) -> Result<(Vec<Node>, NodeCosts), String> {
+    let mut lines = text
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty() && !line.trim_start().starts_with('#'));
+    let Some((_, header)) = lines.next() else {
+        return Err("missing csv header".to_string());
+    };
+    let columns: Vec<&str> = header.split(',').map(str::trim).collect();
+    let column = |name: &str| columns.iter().position(|&c| c == name);
+    let (Some(id), Some(x), Some(y)) = (column("id"), column("x"), column("y")) else {
+        return Err(format!(
+            "csv header needs id, x and y columns, got '{}'",
+            header
+        ));
+    };
+    let (service, weight) = (column("service"), column("weight"));
+
+    let mut nodes = Vec::new();
+    let mut costs = NodeCosts::default();
+    for (number, line) in lines {
+        let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+        if fields.len() != columns.len() {
+            return Err(format!(
+                "line {}: expected {} fields, got {}",
+                number + 1,
+                columns.len(),
+                fields.len()
+            ));
+        }
+        let field = |index: usize, name: &str| {
+            fields[index]
+                .parse::<f64>()
+                .map_err(|err| format!("line {}: invalid {}: {}", number + 1, name, err))
+        };
+        let node = Node {
+            id: fields[id]
+                .parse()
+                .map_err(|err| format!("line {}: invalid id: {}", number + 1, err))?,
+            x: field(x, "x")?,
+            y: field(y, "y")?,
+        };
+        let service = service.map(|i| field(i, "service")).transpose()?;
+        let weight = weight.map(|i| field(i, "weight")).transpose()?;
+        let (service, weight) = (service.unwrap_or(0.0), weight.unwrap_or(1.0));
+        // like plain files, only costs that change something are stored
+        if service != 0.0 || weight != 1.0 {
+            costs.set(node.id, service, weight);
+        }
+        nodes.push(node);
+    }
+    Ok((nodes, costs))
+}
+
+/// `{"name": .., "metric": .., "nodes": [{"id": 0, "x": 1, "y": 2}, ..]}` or just the list of nodes.
+/// ids default to the position in the list, `service` and `weight` are optional like in csv
+fn parse_json_instance(text: &str, name: &str) -> Result<Instance, String> {
+    let document = json::parse(text)?;
+    let list = match &document {
+        Json::Array(list) => list,
+        _ => document
+            .get("nodes")
+            .and_then(Json::as_array)
+            .ok_or("expected a list of nodes or an object with a 'nodes' list")?,
+    };
+    let mut nodes = Vec::with_capacity(list.len());
+    let mut costs = NodeCosts::default();
+    for (position, node) in list.iter().enumerate() {
+        let field = |key: &str| -> Result<Option<f64>, String> {
+            match node.get(key) {
+                None => Ok(None),
+                Some(value) => value
+                    .as_f64()
+                    .map(Some)
+                    .ok_or_else(|| format!("node {}: '{}' isn't a number", position, key)),
+            }
+        };
+        let coordinate =
+            |key: &str| field(key)?.ok_or_else(|| format!("node {}: missing '{}'", position, key));
+        let id = match field("id")? {
+            Some(id) if id >= 0.0 && id.fract() == 0.0 => id as usize,
+            Some(id) => return Err(format!("node {}: invalid id {}", position, id)),
+            None => position,
+        };
+        let (service, weight) = (
+            field("service")?.unwrap_or(0.0),
+            field("weight")?.unwrap_or(1.0),
+        );
+        if service != 0.0 || weight != 1.0 {
+            costs.set(id, service, weight);
+        }
+        nodes.push(Node {
+            id,
+            x: coordinate("x")?,
+            y: coordinate("y")?,
+        });
+    }
+    let metric = match document.get("metric") {
+        Some(metric) => metric.as_str().ok_or("'metric' isn't a string")?.parse()?,
+        None => MetricKind::Euclidean,
+    };
+    let name = document.get("name").and_then(Json::as_str).unwrap_or(name);
+    Ok(Instance {
+        name: name.to_string(),
+        nodes,
+        costs,
+        metric,
+    })
+}
+
+pub fn parse_instance(text: &str, format: Format, name: &str) -> Result<Instance, String> {
+    let (nodes, costs) = match format {
+        Format::Tsplib => {
+            let instance = tsplib::parse_tsp(text)?;
+            let Some(metric) = instance.metric.kind() else {
+                return Err(format!(
+                    "{} distances have no equivalent in the other formats, solve the tsplib file",
+                    instance.metric.weight_type().name()
+                ));
+            };
+            return Ok(Instance {
+                name: instance.name,
+                nodes: instance.nodes,
+                costs: NodeCosts::default(),
+                metric,
+            });
+        }
+        Format::Json => return parse_json_instance(text, name),
+        Format::GeoJson => {
+            let stops = geo::parse_geojson(text)?;
+            (geo::project(&stops), NodeCosts::default())
+        }
+        Format::Plain => formats::parse_instance(text)?,
+        Format::Csv => parse_csv_instance(text)?,
+    };
+    Ok(Instance {
+        name: name.to_string(),
+        nodes,
+        costs,
+        metric: metric_comment(text)?,
+    })
+}
+
+/// the instance as `format`, with warnings about anything the format can't keep.
+/// errors for geojson, which the nodes' coordinates can't be turned back into
+pub fn format_instance(
+    instance: &Instance,
+    format: Format,
+) -> Result<(String, Vec<String>), String> {
+    let mut warnings = Vec::new();
+    let with_costs = !instance.costs.is_neutral();
+    // writing to a String never fails
+    let mut text = String::new();
+    match format {
+        Format::Tsplib => {
+            if with_costs {
+                warnings.push("tsplib has no service times or weights, dropped them".to_string());
+            }
+            if instance.metric == MetricKind::Euclidean {
+                warnings.push(
+                    "written as EXACT_2D, tools that only know EUC_2D will round distances"
+                        .to_string(),
+                );
+            }
+            text = tsplib::format_tsp(&instance.name, instance.metric, &instance.nodes);
+        }
+        Format::Json => {
+            let nodes: Vec<String> = instance
+                .nodes
+                .iter()
+                .map(|node| {
+                    let mut fields =
+                        format!(r#"{{"id": {}, "x": {}, "y": {}"#, node.id, node.x, node.y);
+                    if with_costs {
+                        let _ = write!(
+                            fields,
+                            r#", "service": {}, "weight": {}"#,
+                            instance.costs.service(node.id),
+                            instance.costs.weight(node.id)
+                        );
+                    }
+                    fields + "}"
+                })
+                .collect();
+            let _ = writeln!(
+                text,
+                "{{\n  \"name\": {},\n  \"metric\": {},\n  \"nodes\": [\n    {}\n  ]\n}}",
+                json::quote(&instance.name),
+                json::quote(instance.metric.name()),
+                nodes.join(",\n    ")
+            );
+        }
+        Format::Plain | Format::Csv => {
+            let _ = writeln!(text, "# metric: {}", instance.metric.name());
+            let separator = if format == Format::Csv { "," } else { " " };
+            if format == Format::Csv {
+                text.push_str(if with_costs {
+                    "id,x,y,service,weight\n"
+                } else {
+                    "id,x,y\n"
+                });
+            } else if with_costs {
+                text.push_str("# id x y service weight\n");
+            } else {
+                text.push_str("# id x y\n");
+            }
+            for node in &instance.nodes {
+                let mut fields = vec![node.id.to_string(), node.x.to_string(), node.y.to_string()];
+                if with_costs {
+                    fields.push(instance.costs.service(node.id).to_string());
+                    fields.push(instance.costs.weight(node.id).to_string());
+                }
+                let _ = writeln!(text, "{}", fields.join(separator));
+            }
+        }
+        Format::GeoJson => return Err(GEOJSON_READ_ONLY.to_string()),
+    }
+    Ok((text, warnings))
+}
+
+const GEOJSON_READ_ONLY: &str =
+    "geojson needs latitudes and longitudes, instances only have planar coordinates to write";
+
+pub fn parse_tour(text: &str, format: Format) -> Result<Vec<usize>, String> {
+    match format {
+        Format::Tsplib => tsplib::parse_tour(text),
+        // a csv tour is the plain one with an `id` header
+        Format::Csv => formats::parse_tour_ids(
+            &text
+                .lines()
+                .filter(|line| line.trim() != "id")
+                .collect::<Vec<_>>()
+                .join("\n"),
+        ),
+        Format::Json => {
+            let ids = json::parse(text)?;
+            let ids = ids.as_array().ok_or("expected a list of ids")?;
+            ids.iter()
+                .map(|id| match id.as_f64() {
+                    Some(id) if id >= 0.0 && id.fract() == 0.0 => Ok(id as usize),
+                    _ => Err(format!("invalid id {:?}", id)),
+                })
+                .collect()
+        }
+        Format::Plain => formats::parse_tour_ids(text),
+        Format::GeoJson => Err("geojson files hold stops, not tours".to_string()),
+    }
+}
+
+/// errors for geojson, like `format_instance`
+pub fn format_tour(name: &str, ids: &[usize], format: Format) -> Result<String, String> {
+    let lines: Vec<String> = ids.iter().map(usize::to_string).collect();
+    Ok(match format {
+        Format::Tsplib => tsplib::format_tour(name, ids),
+        Format::Csv => format!("id\n{}\n", lines.join("\n")),
+        Format::Json => format!("[{}]\n", lines.join(", ")),
+        Format::Plain => lines.join("\n") + "\n",
+        Format::GeoJson => return Err("geojson files hold stops, not tours".to_string()),
+    })
+}
+
+/// the instance of a file in the format of its extension
+pub fn read_instance(path: &str) -> Result<Instance, Box<dyn Error>> {
+    let text = std::fs::read_to_string(path)?;
+    let name = Path::new(path)
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or("tsp");
+    Ok(parse_instance(&text, Format::from_path(path), name)
+        .map_err(|err| format!("{}: {}", path, err))?)
+}
+
+/// reads `input` and writes it to `output`, formats by extension.
+/// returns the warnings about what `output` couldn't keep
+pub fn convert_file(input: &str, output: &str, tour: bool) -> Result<Vec<String>, Box<dyn Error>> {
+    let text = std::fs::read_to_string(input)?;
+    let (from, to) = (Format::from_path(input), Format::from_path(output));
+    let name = Path::new(input)
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or("tsp");
+    let in_context = |err: String| format!("{}: {}", input, err);
+    let (converted, warnings) = if tour {
+        let ids = parse_tour(&text, from).map_err(in_context)?;
+        (format_tour(name, &ids, to)?, Vec::new())
+    } else {
+        let instance = parse_instance(&text, from, name).map_err(in_context)?;
+        format_instance(&instance, to)?
+    };
+    std::fs::write(output, converted)?;
+    Ok(warnings)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::convert::*;
+
+    #[test]
+    fn test_instance_round_trips() {
+        let mut costs = NodeCosts::default();
+        costs.set(1, 0.5, 2.0);
+        let instance = Instance {
+            name: "tiny".to_string(),
+            nodes: vec![
+                Node {
+                    id: 0,
+                    x: 0.0,
+                    y: 0.0,
+                },
+                Node {
+                    id: 1,
+                    x: 3.0,
+                    y: 0.5,
+                },
+                Node {
+                    id: 2,
+                    x: 3.0,
+                    y: 4.0,
+                },
+            ],
+            costs,
+            metric: MetricKind::Integer,
+        };
+        for format in [Format::Plain, Format::Csv, Format::Json] {
+            let (text, warnings) = format_instance(&instance, format).unwrap();
+            assert!(warnings.is_empty());
+            let parsed = parse_instance(&text, format, "tiny").unwrap();
+            assert_eq!(parsed.metric, MetricKind::Integer);
+            assert_eq!(parsed.costs, instance.costs);
+            assert_eq!(parsed.nodes[1].y, 0.5);
+        }
+        let (text, warnings) = format_instance(&instance, Format::Tsplib).unwrap();
+        assert_eq!(warnings.len(), 1);
+        let parsed = parse_instance(&text, Format::Tsplib, "other").unwrap();
+        assert_eq!(parsed.name, "tiny");
+        assert_eq!(parsed.metric, MetricKind::Integer);
+        assert_eq!(Format::from_path("data/a280.tsp"), Format::Tsplib);
+        assert_eq!(Format::from_path("data/br17.atsp"), Format::Tsplib);
+        assert_eq!(Format::from_path("nodes.txt"), Format::Plain);
+
+        // geojson comes in projected and goes out as any other format, but not back
+        let stops = vec![
+            geo::GeoStop {
+                id: 0,
+                lat: 52.52,
+                lon: 13.405,
+                address: "Berlin".to_string(),
+            },
+            geo::GeoStop {
+                id: 4,
+                lat: 48.8566,
+                lon: 2.3522,
+                address: "Paris".to_string(),
+            },
+        ];
+        let text = geo::format_geojson(&stops);
+        assert_eq!(Format::from_path("stops.geojson"), Format::GeoJson);
+        let parsed = parse_instance(&text, Format::GeoJson, "cities").unwrap();
+        assert_eq!(parsed.nodes, geo::project(&stops));
+        assert_eq!(parsed.metric, MetricKind::Euclidean);
+        let (csv, _) = format_instance(&parsed, Format::Csv).unwrap();
+        assert_eq!(
+            parse_instance(&csv, Format::Csv, "cities").unwrap().nodes,
+            parsed.nodes
+        );
+        assert_eq!(
+            format_instance(&parsed, Format::GeoJson).unwrap_err(),
+            GEOJSON_READ_ONLY
+        );
+    }
+
+    #[test]
+    fn test_json_node_lists() {
+        let instance = parse_instance(
+            r#"[{"x": 0.5, "y": 1}, {"x": 2, "y": 3, "weight": 2}]"#,
+            Format::Json,
+            "piped",
+        )
+        .unwrap();
+        assert_eq!(instance.name, "piped");
+        assert_eq!(instance.metric, MetricKind::Euclidean);
+        assert_eq!(instance.nodes[1].id, 1);
+        assert_eq!(instance.nodes[0].x, 0.5);
+        assert_eq!(instance.costs.weight(1), 2.0);
+
+        let errors = [
+            (r#"[{"x": 1}]"#, "node 0: missing 'y'"),
+            (r#"[{"id": -1, "x": 1, "y": 2}]"#, "node 0: invalid id -1"),
+            (
+                r#"{"points": []}"#,
+                "expected a list of nodes or an object with a 'nodes' list",
+            ),
+        ];
+        for (text, error) in errors {
+            assert_eq!(
+                parse_instance(text, Format::Json, "bad").unwrap_err(),
+                error
+            );
+        }
+    }
+
+    #[test]
+    fn test_tour_formats() {
+        for format in [Format::Plain, Format::Csv, Format::Json, Format::Tsplib] {
+            let text = format_tour("tiny", &[2, 0, 1], format).unwrap();
+            assert_eq!(parse_tour(&text, format).unwrap(), vec![2, 0, 1]);
+        }
+        assert!(format_tour("tiny", &[2, 0, 1], Format::GeoJson).is_err());
+    }
+}
@@ -0,0 +1,213 @@
+//! the cross-entropy method: sample tours from a matrix of transition probabilities
+//! and move the matrix towards the best of them
+//!
+//! a tour starts at the first node and goes from node i to an unvisited j with probability
+//! proportional to p(i, j). after every round of samples, p becomes `smoothing` times the share of
+//! the elite tours, the shortest `elite` fraction, that go from i to j, plus the rest of the old p.
+//! the matrix starts uniform and ends up concentrated on a few edges per node
+
+use std::str::FromStr;
+
+use rand::rngs::StdRng;
+use rand::Rng;
+
+use crate::metric::Metric;
+use crate::{cancel, events, rng, Node};
+
+/// everything `tsp_cross_entropy` can be tuned with
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CeParams {
+    /// rounds of sampling
+    pub iterations: usize,
+    /// tours sampled per round
+    pub samples: usize,
+    /// share of the samples the matrix moves towards
+    pub elite: f64,
+    /// how far it moves, 1 forgets the old matrix entirely
+    pub smoothing: f64,
+}
+
+impl Default for CeParams {
+    fn default() -> Self {
+        CeParams {
+            iterations: 100,
+            samples: 1000,
+            elite: 0.1,
+            smoothing: 0.7,
+        }
+    }
+}
+
+/// `key=value` pairs separated by `:` over the defaults, e.g. `samples=500:smoothing=0.5`
+impl FromStr for CeParams {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        fn value<T: FromStr>(key: &str, value: &str) -> Result<T, String>
+        where
+            T::Err: std::fmt::Display,
+        {
+            value
+                .parse()
+                .map_err(|err| format!("invalid {} '{}': {}", key, value, err))
+        }
+
+        let mut params = CeParams::default();
+        for pair in s.split(':').filter(|p| !p.is_empty()) {
+            let Some((key, v)) = pair.split_once('=') else {
+                return Err(format!("expected key=value, got '{}'", pair));
+            };
+            match key {
+                "iterations" => params.iterations = value(key, v)?,
+                "samples" => params.samples = value(key, v)?,
+                "elite" => params.elite = value(key, v)?,
+                "smoothing" => params.smoothing = value(key, v)?,
+                _ => {
+                    return Err(format!(
+                        "unknown parameter '{}', try iterations, samples, elite or smoothing",
+                        key
+                    ))
+                }
+            }
+        }
+        if params.samples == 0 {
+            return Err("every round needs at least one sample".to_string());
+        }
+        if !(0.0..=1.0).contains(&params.elite) || !(0.0..=1.0).contains(&params.smoothing) {
+            return Err("elite and smoothing have to be between 0 and 1".to_string());
+        }
+        Ok(params)
+    }
+}
+
+/// the shortest tour sampled and how the rounds went
+#[derive(Debug, Clone)]
+pub struct CeRun {
+    pub tour: Vec<Node>,
+    /// length of the shortest tour so far after every round
+    pub history: Vec<f64>,
+}
+
+/// a tour of positions from the first node, following the probabilities
+fn sample(probabilities: &[f64], n: usize, rng: &mut StdRng) -> Vec<usize> {
+    let mut visited = vec![false; n];
+    let mut tour = Vec::with_capacity(n);
+    let mut current = 0;
+    visited[0] = true;
+    tour.push(0);
+    for _ in 1..n {
+        let row = &probabilities[current * n..(current + 1) * n];
+        let total: f64 = (0..n).filter(|&j| !visited[j]).map(|j| row[j]).sum();
+        let mut ball = rng.gen::<f64>() * total;
+        // rounding can leave the ball past the last candidate, which then gets it
+        let mut next = n;
+        for j in (0..n).filter(|&j| !visited[j]) {
+            next = j;
+            if ball < row[j] {
+                break;
+            }
+            ball -= row[j];
+        }
+        visited[next] = true;
+        tour.push(next);
+        current = next;
+    }
+    tour
+}
+
+/// samples tours on `threads` threads, the same ones for any thread count
+pub fn tsp_cross_entropy(
+    nodes: &[Node],
+    metric: &dyn Metric,
+    params: &CeParams,
+    threads: usize,
+    rng: &mut StdRng,
+) -> CeRun {
+    let n = nodes.len();
+    if n < 4 {
+        return CeRun {
+            tour: nodes.to_vec(),
+            history: Vec::new(),
+        };
+    }
+    let length = |tour: &[usize]| -> f64 {
+        (0..n)
+            .map(|k| metric.distance(&nodes[tour[k]], &nodes[tour[(k + 1) % n]]))
+            .sum()
+    };
+    let recording = events::is_recording();
+    let elites = ((params.elite * params.samples as f64).ceil() as usize).clamp(1, params.samples);
+
+    let mut probabilities = vec![1.0 / (n - 1) as f64; n * n];
+    let mut best: (f64, Vec<usize>) = (length(&(0..n).collect::<Vec<_>>()), (0..n).collect());
+    let mut history = Vec::with_capacity(params.iterations);
+    for iteration in 0..params.iterations {
+        if cancel::is_cancelled() {
+            break;
+        }
+        let master = rng.gen::<u64>();
+        let mut samples: Vec<(f64, Vec<usize>)> = rng::parallel_map(params.samples, threads, |i| {
+            let tour = sample(&probabilities, n, &mut rng::stream_rng(master, i as u64));
+            (length(&tour), tour)
+        });
+        samples.sort_by(|a, b| a.0.total_cmp(&b.0));
+        if samples[0].0 < best.0 {
+            best = samples[0].clone();
+        }
+
+        let mut shares = vec![0.0; n * n];
+        for (_, tour) in &samples[..elites] {
+            for k in 0..n {
+                shares[tour[k] * n + tour[(k + 1) % n]] += 1.0 / elites as f64;
+            }
+        }
+        for (p, share) in probabilities.iter_mut().zip(shares) {
+            *p = params.smoothing * share + (1.0 - params.smoothing) * *p;
+        }
+
+        history.push(best.0);
+        if recording {
+            let tour: Vec<Node> = best.1.iter().map(|&i| nodes[i].clone()).collect();
+            events::record(Some(iteration as u64), best.0, None, &tour);
+        }
+    }
+    CeRun {
+        tour: best.1.iter().map(|&i| nodes[i].clone()).collect(),
+        history,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cross_entropy::*;
+    use crate::metric::Euclidean;
+    use crate::random_nodes;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_converges_and_ignores_threads() {
+        let nodes = random_nodes(25, &mut StdRng::seed_from_u64(40));
+        let params: CeParams = "iterations=60:samples=300".parse().unwrap();
+        let run = tsp_cross_entropy(
+            &nodes,
+            &Euclidean,
+            &params,
+            1,
+            &mut StdRng::seed_from_u64(41),
+        );
+        let ids: Vec<usize> = run.tour.iter().map(|n| n.id).collect();
+        assert!(crate::tour::Tour::from_ids(&ids, &nodes).is_ok());
+        assert_eq!(run.history.len(), 60);
+        assert!(run.history.windows(2).all(|w| w[1] <= w[0]));
+        assert!(run.history[59] < run.history[0] * 0.7);
+
+        let threaded = tsp_cross_entropy(
+            &nodes,
+            &Euclidean,
+            &params,
+            4,
+            &mut StdRng::seed_from_u64(41),
+        );
+        assert_eq!(threaded.history, run.history);
+    }
+}
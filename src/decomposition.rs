@@ -0,0 +1,210 @@
+//! divide and conquer for instances too big to solve as a whole
+//!
+//! karp style: the bounding box is cut into a grid of cells with about `cell_size` nodes each,
+//! every cell is solved on its own and the cell tours are stitched together in an order
+//! that walks from neighboring cell to neighboring cell and ends next to where it started.
+//! a windowed 2-opt around every seam then cleans up the joins
+
+use rand::rngs::StdRng;
+use rand::Rng;
+
+use crate::metric::Metric;
+use crate::rng::stream_rng;
+use crate::solvers::Solver;
+use crate::Node;
+
+/// positions on either side of a seam the boundary 2-opt looks at
+const SEAM_WINDOW: usize = 16;
+
+/// cells of a `side` x `side` grid, rows snake through columns 1.. and column 0 leads back,
+/// so consecutive cells always touch and the last one touches the first. `side` has to be even
+fn cell_order(side: usize) -> Vec<(usize, usize)> {
+    let mut order = Vec::with_capacity(side * side);
+    for row in 0..side {
+        let columns: Vec<usize> = if row % 2 == 0 {
+            (1..side).collect()
+        } else {
+            (1..side).rev().collect()
+        };
+        order.extend(columns.into_iter().map(|column| (row, column)));
+    }
+    order.extend((0..side).rev().map(|row| (row, 0)));
+    order
+}
+
+/// the nodes of each cell of the grid, by row and column
+fn partition(nodes: &[Node], side: usize) -> Vec<Vec<Vec<Node>>> {
+    let (min_x, max_x, min_y, max_y) = nodes.iter().fold(
+        (
+            f64::INFINITY,
+            f64::NEG_INFINITY,
+            f64::INFINITY,
+            f64::NEG_INFINITY,
+        ),
+        |(min_x, max_x, min_y, max_y), n| {
+            (
+                min_x.min(n.x),
+                max_x.max(n.x),
+                min_y.min(n.y),
+                max_y.max(n.y),
+            )
+        },
+    );
+    let cell = |value: f64, min: f64, max: f64| {
+        let width = (max - min).max(f64::MIN_POSITIVE);
+        (((value - min) / width * side as f64) as usize).min(side - 1)
+    };
+    let mut cells = vec![vec![Vec::new(); side]; side];
+    for node in nodes {
+        cells[cell(node.y, min_y, max_y)][cell(node.x, min_x, max_x)].push(node.clone());
+    }
+    cells
+}
+
+/// appends the closed `cycle` to `path`, opened at the edge that's cheapest to replace
+/// with the connection from the end of `path`, in whichever direction is cheaper
+fn append_cycle(path: &mut Vec<Node>, mut cycle: Vec<Node>, metric: &dyn Metric) {
+    let Some(end) = path.last() else {
+        path.extend(cycle);
+        return;
+    };
+    let m = cycle.len();
+    // (cost, index the path continues at, reversed)
+    let mut best = (f64::INFINITY, 0, false);
+    for i in 0..m {
+        let (previous, current) = (&cycle[(i + m - 1) % m], &cycle[i]);
+        let removed = if m > 1 {
+            metric.distance(previous, current)
+        } else {
+            0.0
+        };
+        let forward = metric.distance(end, current) - removed;
+        let backward = metric.distance(end, previous) - removed;
+        if forward < best.0 {
+            best = (forward, i, false);
+        }
+        if backward < best.0 {
+            best = (backward, (i + m - 1) % m, true);
+        }
+    }
+    let (_, start, reversed) = best;
+    if reversed {
+        // previous, previous - 1, ..., current
+        cycle.rotate_left(start + 1);
+        cycle.reverse();
+    } else {
+        cycle.rotate_left(start);
+    }
+    path.extend(cycle);
+}
+
+/// 2-opt restricted to `nodes[start..end]`, until no reversal inside it helps anymore.
+/// O(window²) per pass, only edges within the window are touched
+fn improve_window(nodes: &mut [Node], start: usize, end: usize, metric: &dyn Metric) {
+    const MAX_PASSES: usize = 10;
+    const EPSILON: f64 = 1e-12;
+
+    for _ in 0..MAX_PASSES {
+        let mut improved = false;
+        for i in start..end.saturating_sub(3) {
+            for j in i + 2..end - 1 {
+                // edges (i, i+1) and (j, j+1) become (i, j) and (i+1, j+1)
+                let delta = metric.distance(&nodes[i], &nodes[j])
+                    + metric.distance(&nodes[i + 1], &nodes[j + 1])
+                    - metric.distance(&nodes[i], &nodes[i + 1])
+                    - metric.distance(&nodes[j], &nodes[j + 1]);
+                if delta < -EPSILON {
+                    nodes[i + 1..=j].reverse();
+                    improved = true;
+                }
+            }
+        }
+        if !improved {
+            break;
+        }
+    }
+}
+
+/// tour through all nodes, `inner` solves the cells
+pub fn solve(
+    nodes: &[Node],
+    metric: &dyn Metric,
+    cell_size: usize,
+    inner: &dyn Solver,
+    rng: &mut StdRng,
+) -> Vec<Node> {
+    if nodes.len() <= cell_size.max(1) {
+        return inner.solve(nodes, metric, rng);
+    }
+    // even, see `cell_order`
+    let side = ((nodes.len() as f64 / cell_size.max(1) as f64).sqrt().ceil() as usize).max(2);
+    let side = side + side % 2;
+    let mut cells = partition(nodes, side);
+
+    // every cell gets its own stream, so a cell's tour doesn't depend on the cells before it
+    let seed: u64 = rng.gen();
+    let mut tour = Vec::with_capacity(nodes.len());
+    let mut seams = Vec::new();
+    for (index, (row, column)) in cell_order(side).into_iter().enumerate() {
+        let cell = std::mem::take(&mut cells[row][column]);
+        if cell.is_empty() {
+            continue;
+        }
+        let cell_tour = inner.solve(&cell, metric, &mut stream_rng(seed, index as u64));
+        seams.push(tour.len());
+        append_cycle(&mut tour, cell_tour, metric);
+    }
+
+    for seam in seams.into_iter().skip(1) {
+        let start = seam.saturating_sub(SEAM_WINDOW);
+        let end = (seam + SEAM_WINDOW).min(tour.len());
+        improve_window(&mut tour, start, end, metric);
+    }
+    tour
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::decomposition::*;
+    use crate::metric::Euclidean;
+    use crate::solvers::NearestNeighbor;
+    use crate::tour::Tour;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_cell_order_walks_neighbors() {
+        let order = cell_order(4);
+        assert_eq!(order.len(), 16);
+        for k in 0..order.len() {
+            let ((r1, c1), (r2, c2)) = (order[k], order[(k + 1) % order.len()]);
+            assert_eq!(r1.abs_diff(r2) + c1.abs_diff(c2), 1);
+        }
+    }
+
+    #[test]
+    fn test_decomposition_tour() {
+        let mut rng = StdRng::seed_from_u64(3);
+        let nodes = crate::random_nodes(3000, &mut rng);
+        let tour = solve(&nodes, &Euclidean, 100, &NearestNeighbor, &mut rng);
+        // visits every node exactly once
+        let ids: Vec<usize> = tour.iter().map(|n| n.id).collect();
+        assert!(Tour::from_ids(&ids, &nodes).is_ok());
+        assert_eq!(ids.len(), nodes.len());
+        // nearest neighbor on the whole instance is about 0.92 * sqrt(n * area)
+        let length = Euclidean.tour_length(&tour);
+        assert!(
+            length < 1.1 * 0.92 * (3000.0f64).sqrt(),
+            "length {}",
+            length
+        );
+
+        let mut crossed = [(0.0, 0.0), (1.0, 1.0), (1.0, 0.0), (0.0, 1.0)]
+            .iter()
+            .enumerate()
+            .map(|(id, &(x, y))| Node { id, x, y })
+            .collect::<Vec<_>>();
+        crossed.push(crossed[0].clone());
+        improve_window(&mut crossed, 0, 5, &Euclidean);
+        assert_eq!(Euclidean.tour_length(&crossed[..4]), 4.0);
+    }
+}
@@ -0,0 +1,105 @@
+use std::collections::BTreeSet;
+use std::fmt::Write;
+
+use crate::geometry::{convex_hull, knn_edges, mst_edges};
+use crate::metric::Metric;
+use crate::tour::{Edge, Tour};
+use crate::Node;
+
+/// graphviz scale, unit square coordinates become a 10x10 inch drawing.
+/// `neato -n` reads positions in points, 72 to the inch
+const SCALE: f64 = 720.0;
+
+/// what goes into a dot export besides the nodes
+pub struct DotLayers<'a> {
+    pub tour: Option<&'a Tour>,
+    /// connect every node to its k nearest neighbors
+    pub knn: Option<usize>,
+    pub mst: bool,
+    pub hull: bool,
+}
+
+/// undirected graphviz graph of the nodes at their positions plus the requested layers
+///
+/// render with `neato -n -Tpng`, every layer gets its own color
+/// and a `layer` attribute for network analysis tools
+pub fn to_dot(nodes: &[Node], layers: &DotLayers, metric: &dyn Metric) -> String {
+    let mut dot = String::new();
+    // writing to a String never fails
+    let _ = writeln!(dot, "graph tsp {{");
+    let _ = writeln!(dot, "    node [shape=circle, width=0.2, fontsize=8];");
+    for node in nodes {
+        let _ = writeln!(
+            dot,
+            "    {} [pos=\"{:.4},{:.4}!\"];",
+            node.id,
+            SCALE * node.x,
+            SCALE * node.y
+        );
+    }
+
+    let mut layer = |name: &str, color: &str, edges: &BTreeSet<Edge>| {
+        for (a, b) in edges {
+            let _ = writeln!(
+                dot,
+                "    {} -- {} [layer=\"{}\", color=\"{}\"];",
+                a, b, name, color
+            );
+        }
+    };
+    if let Some(k) = layers.knn {
+        layer("knn", "gray70", &knn_edges(nodes, k, metric));
+    }
+    if layers.mst {
+        layer("mst", "blue", &mst_edges(nodes, metric));
+    }
+    if layers.hull {
+        let hull = convex_hull(nodes);
+        let edges = (0..hull.len())
+            .map(|k| (hull[k], hull[(k + 1) % hull.len()]))
+            .filter(|(a, b)| a != b)
+            .map(|(a, b)| (a.min(b), a.max(b)))
+            .collect();
+        layer("hull", "red", &edges);
+    }
+    if let Some(tour) = layers.tour {
+        layer("tour", "black", &tour.edges());
+    }
+
+    let _ = writeln!(dot, "}}");
+    dot
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dot::*;
+    use crate::metric::Euclidean;
+
+    #[test]
+    fn test_mst_of_line() {
+        let nodes: Vec<Node> = [0.0, 0.3, 0.1, 0.7]
+            .iter()
+            .enumerate()
+            .map(|(id, &x)| Node { id, x, y: 0.0 })
+            .collect();
+        let expected: BTreeSet<Edge> = [(0, 2), (1, 2), (1, 3)].into_iter().collect();
+        assert_eq!(mst_edges(&nodes, &Euclidean), expected);
+        assert_eq!(
+            knn_edges(&nodes, 1, &Euclidean),
+            [(0, 2), (1, 2), (1, 3)].into_iter().collect()
+        );
+
+        let dot = to_dot(
+            &nodes,
+            &DotLayers {
+                tour: None,
+                knn: None,
+                mst: true,
+                hull: false,
+            },
+            &Euclidean,
+        );
+        assert!(dot.contains("1 [pos=\"216.0000,0.0000!\"];"));
+        assert_eq!(dot.matches("layer=\"mst\"").count(), 3);
+    }
+}
@@ -0,0 +1,234 @@
+//! checkpoint logs of solver runs, so plots and animations can be redrawn without solving again
+//!
+//! while recording, the pipeline logs the tour after every stage and simulated annealing
+//! logs it every so many iterations. the log names tours by node ids,
+//! the instance comes from its seed and size or the instance file when replaying.
+//! `collect_history` gets the lengths alone, on one thread and without a log
+
+use std::cell::RefCell;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use crate::Node;
+
+/// checkpoints simulated annealing logs per run, whatever its iteration count
+pub const CHECKPOINTS_PER_RUN: u32 = 200;
+
+/// a tour at some point of the run
+#[derive(Debug, Clone, PartialEq)]
+pub struct Checkpoint {
+    /// 0 is the starting tour, 1 the first stage and so on
+    pub stage: usize,
+    /// iteration inside the stage, None once the stage finished
+    pub iteration: Option<u64>,
+    pub length: f64,
+    pub temperature: Option<f64>,
+    pub tour: Vec<usize>,
+}
+
+/// everything needed to redraw a run
+#[derive(Debug, Clone, PartialEq)]
+pub struct EventLog {
+    pub seed: u64,
+    /// node count of the instance
+    pub nodes: usize,
+    pub stages: Vec<String>,
+    pub checkpoints: Vec<Checkpoint>,
+}
+
+// recording is process wide like `cancel::INTERRUPT`, only `solve --record` turns it on
+static RECORDING: AtomicBool = AtomicBool::new(false);
+static STAGE: AtomicUsize = AtomicUsize::new(0);
+static CHECKPOINTS: Mutex<Vec<Checkpoint>> = Mutex::new(Vec::new());
+
+thread_local! {
+    // lengths for `collect_history`, None while nothing collects on this thread
+    static HISTORY: RefCell<Option<Vec<f64>>> = const { RefCell::new(None) };
+}
+
+pub fn start_recording() {
+    RECORDING.store(true, Ordering::Relaxed);
+}
+
+/// cheap enough to call every iteration, also true while `collect_history` runs on this thread
+pub fn is_recording() -> bool {
+    RECORDING.load(Ordering::Relaxed) || HISTORY.with(|history| history.borrow().is_some())
+}
+
+/// runs `f` and returns the lengths it recorded on this thread, whether or not the
+/// log records too. nested calls see their own lengths, which count for the outer one as well
+pub fn collect_history<T>(f: impl FnOnce() -> T) -> (T, Vec<f64>) {
+    let outer = HISTORY.with(|history| history.borrow_mut().replace(Vec::new()));
+    let result = f();
+    let lengths = HISTORY.with(|history| {
+        let lengths = history.borrow_mut().take().unwrap_or_default();
+        if let Some(mut outer) = outer {
+            outer.extend_from_slice(&lengths);
+            *history.borrow_mut() = Some(outer);
+        }
+        lengths
+    });
+    (result, lengths)
+}
+
+/// checkpoints recorded from now on belong to `stage`
+pub fn set_stage(stage: usize) {
+    STAGE.store(stage, Ordering::Relaxed);
+}
+
+/// logs the tour if recording is on
+pub fn record(iteration: Option<u64>, length: f64, temperature: Option<f64>, tour: &[Node]) {
+    HISTORY.with(|history| {
+        if let Some(lengths) = history.borrow_mut().as_mut() {
+            lengths.push(length);
+        }
+    });
+    if !RECORDING.load(Ordering::Relaxed) {
+        return;
+    }
+    let checkpoint = Checkpoint {
+        stage: STAGE.load(Ordering::Relaxed),
+        iteration,
+        length,
+        temperature,
+        tour: tour.iter().map(|n| n.id).collect(),
+    };
+    // a panicking recorder elsewhere doesn't make the checkpoints wrong
+    CHECKPOINTS
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .push(checkpoint);
+}
+
+/// the checkpoints recorded so far, leaves none behind
+pub fn take_checkpoints() -> Vec<Checkpoint> {
+    std::mem::take(&mut *CHECKPOINTS.lock().unwrap_or_else(|p| p.into_inner()))
+}
+
+impl EventLog {
+    /// `seed`, `nodes` and one `stage <name>` line per stage, then one
+    /// `checkpoint <stage> <iteration|end> <length> <temperature|-> <ids>` line per checkpoint
+    pub fn format(&self) -> String {
+        // writing to a String never fails
+        let mut text = String::from("# tsp event log\n");
+        let _ = writeln!(text, "seed {}\nnodes {}", self.seed, self.nodes);
+        for stage in &self.stages {
+            let _ = writeln!(text, "stage {}", stage);
+        }
+        for checkpoint in &self.checkpoints {
+            let iteration = checkpoint
+                .iteration
+                .map_or("end".to_string(), |i| i.to_string());
+            let temperature = checkpoint
+                .temperature
+                .map_or("-".to_string(), |t| format!("{:?}", t));
+            let _ = write!(
+                text,
+                "checkpoint {} {} {:?} {}",
+                checkpoint.stage, iteration, checkpoint.length, temperature
+            );
+            for id in &checkpoint.tour {
+                let _ = write!(text, " {}", id);
+            }
+            text.push('\n');
+        }
+        text
+    }
+
+    pub fn parse(text: &str) -> Result<EventLog, String> {
+        let mut log = EventLog {
+            seed: 0,
+            nodes: 0,
+            stages: Vec::new(),
+            checkpoints: Vec::new(),
+        };
+        for (number, line) in text.lines().enumerate() {
+            let error = |message: &str| format!("line {}: {}", number + 1, message);
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (kind, rest) = line.split_once(' ').unwrap_or((line, ""));
+            match kind {
+                "seed" => log.seed = rest.parse().map_err(|_| error("invalid seed"))?,
+                "nodes" => log.nodes = rest.parse().map_err(|_| error("invalid node count"))?,
+                "stage" => log.stages.push(rest.to_string()),
+                "checkpoint" => {
+                    let fields: Vec<&str> = rest.split_whitespace().collect();
+                    let [stage, iteration, length, temperature, ids @ ..] = &fields[..] else {
+                        return Err(error(
+                            "expected 'checkpoint stage iteration length temperature ids'",
+                        ));
+                    };
+                    log.checkpoints.push(Checkpoint {
+                        stage: stage.parse().map_err(|_| error("invalid stage"))?,
+                        iteration: match *iteration {
+                            "end" => None,
+                            i => Some(i.parse().map_err(|_| error("invalid iteration"))?),
+                        },
+                        length: length.parse().map_err(|_| error("invalid length"))?,
+                        temperature: match *temperature {
+                            "-" => None,
+                            t => Some(t.parse().map_err(|_| error("invalid temperature"))?),
+                        },
+                        tour: ids
+                            .iter()
+                            .map(|id| id.parse().map_err(|_| error("invalid node id")))
+                            .collect::<Result<_, _>>()?,
+                    });
+                }
+                _ => return Err(error(&format!("unknown entry '{}'", kind))),
+            }
+        }
+        Ok(log)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::events::*;
+
+    #[test]
+    fn test_log_round_trip() {
+        let log = EventLog {
+            seed: 42,
+            nodes: 3,
+            stages: vec![
+                "nearest neighbor".to_string(),
+                "simulated annealing".to_string(),
+            ],
+            checkpoints: vec![
+                Checkpoint {
+                    stage: 2,
+                    iteration: Some(1000),
+                    length: 0.1 + 0.2,
+                    temperature: Some(0.05),
+                    tour: vec![2, 0, 1],
+                },
+                Checkpoint {
+                    stage: 2,
+                    iteration: None,
+                    length: 2.5,
+                    temperature: None,
+                    tour: vec![0, 1, 2],
+                },
+            ],
+        };
+        let text = log.format();
+        assert!(text.contains("checkpoint 2 end 2.5 - 0 1 2\n"));
+        assert_eq!(EventLog::parse(&text).unwrap(), log);
+        assert!(EventLog::parse("checkpoint 1 end").is_err());
+    }
+
+    #[test]
+    fn test_collect_history_nests() {
+        let ((), outer) = collect_history(|| {
+            assert!(is_recording());
+            record(None, 3.0, None, &[]);
+            let ((), inner) = collect_history(|| record(None, 2.0, None, &[]));
+            assert_eq!(inner, vec![2.0]);
+        });
+        assert_eq!(outer, vec![3.0, 2.0]);
+    }
+}
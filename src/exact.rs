@@ -0,0 +1,227 @@
+//! exact comparisons of euclidean tour lengths, behind the `exact` feature
+//!
+//! every f64 is a dyadic rational, so shifting all coordinates to a common
+//! power of two turns them into big integers without losing anything.
+//! squared distances are then exact integers and a tour length is a sum of
+//! square roots of integers, which gets compared with interval arithmetic
+//! at increasing precision where floats would just guess
+
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use itertools::Itertools;
+use num_bigint::{BigInt, BigUint};
+
+use crate::metric::Metric;
+use crate::tour::Tour;
+use crate::Node;
+
+/// fractional bits of the square roots in the first comparison round, doubled every round
+const START_BITS: usize = 64;
+/// sums that still overlap at this precision are treated as equal
+const MAX_BITS: usize = 4096;
+/// float lengths closer than this (relative) are compared exactly
+const NEAR_TIE: f64 = 1e-9;
+
+/// mantissa and exponent with `x == mantissa * 2^exponent`, for finite x
+fn decompose(x: f64) -> (i64, i64) {
+    let bits = x.to_bits();
+    let sign = if bits >> 63 == 0 { 1 } else { -1 };
+    let exponent = ((bits >> 52) & 0x7ff) as i64;
+    let fraction = (bits & ((1 << 52) - 1)) as i64;
+    if exponent == 0 {
+        // subnormal
+        (sign * fraction, -1074)
+    } else {
+        (sign * (fraction | 1 << 52), exponent - 1075)
+    }
+}
+
+/// the nodes' coordinates as integers in units of a common power of two
+pub struct ExactEuclidean {
+    coordinates: HashMap<usize, (BigInt, BigInt)>,
+}
+
+impl ExactEuclidean {
+    pub fn new(nodes: &[Node]) -> Result<Self, String> {
+        if let Some(node) = nodes.iter().find(|n| !n.x.is_finite() || !n.y.is_finite()) {
+            return Err(format!("node {} has a non finite coordinate", node.id));
+        }
+        let parts = |node: &Node| [decompose(node.x), decompose(node.y)];
+        let unit = nodes
+            .iter()
+            .flat_map(parts)
+            .filter(|&(mantissa, _)| mantissa != 0)
+            .map(|(_, exponent)| exponent)
+            .min()
+            .unwrap_or(0);
+        // zeros have no meaningful exponent, every nonzero one is at least `unit`
+        let integer = |(mantissa, exponent): (i64, i64)| match mantissa {
+            0 => BigInt::from(0),
+            _ => BigInt::from(mantissa) << (exponent - unit),
+        };
+        let coordinates = nodes
+            .iter()
+            .map(|node| {
+                let [x, y] = parts(node);
+                (node.id, (integer(x), integer(y)))
+            })
+            .collect();
+        Ok(ExactEuclidean { coordinates })
+    }
+
+    /// squared distance in units of the common power of two, squared
+    pub fn squared_distance(&self, a: &Node, b: &Node) -> BigUint {
+        let (ax, ay) = &self.coordinates[&a.id];
+        let (bx, by) = &self.coordinates[&b.id];
+        let dx = ax - bx;
+        let dy = ay - by;
+        (&dx * &dx + &dy * &dy)
+            .to_biguint()
+            .expect("sums of squares are never negative")
+    }
+
+    fn squared_edges(&self, tour: &[&Node]) -> Vec<BigUint> {
+        let mut edges: Vec<BigUint> = tour
+            .iter()
+            .circular_tuple_windows()
+            .map(|(a, b)| self.squared_distance(a, b))
+            .collect();
+        edges.sort();
+        edges
+    }
+
+    /// compares the lengths of two closed tours
+    ///
+    /// edges both tours have cancel out, the sums of the square roots of the rest
+    /// are bracketed by integer square roots until the brackets separate.
+    /// returns `Equal` for tours with the same edge lengths and for differences
+    /// below 2^-4096 of the coordinate unit, which is as close as exact gets here
+    pub fn compare_tours(&self, a: &[&Node], b: &[&Node]) -> Ordering {
+        let (a, b) = cancel_common(self.squared_edges(a), self.squared_edges(b));
+        if a.is_empty() && b.is_empty() {
+            return Ordering::Equal;
+        }
+        let mut bits = START_BITS;
+        while bits <= MAX_BITS {
+            // floor(sqrt(s * 4^bits)) <= sqrt(s) * 2^bits < floor(...) + 1
+            let lower = |edges: &[BigUint]| -> BigUint {
+                edges.iter().map(|s| (s << (2 * bits)).sqrt()).sum()
+            };
+            let (lower_a, lower_b) = (lower(&a), lower(&b));
+            if &lower_a + a.len() <= lower_b {
+                return Ordering::Less;
+            }
+            if &lower_b + b.len() <= lower_a {
+                return Ordering::Greater;
+            }
+            bits *= 2;
+        }
+        Ordering::Equal
+    }
+}
+
+/// removes the values both sorted lists contain, as often as both contain them
+fn cancel_common(a: Vec<BigUint>, b: Vec<BigUint>) -> (Vec<BigUint>, Vec<BigUint>) {
+    let mut only_a = Vec::new();
+    let mut only_b = Vec::new();
+    for pair in a.into_iter().merge_join_by(b, |x, y| x.cmp(y)) {
+        match pair {
+            itertools::EitherOrBoth::Left(x) => only_a.push(x),
+            itertools::EitherOrBoth::Right(y) => only_b.push(y),
+            itertools::EitherOrBoth::Both(_, _) => {}
+        }
+    }
+    (only_a, only_b)
+}
+
+/// brute force where near-ties are decided exactly and real ties go to the
+/// tour with the smallest canonical ids, so the result doesn't depend on float noise
+/// or the input order
+///
+/// exact comparisons use euclidean distances, `metric` only screens out the clear cases
+pub fn tsp_brute_force_exact(nodes: &[Node], metric: &dyn Metric) -> Result<Vec<Node>, String> {
+    let exact = ExactEuclidean::new(nodes)?;
+    let canonical = |tour: &[&Node]| {
+        Tour::from(tour.iter().map(|&n| n.clone()).collect::<Vec<_>>()).canonical_ids()
+    };
+
+    let mut optimal_tour: Vec<&Node> = nodes.iter().collect();
+    let mut optimal_length = metric.tour_length_refs(&optimal_tour);
+    for tour in nodes.iter().permutations(nodes.len()).unique() {
+        let length = metric.tour_length_refs(&tour);
+        let better = if length < optimal_length * (1.0 - NEAR_TIE) {
+            true
+        } else if length > optimal_length * (1.0 + NEAR_TIE) {
+            false
+        } else {
+            match exact.compare_tours(&tour, &optimal_tour) {
+                Ordering::Less => true,
+                Ordering::Greater => false,
+                Ordering::Equal => canonical(&tour) < canonical(&optimal_tour),
+            }
+        };
+        if better {
+            optimal_length = length;
+            optimal_tour = tour;
+        }
+    }
+    Ok(optimal_tour.into_iter().cloned().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::exact::*;
+    use crate::metric::Euclidean;
+
+    fn node(id: usize, x: f64, y: f64) -> Node {
+        Node { id, x, y }
+    }
+
+    #[test]
+    fn test_compare_near_ties() {
+        // sqrt(2) + sqrt(8) + sqrt(18) == 2 sqrt(18), floats disagree in the last bit
+        let nodes = [
+            node(0, 0.0, 0.0),
+            node(1, 1.0, 1.0),
+            node(2, 3.0, 3.0),
+            node(3, 3.0, 0.0),
+        ];
+        let exact = ExactEuclidean::new(&nodes).unwrap();
+        let [a, b, c, d] = [&nodes[0], &nodes[1], &nodes[2], &nodes[3]];
+        assert_ne!(
+            Euclidean.tour_length_refs(&[a, b, c]),
+            Euclidean.tour_length_refs(&[a, c])
+        );
+        assert_eq!(exact.compare_tours(&[a, b, c], &[a, c]), Ordering::Equal);
+        // 6 + sqrt(18) against 3 + sqrt(18) + sqrt(5) + sqrt(2)
+        assert_eq!(
+            exact.compare_tours(&[a, b, c, d], &[a, c, d, b]),
+            Ordering::Less
+        );
+        assert_eq!(
+            exact.compare_tours(&[a, c, d, b], &[a, b, c, d]),
+            Ordering::Greater
+        );
+
+        assert!(ExactEuclidean::new(&[node(0, f64::NAN, 0.0)]).is_err());
+    }
+
+    #[test]
+    fn test_exact_brute_force_ignores_input_order() {
+        // a square has several optimal tours, all of them tie
+        let square = [
+            node(0, 0.0, 0.0),
+            node(1, 1.0, 0.0),
+            node(2, 1.0, 1.0),
+            node(3, 0.0, 1.0),
+        ];
+        let mut shuffled = square.clone();
+        shuffled.reverse();
+        let canonical = |nodes: Vec<Node>| Tour::from(nodes).canonical_ids();
+        let a = tsp_brute_force_exact(&square, &Euclidean).unwrap();
+        let b = tsp_brute_force_exact(&shuffled, &Euclidean).unwrap();
+        assert_eq!(canonical(a), vec![0, 1, 2, 3]);
+        assert_eq!(canonical(b), vec![0, 1, 2, 3]);
+    }
+}
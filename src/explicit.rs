@@ -0,0 +1,215 @@
+//! instances given by their costs instead of coordinates
+//!
+//! an `ExplicitMatrix` is a full matrix over the ids 0..n, from memory or a csv file,
+//! and the metric of its instance. `directed` ones may cost more one way than the other,
+//! like one-way streets or tsplib's ATSP instances. the nodes are only placeholders on a circle, so tours can
+//! still be drawn and every solver runs unchanged, the ones that measure through the metric
+//! see the costs, geometric shortcuts like neighbor lists from a k-d tree see the circle
+
+use crate::metric::Metric;
+use crate::result_cache::KeyBuilder;
+use crate::Node;
+
+#[derive(Debug, Clone)]
+pub struct ExplicitMatrix {
+    side: usize,
+    /// row by row
+    costs: Vec<f64>,
+    symmetric: bool,
+}
+
+impl ExplicitMatrix {
+    /// checks that the rows are square, symmetric and zero on the diagonal, with finite costs
+    pub fn new(rows: &[Vec<f64>]) -> Result<Self, String> {
+        let matrix = ExplicitMatrix::directed(rows)?;
+        if let Some((i, j)) = matrix.asymmetric_pair() {
+            let side = matrix.side;
+            return Err(format!(
+                "cost from {} to {} is {} but back it's {}, the matrix has to be symmetric",
+                i,
+                j,
+                matrix.costs[i * side + j],
+                matrix.costs[j * side + i]
+            ));
+        }
+        Ok(matrix)
+    }
+
+    /// `new` without the symmetry, costs from row to column
+    pub fn directed(rows: &[Vec<f64>]) -> Result<Self, String> {
+        let side = rows.len();
+        let mut costs = Vec::with_capacity(side * side);
+        for (i, row) in rows.iter().enumerate() {
+            if row.len() != side {
+                return Err(format!("row {} has {} of {} costs", i, row.len(), side));
+            }
+            costs.extend_from_slice(row);
+        }
+        for i in 0..side {
+            for j in 0..side {
+                let cost = costs[i * side + j];
+                if !cost.is_finite() {
+                    return Err(format!("cost {} from {} to {} isn't finite", cost, i, j));
+                }
+                if i == j && cost != 0.0 {
+                    return Err(format!("cost {} from {} to itself isn't 0", cost, i));
+                }
+            }
+        }
+        let mut matrix = ExplicitMatrix {
+            side,
+            costs,
+            symmetric: true,
+        };
+        matrix.symmetric = matrix.asymmetric_pair().is_none();
+        Ok(matrix)
+    }
+
+    /// the first ids that cost something else one way than the other
+    fn asymmetric_pair(&self) -> Option<(usize, usize)> {
+        let side = self.side;
+        (0..side)
+            .flat_map(|i| (i + 1..side).map(move |j| (i, j)))
+            .find(|&(i, j)| self.costs[i * side + j] != self.costs[j * side + i])
+    }
+
+    pub fn len(&self) -> usize {
+        self.side
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.side == 0
+    }
+
+    /// a node per row, on a circle
+    pub fn nodes(&self) -> Vec<Node> {
+        circle(self.side)
+    }
+
+    /// tells matrices apart in cache keys, by a hash of their costs
+    pub fn name(&self) -> String {
+        let mut key = KeyBuilder::unversioned();
+        key.number(self.side as u64);
+        for cost in &self.costs {
+            key.number(cost.to_bits());
+        }
+        format!("explicit {:016x}", key.finish().0)
+    }
+}
+
+impl Metric for ExplicitMatrix {
+    fn distance(&self, from: &Node, to: &Node) -> f64 {
+        assert!(
+            from.id < self.side && to.id < self.side,
+            "only nodes of the instance are measured"
+        );
+        self.costs[from.id * self.side + to.id]
+    }
+
+    fn is_symmetric(&self) -> bool {
+        self.symmetric
+    }
+}
+
+/// `n` nodes evenly on a circle in the unit square, for drawing instances without coordinates
+pub fn circle(n: usize) -> Vec<Node> {
+    (0..n)
+        .map(|id| {
+            let angle = std::f64::consts::TAU * id as f64 / n as f64;
+            Node {
+                id,
+                x: 0.5 + 0.5 * angle.cos(),
+                y: 0.5 + 0.5 * angle.sin(),
+            }
+        })
+        .collect()
+}
+
+/// whether the first line of a csv looks like a cost matrix rather than an `id,x,y` instance
+pub fn is_matrix_csv(text: &str) -> bool {
+    text.lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty() && !line.starts_with('#'))
+        .is_some_and(|header| {
+            let columns: Vec<&str> = header.split(',').map(str::trim).collect();
+            !(columns.contains(&"x") && columns.contains(&"y"))
+        })
+}
+
+/// rows of comma separated costs, the way spreadsheets export them: a header row of labels
+/// and a first column of labels are skipped if they aren't numbers, `#` lines are comments
+pub fn parse_matrix_csv(text: &str) -> Result<Vec<Vec<f64>>, String> {
+    let lines: Vec<(usize, Vec<&str>)> = text
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty() && !line.trim_start().starts_with('#'))
+        .map(|(number, line)| (number + 1, line.split(',').map(str::trim).collect()))
+        .collect();
+    let numeric = |field: &str| field.parse::<f64>().is_ok();
+    let header = lines
+        .first()
+        .is_some_and(|(_, fields)| !fields.iter().skip(1).all(|field| numeric(field)));
+    let rows = &lines[header as usize..];
+    let labels = rows
+        .first()
+        .is_some_and(|(_, fields)| fields.first().is_some_and(|field| !numeric(field)));
+    rows.iter()
+        .map(|(number, fields)| {
+            fields[labels as usize..]
+                .iter()
+                .map(|field| {
+                    field
+                        .parse()
+                        .map_err(|_| format!("line {}: invalid cost '{}'", number, field))
+                })
+                .collect()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::explicit::*;
+
+    #[test]
+    fn test_matrix_instances() {
+        let text =
+            "# driving minutes\n,depot,a,b,c\ndepot,0,5,9,4\na,5,0,3,7\nb,9,3,0,2\nc,4,7,2,0\n";
+        assert!(is_matrix_csv(text));
+        assert!(!is_matrix_csv("id,x,y\n0,1,2\n"));
+        let rows = parse_matrix_csv(text).unwrap();
+        assert_eq!(
+            rows,
+            parse_matrix_csv("0,5,9,4\n5,0,3,7\n9,3,0,2\n4,7,2,0").unwrap()
+        );
+        let matrix = ExplicitMatrix::new(&rows).unwrap();
+        let nodes = matrix.nodes();
+        assert_eq!(nodes.len(), 4);
+        assert_eq!(matrix.tour_length(&nodes), 5.0 + 3.0 + 2.0 + 4.0);
+        assert_eq!(matrix.distance(&nodes[3], &nodes[1]), 7.0);
+
+        // solvers only need the metric
+        let tour = crate::solvers::tsp_two_opt(
+            &crate::solvers::tsp_nearest_neighbor(&nodes, &matrix),
+            &matrix,
+        );
+        assert_eq!(matrix.tour_length(&tour), 14.0);
+
+        let mut asymmetric = rows.clone();
+        asymmetric[0][1] = 6.0;
+        assert!(ExplicitMatrix::new(&asymmetric)
+            .unwrap_err()
+            .contains("has to be symmetric"));
+        assert!(matrix.is_symmetric());
+        let directed = ExplicitMatrix::directed(&asymmetric).unwrap();
+        assert!(!directed.is_symmetric());
+        let backwards: Vec<Node> = nodes.iter().rev().cloned().collect();
+        assert_eq!(directed.tour_length(&nodes), 15.0);
+        assert_eq!(directed.tour_length(&backwards), 14.0);
+        assert!(ExplicitMatrix::new(&rows[1..]).is_err());
+        assert_eq!(
+            parse_matrix_csv("0,1\n1,x").unwrap_err(),
+            "line 2: invalid cost 'x'"
+        );
+    }
+}
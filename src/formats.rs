@@ -0,0 +1,342 @@
+//! plain text instance and solution files
+//!
+//! instances are one `id x y [service [weight]]` line per node,
+//! tours are lists of node ids. `#` starts a comment in both
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::Write;
+use std::io::{BufRead, BufReader};
+
+use crate::objective::NodeCosts;
+use crate::Node;
+
+/// contents of a line without its comment
+fn strip_comment(line: &str) -> &str {
+    line.split('#').next().unwrap_or("").trim()
+}
+
+/// one node of an instance file
+#[derive(Debug, Clone, PartialEq)]
+pub struct InstanceLine {
+    pub node: Node,
+    pub service: f64,
+    pub weight: f64,
+    /// line number in the file, starting at 1
+    pub line: usize,
+}
+
+/// `id x y [service [weight]]`, None for empty and comment lines
+fn parse_instance_line(line: &str, number: usize) -> Result<Option<InstanceLine>, String> {
+    let line = strip_comment(line);
+    if line.is_empty() {
+        return Ok(None);
+    }
+    let mut fields = line.split_whitespace();
+    let mut columns = [""; 5];
+    let mut count = 0;
+    for field in fields.by_ref().take(5) {
+        columns[count] = field;
+        count += 1;
+    }
+    if count < 3 || fields.next().is_some() {
+        return Err(format!(
+            "line {}: expected 'id x y [service [weight]]', got '{}'",
+            number, line
+        ));
+    }
+    let [id, x, y, service, weight] = columns;
+    let field_error = |name: &str, err: &dyn std::fmt::Display| {
+        format!("line {}: invalid {}: {}", number, name, err)
+    };
+    let optional = |value: &str, default: f64, name: &str| match value {
+        "" => Ok(default),
+        value => value.parse().map_err(|err| field_error(name, &err)),
+    };
+    Ok(Some(InstanceLine {
+        node: Node {
+            id: id.parse().map_err(|err| field_error("id", &err))?,
+            x: x.parse().map_err(|err| field_error("x", &err))?,
+            y: y.parse().map_err(|err| field_error("y", &err))?,
+        },
+        service: optional(service, 0.0, "service")?,
+        weight: optional(weight, 1.0, "weight")?,
+        line: number,
+    }))
+}
+
+/// streams the nodes of an instance one line at a time,
+/// so files with millions of nodes never have to be in memory as text
+pub struct InstanceReader<R: BufRead> {
+    reader: R,
+    buffer: String,
+    number: usize,
+    bytes_read: u64,
+}
+
+impl<R: BufRead> InstanceReader<R> {
+    pub fn new(reader: R) -> Self {
+        InstanceReader {
+            reader,
+            buffer: String::new(),
+            number: 0,
+            bytes_read: 0,
+        }
+    }
+
+    /// bytes consumed so far, for progress reports
+    pub fn bytes_read(&self) -> u64 {
+        self.bytes_read
+    }
+}
+
+impl<R: BufRead> Iterator for InstanceReader<R> {
+    type Item = Result<InstanceLine, String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            self.buffer.clear();
+            match self.reader.read_line(&mut self.buffer) {
+                Ok(0) => return None,
+                Ok(bytes) => self.bytes_read += bytes as u64,
+                Err(err) => return Some(Err(format!("line {}: {}", self.number + 1, err))),
+            }
+            self.number += 1;
+            if let Some(parsed) = parse_instance_line(&self.buffer, self.number).transpose() {
+                return Some(parsed);
+            }
+        }
+    }
+}
+
+/// how often `collect_instance` reports progress
+const PROGRESS_INTERVAL: usize = 1 << 16;
+
+/// drains `reader` into storage preallocated for `capacity` nodes, ids have to be unique.
+/// `progress` gets the bytes read every `PROGRESS_INTERVAL` nodes and at the end
+pub fn collect_instance<R: BufRead>(
+    mut reader: InstanceReader<R>,
+    capacity: usize,
+    mut progress: impl FnMut(u64),
+) -> Result<(Vec<Node>, NodeCosts), String> {
+    let mut nodes = Vec::with_capacity(capacity);
+    let mut costs = NodeCosts::default();
+    let mut seen: HashMap<usize, usize> = HashMap::with_capacity(capacity);
+    while let Some(parsed) = reader.next() {
+        let InstanceLine {
+            node,
+            service,
+            weight,
+            line,
+        } = parsed?;
+        if let Some(first) = seen.insert(node.id, line) {
+            return Err(format!(
+                "line {}: id {} is already used on line {}",
+                line, node.id, first
+            ));
+        }
+        // neutral costs aren't stored, most instances don't have any
+        if service != 0.0 || weight != 1.0 {
+            costs.set(node.id, service, weight);
+        }
+        nodes.push(node);
+        if nodes.len() % PROGRESS_INTERVAL == 0 {
+            progress(reader.bytes_read());
+        }
+    }
+    progress(reader.bytes_read());
+    Ok((nodes, costs))
+}
+
+/// `id x y [service [weight]]` lines, ids have to be unique.
+/// see `NodeCosts` for what the optional columns mean
+pub fn parse_instance(text: &str) -> Result<(Vec<Node>, NodeCosts), String> {
+    collect_instance(InstanceReader::new(text.as_bytes()), 0, |_| {})
+}
+
+pub fn format_nodes(nodes: &[Node]) -> String {
+    let mut text = String::from("# id x y\n");
+    for node in nodes {
+        // writing to a String never fails
+        let _ = writeln!(text, "{} {} {}", node.id, node.x, node.y);
+    }
+    text
+}
+
+/// streams the instance file, `progress` gets the bytes read so far and the file size
+pub fn read_instance_with_progress(
+    path: &str,
+    mut progress: impl FnMut(u64, u64),
+) -> Result<(Vec<Node>, NodeCosts), Box<dyn Error>> {
+    let file = std::fs::File::open(path)?;
+    let size = file.metadata()?.len();
+    let mut reader = BufReader::with_capacity(1 << 16, file);
+    // estimate the node count from the lines in the first block,
+    // so the storage doesn't have to grow (and be copied) while loading
+    let sample = reader.fill_buf()?;
+    let sample_lines = sample.iter().filter(|&&b| b == b'\n').count().max(1);
+    let capacity = (size as f64 / sample.len().max(1) as f64 * sample_lines as f64 * 1.05) as usize;
+
+    let instance = collect_instance(InstanceReader::new(reader), capacity, |bytes| {
+        progress(bytes, size)
+    })
+    .map_err(|err| format!("{}: {}", path, err))?;
+    Ok(instance)
+}
+
+/// node ids of a tour, in visiting order
+///
+/// understands plain lists of ids (separated by whitespace or commas,
+/// a trailing -1 is ignored) and solution files of the exported milp models,
+/// i.e. `x_i_j value` lines like gurobi and highs write them
+pub fn parse_tour_ids(text: &str) -> Result<Vec<usize>, String> {
+    let content: Vec<&str> = text.lines().map(strip_comment).collect();
+    if content.iter().any(|line| line.starts_with("x_")) {
+        return parse_milp_solution(&content);
+    }
+
+    let mut ids = Vec::new();
+    for (number, line) in content.iter().enumerate() {
+        for token in line
+            .split(|c: char| c.is_whitespace() || c == ',')
+            .filter(|t| !t.is_empty())
+        {
+            if token == "-1" {
+                return Ok(ids);
+            }
+            ids.push(
+                token.parse().map_err(|err| {
+                    format!("line {}: invalid id '{}': {}", number + 1, token, err)
+                })?,
+            );
+        }
+    }
+    Ok(ids)
+}
+
+/// follows the successors of the `x_i_j` set to 1, starting at the smallest i
+fn parse_milp_solution(lines: &[&str]) -> Result<Vec<usize>, String> {
+    let mut successors: HashMap<usize, usize> = HashMap::new();
+    for (number, line) in lines.iter().enumerate() {
+        let mut fields = line.split_whitespace();
+        let (Some(name), Some(value)) = (fields.next(), fields.next()) else {
+            continue;
+        };
+        let Some(indices) = name.strip_prefix("x_") else {
+            continue;
+        };
+        let value: f64 = value
+            .parse()
+            .map_err(|err| format!("line {}: invalid value of {}: {}", number + 1, name, err))?;
+        if value < 0.5 {
+            continue;
+        }
+        let parsed = indices
+            .split_once('_')
+            .and_then(|(i, j)| Some((i.parse().ok()?, j.parse().ok()?)));
+        let Some((i, j)) = parsed else {
+            return Err(format!(
+                "line {}: invalid variable name {}",
+                number + 1,
+                name
+            ));
+        };
+        if successors.insert(i, j).is_some() {
+            return Err(format!("line {}: node {} is left twice", number + 1, i));
+        }
+    }
+
+    let Some(&start) = successors.keys().min() else {
+        return Ok(Vec::new());
+    };
+    let mut ids = vec![start];
+    let mut current = successors[&start];
+    while current != start {
+        if ids.len() > successors.len() {
+            return Err("solution doesn't form a cycle".to_string());
+        }
+        ids.push(current);
+        current = *successors
+            .get(&current)
+            .ok_or_else(|| format!("node {} is entered but never left", current))?;
+    }
+    if ids.len() != successors.len() {
+        return Err(format!(
+            "solution contains subtours, the one through node {} only visits {} of {} nodes",
+            start,
+            ids.len(),
+            successors.len()
+        ));
+    }
+    Ok(ids)
+}
+
+/// `parse_tour_ids` of a file, or tsplib's tour format for `.tour` files
+pub fn read_tour_ids(path: &str) -> Result<Vec<usize>, Box<dyn Error>> {
+    if path.ends_with(".tour") {
+        return Ok(crate::tsplib::read_tour(path)?);
+    }
+    let text = std::fs::read_to_string(path)?;
+    Ok(parse_tour_ids(&text).map_err(|err| format!("{}: {}", path, err))?)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::formats::*;
+
+    #[test]
+    fn test_nodes_round_trip() {
+        let nodes = crate::random_nodes(5, &mut rand::thread_rng());
+        let (parsed, costs) = parse_instance(&format_nodes(&nodes)).unwrap();
+        assert!(costs.is_neutral());
+        assert_eq!(parsed, nodes);
+        assert!(parsed
+            .iter()
+            .zip(&nodes)
+            .all(|(a, b)| a.x == b.x && a.y == b.y));
+
+        assert_eq!(
+            parse_instance("0 0 0\n0 1 1").unwrap_err(),
+            "line 2: id 0 is already used on line 1"
+        );
+
+        let (nodes, costs) = parse_instance("0 0 0\n1 1 0 0.5\n2 1 1 0 2").unwrap();
+        assert_eq!(nodes.len(), 3);
+        assert_eq!((costs.service(1), costs.weight(1)), (0.5, 1.0));
+        assert_eq!((costs.service(2), costs.weight(2)), (0.0, 2.0));
+    }
+
+    #[test]
+    fn test_streaming_instance() {
+        let text = "# header\n0 0.5 0.5\n\n1 1 1 # comment\n2 x 0\n";
+        let mut reader = InstanceReader::new(text.as_bytes());
+        assert_eq!(reader.next().unwrap().unwrap().line, 2);
+        assert_eq!(reader.next().unwrap().unwrap().node.id, 1);
+        assert_eq!(reader.bytes_read(), 36);
+        assert_eq!(
+            reader.next().unwrap().unwrap_err(),
+            "line 5: invalid x: invalid float literal"
+        );
+        assert!(reader.next().is_none());
+
+        let path = std::env::temp_dir().join("tsp_test_streaming_instance.txt");
+        let nodes = crate::random_nodes(1000, &mut rand::thread_rng());
+        std::fs::write(&path, format_nodes(&nodes)).unwrap();
+        let mut reports = Vec::new();
+        let (read, _) = read_instance_with_progress(path.to_str().unwrap(), |bytes, size| {
+            reports.push((bytes, size))
+        })
+        .unwrap();
+        assert_eq!(read, nodes);
+        let (bytes, size) = *reports.last().unwrap();
+        assert_eq!(bytes, size);
+    }
+
+    #[test]
+    fn test_tour_formats() {
+        assert_eq!(parse_tour_ids("3, 1 2\n0 -1\n7").unwrap(), vec![3, 1, 2, 0]);
+        let solution = "# Objective value = 4\nx_0_1 1\nx_0_2 0\nx_2_0 1\nx_1_2 1\n";
+        assert_eq!(parse_tour_ids(solution).unwrap(), vec![0, 1, 2]);
+        assert!(parse_tour_ids("x_0_1 1\nx_1_0 1\nx_2_3 1\nx_3_2 1").is_err());
+    }
+}
@@ -0,0 +1,322 @@
+//! genetic algorithm over permutations
+//!
+//! a population of tours breeds for a number of generations: parents are picked by tournament
+//! selection, children get a slice of one parent and the remaining nodes in the order of the other
+//! (order crossover, OX), and some mutate by a swap or a segment reversal. the best few tours
+//! survive every generation unchanged. the first tour of the population is the one the solver
+//! was given, the others are random
+//!
+//! the memetic variant 2-opts every tour it adds to the population, the starting ones too,
+//! so it breeds local optima. far fewer and smaller generations get far shorter tours
+
+use std::str::FromStr;
+
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+use crate::cancel;
+use crate::local_search::{local_search, TwoOpt};
+use crate::metric::Metric;
+use crate::Node;
+
+/// everything `tsp_genetic` can be tuned with
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GaParams {
+    pub population: usize,
+    pub generations: usize,
+    /// tours competing for every parent slot, more means stronger selection
+    pub tournament: usize,
+    /// chance that a child mutates
+    pub mutation: f64,
+    /// best tours carried over to the next generation unchanged
+    pub elites: usize,
+}
+
+impl Default for GaParams {
+    fn default() -> Self {
+        GaParams {
+            population: 100,
+            generations: 500,
+            tournament: 5,
+            mutation: 0.3,
+            elites: 2,
+        }
+    }
+}
+
+impl GaParams {
+    /// the defaults of `tsp_memetic`, whose generations are much more expensive and much better
+    pub fn memetic() -> Self {
+        GaParams {
+            population: 30,
+            generations: 50,
+            ..GaParams::default()
+        }
+    }
+
+    /// `key=value` pairs separated by `:` over these parameters, e.g. `population=200:mutation=0.1`
+    pub fn overridden(self, s: &str) -> Result<Self, String> {
+        fn value<T: FromStr>(key: &str, value: &str) -> Result<T, String>
+        where
+            T::Err: std::fmt::Display,
+        {
+            value
+                .parse()
+                .map_err(|err| format!("invalid {} '{}': {}", key, value, err))
+        }
+
+        let mut params = self;
+        for pair in s.split(':').filter(|p| !p.is_empty()) {
+            let Some((key, v)) = pair.split_once('=') else {
+                return Err(format!("expected key=value, got '{}'", pair));
+            };
+            match key {
+                "population" => params.population = value(key, v)?,
+                "generations" => params.generations = value(key, v)?,
+                "tournament" => params.tournament = value(key, v)?,
+                "mutation" => params.mutation = value(key, v)?,
+                "elites" => params.elites = value(key, v)?,
+                _ => {
+                    return Err(format!(
+                        "unknown parameter '{}', try population, generations, tournament, \
+                         mutation or elites",
+                        key
+                    ))
+                }
+            }
+        }
+        if params.population < 2 {
+            return Err("the population needs at least 2 tours".to_string());
+        }
+        if params.elites >= params.population {
+            return Err("elites have to leave room for children".to_string());
+        }
+        Ok(params)
+    }
+}
+
+/// `key=value` pairs separated by `:` over the defaults, see `GaParams::overridden`
+impl FromStr for GaParams {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        GaParams::default().overridden(s)
+    }
+}
+
+/// lengths in the population after a generation
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Generation {
+    pub best: f64,
+    pub mean: f64,
+}
+
+/// best tour of the last generation plus how every generation did
+#[derive(Debug, Clone)]
+pub struct GaRun {
+    pub tour: Vec<Node>,
+    /// the starting population first, then one entry per generation
+    pub history: Vec<Generation>,
+}
+
+/// the child of order crossover: `first[a..=b]` in place,
+/// the other positions in the order of `second` starting after b
+fn order_crossover(first: &[usize], second: &[usize], rng: &mut StdRng) -> Vec<usize> {
+    let n = first.len();
+    let (mut a, mut b) = (rng.gen_range(0..n), rng.gen_range(0..n));
+    if a > b {
+        std::mem::swap(&mut a, &mut b);
+    }
+    let mut taken = vec![false; n];
+    let mut child = vec![usize::MAX; n];
+    for i in a..=b {
+        child[i] = first[i];
+        taken[first[i]] = true;
+    }
+    let mut rest = (1..=n)
+        .map(|k| second[(b + k) % n])
+        .filter(|&node| !taken[node]);
+    for k in 1..=n - (b - a + 1) {
+        child[(b + k) % n] = rest.next().expect("the rest fills the other positions");
+    }
+    child
+}
+
+/// swaps two positions or reverses a segment, half the time each
+fn mutate(tour: &mut [usize], rng: &mut StdRng) {
+    let n = tour.len();
+    let (mut a, mut b) = (rng.gen_range(0..n), rng.gen_range(0..n));
+    if rng.gen_bool(0.5) {
+        tour.swap(a, b);
+    } else {
+        if a > b {
+            std::mem::swap(&mut a, &mut b);
+        }
+        tour[a..=b].reverse();
+    }
+}
+
+/// the index of the shortest of `size` random tours
+fn tournament(lengths: &[f64], size: usize, rng: &mut StdRng) -> usize {
+    (0..size.max(1))
+        .map(|_| rng.gen_range(0..lengths.len()))
+        .min_by(|&a, &b| lengths[a].total_cmp(&lengths[b]))
+        .expect("tournaments have at least one tour")
+}
+
+pub fn tsp_genetic(
+    nodes: &[Node],
+    metric: &dyn Metric,
+    params: &GaParams,
+    rng: &mut StdRng,
+) -> GaRun {
+    evolve(nodes, metric, params, false, rng)
+}
+
+/// the genetic algorithm with every new tour 2-opted
+pub fn tsp_memetic(
+    nodes: &[Node],
+    metric: &dyn Metric,
+    params: &GaParams,
+    rng: &mut StdRng,
+) -> GaRun {
+    evolve(nodes, metric, params, true, rng)
+}
+
+fn evolve(
+    nodes: &[Node],
+    metric: &dyn Metric,
+    params: &GaParams,
+    memetic: bool,
+    rng: &mut StdRng,
+) -> GaRun {
+    let n = nodes.len();
+    if n < 4 {
+        return GaRun {
+            tour: nodes.to_vec(),
+            history: Vec::new(),
+        };
+    }
+    let length = |order: &[usize]| -> f64 {
+        (0..n)
+            .map(|i| metric.distance(&nodes[order[i]], &nodes[order[(i + 1) % n]]))
+            .sum()
+    };
+    // the local search measures the nodes by their own ids, its tour maps back by position
+    let positions = crate::positions_by_id(nodes);
+    let improve = |order: &mut Vec<usize>| {
+        if memetic {
+            let mut tour: Vec<Node> = order.iter().map(|&i| nodes[i].clone()).collect();
+            local_search(&mut tour, &TwoOpt, metric);
+            *order = tour.iter().map(|node| positions[node.id]).collect();
+        }
+    };
+    let stats = |lengths: &[f64]| Generation {
+        best: lengths.iter().copied().fold(f64::INFINITY, f64::min),
+        mean: lengths.iter().sum::<f64>() / lengths.len() as f64,
+    };
+
+    let mut population: Vec<Vec<usize>> = vec![(0..n).collect()];
+    while population.len() < params.population.max(2) {
+        let mut order: Vec<usize> = (0..n).collect();
+        order.shuffle(rng);
+        population.push(order);
+    }
+    for order in &mut population {
+        improve(order);
+    }
+    let mut lengths: Vec<f64> = population.iter().map(|order| length(order)).collect();
+    let mut history = vec![stats(&lengths)];
+
+    for _ in 0..params.generations {
+        if cancel::is_cancelled() {
+            break;
+        }
+        let mut ranked: Vec<usize> = (0..population.len()).collect();
+        ranked.sort_by(|&a, &b| lengths[a].total_cmp(&lengths[b]));
+        let mut next: Vec<Vec<usize>> = ranked
+            .iter()
+            .take(params.elites)
+            .map(|&i| population[i].clone())
+            .collect();
+        while next.len() < population.len() {
+            let first = tournament(&lengths, params.tournament, rng);
+            let second = tournament(&lengths, params.tournament, rng);
+            let mut child = order_crossover(&population[first], &population[second], rng);
+            if rng.gen_bool(params.mutation.clamp(0.0, 1.0)) {
+                mutate(&mut child, rng);
+            }
+            improve(&mut child);
+            next.push(child);
+        }
+        population = next;
+        lengths = population.iter().map(|order| length(order)).collect();
+        history.push(stats(&lengths));
+    }
+
+    let best = (0..population.len())
+        .min_by(|&a, &b| lengths[a].total_cmp(&lengths[b]))
+        .expect("the population isn't empty");
+    GaRun {
+        tour: population[best].iter().map(|&i| nodes[i].clone()).collect(),
+        history,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::genetic::*;
+    use crate::metric::Euclidean;
+    use crate::random_nodes;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_order_crossover_is_a_permutation() {
+        let mut rng = StdRng::seed_from_u64(17);
+        let first: Vec<usize> = (0..12).collect();
+        let mut second = first.clone();
+        second.shuffle(&mut rng);
+        for _ in 0..50 {
+            let mut child = order_crossover(&first, &second, &mut rng);
+            mutate(&mut child, &mut rng);
+            child.sort_unstable();
+            assert_eq!(child, first);
+        }
+        assert!("population=1".parse::<GaParams>().is_err());
+        assert_eq!("elites=0".parse::<GaParams>().unwrap().elites, 0);
+    }
+
+    #[test]
+    fn test_generations_improve() {
+        let nodes = random_nodes(30, &mut StdRng::seed_from_u64(18));
+        let params = GaParams {
+            generations: 200,
+            ..GaParams::default()
+        };
+        let run = tsp_genetic(&nodes, &Euclidean, &params, &mut StdRng::seed_from_u64(19));
+        assert_eq!(run.history.len(), 201);
+        // elitism never loses the best tour
+        assert!(run.history.windows(2).all(|w| w[1].best <= w[0].best));
+        assert!(run.history[200].best < run.history[0].best * 0.6);
+        assert!((Euclidean.tour_length(&run.tour) - run.history[200].best).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_memetic_beats_genetic() {
+        let nodes = random_nodes(60, &mut StdRng::seed_from_u64(29));
+        let params = GaParams::memetic().overridden("generations=10").unwrap();
+        assert_eq!((params.population, params.generations), (30, 10));
+        let memetic = tsp_memetic(&nodes, &Euclidean, &params, &mut StdRng::seed_from_u64(30));
+        let ids: Vec<usize> = memetic.tour.iter().map(|n| n.id).collect();
+        assert!(crate::tour::Tour::from_ids(&ids, &nodes).is_ok());
+
+        let genetic = tsp_genetic(
+            &nodes,
+            &Euclidean,
+            &GaParams::default(),
+            &mut StdRng::seed_from_u64(30),
+        );
+        assert!(Euclidean.tour_length(&memetic.tour) < Euclidean.tour_length(&genetic.tour));
+    }
+}
@@ -0,0 +1,322 @@
+//! geographic instances: stops with an address and a position on earth
+//!
+//! stops files are csv with an `id,lat,lon,address` header, `geocode` writes them
+//! and `--instance` reads them, like geojson collections of points. solvers get the stops
+//! projected onto a plane in kilometers, for drawing and the geometric heuristics,
+//! and `Haversine` measures the great circle distances between them
+
+use std::fmt::Write;
+
+use crate::json::{self, Json};
+use crate::metric::Metric;
+use crate::Node;
+
+/// header of stops files, tells them apart from instance csvs
+pub const STOPS_HEADER: &str = "id,lat,lon,address";
+
+/// kilometers per degree of latitude, and of longitude at the equator
+const KM_PER_DEGREE: f64 = 111.2;
+
+/// mean radius of the earth in kilometers
+pub const EARTH_RADIUS: f64 = 6371.0088;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GeoStop {
+    pub id: usize,
+    pub lat: f64,
+    pub lon: f64,
+    pub address: String,
+}
+
+/// fields of one csv line, fields in double quotes may contain commas and `""` for a quote
+pub fn split_csv_line(line: &str) -> Vec<String> {
+    let mut fields = vec![String::new()];
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        let field = fields.last_mut().expect("starts with one field");
+        match (c, quoted) {
+            ('"', true) if chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            ('"', _) => quoted = !quoted,
+            (',', false) => fields.push(String::new()),
+            (c, _) => field.push(c),
+        }
+    }
+    fields.into_iter().map(|f| f.trim().to_string()).collect()
+}
+
+/// quotes the field if a csv reader would split it or change it otherwise
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"']) || field.trim() != field {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// whether `text` starts like a stops file, comments aside
+pub fn is_stops_file(text: &str) -> bool {
+    text.lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty() && !line.starts_with('#'))
+        .is_some_and(|header| header.replace(' ', "") == STOPS_HEADER)
+}
+
+pub fn format_stops(stops: &[GeoStop]) -> String {
+    // writing to a String never fails
+    let mut text = format!("{}\n", STOPS_HEADER);
+    for stop in stops {
+        let _ = writeln!(
+            text,
+            "{},{},{},{}",
+            stop.id,
+            stop.lat,
+            stop.lon,
+            csv_field(&stop.address)
+        );
+    }
+    text
+}
+
+pub fn parse_stops(text: &str) -> Result<Vec<GeoStop>, String> {
+    let mut lines = text
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty() && !line.trim_start().starts_with('#'));
+    if !lines
+        .next()
+        .is_some_and(|(_, header)| is_stops_file(header))
+    {
+        return Err(format!("stops files start with '{}'", STOPS_HEADER));
+    }
+    let mut stops = Vec::new();
+    for (number, line) in lines {
+        let error = |message: &str| format!("line {}: {}", number + 1, message);
+        let fields = split_csv_line(line);
+        let [id, lat, lon, address] = &fields[..] else {
+            return Err(error(&format!("expected 4 fields, got {}", fields.len())));
+        };
+        let stop = GeoStop {
+            id: id.parse().map_err(|_| error("invalid id"))?,
+            lat: lat.parse().map_err(|_| error("invalid latitude"))?,
+            lon: lon.parse().map_err(|_| error("invalid longitude"))?,
+            address: address.clone(),
+        };
+        if !(-90.0..=90.0).contains(&stop.lat) || !(-180.0..=180.0).contains(&stop.lon) {
+            return Err(error("latitude or longitude out of range"));
+        }
+        stops.push(stop);
+    }
+    Ok(stops)
+}
+
+/// whether `text` starts like a geojson FeatureCollection
+pub fn is_geojson(text: &str) -> bool {
+    text.trim_start().starts_with('{') && text.contains("\"FeatureCollection\"")
+}
+
+/// the Point features of a geojson FeatureCollection, `[lon, lat]` as geojson orders them.
+/// ids are the features' numeric `id`s or their positions, the address their `name` or `address`
+/// property if they have one
+pub fn parse_geojson(text: &str) -> Result<Vec<GeoStop>, String> {
+    let document = json::parse(text)?;
+    if document.get("type").and_then(Json::as_str) != Some("FeatureCollection") {
+        return Err("expected a geojson FeatureCollection".to_string());
+    }
+    let features = document
+        .get("features")
+        .and_then(Json::as_array)
+        .ok_or("the FeatureCollection has no 'features' list")?;
+    let mut stops = Vec::with_capacity(features.len());
+    let mut seen = std::collections::HashSet::new();
+    for (position, feature) in features.iter().enumerate() {
+        let error = |message: &str| format!("feature {}: {}", position, message);
+        let geometry = feature
+            .get("geometry")
+            .ok_or_else(|| error("no geometry"))?;
+        if geometry.get("type").and_then(Json::as_str) != Some("Point") {
+            return Err(error("only Point geometries are stops"));
+        }
+        let coordinates: Vec<f64> = geometry
+            .get("coordinates")
+            .and_then(Json::as_array)
+            .map(|values| values.iter().filter_map(Json::as_f64).collect())
+            .unwrap_or_default();
+        let [lon, lat, ..] = coordinates[..] else {
+            return Err(error("a Point needs [lon, lat] coordinates"));
+        };
+        if !(-90.0..=90.0).contains(&lat) || !(-180.0..=180.0).contains(&lon) {
+            return Err(error("latitude or longitude out of range"));
+        }
+        let id = match feature.get("id").and_then(Json::as_f64) {
+            Some(id) if id >= 0.0 && id.fract() == 0.0 => id as usize,
+            Some(id) => return Err(error(&format!("invalid id {}", id))),
+            None => position,
+        };
+        if !seen.insert(id) {
+            return Err(error(&format!("id {} is already used", id)));
+        }
+        let properties = feature.get("properties");
+        let address = ["name", "address"]
+            .iter()
+            .find_map(|key| properties?.get(key)?.as_str())
+            .unwrap_or_default();
+        stops.push(GeoStop {
+            id,
+            lat,
+            lon,
+            address: address.to_string(),
+        });
+    }
+    Ok(stops)
+}
+
+/// the stops as a FeatureCollection that `parse_geojson` reads back
+pub fn format_geojson(stops: &[GeoStop]) -> String {
+    let features: Vec<String> = stops
+        .iter()
+        .map(|stop| {
+            format!(
+                r#"{{"type": "Feature", "id": {}, "geometry": {{"type": "Point", "coordinates": [{}, {}]}}, "properties": {{"address": {}}}}}"#,
+                stop.id,
+                stop.lon,
+                stop.lat,
+                json::quote(&stop.address)
+            )
+        })
+        .collect();
+    format!(
+        "{{\"type\": \"FeatureCollection\", \"features\": [\n  {}\n]}}\n",
+        features.join(",\n  ")
+    )
+}
+
+/// great circle distance in kilometers between the positions of the stops, by node id,
+/// on a sphere of `EARTH_RADIUS`. off by up to half a percent from the real ellipsoid
+pub struct Haversine {
+    /// latitude and longitude in radians by node id
+    positions: Vec<Option<(f64, f64)>>,
+}
+
+impl Haversine {
+    pub fn new(stops: &[GeoStop]) -> Self {
+        let size = stops.iter().map(|stop| stop.id + 1).max().unwrap_or(0);
+        let mut positions = vec![None; size];
+        for stop in stops {
+            positions[stop.id] = Some((stop.lat.to_radians(), stop.lon.to_radians()));
+        }
+        Haversine { positions }
+    }
+
+    fn position(&self, node: &Node) -> (f64, f64) {
+        self.positions
+            .get(node.id)
+            .copied()
+            .flatten()
+            .expect("only nodes of the instance are measured")
+    }
+}
+
+impl Metric for Haversine {
+    fn distance(&self, from: &Node, to: &Node) -> f64 {
+        let (lat, lon) = self.position(from);
+        let (other_lat, other_lon) = self.position(to);
+        let a = ((other_lat - lat) / 2.0).sin().powi(2)
+            + lat.cos() * other_lat.cos() * ((other_lon - lon) / 2.0).sin().powi(2);
+        2.0 * EARTH_RADIUS * a.sqrt().min(1.0).asin()
+    }
+}
+
+/// equirectangular projection around the mean latitude, in kilometers from the southwest corner.
+/// good to a fraction of a percent for areas of a few hundred kilometers
+pub fn project(stops: &[GeoStop]) -> Vec<Node> {
+    let mean_lat = stops.iter().map(|s| s.lat).sum::<f64>() / stops.len().max(1) as f64;
+    let km_per_lon = KM_PER_DEGREE * mean_lat.to_radians().cos();
+    let min_lat = stops.iter().map(|s| s.lat).fold(f64::INFINITY, f64::min);
+    let min_lon = stops.iter().map(|s| s.lon).fold(f64::INFINITY, f64::min);
+    stops
+        .iter()
+        .map(|stop| Node {
+            id: stop.id,
+            x: (stop.lon - min_lon) * km_per_lon,
+            y: (stop.lat - min_lat) * KM_PER_DEGREE,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::geo::*;
+
+    #[test]
+    fn test_stops_round_trip_and_projection() {
+        assert_eq!(
+            split_csv_line(r#"3, "Unter den Linden 1, Berlin", "say ""hi""""#),
+            vec!["3", "Unter den Linden 1, Berlin", "say \"hi\""]
+        );
+        let stops = vec![
+            GeoStop {
+                id: 0,
+                lat: 52.5,
+                lon: 13.4,
+                address: "Alexanderplatz, Berlin".to_string(),
+            },
+            GeoStop {
+                id: 1,
+                lat: 52.6,
+                lon: 13.4,
+                address: "plain".to_string(),
+            },
+        ];
+        let text = format_stops(&stops);
+        assert!(is_stops_file(&text));
+        assert_eq!(parse_stops(&text).unwrap(), stops);
+        assert!(parse_stops("id,x,y\n0,1,2").is_err());
+
+        // a tenth of a degree north is about 11 km
+        let nodes = project(&stops);
+        assert!((crate::node_distance(&nodes[0], &nodes[1]) - 11.12).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_geojson_and_great_circles() {
+        let text = r#"{"type": "FeatureCollection", "features": [
+            {"type": "Feature", "geometry": {"type": "Point", "coordinates": [2.3522, 48.8566]},
+             "properties": {"name": "Paris"}},
+            {"type": "Feature", "geometry": {"type": "Point", "coordinates": [-0.1276, 51.5072]},
+             "properties": {"name": "London"}},
+            {"type": "Feature", "geometry": {"type": "Point", "coordinates": [-74.006, 40.7128]},
+             "properties": null}
+        ]}"#;
+        assert!(is_geojson(text));
+        let stops = parse_geojson(text).unwrap();
+        assert_eq!(stops[1].address, "London");
+        assert_eq!((stops[2].id, stops[2].lat), (2, 40.7128));
+        assert_eq!(parse_geojson(&format_geojson(&stops)).unwrap(), stops);
+        let line = text.replacen(
+            "\"Point\", \"coordinates\": [2.3522, 48.8566]",
+            "\"LineString\", \"coordinates\": []",
+            1,
+        );
+        assert_eq!(
+            parse_geojson(&line).unwrap_err(),
+            "feature 0: only Point geometries are stops"
+        );
+
+        // paris to london is 344 km and london to new york 5570 km as the crow flies
+        let nodes = project(&stops);
+        let metric = Haversine::new(&stops);
+        assert!((metric.distance(&nodes[0], &nodes[1]) - 343.6).abs() < 1.0);
+        assert!((metric.distance(&nodes[1], &nodes[2]) - 5570.0).abs() < 5.0);
+        assert_eq!(metric.distance(&nodes[2], &nodes[2]), 0.0);
+
+        assert!(parse_geojson(r#"{"type": "Feature"}"#).is_err());
+        assert_eq!(
+            parse_geojson(&text.replace("48.8566", "98.8566")).unwrap_err(),
+            "feature 0: latitude or longitude out of range"
+        );
+    }
+}
@@ -0,0 +1,220 @@
+//! turning a csv of street addresses into stops, see `geo`
+//!
+//! geocoders are pluggable, the crate ships a lookup table for addresses geocoded before
+//! and a client for openstreetmap's nominatim behind the `nominatim` feature.
+//! every address is looked up once, the stops file written afterwards is what later runs use
+
+use std::collections::HashMap;
+
+use crate::geo::{split_csv_line, GeoStop};
+
+/// finds the position of an address
+pub trait Geocoder {
+    fn name(&self) -> &str;
+
+    /// (latitude, longitude) of the best match, None if nothing matches
+    fn geocode(&self, address: &str) -> Result<Option<(f64, f64)>, String>;
+}
+
+/// the addresses of an address csv in order
+///
+/// the header names the columns, an `address` column is used as it is.
+/// without one all columns are joined, so `street,zip,city` files work too
+pub fn parse_addresses(text: &str) -> Result<Vec<String>, String> {
+    let mut lines = text
+        .lines()
+        .filter(|line| !line.trim().is_empty() && !line.trim_start().starts_with('#'));
+    let Some(header) = lines.next() else {
+        return Err("missing csv header".to_string());
+    };
+    let columns = split_csv_line(header);
+    let address = columns
+        .iter()
+        .position(|c| c.eq_ignore_ascii_case("address"));
+    Ok(lines
+        .map(|line| {
+            let fields = split_csv_line(line);
+            match address {
+                Some(i) => fields.get(i).cloned().unwrap_or_default(),
+                None => fields
+                    .into_iter()
+                    .filter(|f| !f.is_empty())
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            }
+        })
+        .collect())
+}
+
+/// stops of all addresses the geocoder finds, ids in the order of the file,
+/// plus a message for every address it doesn't. errors of the geocoder itself abort
+pub fn geocode_all(
+    addresses: &[String],
+    geocoder: &dyn Geocoder,
+    mut progress: impl FnMut(usize),
+) -> Result<(Vec<GeoStop>, Vec<String>), String> {
+    let mut stops = Vec::new();
+    let mut missing = Vec::new();
+    for (i, address) in addresses.iter().enumerate() {
+        progress(i);
+        if address.is_empty() {
+            missing.push(format!("row {} has no address", i + 1));
+            continue;
+        }
+        match geocoder.geocode(address)? {
+            Some((lat, lon)) => stops.push(GeoStop {
+                id: stops.len(),
+                lat,
+                lon,
+                address: address.clone(),
+            }),
+            None => missing.push(format!("no match for '{}'", address)),
+        }
+    }
+    Ok((stops, missing))
+}
+
+/// addresses with known positions, e.g. an export of another geocoding service.
+/// matches ignore case and surrounding whitespace
+pub struct Gazetteer {
+    positions: HashMap<String, (f64, f64)>,
+}
+
+impl Gazetteer {
+    /// csv with `address`, `lat` and `lon` columns
+    pub fn parse(text: &str) -> Result<Gazetteer, String> {
+        let mut lines = text
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty() && !line.trim_start().starts_with('#'));
+        let Some((_, header)) = lines.next() else {
+            return Err("missing csv header".to_string());
+        };
+        let columns = split_csv_line(header);
+        let column = |name: &str| columns.iter().position(|c| c.eq_ignore_ascii_case(name));
+        let (Some(address), Some(lat), Some(lon)) =
+            (column("address"), column("lat"), column("lon"))
+        else {
+            return Err(format!(
+                "gazetteer header needs address, lat and lon columns, got '{}'",
+                header
+            ));
+        };
+        let mut positions = HashMap::new();
+        for (number, line) in lines {
+            let fields = split_csv_line(line);
+            let field = |i: usize, name: &str| {
+                fields
+                    .get(i)
+                    .and_then(|f| f.parse::<f64>().ok())
+                    .ok_or_else(|| format!("line {}: invalid {}", number + 1, name))
+            };
+            let position = (field(lat, "lat")?, field(lon, "lon")?);
+            let key = fields
+                .get(address)
+                .map_or(String::new(), |a| a.to_lowercase());
+            positions.insert(key, position);
+        }
+        Ok(Gazetteer { positions })
+    }
+}
+
+impl Geocoder for Gazetteer {
+    fn name(&self) -> &str {
+        "gazetteer"
+    }
+
+    fn geocode(&self, address: &str) -> Result<Option<(f64, f64)>, String> {
+        Ok(self.positions.get(&address.trim().to_lowercase()).copied())
+    }
+}
+
+/// client of a nominatim server, https://nominatim.org
+///
+/// the public server wants a user agent naming the application
+/// and at most one request per second, both are kept to
+#[cfg(feature = "nominatim")]
+pub struct Nominatim {
+    pub base_url: String,
+    pub user_agent: String,
+}
+
+#[cfg(feature = "nominatim")]
+impl Default for Nominatim {
+    fn default() -> Self {
+        Nominatim {
+            base_url: "https://nominatim.openstreetmap.org".to_string(),
+            user_agent: concat!("travelling-salesman-problem/", env!("CARGO_PKG_VERSION"))
+                .to_string(),
+        }
+    }
+}
+
+#[cfg(feature = "nominatim")]
+impl Geocoder for Nominatim {
+    fn name(&self) -> &str {
+        "nominatim"
+    }
+
+    fn geocode(&self, address: &str) -> Result<Option<(f64, f64)>, String> {
+        std::thread::sleep(std::time::Duration::from_secs(1));
+        let body = ureq::get(&format!("{}/search", self.base_url))
+            .set("User-Agent", &self.user_agent)
+            .query("q", address)
+            .query("format", "json")
+            .query("limit", "1")
+            .call()
+            .map_err(|err| format!("{}: {}", self.base_url, err))?
+            .into_string()
+            .map_err(|err| err.to_string())?;
+        parse_nominatim(&body)
+    }
+}
+
+/// position of the first result of a nominatim json search, which quotes its numbers
+#[cfg_attr(not(feature = "nominatim"), allow(dead_code))]
+fn parse_nominatim(body: &str) -> Result<Option<(f64, f64)>, String> {
+    let field = |name: &str| -> Result<Option<f64>, String> {
+        let key = format!("\"{}\":\"", name);
+        let Some(start) = body.find(&key).map(|i| i + key.len()) else {
+            return Ok(None);
+        };
+        let end = body[start..]
+            .find('"')
+            .ok_or("unterminated string in response")?;
+        let value = &body[start..start + end];
+        value
+            .parse()
+            .map(Some)
+            .map_err(|_| format!("invalid {} '{}' in response", name, value))
+    };
+    match (field("lat")?, field("lon")?) {
+        (Some(lat), Some(lon)) => Ok(Some((lat, lon))),
+        _ if body.trim() == "[]" => Ok(None),
+        _ => Err(format!("unexpected response '{}'", body.trim())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::geocode::*;
+
+    #[test]
+    fn test_geocode_addresses() {
+        let addresses =
+            parse_addresses("street,city\n\"Main St 1\",Springfield\nNowhere,\n").unwrap();
+        assert_eq!(addresses, vec!["Main St 1, Springfield", "Nowhere"]);
+        let gazetteer =
+            Gazetteer::parse("lat,lon,address\n1,2,\"main st 1, springfield\"").unwrap();
+        let (stops, missing) = geocode_all(&addresses, &gazetteer, |_| {}).unwrap();
+        assert_eq!(stops.len(), 1);
+        assert_eq!((stops[0].lat, stops[0].lon), (1.0, 2.0));
+        assert_eq!(missing, vec!["no match for 'Nowhere'"]);
+
+        assert_eq!(
+            parse_nominatim(r#"[{"place_id":1,"lat":"52.5170365","lon":"13.3888599"}]"#),
+            Ok(Some((52.5170365, 13.3888599)))
+        );
+        assert_eq!(parse_nominatim("[]"), Ok(None));
+    }
+}
@@ -0,0 +1,214 @@
+//! geometric structures of a node set: convex hull, minimum spanning tree, nearest neighbors,
+//! and edges of a tour crossing each other
+//!
+//! building blocks for insertion heuristics, lower bounds, graph exports and diagnostics
+
+use std::collections::{BTreeSet, HashMap};
+
+use crate::metric::Metric;
+use crate::tour::Edge;
+use crate::Node;
+
+/// z component of the cross product of `a -> b` and `a -> c`,
+/// positive if `c` is left of the line through `a` and `b`
+fn cross(a: &Node, b: &Node, c: &Node) -> f64 {
+    (b.x - a.x) * (c.y - a.y) - (b.y - a.y) * (c.x - a.x)
+}
+
+/// ids of the nodes on the convex hull, counter-clockwise starting at the lowest leftmost node
+///
+/// andrew's monotone chain, O(n log n). nodes in the middle of a hull edge
+/// and duplicate positions aren't part of it
+pub fn convex_hull(nodes: &[Node]) -> Vec<usize> {
+    let mut sorted: Vec<&Node> = nodes.iter().collect();
+    sorted.sort_by(|a, b| a.x.total_cmp(&b.x).then(a.y.total_cmp(&b.y)));
+    sorted.dedup_by(|a, b| a.x == b.x && a.y == b.y);
+    if sorted.len() < 3 {
+        return sorted.iter().map(|n| n.id).collect();
+    }
+
+    let mut hull: Vec<&Node> = Vec::with_capacity(2 * sorted.len());
+    // lower hull left to right, then upper hull right to left
+    for pass in [sorted.clone(), sorted.iter().rev().copied().collect()] {
+        let start = hull.len();
+        for node in pass {
+            while hull.len() >= start + 2
+                && cross(hull[hull.len() - 2], hull[hull.len() - 1], node) <= 0.0
+            {
+                hull.pop();
+            }
+            hull.push(node);
+        }
+        // the last node of each chain is the first of the other one
+        hull.pop();
+    }
+    hull.iter().map(|n| n.id).collect()
+}
+
+/// edges from every node to its k closest other nodes, O(n² log n)
+pub fn knn_edges(nodes: &[Node], k: usize, metric: &dyn Metric) -> BTreeSet<Edge> {
+    let mut edges = BTreeSet::new();
+    for node in nodes {
+        let mut others: Vec<(f64, usize)> = nodes
+            .iter()
+            .filter(|other| other.id != node.id)
+            .map(|other| (metric.distance(node, other), other.id))
+            .collect();
+        others.sort_by(|a, b| a.0.total_cmp(&b.0));
+        for (_, id) in others.into_iter().take(k) {
+            edges.insert((node.id.min(id), node.id.max(id)));
+        }
+    }
+    edges
+}
+
+/// minimum spanning tree with prim's algorithm, O(n²)
+pub fn mst_edges(nodes: &[Node], metric: &dyn Metric) -> BTreeSet<Edge> {
+    let mut edges = BTreeSet::new();
+    if nodes.is_empty() {
+        return edges;
+    }
+
+    let mut in_tree = vec![false; nodes.len()];
+    // cheapest known connection of every node to the tree: (distance, tree node index)
+    let mut best: Vec<(f64, usize)> = vec![(f64::INFINITY, 0); nodes.len()];
+    let mut current = 0;
+    in_tree[0] = true;
+    for _ in 1..nodes.len() {
+        for (i, node) in nodes.iter().enumerate() {
+            if !in_tree[i] {
+                let distance = metric.distance(&nodes[current], node);
+                if distance < best[i].0 {
+                    best[i] = (distance, current);
+                }
+            }
+        }
+        let next = (0..nodes.len())
+            .filter(|&i| !in_tree[i])
+            .min_by(|&a, &b| best[a].0.total_cmp(&best[b].0))
+            .expect("loop runs once per node not in the tree yet");
+        in_tree[next] = true;
+        let (a, b) = (nodes[next].id, nodes[best[next].1].id);
+        edges.insert((a.min(b), a.max(b)));
+        current = next;
+    }
+    edges
+}
+
+/// total length of the minimum spanning tree, a lower bound on every tour:
+/// dropping any edge of a tour leaves a spanning tree
+pub fn mst_length(nodes: &[Node], metric: &dyn Metric) -> f64 {
+    let by_id: HashMap<usize, &Node> = nodes.iter().map(|n| (n.id, n)).collect();
+    mst_edges(nodes, metric)
+        .iter()
+        .map(|(a, b)| metric.distance(by_id[a], by_id[b]))
+        .sum()
+}
+
+/// tours above this size aren't checked for crossings, bad tours make the check quadratic
+pub const CROSSINGS_MAX_NODES: usize = 100_000;
+
+/// whether segments a-b and c-d cross in a single point inside both of them,
+/// touching and overlapping collinear segments don't count
+fn segments_cross(a: &Node, b: &Node, c: &Node, d: &Node) -> bool {
+    cross(a, b, c) * cross(a, b, d) < 0.0 && cross(c, d, a) * cross(c, d, b) < 0.0
+}
+
+/// pairs of tour edges that cross, edge i runs from `tour[i]` to the node after it.
+/// every crossing is slack, a 2-opt move reversing the part between the edges removes it
+///
+/// sweeps the edges by their leftmost x, so only edges overlapping in x are compared,
+/// about O(n √n) for decent tours of uniform instances
+pub fn crossings(tour: &[Node]) -> Vec<(usize, usize)> {
+    let n = tour.len();
+    if n < 4 {
+        return Vec::new();
+    }
+    let edge = |i: usize| (&tour[i], &tour[(i + 1) % n]);
+    let span = |i: usize| {
+        let (a, b) = edge(i);
+        (a.x.min(b.x), a.x.max(b.x))
+    };
+    let mut by_left: Vec<usize> = (0..n).collect();
+    by_left.sort_by(|&i, &j| span(i).0.total_cmp(&span(j).0));
+
+    let mut pairs = Vec::new();
+    for (k, &i) in by_left.iter().enumerate() {
+        let right = span(i).1;
+        let (a, b) = edge(i);
+        for &j in by_left[k + 1..].iter().take_while(|&&j| span(j).0 <= right) {
+            // neighboring edges share a node, they can't cross
+            if (i + 1) % n == j || (j + 1) % n == i {
+                continue;
+            }
+            let (c, d) = edge(j);
+            if segments_cross(a, b, c, d) {
+                pairs.push((i.min(j), i.max(j)));
+            }
+        }
+    }
+    pairs.sort_unstable();
+    pairs
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::geometry::*;
+    use crate::metric::Euclidean;
+
+    #[test]
+    fn test_convex_hull() {
+        // square with a node inside, one on an edge and a duplicate corner
+        let nodes: Vec<Node> = [
+            (1.0, 1.0),
+            (0.0, 0.0),
+            (0.5, 0.5),
+            (1.0, 0.0),
+            (0.5, 1.0),
+            (0.0, 1.0),
+            (0.0, 0.0),
+        ]
+        .iter()
+        .enumerate()
+        .map(|(id, &(x, y))| Node { id, x, y })
+        .collect();
+        assert_eq!(convex_hull(&nodes), vec![1, 3, 0, 5]);
+        assert_eq!(convex_hull(&nodes[..2]), vec![1, 0]);
+        assert!(convex_hull(&[]).is_empty());
+
+        let random = crate::random_nodes(200, &mut rand::thread_rng());
+        let hull = convex_hull(&random);
+        let by_id: HashMap<usize, &Node> = random.iter().map(|n| (n.id, n)).collect();
+        for k in 0..hull.len() {
+            let (a, b) = (by_id[&hull[k]], by_id[&hull[(k + 1) % hull.len()]]);
+            assert!(random.iter().all(|c| cross(a, b, c) >= 0.0));
+        }
+        let corners = [nodes[1].clone(), nodes[3].clone(), nodes[5].clone()];
+        assert_eq!(mst_length(&corners, &Euclidean), 2.0);
+    }
+
+    #[test]
+    fn test_crossings() {
+        let square = |order: [usize; 4]| -> Vec<Node> {
+            let corners = [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)];
+            order
+                .iter()
+                .map(|&id| Node {
+                    id,
+                    x: corners[id].0,
+                    y: corners[id].1,
+                })
+                .collect()
+        };
+        assert!(crossings(&square([0, 1, 2, 3])).is_empty());
+        // edges 0 (0 -> 2) and 2 (1 -> 3) are the diagonals
+        assert_eq!(crossings(&square([0, 2, 1, 3])), vec![(0, 2)]);
+
+        // 2-opt optima have no crossings, random tours plenty
+        let mut rng = rand::thread_rng();
+        let mut tour = crate::random_nodes(60, &mut rng);
+        assert!(!crossings(&tour).is_empty());
+        crate::local_search::local_search(&mut tour, &crate::local_search::TwoHOpt, &Euclidean);
+        assert!(crossings(&tour).is_empty());
+    }
+}
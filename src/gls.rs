@@ -0,0 +1,170 @@
+//! guided local search: 2-opt on the tour length plus penalties for edges
+//!
+//! at every local optimum the edges of the tour with the highest utility, their length divided
+//! by one more than their penalty so far, get one more penalty. 2-opt then runs again on
+//! the augmented length, where every penalty adds `lambda` to its edge, which pushes the tour
+//! away from long edges that keep showing up until something else beats them.
+//! `lambda` is `alpha` times the mean edge of the first local optimum
+
+use std::str::FromStr;
+
+use crate::cancel;
+use crate::local_search::{local_search, TwoOpt};
+use crate::metric::Metric;
+use crate::Node;
+
+/// everything `tsp_guided_local_search` can be tuned with
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GlsParams {
+    /// penalty rounds, each followed by 2-opt
+    pub iterations: usize,
+    /// the weight of a penalty relative to the mean edge
+    pub alpha: f64,
+}
+
+impl Default for GlsParams {
+    fn default() -> Self {
+        GlsParams {
+            iterations: 500,
+            alpha: 0.3,
+        }
+    }
+}
+
+/// `key=value` pairs separated by `:` over the defaults, e.g. `iterations=2000:alpha=0.1`
+impl FromStr for GlsParams {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        fn value<T: FromStr>(key: &str, value: &str) -> Result<T, String>
+        where
+            T::Err: std::fmt::Display,
+        {
+            value
+                .parse()
+                .map_err(|err| format!("invalid {} '{}': {}", key, value, err))
+        }
+
+        let mut params = GlsParams::default();
+        for pair in s.split(':').filter(|p| !p.is_empty()) {
+            let Some((key, v)) = pair.split_once('=') else {
+                return Err(format!("expected key=value, got '{}'", pair));
+            };
+            match key {
+                "iterations" => params.iterations = value(key, v)?,
+                "alpha" => params.alpha = value(key, v)?,
+                _ => {
+                    return Err(format!(
+                        "unknown parameter '{}', try iterations or alpha",
+                        key
+                    ))
+                }
+            }
+        }
+        Ok(params)
+    }
+}
+
+/// penalties of every edge by the ids of its nodes
+struct Penalties {
+    n: usize,
+    counts: Vec<u32>,
+}
+
+impl Penalties {
+    fn get(&self, a: &Node, b: &Node) -> u32 {
+        self.counts[a.id * self.n + b.id]
+    }
+
+    fn add(&mut self, a: &Node, b: &Node) {
+        self.counts[a.id * self.n + b.id] += 1;
+        self.counts[b.id * self.n + a.id] += 1;
+    }
+}
+
+/// the augmented objective 2-opt runs on
+struct Penalized<'a> {
+    metric: &'a dyn Metric,
+    penalties: &'a Penalties,
+    lambda: f64,
+}
+
+impl Metric for Penalized<'_> {
+    fn distance(&self, from: &Node, to: &Node) -> f64 {
+        self.metric.distance(from, to) + self.lambda * self.penalties.get(from, to) as f64
+    }
+
+    fn is_symmetric(&self) -> bool {
+        self.metric.is_symmetric()
+    }
+}
+
+/// the shortest tour, by the real length, any of the local optima had
+pub fn tsp_guided_local_search(
+    nodes: &[Node],
+    metric: &dyn Metric,
+    params: &GlsParams,
+) -> Vec<Node> {
+    let n = nodes.len();
+    if n < 5 {
+        return nodes.to_vec();
+    }
+    let mut tour = nodes.to_vec();
+    local_search(&mut tour, &TwoOpt, metric);
+    let mut best = (metric.tour_length(&tour), tour.clone());
+    let lambda = params.alpha * best.0 / n as f64;
+    let ids = nodes.iter().map(|node| node.id + 1).max().unwrap_or(0);
+    let mut penalties = Penalties {
+        n: ids,
+        counts: vec![0; ids * ids],
+    };
+
+    for _ in 0..params.iterations {
+        if cancel::is_cancelled() {
+            break;
+        }
+        let utility = |i: usize| {
+            let (a, b) = (&tour[i], &tour[(i + 1) % n]);
+            metric.distance(a, b) / (1 + penalties.get(a, b)) as f64
+        };
+        let utilities: Vec<f64> = (0..n).map(utility).collect();
+        let most = utilities.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        for i in (0..n).filter(|&i| utilities[i] >= most - 1e-12) {
+            penalties.add(&tour[i], &tour[(i + 1) % n]);
+        }
+
+        let penalized = Penalized {
+            metric,
+            penalties: &penalties,
+            lambda,
+        };
+        local_search(&mut tour, &TwoOpt, &penalized);
+        let length = metric.tour_length(&tour);
+        if length < best.0 {
+            best = (length, tour.clone());
+        }
+    }
+    best.1
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::gls::*;
+    use crate::metric::Euclidean;
+    use crate::random_nodes;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_penalties_escape_two_opt_optimum() {
+        let nodes = random_nodes(80, &mut StdRng::seed_from_u64(34));
+        let mut two = nodes.clone();
+        local_search(&mut two, &TwoOpt, &Euclidean);
+
+        let tour = tsp_guided_local_search(&two, &Euclidean, &GlsParams::default());
+        let ids: Vec<usize> = tour.iter().map(|n| n.id).collect();
+        assert!(crate::tour::Tour::from_ids(&ids, &nodes).is_ok());
+        assert!(Euclidean.tour_length(&tour) < Euclidean.tour_length(&two));
+        assert!("lambda=1".parse::<GlsParams>().is_err());
+    }
+}
@@ -0,0 +1,153 @@
+//! grasp, greedy randomized adaptive search: build a tour greedily with some randomness,
+//! 2-opt it to a local optimum, repeat and keep the best
+//!
+//! the construction is nearest neighbor picking at random from its restricted candidate list,
+//! the unvisited nodes within `alpha` of the way from the closest to the farthest one.
+//! alpha 0 is plain nearest neighbor, 1 a random tour
+
+use std::str::FromStr;
+
+use rand::rngs::StdRng;
+use rand::Rng;
+
+use crate::cancel;
+use crate::local_search::{local_search, TwoOpt};
+use crate::metric::Metric;
+use crate::Node;
+
+/// everything `tsp_grasp` can be tuned with
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GraspParams {
+    /// constructions, each followed by local search
+    pub iterations: usize,
+    /// greediness of the construction, from 0 (greedy) to 1 (random)
+    pub alpha: f64,
+}
+
+impl Default for GraspParams {
+    fn default() -> Self {
+        GraspParams {
+            iterations: 20,
+            alpha: 0.02,
+        }
+    }
+}
+
+/// `key=value` pairs separated by `:` over the defaults, e.g. `iterations=50:alpha=0.2`
+impl FromStr for GraspParams {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        fn value<T: FromStr>(key: &str, value: &str) -> Result<T, String>
+        where
+            T::Err: std::fmt::Display,
+        {
+            value
+                .parse()
+                .map_err(|err| format!("invalid {} '{}': {}", key, value, err))
+        }
+
+        let mut params = GraspParams::default();
+        for pair in s.split(':').filter(|p| !p.is_empty()) {
+            let Some((key, v)) = pair.split_once('=') else {
+                return Err(format!("expected key=value, got '{}'", pair));
+            };
+            match key {
+                "iterations" => params.iterations = value(key, v)?,
+                "alpha" => params.alpha = value(key, v)?,
+                _ => {
+                    return Err(format!(
+                        "unknown parameter '{}', try iterations or alpha",
+                        key
+                    ))
+                }
+            }
+        }
+        if !(0.0..=1.0).contains(&params.alpha) {
+            return Err(format!("alpha {} isn't between 0 and 1", params.alpha));
+        }
+        Ok(params)
+    }
+}
+
+/// nearest neighbor from a random node, every step picking a random node of the restricted
+/// candidate list instead of the closest one
+pub fn randomized_nearest_neighbor(
+    nodes: &[Node],
+    metric: &dyn Metric,
+    alpha: f64,
+    rng: &mut StdRng,
+) -> Vec<Node> {
+    let mut left: Vec<&Node> = nodes.iter().collect();
+    let mut tour = Vec::with_capacity(nodes.len());
+    if left.is_empty() {
+        return tour;
+    }
+    let mut current = left.swap_remove(rng.gen_range(0..left.len()));
+    tour.push(current.clone());
+    let mut candidates: Vec<usize> = Vec::new();
+    while !left.is_empty() {
+        let distances: Vec<f64> = left.iter().map(|n| metric.distance(current, n)).collect();
+        let (min, max) = distances
+            .iter()
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), &d| {
+                (min.min(d), max.max(d))
+            });
+        let threshold = min + alpha * (max - min);
+        candidates.clear();
+        candidates.extend((0..left.len()).filter(|&i| distances[i] <= threshold));
+        let pick = candidates[rng.gen_range(0..candidates.len())];
+        current = left.swap_remove(pick);
+        tour.push(current.clone());
+    }
+    tour
+}
+
+/// the best of `iterations` randomized constructions, each 2-opted to a local optimum
+pub fn tsp_grasp(
+    nodes: &[Node],
+    metric: &dyn Metric,
+    params: &GraspParams,
+    rng: &mut StdRng,
+) -> Vec<Node> {
+    let mut best: Option<(f64, Vec<Node>)> = None;
+    for _ in 0..params.iterations.max(1) {
+        // the first iteration always runs, so there's a tour to return
+        if best.is_some() && cancel::is_cancelled() {
+            break;
+        }
+        let mut tour = randomized_nearest_neighbor(nodes, metric, params.alpha, rng);
+        local_search(&mut tour, &TwoOpt, metric);
+        let length = metric.tour_length(&tour);
+        if best.as_ref().is_none_or(|(b, _)| length < *b) {
+            best = Some((length, tour));
+        }
+    }
+    best.map(|(_, tour)| tour).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::grasp::*;
+    use crate::metric::Euclidean;
+    use crate::random_nodes;
+    use crate::solvers::tsp_two_opt;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_grasp_beats_single_two_opt() {
+        let nodes = random_nodes(80, &mut StdRng::seed_from_u64(21));
+        let mut rng = StdRng::seed_from_u64(22);
+        // alpha 0 is plain nearest neighbor apart from the start
+        let greedy = randomized_nearest_neighbor(&nodes, &Euclidean, 0.0, &mut rng);
+        assert_eq!(greedy.len(), nodes.len());
+
+        let params: GraspParams = "iterations=10:alpha=0.2".parse().unwrap();
+        let tour = tsp_grasp(&nodes, &Euclidean, &params, &mut rng);
+        let ids: Vec<usize> = tour.iter().map(|n| n.id).collect();
+        assert!(crate::tour::Tour::from_ids(&ids, &nodes).is_ok());
+        let single = tsp_two_opt(&nodes, &Euclidean);
+        assert!(Euclidean.tour_length(&tour) < Euclidean.tour_length(&single));
+        assert!("alpha=2".parse::<GraspParams>().is_err());
+    }
+}
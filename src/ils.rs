@@ -0,0 +1,57 @@
+//! iterated local search: 2-opt to a local optimum, kick it with a double bridge, 2-opt again
+//! and keep the result when it's no longer than before, over and over
+
+use rand::rngs::StdRng;
+
+use crate::cancel;
+use crate::local_search::{local_search, TwoOpt};
+use crate::metric::Metric;
+use crate::perturbation::double_bridge;
+use crate::Node;
+
+/// the shortest local optimum `iterations` kicks from the given tour found
+pub fn tsp_iterated_local_search(
+    nodes: &[Node],
+    metric: &dyn Metric,
+    iterations: usize,
+    rng: &mut StdRng,
+) -> Vec<Node> {
+    let mut current = nodes.to_vec();
+    local_search(&mut current, &TwoOpt, metric);
+    let mut length = metric.tour_length(&current);
+    for _ in 0..iterations {
+        if cancel::is_cancelled() {
+            break;
+        }
+        let mut candidate = current.clone();
+        double_bridge(&mut candidate, rng);
+        local_search(&mut candidate, &TwoOpt, metric);
+        let candidate_length = metric.tour_length(&candidate);
+        // equal tours are accepted too, to drift along plateaus
+        if candidate_length <= length {
+            current = candidate;
+            length = candidate_length;
+        }
+    }
+    current
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ils::*;
+    use crate::metric::Euclidean;
+    use crate::random_nodes;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_kicks_beat_two_opt() {
+        let nodes = random_nodes(80, &mut StdRng::seed_from_u64(24));
+        let mut two = nodes.clone();
+        local_search(&mut two, &TwoOpt, &Euclidean);
+
+        let tour = tsp_iterated_local_search(&two, &Euclidean, 200, &mut StdRng::seed_from_u64(25));
+        let ids: Vec<usize> = tour.iter().map(|n| n.id).collect();
+        assert!(crate::tour::Tour::from_ids(&ids, &nodes).is_ok());
+        assert!(Euclidean.tour_length(&tour) < Euclidean.tour_length(&two));
+    }
+}
@@ -0,0 +1,171 @@
+//! run metadata embedded in drawn tours
+//!
+//! pngs get one text chunk per entry (tEXt, iTXt if it isn't latin-1),
+//! svgs a `<metadata>` element, so an image alone says how to reproduce it
+
+use std::fmt::Display;
+
+use crate::result_cache::KeyBuilder;
+use crate::Node;
+
+/// key value pairs describing the run that produced a tour
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RunMetadata {
+    pub entries: Vec<(&'static str, String)>,
+}
+
+impl RunMetadata {
+    /// algorithm, seed, tour length and instance hash, plus the software that drew it
+    pub fn new(algorithm: &str, seed: u64, nodes: &[Node], length: f64) -> Self {
+        RunMetadata {
+            entries: vec![
+                (
+                    "Software",
+                    format!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION")),
+                ),
+                ("algorithm", algorithm.to_string()),
+                ("seed", seed.to_string()),
+                ("tour length", format!("{:?}", length)),
+                ("instance hash", format!("{:016x}", instance_hash(nodes))),
+            ],
+        }
+    }
+
+    pub fn with(mut self, key: &'static str, value: impl Display) -> Self {
+        self.entries.push((key, value.to_string()));
+        self
+    }
+}
+
+/// hash of the nodes in id order, the same for every tour through an instance
+pub fn instance_hash(nodes: &[Node]) -> u64 {
+    let mut sorted = nodes.to_vec();
+    sorted.sort_by_key(|node| node.id);
+    KeyBuilder::unversioned().nodes(&sorted).finish().0
+}
+
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+
+/// the crc-32 png chunks end with, over their type and data
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+fn chunk(kind: &[u8; 4], data: &[u8]) -> Vec<u8> {
+    let mut chunk = Vec::with_capacity(data.len() + 12);
+    chunk.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    chunk.extend_from_slice(kind);
+    chunk.extend_from_slice(data);
+    chunk.extend_from_slice(&crc32(&chunk[4..]).to_be_bytes());
+    chunk
+}
+
+/// tEXt for latin-1 values, uncompressed iTXt for everything else
+fn text_chunk(key: &str, value: &str) -> Vec<u8> {
+    let mut data = key.as_bytes().to_vec();
+    data.push(0);
+    if value.chars().all(|c| (c as u32) < 256) {
+        data.extend(value.chars().map(|c| c as u8));
+        chunk(b"tEXt", &data)
+    } else {
+        // no compression, empty language tag and translated keyword
+        data.extend_from_slice(&[0, 0, 0, 0]);
+        data.extend_from_slice(value.as_bytes());
+        chunk(b"iTXt", &data)
+    }
+}
+
+/// the png with a text chunk per entry in front of its IEND chunk
+pub fn png_with_metadata(png: &[u8], metadata: &RunMetadata) -> Result<Vec<u8>, String> {
+    if !png.starts_with(&PNG_SIGNATURE) {
+        return Err("not a png".to_string());
+    }
+    // walk the chunks, IEND is the last one
+    let mut offset = PNG_SIGNATURE.len();
+    loop {
+        let Some(header) = png.get(offset..offset + 8) else {
+            return Err("png has no IEND chunk".to_string());
+        };
+        if &header[4..] == b"IEND" {
+            break;
+        }
+        let length = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+        offset += length + 12;
+    }
+    let mut out = png[..offset].to_vec();
+    for (key, value) in &metadata.entries {
+        out.extend(text_chunk(key, value));
+    }
+    out.extend_from_slice(&png[offset..]);
+    Ok(out)
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// the svg with a `<metadata>` element right after its opening tag
+pub fn svg_with_metadata(svg: &str, metadata: &RunMetadata) -> Result<String, String> {
+    if metadata.entries.is_empty() {
+        return Ok(svg.to_string());
+    }
+    let start = svg.find("<svg").ok_or("not an svg")?;
+    let end = start + svg[start..].find('>').ok_or("unterminated svg tag")? + 1;
+    let mut element = format!(
+        "\n<metadata>\n<run xmlns=\"urn:{}\">\n",
+        env!("CARGO_PKG_NAME")
+    );
+    for (key, value) in &metadata.entries {
+        element.push_str(&format!(
+            "<entry key=\"{}\">{}</entry>\n",
+            escape_xml(key),
+            escape_xml(value)
+        ));
+    }
+    element.push_str("</run>\n</metadata>");
+    Ok(format!("{}{}{}", &svg[..end], element, &svg[end..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::image_meta::*;
+
+    #[test]
+    fn test_png_text_chunks() {
+        // the standard check value of crc-32
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+        let mut png = PNG_SIGNATURE.to_vec();
+        png.extend(chunk(b"IHDR", &[0; 13]));
+        png.extend(chunk(b"IEND", &[]));
+
+        let nodes = crate::random_nodes(5, &mut rand::thread_rng());
+        let mut reversed = nodes.clone();
+        reversed.reverse();
+        assert_eq!(instance_hash(&nodes), instance_hash(&reversed));
+        let metadata = RunMetadata::new("nn -> sa", 7, &nodes, 2.5).with("stages", "nn,sa ∘");
+        let tagged = png_with_metadata(&png, &metadata).unwrap();
+        assert!(tagged.ends_with(&chunk(b"IEND", &[])));
+        let text = String::from_utf8_lossy(&tagged);
+        assert!(text.contains("tEXtseed\u{0}7"));
+        assert!(text.contains("iTXtstages"));
+        assert!(png_with_metadata(b"GIF89a", &metadata).is_err());
+
+        let svg = svg_with_metadata("<?xml?>\n<svg width=\"2\"><rect/></svg>", &metadata).unwrap();
+        assert!(svg.contains("<svg width=\"2\">\n<metadata>"));
+        assert!(svg.contains("<entry key=\"algorithm\">nn -&gt; sa</entry>"));
+    }
+}
@@ -0,0 +1,61 @@
+//! what it costs to put a node into a tour, for everything that builds or repairs tours
+//! by inserting nodes
+
+use crate::metric::Metric;
+use crate::Node;
+
+/// how much longer the tour gets with `node` between `a` and `b`
+pub fn insertion_cost(a: &Node, node: &Node, b: &Node, metric: &dyn Metric) -> f64 {
+    metric.distance(a, node) + metric.distance(node, b) - metric.distance(a, b)
+}
+
+/// the cheapest place for `node` in the tour and the second cheapest,
+/// as (cost, position to insert at)
+pub fn cheapest_insertions(tour: &[Node], node: &Node, metric: &dyn Metric) -> [(f64, usize); 2] {
+    let n = tour.len();
+    if n < 2 {
+        return [(0.0, n), (0.0, n)];
+    }
+    let mut best = [(f64::INFINITY, n), (f64::INFINITY, n)];
+    for p in 0..n {
+        let cost = insertion_cost(&tour[p], node, &tour[(p + 1) % n], metric);
+        if cost < best[0].0 {
+            best = [(cost, p + 1), best[0]];
+        } else if cost < best[1].0 {
+            best[1] = (cost, p + 1);
+        }
+    }
+    best
+}
+
+/// inserts the nodes one after another, each at its cheapest place at that point
+pub fn insert_each(tour: &mut Vec<Node>, nodes: Vec<Node>, metric: &dyn Metric) {
+    for node in nodes {
+        let [(_, position), _] = cheapest_insertions(tour, &node, metric);
+        tour.insert(position, node);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::insertion::*;
+    use crate::metric::Euclidean;
+    use crate::random_nodes;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_costs_match_lengths() {
+        let nodes = random_nodes(20, &mut StdRng::seed_from_u64(35));
+        let (mut tour, rest) = (nodes[..10].to_vec(), nodes[10..].to_vec());
+        for node in rest {
+            let before = Euclidean.tour_length(&tour);
+            let [(cost, position), (second, _)] = cheapest_insertions(&tour, &node, &Euclidean);
+            assert!(cost <= second);
+            let id = node.id;
+            insert_each(&mut tour, vec![node], &Euclidean);
+            assert_eq!(tour[position].id, id);
+            assert!((Euclidean.tour_length(&tour) - before - cost).abs() < 1e-9);
+        }
+    }
+}
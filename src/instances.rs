@@ -0,0 +1,91 @@
+use rand::Rng;
+
+use crate::{random_nodes, Node};
+
+/// kinds of randomly generated instances, all inside the unit square
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Family {
+    /// uniformly distributed nodes
+    Uniform,
+    /// nodes scattered around a handful of random centers
+    Clustered,
+    /// nodes on a regular grid with a little jitter
+    Grid,
+}
+
+impl Family {
+    pub const ALL: [Family; 3] = [Family::Uniform, Family::Clustered, Family::Grid];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Family::Uniform => "uniform",
+            Family::Clustered => "clustered",
+            Family::Grid => "grid",
+        }
+    }
+
+    pub fn generate(&self, N: usize, rng: &mut impl Rng) -> Vec<Node> {
+        match self {
+            Family::Uniform => random_nodes(N, rng),
+            Family::Clustered => clustered_nodes(N, rng),
+            Family::Grid => grid_nodes(N, rng),
+        }
+    }
+}
+
+/// roughly sqrt(N) / 2 clusters with gaussian-ish spread around their centers
+fn clustered_nodes(N: usize, rng: &mut impl Rng) -> Vec<Node> {
+    const SPREAD: f64 = 0.05;
+
+    let cluster_count = ((N as f64).sqrt() / 2.0).ceil().max(1.0) as usize;
+    let centers: Vec<(f64, f64)> = (0..cluster_count)
+        .map(|_| (rng.gen_range(0.1..0.9), rng.gen_range(0.1..0.9)))
+        .collect();
+
+    (0..N)
+        .map(|i| {
+            let (cx, cy) = centers[rng.gen_range(0..cluster_count)];
+            // sum of uniforms is a cheap approximation of a normal distribution
+            let dx: f64 = (0..3).map(|_| rng.gen_range(-SPREAD..SPREAD)).sum();
+            let dy: f64 = (0..3).map(|_| rng.gen_range(-SPREAD..SPREAD)).sum();
+            Node {
+                id: i,
+                x: (cx + dx).clamp(0.0, 1.0),
+                y: (cy + dy).clamp(0.0, 1.0),
+            }
+        })
+        .collect()
+}
+
+/// fills a ceil(sqrt(N)) wide grid row by row, jitter keeps distances from tying
+fn grid_nodes(N: usize, rng: &mut impl Rng) -> Vec<Node> {
+    const JITTER: f64 = 0.01;
+
+    let side = (N as f64).sqrt().ceil().max(1.0) as usize;
+    let cell = 1.0 / side as f64;
+
+    (0..N)
+        .map(|i| Node {
+            id: i,
+            x: (((i % side) as f64 + 0.5) * cell + rng.gen_range(-JITTER..JITTER)).clamp(0.0, 1.0),
+            y: (((i / side) as f64 + 0.5) * cell + rng.gen_range(-JITTER..JITTER)).clamp(0.0, 1.0),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::instances::*;
+
+    #[test]
+    fn test_families_stay_in_unit_square() {
+        let mut rng = rand::thread_rng();
+        for family in Family::ALL {
+            let nodes = family.generate(30, &mut rng);
+            assert_eq!(nodes.len(), 30);
+            assert!(nodes
+                .iter()
+                .all(|n| (0.0..=1.0).contains(&n.x) && (0.0..=1.0).contains(&n.y)));
+        }
+    }
+}
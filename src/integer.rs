@@ -0,0 +1,145 @@
+//! integer coordinates and distances
+//!
+//! most classic benchmarks (tsplib's EUC_2D and friends) place nodes on integer
+//! coordinates and round every distance to the nearest integer, so tour lengths are
+//! integers too. here that is done without floats: squared distances are i64,
+//! the rounded square root is an integer square root and lengths are summed as i64
+
+use rand::Rng;
+
+use crate::metric::Metric;
+use crate::Node;
+
+/// coordinates have to stay below this in magnitude, so squared distances fit in an i64
+pub const MAX_COORDINATE: i64 = 1 << 30;
+
+/// nearest integer to the square root of `s`, ties can't happen for integers
+pub fn rounded_sqrt(s: u64) -> u64 {
+    let root = s.isqrt();
+    // sqrt(s) >= root + 1/2  <=>  s >= root² + root + 1/4  <=>  s > root² + root
+    if s - root * root > root {
+        root + 1
+    } else {
+        root
+    }
+}
+
+/// N random nodes on the integer grid 0..=side
+pub fn random_nodes(N: usize, side: i64, rng: &mut impl Rng) -> Vec<Node> {
+    (0..N)
+        .map(|id| Node {
+            id,
+            x: rng.gen_range(0..=side) as f64,
+            y: rng.gen_range(0..=side) as f64,
+        })
+        .collect()
+}
+
+/// rounded euclidean distance between integer coordinates, like tsplib's EUC_2D
+pub struct IntegerEuclidean {
+    /// coordinates by node id
+    coordinates: Vec<Option<(i64, i64)>>,
+}
+
+impl IntegerEuclidean {
+    /// fails if a coordinate isn't an integer or is too big, see `MAX_COORDINATE`
+    pub fn new(nodes: &[Node]) -> Result<Self, String> {
+        let size = nodes.iter().map(|n| n.id + 1).max().unwrap_or(0);
+        let mut coordinates = vec![None; size];
+        for node in nodes {
+            let integer = |value: f64| {
+                if value.fract() != 0.0 || value.abs() >= MAX_COORDINATE as f64 {
+                    Err(format!(
+                        "node {}: coordinate {} isn't an integer below {}",
+                        node.id, value, MAX_COORDINATE
+                    ))
+                } else {
+                    Ok(value as i64)
+                }
+            };
+            coordinates[node.id] = Some((integer(node.x)?, integer(node.y)?));
+        }
+        Ok(IntegerEuclidean { coordinates })
+    }
+
+    fn position(&self, node: &Node) -> (i64, i64) {
+        self.coordinates
+            .get(node.id)
+            .copied()
+            .flatten()
+            .expect("only nodes of the instance are measured")
+    }
+
+    pub fn integer_distance(&self, from: &Node, to: &Node) -> i64 {
+        let (ax, ay) = self.position(from);
+        let (bx, by) = self.position(to);
+        let (dx, dy) = (ax - bx, ay - by);
+        rounded_sqrt((dx * dx + dy * dy) as u64) as i64
+    }
+
+    /// closed tour length, summed exactly
+    pub fn integer_tour_length<'a>(&self, mut nodes: impl Iterator<Item = &'a Node>) -> i64 {
+        let Some(first) = nodes.next() else {
+            return 0;
+        };
+        let mut length = 0;
+        let mut previous = first;
+        for node in nodes {
+            length += self.integer_distance(previous, node);
+            previous = node;
+        }
+        length + self.integer_distance(previous, first)
+    }
+}
+
+/// solvers see the integers as f64, which represents them exactly below 2^53,
+/// so their comparisons and sums stay exact as well
+impl Metric for IntegerEuclidean {
+    fn distance(&self, from: &Node, to: &Node) -> f64 {
+        self.integer_distance(from, to) as f64
+    }
+
+    fn tour_length(&self, nodes: &[Node]) -> f64 {
+        self.integer_tour_length(nodes.iter()) as f64
+    }
+
+    fn tour_length_refs(&self, nodes: &[&Node]) -> f64 {
+        self.integer_tour_length(nodes.iter().copied()) as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::integer::*;
+
+    #[test]
+    fn test_rounded_sqrt() {
+        let expected = [
+            (0, 0),
+            (1, 1),
+            (2, 1),
+            (3, 2),
+            (6, 2),
+            (7, 3),
+            (12, 3),
+            (13, 4),
+        ];
+        for (s, root) in expected {
+            assert_eq!(rounded_sqrt(s), root, "sqrt({})", s);
+        }
+        // (2^31 + 1/2)² = 2^62 + 2^31 + 1/4
+        assert_eq!(rounded_sqrt((1 << 62) + (1 << 31)), 1 << 31);
+        assert_eq!(rounded_sqrt((1 << 62) + (1 << 31) + 1), (1 << 31) + 1);
+    }
+
+    #[test]
+    fn test_integer_tour_length() {
+        let node = |id, x, y| Node { id, x, y };
+        let nodes = [node(0, 0.0, 0.0), node(1, 3.0, 4.0), node(2, 3.0, 0.0)];
+        let metric = IntegerEuclidean::new(&nodes).unwrap();
+        assert_eq!(metric.integer_tour_length(nodes.iter()), 12);
+        assert_eq!(metric.distance(&nodes[0], &nodes[1]), 5.0);
+
+        assert!(IntegerEuclidean::new(&[node(0, 0.5, 0.0)]).is_err());
+    }
+}
@@ -0,0 +1,306 @@
+//! just enough json to read and write node lists, without a dependency
+//!
+//! the whole of json is parsed into a `Json` tree, numbers as f64. objects keep their
+//! keys in order, `get` finds the first one of a name
+
+use std::fmt::Write;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    /// the value of `key` of an object
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(fields) => fields
+                .iter()
+                .find(|(name, _)| name == key)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Json::Number(number) => Some(*number),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Json]> {
+        match self {
+            Json::Array(values) => Some(values),
+            _ => None,
+        }
+    }
+}
+
+/// a json document, errors say on which line and column it went wrong
+pub fn parse(text: &str) -> Result<Json, String> {
+    let mut parser = Parser { text, position: 0 };
+    let value = parser.value()?;
+    parser.whitespace();
+    if parser.position < text.len() {
+        return Err(parser.error("trailing characters after the document"));
+    }
+    Ok(value)
+}
+
+/// `text` as a json string, with quotes
+pub fn quote(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            // writing to a String never fails
+            c if (c as u32) < 0x20 => {
+                let _ = write!(quoted, "\\u{:04x}", c as u32);
+            }
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    /// byte offset of the next character
+    position: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, message: &str) -> String {
+        let before = &self.text[..self.position];
+        let line = before.matches('\n').count() + 1;
+        let column = before.len() - before.rfind('\n').map_or(0, |i| i + 1) + 1;
+        format!("line {}, column {}: {}", line, column, message)
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.text.as_bytes().get(self.position).copied()
+    }
+
+    fn whitespace(&mut self) {
+        while matches!(self.peek(), Some(b' ' | b'\t' | b'\n' | b'\r')) {
+            self.position += 1;
+        }
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<(), String> {
+        self.whitespace();
+        if self.peek() != Some(byte) {
+            return Err(self.error(&format!("expected '{}'", byte as char)));
+        }
+        self.position += 1;
+        Ok(())
+    }
+
+    fn value(&mut self) -> Result<Json, String> {
+        self.whitespace();
+        match self.peek() {
+            Some(b'{') => self.object(),
+            Some(b'[') => self.array(),
+            Some(b'"') => Ok(Json::String(self.string()?)),
+            Some(b'-' | b'0'..=b'9') => self.number(),
+            Some(_) => {
+                for (word, value) in [
+                    ("null", Json::Null),
+                    ("true", Json::Bool(true)),
+                    ("false", Json::Bool(false)),
+                ] {
+                    if self.text[self.position..].starts_with(word) {
+                        self.position += word.len();
+                        return Ok(value);
+                    }
+                }
+                Err(self.error("expected a value"))
+            }
+            None => Err(self.error("unexpected end of the document")),
+        }
+    }
+
+    /// `[` and `{` lists up to their closing `end`
+    fn list(
+        &mut self,
+        end: u8,
+        mut item: impl FnMut(&mut Self) -> Result<(), String>,
+    ) -> Result<(), String> {
+        self.position += 1;
+        self.whitespace();
+        if self.peek() == Some(end) {
+            self.position += 1;
+            return Ok(());
+        }
+        loop {
+            item(self)?;
+            self.whitespace();
+            match self.peek() {
+                Some(b',') => self.position += 1,
+                Some(byte) if byte == end => {
+                    self.position += 1;
+                    return Ok(());
+                }
+                _ => return Err(self.error(&format!("expected ',' or '{}'", end as char))),
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<Json, String> {
+        let mut values = Vec::new();
+        self.list(b']', |parser| {
+            values.push(parser.value()?);
+            Ok(())
+        })?;
+        Ok(Json::Array(values))
+    }
+
+    fn object(&mut self) -> Result<Json, String> {
+        let mut fields = Vec::new();
+        self.list(b'}', |parser| {
+            parser.whitespace();
+            if parser.peek() != Some(b'"') {
+                return Err(parser.error("expected a key"));
+            }
+            let key = parser.string()?;
+            parser.expect(b':')?;
+            fields.push((key, parser.value()?));
+            Ok(())
+        })?;
+        Ok(Json::Object(fields))
+    }
+
+    fn number(&mut self) -> Result<Json, String> {
+        let start = self.position;
+        while matches!(
+            self.peek(),
+            Some(b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9')
+        ) {
+            self.position += 1;
+        }
+        let number = &self.text[start..self.position];
+        number.parse().map(Json::Number).map_err(|_| {
+            self.position = start;
+            self.error(&format!("invalid number '{}'", number))
+        })
+    }
+
+    /// four hex digits of a `\u` escape
+    fn hex(&mut self) -> Result<u32, String> {
+        let digits = self
+            .text
+            .get(self.position..self.position + 4)
+            .and_then(|digits| u32::from_str_radix(digits, 16).ok())
+            .ok_or_else(|| self.error("expected 4 hex digits"))?;
+        self.position += 4;
+        Ok(digits)
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.position += 1;
+        let mut text = String::new();
+        loop {
+            let Some(c) = self.text[self.position..].chars().next() else {
+                return Err(self.error("unterminated string"));
+            };
+            self.position += c.len_utf8();
+            match c {
+                '"' => return Ok(text),
+                '\\' => {
+                    let Some(escape) = self.peek() else {
+                        return Err(self.error("unterminated string"));
+                    };
+                    self.position += 1;
+                    text.push(match escape {
+                        b'"' => '"',
+                        b'\\' => '\\',
+                        b'/' => '/',
+                        b'b' => '\u{8}',
+                        b'f' => '\u{c}',
+                        b'n' => '\n',
+                        b'r' => '\r',
+                        b't' => '\t',
+                        b'u' => {
+                            let mut code = self.hex()?;
+                            // characters beyond the basic plane come as a surrogate pair
+                            if (0xd800..0xdc00).contains(&code)
+                                && self.text[self.position..].starts_with("\\u")
+                            {
+                                self.position += 2;
+                                let low = self.hex()?;
+                                code = 0x10000
+                                    + ((code - 0xd800) << 10)
+                                    + (low.wrapping_sub(0xdc00) & 0x3ff);
+                            }
+                            char::from_u32(code)
+                                .ok_or_else(|| self.error("invalid unicode escape"))?
+                        }
+                        _ => return Err(self.error("invalid escape")),
+                    });
+                }
+                c => text.push(c),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::json::*;
+
+    #[test]
+    fn test_json_documents() {
+        let document = parse(
+            r#" {"name": "tiny \"one\"", "nodes": [{"id": 0, "x": -1.5e1, "y": 2},
+                 {"id": 1, "x": 0, "y": 0.25}], "ok": true, "none": null, "text": "é😀"} "#,
+        )
+        .unwrap();
+        assert_eq!(
+            document.get("name").and_then(Json::as_str),
+            Some("tiny \"one\"")
+        );
+        let nodes = document.get("nodes").and_then(Json::as_array).unwrap();
+        assert_eq!(nodes[0].get("x").and_then(Json::as_f64), Some(-15.0));
+        assert_eq!(nodes[1].get("y").and_then(Json::as_f64), Some(0.25));
+        assert_eq!(document.get("ok"), Some(&Json::Bool(true)));
+        assert_eq!(document.get("none"), Some(&Json::Null));
+        assert_eq!(document.get("text").and_then(Json::as_str), Some("é😀"));
+        assert_eq!(
+            parse(&quote("a \"b\"\n\u{1}")).unwrap(),
+            Json::String("a \"b\"\n\u{1}".to_string())
+        );
+
+        let errors = [
+            ("[1, 2", "line 1, column 6: expected ',' or ']'"),
+            ("{\"a\" 1}", "line 1, column 6: expected ':'"),
+            ("[1,\n -]", "line 2, column 2: invalid number '-'"),
+            (
+                "[] x",
+                "line 1, column 4: trailing characters after the document",
+            ),
+            ("\"abc", "line 1, column 5: unterminated string"),
+        ];
+        for (text, error) in errors {
+            assert_eq!(parse(text).unwrap_err(), error);
+        }
+    }
+}
@@ -0,0 +1,196 @@
+//! solvers for the travelling salesman problem, and everything around them
+//!
+//! a tour is a `Vec<Node>` in visiting order, back to the first node at the end.
+//! the algorithms live in `solvers`, drawing in `plot`, everything else in its own module.
+//! the `travelling-salesman-problem` binary is a thin command line on top of this
+//!
+//! ```
+//! use rand::SeedableRng;
+//! use tsp::metric::Euclidean;
+//! use tsp::solvers::{tsp_nearest_neighbor, SimulatedAnnealing, Solver};
+//!
+//! let mut rng = rand::rngs::StdRng::seed_from_u64(1);
+//! let nodes = tsp::random_nodes(50, &mut rng);
+//! let tour = tsp_nearest_neighbor(&nodes, &Euclidean);
+//! let annealed = SimulatedAnnealing::default().solve(&tour, &Euclidean, &mut rng);
+//! println!("{} -> {}", tsp::get_tour_length(&tour), tsp::get_tour_length(&annealed));
+//! ```
+
+#![allow(non_snake_case)]
+
+pub mod alns;
+pub mod annealing;
+pub mod async_solve;
+pub mod branch_and_bound;
+pub mod cancel;
+pub mod construction;
+pub mod convert;
+pub mod cross_entropy;
+pub mod decomposition;
+pub mod dot;
+pub mod events;
+#[cfg(feature = "exact")]
+pub mod exact;
+pub mod explicit;
+pub mod formats;
+pub mod genetic;
+pub mod geo;
+pub mod geocode;
+pub mod geometry;
+pub mod gls;
+pub mod grasp;
+pub mod ils;
+pub mod image_meta;
+pub mod insertion;
+pub mod instances;
+pub mod integer;
+pub mod json;
+pub mod lin_kernighan;
+pub mod local_search;
+pub mod matrix_cache;
+pub mod memory;
+pub mod metric;
+pub mod model;
+pub mod mtsp;
+pub mod multistart;
+pub mod navigation;
+pub mod objective;
+pub mod path;
+pub mod perturbation;
+pub mod pipeline;
+pub mod plot;
+pub mod profiling;
+pub mod quality;
+pub mod result_cache;
+pub mod rng;
+pub mod ruin_recreate;
+pub mod solvers;
+pub mod som;
+pub mod spatial;
+pub mod stats;
+pub mod tabu;
+pub mod tikz;
+pub mod tour;
+pub mod tour_lengths;
+pub mod tour_order;
+pub mod tournament;
+pub mod tsplib;
+pub mod vns;
+
+use std::hash::{Hash, Hasher};
+
+use rand::Rng;
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Node {
+    pub id: usize,
+    pub x: f64,
+    pub y: f64,
+}
+
+impl Hash for Node {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.id.hash(state);
+    }
+}
+
+impl PartialEq for Node {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+impl Eq for Node {}
+
+pub fn random_nodes(N: usize, rng: &mut impl Rng) -> Vec<Node> {
+    (0..N)
+        .map(|i| Node {
+            id: i,
+            x: rng.gen::<f64>(),
+            y: rng.gen::<f64>(),
+        })
+        .collect()
+}
+
+/// position of every node of `nodes` by its id, to map tours of the same nodes back to positions
+pub fn positions_by_id(nodes: &[Node]) -> Vec<usize> {
+    let mut positions = vec![0; nodes.iter().map(|node| node.id + 1).max().unwrap_or(0)];
+    for (position, node) in nodes.iter().enumerate() {
+        positions[node.id] = position;
+    }
+    positions
+}
+
+/// euclidian distance between 2 nodes
+pub fn node_distance(node1: &Node, node2: &Node) -> f64 {
+    ((node2.x - node1.x).powi(2) + (node2.y - node1.y).powi(2)).sqrt()
+}
+
+/// traverses pairs of nodes in order and sums the distances
+pub fn get_tour_length(nodes: &[Node]) -> f64 {
+    let mut length: f64 = 0.0;
+    for window_slice in nodes.windows(2) {
+        match window_slice {
+            [n1, n2] => length += node_distance(n1, n2),
+            _ => unreachable!(".windows should guarantee slices of 2 always"),
+        }
+    }
+    // if vector has at least 1 element, add distance from last to first node
+    if let Some(first) = nodes.first() {
+        if let Some(last) = nodes.last() {
+            length += node_distance(last, first);
+        }
+    }
+    length
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    #[test]
+    fn test_node_distance_simple() {
+        assert_eq!(
+            node_distance(
+                &Node {
+                    id: 0,
+                    x: 0.0,
+                    y: 0.0,
+                },
+                &Node {
+                    id: 0,
+                    x: 0.0,
+                    y: 1.0,
+                }
+            ),
+            1.0
+        );
+    }
+
+    #[test]
+    fn test_tour_length_simple() {
+        let tour = [
+            Node {
+                id: 0,
+                x: 0.0,
+                y: 0.0,
+            },
+            Node {
+                id: 0,
+                x: 1.0,
+                y: 0.0,
+            },
+            Node {
+                id: 0,
+                x: 1.0,
+                y: 1.0,
+            },
+            Node {
+                id: 0,
+                x: 0.0,
+                y: 1.0,
+            },
+        ];
+        assert_eq!(get_tour_length(&tour), 4.0);
+    }
+}
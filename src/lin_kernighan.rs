@@ -0,0 +1,298 @@
+//! lin-kernighan: sequential edge exchanges of variable depth
+//!
+//! from a base node t1 with its tour neighbor t2 the edge (t1, t2) is broken, t2 is joined to a
+//! close node t3 and the tour edge (t4, t3) that keeps it a tour is broken next, so every step is a
+//! 2-opt move and the tour stays valid throughout. the chain goes on from t4 as long as the gain so
+//! far stays positive, up to `depth` steps, and is rolled back to its best closed tour.
+//! the first step tries every candidate of t2, deeper steps only the most promising one.
+//! candidates are the `neighbors` closest nodes of every node.
+//! the gains take every edge to cost the same both ways, on asymmetric metrics a chain is only
+//! kept if the tour it ends with is shorter when measured
+
+use crate::cancel;
+use crate::metric::Metric;
+use crate::profiling;
+use crate::rng;
+use crate::Node;
+
+/// gains below this are float noise, taking them could cycle forever
+const EPSILON: f64 = 1e-12;
+
+/// a 2-opt move (a, b, c, d), see `ArrayTour::two_opt_move`
+type Exchange = (usize, usize, usize, usize);
+
+/// the tour as positions of node indices, both ways
+struct ArrayTour {
+    order: Vec<usize>,
+    position: Vec<usize>,
+}
+
+impl ArrayTour {
+    fn new(n: usize) -> Self {
+        ArrayTour {
+            order: (0..n).collect(),
+            position: (0..n).collect(),
+        }
+    }
+
+    fn next(&self, node: usize) -> usize {
+        self.order[(self.position[node] + 1) % self.order.len()]
+    }
+
+    fn prev(&self, node: usize) -> usize {
+        let n = self.order.len();
+        self.order[(self.position[node] + n - 1) % n]
+    }
+
+    /// replaces edges (a, b) and (c, d), where b follows a and d follows c,
+    /// with (a, c) and (b, d) by reversing whichever side of the tour is shorter
+    fn two_opt_move(&mut self, (_, b, c, d): Exchange) {
+        let n = self.order.len();
+        let inner = (self.position[c] + n - self.position[b]) % n + 1;
+        let (from, length) = if 2 * inner <= n {
+            (self.position[b], inner)
+        } else {
+            (self.position[d], n - inner)
+        };
+        for k in 0..length / 2 {
+            let (i, j) = ((from + k) % n, (from + length - 1 - k) % n);
+            self.order.swap(i, j);
+            self.position[self.order[i]] = i;
+            self.position[self.order[j]] = j;
+        }
+    }
+
+    /// undoes `two_opt_move(a, b, c, d)`, whichever way round the tour runs now
+    fn undo(&mut self, (a, b, c, d): Exchange) {
+        if self.next(a) == c {
+            self.two_opt_move((a, c, b, d));
+        } else {
+            self.two_opt_move((d, b, c, a));
+        }
+    }
+}
+
+/// the `k` closest other nodes of every node, closest first, on all cores
+fn candidates(nodes: &[Node], k: usize, metric: &dyn Metric) -> Vec<Vec<usize>> {
+    let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+    rng::parallel_map(nodes.len(), threads, |i| {
+        let mut others: Vec<(f64, usize)> = (0..nodes.len())
+            .filter(|&j| j != i)
+            .map(|j| (metric.distance(&nodes[i], &nodes[j]), j))
+            .collect();
+        others.sort_by(|a, b| a.0.total_cmp(&b.0));
+        others.into_iter().take(k).map(|(_, j)| j).collect()
+    })
+}
+
+struct Search<'a> {
+    nodes: &'a [Node],
+    metric: &'a dyn Metric,
+    candidates: Vec<Vec<usize>>,
+    tour: ArrayTour,
+    depth: usize,
+}
+
+impl Search<'_> {
+    fn d(&self, a: usize, b: usize) -> f64 {
+        self.metric.distance(&self.nodes[a], &self.nodes[b])
+    }
+
+    fn length(&self) -> f64 {
+        let n = self.tour.order.len();
+        (0..n)
+            .map(|i| self.d(self.tour.order[i], self.tour.order[(i + 1) % n]))
+            .sum()
+    }
+
+    /// t4 for joining t2 to t3 while t2 is on the `forward` side of t1,
+    /// None if the step would break the tour or undo itself
+    fn partner(&self, t1: usize, t2: usize, t3: usize, forward: bool) -> Option<usize> {
+        let t4 = if forward {
+            self.tour.prev(t3)
+        } else {
+            self.tour.next(t3)
+        };
+        (t3 != t1 && t3 != t2 && t4 != t2).then_some(t4)
+    }
+
+    /// applies the step t1-t2, t3-t4 for t2 on the `forward` side of t1
+    fn step(&mut self, t1: usize, t2: usize, t3: usize, t4: usize, forward: bool) -> Exchange {
+        let exchange = if forward {
+            (t1, t2, t4, t3)
+        } else {
+            (t3, t4, t2, t1)
+        };
+        self.tour.two_opt_move(exchange);
+        exchange
+    }
+
+    /// runs chains from `t1` in both directions, keeps the first one that shortens the tour
+    /// and returns its gain
+    fn improve_from(&mut self, t1: usize) -> Option<f64> {
+        for forward in [true, false] {
+            let t2 = if forward {
+                self.tour.next(t1)
+            } else {
+                self.tour.prev(t1)
+            };
+            for first in 0..self.candidates[t2].len() {
+                let t3 = self.candidates[t2][first];
+                if let Some(gain) = self.chain(t1, t2, t3, forward) {
+                    return Some(gain);
+                }
+            }
+        }
+        None
+    }
+
+    /// the chain starting with the step t1-t2, t3, rolled back to its best tour.
+    /// None and the tour unchanged if no point of the chain is shorter
+    fn chain(&mut self, t1: usize, t2: usize, t3: usize, forward: bool) -> Option<f64> {
+        let mut gain = self.d(t1, t2) - self.d(t2, t3);
+        let mut t4 = self.partner(t1, t2, t3, forward)?;
+        if gain <= EPSILON {
+            return None;
+        }
+        let measured = (!self.metric.is_symmetric()).then(|| self.length());
+
+        let mut moves = Vec::new();
+        let mut added = vec![(t2, t3)];
+        let (mut t2, mut forward, mut t3) = (t2, forward, t3);
+        let mut best = (EPSILON, 0);
+        loop {
+            moves.push(self.step(t1, t2, t3, t4, forward));
+            gain += self.d(t4, t3);
+            let closed = gain - self.d(t4, t1);
+            if closed > best.0 {
+                best = (closed, moves.len());
+            }
+            if moves.len() >= self.depth {
+                break;
+            }
+
+            // t4 is t1's neighbor now, the edge between them is the next one to break
+            t2 = t4;
+            forward = self.tour.next(t1) == t2;
+            let mut next: Option<(f64, usize, usize)> = None;
+            for &candidate in &self.candidates[t2] {
+                let opened = gain - self.d(t2, candidate);
+                if opened <= EPSILON {
+                    // candidates are sorted, the rest only open less
+                    break;
+                }
+                let Some(partner) = self.partner(t1, t2, candidate, forward) else {
+                    continue;
+                };
+                // edges joined earlier in the chain stay
+                if added
+                    .iter()
+                    .any(|&(x, y)| (x, y) == (partner, candidate) || (y, x) == (partner, candidate))
+                {
+                    continue;
+                }
+                let promise = self.d(partner, candidate) - self.d(t2, candidate);
+                if next.is_none_or(|(p, ..)| promise > p) {
+                    next = Some((promise, candidate, partner));
+                }
+            }
+            let Some((_, candidate, partner)) = next else {
+                break;
+            };
+            gain -= self.d(t2, candidate);
+            added.push((t2, candidate));
+            (t3, t4) = (candidate, partner);
+        }
+
+        let (closed, keep) = best;
+        for exchange in moves.drain(keep..).rev() {
+            self.tour.undo(exchange);
+        }
+        if keep == 0 {
+            return None;
+        }
+        let Some(before) = measured else {
+            return Some(closed);
+        };
+        let gain = before - self.length();
+        if gain <= EPSILON {
+            for exchange in moves.drain(..).rev() {
+                self.tour.undo(exchange);
+            }
+            return None;
+        }
+        Some(gain)
+    }
+}
+
+/// improves the tour given by the order of `nodes` until no chain from any node shortens it,
+/// or the run is interrupted
+pub fn solve(nodes: &[Node], metric: &dyn Metric, depth: usize, neighbors: usize) -> Vec<Node> {
+    let n = nodes.len();
+    if n < 5 {
+        return nodes.to_vec();
+    }
+    let mut search = Search {
+        nodes,
+        metric,
+        candidates: candidates(nodes, neighbors.min(n - 1), metric),
+        tour: ArrayTour::new(n),
+        depth: depth.max(1),
+    };
+    let mut improved = true;
+    while improved && !cancel::is_cancelled() {
+        improved = false;
+        for t1 in 0..n {
+            if cancel::is_cancelled() {
+                break;
+            }
+            let _scope = profiling::scope("move evaluation");
+            while search.improve_from(t1).is_some() {
+                improved = true;
+            }
+        }
+    }
+    search
+        .tour
+        .order
+        .iter()
+        .map(|&i| nodes[i].clone())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lin_kernighan::*;
+    use crate::local_search::{local_search, ThreeOpt};
+    use crate::metric::Euclidean;
+    use crate::random_nodes;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_moves_undo() {
+        let mut tour = ArrayTour::new(9);
+        let (a, b, c, d) = (7, 8, 2, 3);
+        tour.two_opt_move((a, b, c, d));
+        assert!(tour.next(a) == c || tour.prev(a) == c);
+        assert!(tour.next(b) == d || tour.prev(b) == d);
+        tour.undo((a, b, c, d));
+        let start = tour.position[0];
+        let forward: Vec<usize> = (0..9).map(|k| tour.order[(start + k) % 9]).collect();
+        let backward: Vec<usize> = (0..9).map(|k| tour.order[(start + 9 - k) % 9]).collect();
+        assert!(forward == (0..9).collect::<Vec<_>>() || backward == (0..9).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_beats_three_opt() {
+        let nodes = random_nodes(120, &mut StdRng::seed_from_u64(9));
+        let tour = solve(&nodes, &Euclidean, 10, 8);
+        let ids: Vec<usize> = tour.iter().map(|n| n.id).collect();
+        assert!(crate::tour::Tour::from_ids(&ids, &nodes).is_ok());
+
+        let mut three = nodes.clone();
+        local_search(&mut three, &ThreeOpt::default(), &Euclidean);
+        assert!(Euclidean.tour_length(&tour) <= Euclidean.tour_length(&three) * 1.02);
+        assert!(crate::geometry::crossings(&tour).is_empty());
+    }
+}
@@ -0,0 +1,1391 @@
+//! improving moves and the local search that applies them until none is left
+//!
+//! a move operator looks at one edge of the tour at a time and applies the best improving
+//! move that removes it, or the first one with `Improvement::First`.
+//! the search sweeps all edges until a whole pass finds nothing.
+//! `local_search_near` only tries moves that join a node to one of its closest nodes,
+//! its `Candidates`, which takes a pass from quadratic to linear in the tour length.
+//! `local_search_order` does the same on a `TourOrder` for the operators that support it.
+//! on asymmetric metrics both fall back to `local_search`, whose `improve_at` of every operator
+//! also counts the edges inside the chains it reverses
+
+use rand::rngs::StdRng;
+use rand::seq::{index, SliceRandom};
+use rand::Rng;
+
+use crate::cancel;
+use crate::metric::Metric;
+use crate::profiling;
+use crate::tour_order::{exchange, Representation, TourOrder};
+use crate::Node;
+
+/// gains below this are float noise, taking them could cycle forever
+const EPSILON: f64 = 1e-12;
+
+/// a neighborhood for `local_search`
+pub trait MoveOperator: Send + Sync {
+    fn name(&self) -> &str;
+
+    /// applies an improving move that removes the edge from position `i` to `i + 1`,
+    /// the best one unless the operator says otherwise.
+    /// returns how much shorter the tour got, None if no move improves it
+    fn improve_at(&self, tour: &mut [Node], i: usize, metric: &dyn Metric) -> Option<f64>;
+
+    /// applies a random move of the neighborhood whether it helps or not, to shake a tour
+    /// out of its local optimum
+    fn random_move(&self, tour: &mut [Node], rng: &mut StdRng);
+
+    /// `improve_at` among the moves that add an edge from a node to one of its `candidates`,
+    /// keeping their positions up to date. operators without such a version try every move
+    fn improve_near(
+        &self,
+        tour: &mut [Node],
+        i: usize,
+        candidates: &mut Candidates,
+        metric: &dyn Metric,
+    ) -> Option<f64> {
+        let gain = self.improve_at(tour, i, metric)?;
+        candidates.update(tour, 0..tour.len());
+        Some(gain)
+    }
+
+    /// `improve_near` at both edges of `node` for `local_search_order`, on a `TourOrder` over
+    /// the positions in `nodes` with `candidates` by position.
+    /// returns the nodes that got another neighbor, None if no move improves the tour
+    /// or the operator only works on arrays
+    fn improve_order(
+        &self,
+        _order: &mut dyn TourOrder,
+        _node: usize,
+        _nodes: &[Node],
+        _candidates: &[Vec<usize>],
+        _metric: &dyn Metric,
+    ) -> Option<Vec<usize>> {
+        None
+    }
+}
+
+/// candidate lists of `k` nodes per node, a good default
+pub const DEFAULT_NEIGHBORS: usize = 10;
+
+/// the closest nodes of every node by euclidean distance, and where every node is in the tour
+///
+/// it also notices the nodes that got other neighbors in the tour, for the don't-look bits
+/// of `local_search_near`
+pub struct Candidates {
+    /// by node id, ids closest first
+    lists: Vec<Vec<usize>>,
+    /// by node id
+    positions: Vec<usize>,
+    /// by node id, the ids of its neighbors in the tour, smaller first
+    links: Vec<(usize, usize)>,
+    /// ids whose links changed since the last `take_woken`
+    woken: Vec<usize>,
+}
+
+impl Candidates {
+    /// the `k` closest other nodes of every node of `tour`, with a k-d tree in O(n log n)
+    pub fn new(tour: &[Node], k: usize) -> Self {
+        let size = tour.iter().map(|n| n.id + 1).max().unwrap_or(0);
+        let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+        let mut lists = vec![Vec::new(); size];
+        for (node, list) in tour
+            .iter()
+            .zip(crate::spatial::neighbor_lists(tour, k, threads))
+        {
+            lists[node.id] = list.into_iter().map(|j| tour[j].id).collect();
+        }
+        let mut candidates = Candidates {
+            lists,
+            positions: vec![0; size],
+            links: vec![(usize::MAX, usize::MAX); size],
+            woken: Vec::new(),
+        };
+        candidates.update(tour, 0..tour.len());
+        candidates
+    }
+
+    /// positions in the tour of the candidates of `node`, closest first
+    pub fn of(&self, node: &Node) -> impl Iterator<Item = usize> + '_ {
+        self.lists[node.id].iter().map(|&id| self.positions[id])
+    }
+
+    /// records the positions of the nodes in `range`, consecutive positions, after a move
+    /// rearranged them
+    pub fn update(&mut self, tour: &[Node], range: impl IntoIterator<Item = usize>) {
+        let n = tour.len();
+        let mut range = range.into_iter().peekable();
+        let Some(&first) = range.peek() else {
+            return;
+        };
+        let mut last = first;
+        for position in range {
+            self.positions[tour[position].id] = position;
+            self.relink(tour, position);
+            last = position;
+        }
+        // the nodes right outside of the range can have a new neighbor too
+        self.relink(tour, (first + n - 1) % n);
+        self.relink(tour, (last + 1) % n);
+    }
+
+    fn relink(&mut self, tour: &[Node], position: usize) {
+        let n = tour.len();
+        let (prev, next) = (tour[(position + n - 1) % n].id, tour[(position + 1) % n].id);
+        let (id, link) = (tour[position].id, (prev.min(next), prev.max(next)));
+        if self.links[id] != link {
+            self.links[id] = link;
+            self.woken.push(id);
+        }
+    }
+
+    /// ids of the nodes that got another neighbor in the tour since the last call
+    pub fn take_woken(&mut self) -> Vec<usize> {
+        std::mem::take(&mut self.woken)
+    }
+}
+
+/// what reversing a chain of the tour adds to the edges inside it, which point the other way
+/// afterwards. nothing on symmetric metrics, on asymmetric ones prefix sums of the edges
+/// forwards and backwards make it O(1) per chain after a pass over the tour
+struct Reversals {
+    /// by position, the edges before it along the tour and against it
+    forward: Vec<f64>,
+    backward: Vec<f64>,
+}
+
+impl Reversals {
+    /// None on symmetric metrics
+    fn new(tour: &[Node], metric: &dyn Metric) -> Option<Self> {
+        if metric.is_symmetric() {
+            return None;
+        }
+        let (mut forward, mut backward) = (vec![0.0], vec![0.0]);
+        for pair in tour.windows(2) {
+            forward.push(forward[forward.len() - 1] + metric.distance(&pair[0], &pair[1]));
+            backward.push(backward[backward.len() - 1] + metric.distance(&pair[1], &pair[0]));
+        }
+        Some(Reversals { forward, backward })
+    }
+
+    /// how much longer reversing positions from..=to makes the edges between them
+    fn cost(reversals: &Option<Reversals>, from: usize, to: usize) -> f64 {
+        reversals.as_ref().map_or(0.0, |r| {
+            (r.backward[to] - r.backward[from]) - (r.forward[to] - r.forward[from])
+        })
+    }
+}
+
+/// `count` distinct positions below `below`, ascending
+fn random_positions(below: usize, count: usize, rng: &mut StdRng) -> Vec<usize> {
+    let mut positions = index::sample(rng, below, count).into_vec();
+    positions.sort_unstable();
+    positions
+}
+
+/// swap: exchanges two nodes, the best exchange for every node
+pub struct Swap;
+
+impl Swap {
+    /// how much shorter swapping the nodes at `i` and `j` makes the tour
+    fn gain(tour: &[Node], i: usize, j: usize, metric: &dyn Metric) -> f64 {
+        let n = tour.len();
+        let swapped = |k: usize| match k {
+            _ if k == i => j,
+            _ if k == j => i,
+            _ => k,
+        };
+        // the edges starting at these positions touch i or j, adjacent ones share an edge
+        let starts = [(i + n - 1) % n, i, (j + n - 1) % n, j];
+        let mut gain = 0.0;
+        for (s, &k) in starts.iter().enumerate() {
+            if starts[..s].contains(&k) {
+                continue;
+            }
+            let l = (k + 1) % n;
+            gain += metric.distance(&tour[k], &tour[l])
+                - metric.distance(&tour[swapped(k)], &tour[swapped(l)]);
+        }
+        gain
+    }
+}
+
+impl MoveOperator for Swap {
+    fn name(&self) -> &str {
+        "swap"
+    }
+
+    fn improve_at(&self, tour: &mut [Node], i: usize, metric: &dyn Metric) -> Option<f64> {
+        let n = tour.len();
+        if n < 4 {
+            return None;
+        }
+        let mut best = (EPSILON, i);
+        for j in (0..n).filter(|&j| j != i) {
+            let gain = Swap::gain(tour, i, j, metric);
+            if gain > best.0 {
+                best = (gain, j);
+            }
+        }
+        let (gain, j) = best;
+        if j == i {
+            return None;
+        }
+        tour.swap(i, j);
+        Some(gain)
+    }
+
+    fn random_move(&self, tour: &mut [Node], rng: &mut StdRng) {
+        if tour.len() >= 2 {
+            let positions = random_positions(tour.len(), 2, rng);
+            tour.swap(positions[0], positions[1]);
+        }
+    }
+}
+
+/// sweeps every edge with `operator` until a full pass doesn't improve anything,
+/// stops early when the run is interrupted. returns the number of moves applied
+pub fn local_search(tour: &mut [Node], operator: &dyn MoveOperator, metric: &dyn Metric) -> usize {
+    let mut moves = 0;
+    loop {
+        let mut improved = false;
+        for i in 0..tour.len() {
+            if cancel::is_cancelled() {
+                return moves;
+            }
+            let improvement = {
+                let _scope = profiling::scope("move evaluation");
+                operator.improve_at(tour, i, metric)
+            };
+            if improvement.is_some() {
+                moves += 1;
+                improved = true;
+            }
+        }
+        if !improved {
+            return moves;
+        }
+    }
+}
+
+/// `local_search` with `improve_near` and `neighbors` candidates per node, and don't-look bits
+///
+/// nodes wait in a queue and try the moves at both of their edges in turn. a node that finds
+/// nothing leaves the queue, and only comes back once a move gives it another neighbor, so after
+/// the first sweep the search only looks where the tour changed.
+/// the operators never start a move at the closing edge, and without trying all moves they
+/// might not get to it from the others either, so once the queue is empty the tour is rotated by
+/// half and all nodes are queued again, until two such rounds in a row find nothing
+pub fn local_search_near(
+    tour: &mut [Node],
+    operator: &dyn MoveOperator,
+    metric: &dyn Metric,
+    neighbors: usize,
+) -> usize {
+    if !metric.is_symmetric() {
+        return local_search(tour, operator, metric);
+    }
+    let n = tour.len();
+    let mut candidates = Candidates::new(tour, neighbors);
+    let mut queued = vec![false; candidates.positions.len()];
+    let mut queue = std::collections::VecDeque::with_capacity(n);
+    let (mut moves, mut idle, mut shift) = (0, 0, 0);
+    'search: while idle < 2 {
+        candidates.take_woken();
+        for node in tour.iter() {
+            queued[node.id] = true;
+            queue.push_back(node.id);
+        }
+        let before = moves;
+        while let Some(id) = queue.pop_front() {
+            if cancel::is_cancelled() {
+                break 'search;
+            }
+            queued[id] = false;
+            let i = candidates.positions[id];
+            let improved = [(i + n - 1) % n, i].into_iter().any(|edge| {
+                let _scope = profiling::scope("move evaluation");
+                operator
+                    .improve_near(tour, edge, &mut candidates, metric)
+                    .is_some()
+            });
+            if improved {
+                moves += 1;
+                for woken in candidates.take_woken() {
+                    if !queued[woken] {
+                        queued[woken] = true;
+                        queue.push_back(woken);
+                    }
+                }
+            }
+        }
+        idle = if moves > before { 0 } else { idle + 1 };
+        tour.rotate_left(n / 2);
+        shift = (shift + n / 2) % n.max(1);
+        candidates.update(tour, 0..n);
+    }
+    tour.rotate_right(shift);
+    moves
+}
+
+/// `local_search_near` on the `TourOrder` of `representation` instead of the array, with the
+/// operator's `improve_order`, for the operators that have one
+///
+/// moves only relink the nodes they touch, so the don't-look bits come from the moves directly,
+/// and without positions there is no closing edge to rotate around. a move can still open up one
+/// for a node far away that joins the new edges, so once the queue is empty all nodes are queued
+/// again, until a sweep finds nothing
+pub fn local_search_order(
+    tour: &mut [Node],
+    operator: &dyn MoveOperator,
+    metric: &dyn Metric,
+    neighbors: usize,
+    representation: Representation,
+) -> usize {
+    if !metric.is_symmetric() {
+        return local_search(tour, operator, metric);
+    }
+    let n = tour.len();
+    if n < 4 {
+        return 0;
+    }
+    let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+    let candidates = crate::spatial::neighbor_lists(tour, neighbors, threads);
+    let mut order = representation.new_order(n);
+    let mut queued = vec![false; n];
+    let mut queue = std::collections::VecDeque::with_capacity(n);
+    let mut moves = 0;
+    'search: loop {
+        let before = moves;
+        queued.fill(true);
+        queue.extend(0..n);
+        while let Some(node) = queue.pop_front() {
+            if cancel::is_cancelled() {
+                break 'search;
+            }
+            queued[node] = false;
+            let woken = {
+                let _scope = profiling::scope("move evaluation");
+                operator.improve_order(order.as_mut(), node, tour, &candidates, metric)
+            };
+            if let Some(woken) = woken {
+                moves += 1;
+                for woken in woken {
+                    if !queued[woken] {
+                        queued[woken] = true;
+                        queue.push_back(woken);
+                    }
+                }
+            }
+        }
+        if moves == before {
+            break;
+        }
+    }
+    let original = tour.to_vec();
+    for (slot, i) in tour.iter_mut().zip(order.order()) {
+        *slot = original[i].clone();
+    }
+    moves
+}
+
+/// 2-opt: replaces edges (a, b) and (c, d) with (a, c) and (b, d) by reversing b..=c,
+/// the best such exchange for every edge
+pub struct TwoOpt;
+
+impl MoveOperator for TwoOpt {
+    fn name(&self) -> &str {
+        "2-opt"
+    }
+
+    fn improve_at(&self, tour: &mut [Node], i: usize, metric: &dyn Metric) -> Option<f64> {
+        let n = tour.len();
+        if n < 4 {
+            return None;
+        }
+        let d = |x: usize, y: usize| metric.distance(&tour[x], &tour[y % n]);
+        let (a, b) = (i, i + 1);
+        if b >= n {
+            // the closing edge is covered as the second edge of the others
+            return None;
+        }
+        let ab = d(a, b);
+        // d(a, c) and d(b, c + 1) of every c in one batch each
+        let (mut from_a, mut from_b) = (Vec::new(), Vec::new());
+        metric.distances(&tour[a], &tour[b + 1..], &mut from_a);
+        metric.distances(&tour[b], &tour[(b + 2).min(n)..], &mut from_b);
+        from_b.push(d(b, 0));
+        let reversals = Reversals::new(tour, metric);
+
+        let mut best = (EPSILON, 0);
+        for c in b + 1..n {
+            // the edges would share a node
+            if a == 0 && c == n - 1 {
+                continue;
+            }
+            let gain = ab + d(c, c + 1)
+                - from_a[c - b - 1]
+                - from_b[c - b - 1]
+                - Reversals::cost(&reversals, b, c);
+            if gain > best.0 {
+                best = (gain, c);
+            }
+        }
+
+        let (gain, c) = best;
+        if c == 0 {
+            return None;
+        }
+        tour[b..=c].reverse();
+        Some(gain)
+    }
+
+    fn random_move(&self, tour: &mut [Node], rng: &mut StdRng) {
+        if tour.len() >= 4 {
+            let positions = random_positions(tour.len(), 2, rng);
+            tour[positions[0]..=positions[1]].reverse();
+        }
+    }
+
+    /// joins a to a candidate c and b to the node after c, or b to a candidate c and a to the
+    /// node before c. candidates are closest first, once they are as far as b from a
+    /// (or a from b) the new edge alone costs more than the removed one saves
+    fn improve_near(
+        &self,
+        tour: &mut [Node],
+        i: usize,
+        candidates: &mut Candidates,
+        metric: &dyn Metric,
+    ) -> Option<f64> {
+        let n = tour.len();
+        let (a, b) = (i, i + 1);
+        if n < 4 || b >= n {
+            return None;
+        }
+        let d = |x: usize, y: usize| metric.distance(&tour[x], &tour[y % n]);
+        let ab = d(a, b);
+
+        // gain and the positions to reverse
+        let mut best = (EPSILON, 0, 0);
+        let mut consider = |gain: f64, from: usize, to: usize| {
+            if gain > best.0 {
+                best = (gain, from, to);
+            }
+        };
+        for c in candidates.of(&tour[a]) {
+            if d(a, c) >= ab {
+                break;
+            }
+            if c > b && !(a == 0 && c == n - 1) {
+                consider(ab + d(c, c + 1) - d(a, c) - d(b, c + 1), b, c);
+            } else if c < a {
+                consider(ab + d(c, c + 1) - d(c, a) - d(c + 1, b), c + 1, a);
+            }
+        }
+        for c in candidates.of(&tour[b]) {
+            if d(b, c) >= ab {
+                break;
+            }
+            if c > b + 1 {
+                consider(ab + d(c - 1, c) - d(a, c - 1) - d(b, c), b, c - 1);
+            } else if c >= 1 && c < a {
+                consider(ab + d(c - 1, c) - d(c - 1, a) - d(c, b), c, a);
+            }
+        }
+
+        let (gain, from, to) = best;
+        if to == 0 {
+            return None;
+        }
+        tour[from..=to].reverse();
+        candidates.update(tour, from..=to);
+        Some(gain)
+    }
+
+    /// joins `node` to a candidate c and its neighbor on either side to the neighbor of c
+    /// on the same side, with the same cutoff as `improve_near`
+    fn improve_order(
+        &self,
+        order: &mut dyn TourOrder,
+        node: usize,
+        nodes: &[Node],
+        candidates: &[Vec<usize>],
+        metric: &dyn Metric,
+    ) -> Option<Vec<usize>> {
+        let d = |x: usize, y: usize| metric.distance(&nodes[x], &nodes[y]);
+        let a = node;
+        let mut best = (EPSILON, None);
+        for forward in [true, false] {
+            let step = |x: usize| {
+                if forward {
+                    order.next(x)
+                } else {
+                    order.prev(x)
+                }
+            };
+            let b = step(a);
+            let ab = d(a, b);
+            for &c in &candidates[a] {
+                let ac = d(a, c);
+                if ac >= ab {
+                    break;
+                }
+                let e = step(c);
+                if c == b || e == a {
+                    continue;
+                }
+                let gain = ab + d(c, e) - ac - d(b, e);
+                if gain > best.0 {
+                    best = (gain, Some([a, b, c, e]));
+                }
+            }
+        }
+        let [a, b, c, e] = best.1?;
+        exchange(order, a, b, c, e);
+        Some(vec![a, b, c, e])
+    }
+}
+
+/// 2h-opt or 2.5-opt: for every pair of edges it compares the 2-opt exchange
+/// with moving either node of the first edge next to the other edge
+///
+/// the insertions fix single misplaced nodes that 2-opt can only fix in several
+/// worsening steps, and they cost no more than the 2-opt evaluation they share the pair with
+pub struct TwoHOpt;
+
+/// the three moves `TwoHOpt` considers for edges (a, b) and (c, d)
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TwoHMove {
+    /// a-c and b-d, reversing b..=c
+    Exchange,
+    /// b moves between c and d
+    ShiftFirst,
+    /// c moves between a and b
+    ShiftSecond,
+}
+
+impl MoveOperator for TwoHOpt {
+    fn name(&self) -> &str {
+        "2.5-opt"
+    }
+
+    fn improve_at(&self, tour: &mut [Node], i: usize, metric: &dyn Metric) -> Option<f64> {
+        let n = tour.len();
+        if n < 5 {
+            return None;
+        }
+        let d = |x: usize, y: usize| metric.distance(&tour[x], &tour[y % n]);
+        let (a, b) = (i, i + 1);
+        if b >= n {
+            // the closing edge is covered as the second edge of the others
+            return None;
+        }
+        let ab = d(a, b);
+        let reversals = Reversals::new(tour, metric);
+
+        let mut best = (EPSILON, 0, TwoHMove::Exchange);
+        for c in b + 1..n {
+            // the edges would share a node
+            if a == 0 && c == n - 1 {
+                continue;
+            }
+            let cd = d(c, c + 1);
+            let mut consider = |gain: f64, kind: TwoHMove| {
+                if gain > best.0 {
+                    best = (gain, c, kind);
+                }
+            };
+            consider(
+                ab + cd - d(a, c) - d(b, c + 1) - Reversals::cost(&reversals, b, c),
+                TwoHMove::Exchange,
+            );
+            // b's own neighbors are a and b + 1 (b + 1 == c when they're adjacent)
+            consider(
+                ab + d(b, b + 1) - d(a, b + 1) + cd - d(c, b) - d(b, c + 1),
+                TwoHMove::ShiftFirst,
+            );
+            consider(
+                d(c - 1, c) + cd - d(c - 1, c + 1) + ab - d(a, c) - d(c, b),
+                TwoHMove::ShiftSecond,
+            );
+        }
+
+        let (gain, c, kind) = best;
+        if c == 0 {
+            return None;
+        }
+        match kind {
+            TwoHMove::Exchange => tour[b..=c].reverse(),
+            TwoHMove::ShiftFirst => tour[b..=c].rotate_left(1),
+            TwoHMove::ShiftSecond => tour[b..=c].rotate_right(1),
+        }
+        Some(gain)
+    }
+
+    fn random_move(&self, tour: &mut [Node], rng: &mut StdRng) {
+        if tour.len() < 5 {
+            return;
+        }
+        let positions = random_positions(tour.len(), 2, rng);
+        let segment = &mut tour[positions[0]..=positions[1]];
+        match [
+            TwoHMove::Exchange,
+            TwoHMove::ShiftFirst,
+            TwoHMove::ShiftSecond,
+        ]
+        .choose(rng)
+        {
+            Some(TwoHMove::Exchange) => segment.reverse(),
+            Some(TwoHMove::ShiftFirst) => segment.rotate_left(1),
+            _ => segment.rotate_right(1),
+        }
+    }
+}
+
+/// which improving move an operator applies once it has found some
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Improvement {
+    /// the first one, cheaper per move but usually more moves until the optimum
+    First,
+    /// the best one of the whole neighborhood
+    #[default]
+    Best,
+}
+
+impl std::str::FromStr for Improvement {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "first" => Ok(Improvement::First),
+            "best" => Ok(Improvement::Best),
+            _ => Err(format!(
+                "unknown improvement strategy '{}', expected first or best",
+                s
+            )),
+        }
+    }
+}
+
+/// 3-opt: removes edges (a, b), (c, d) and (e, f) and reconnects the segments b..=c and d..=e
+/// in any of the 7 other ways, reversed, swapped or both
+///
+/// the three edges are tried in every combination, so a pass is cubic in the tour length,
+/// which is fine for a few hundred nodes. the 2-opt exchanges are among the reconnections,
+/// 3-opt optima are 2-opt optima too
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ThreeOpt {
+    pub improvement: Improvement,
+}
+
+/// how `ThreeOpt` puts the segments b..=c and d..=e back between a and f
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Reconnection {
+    /// a-c, b-d: first segment reversed
+    ReverseFirst,
+    /// c-e, d-f: second segment reversed
+    ReverseSecond,
+    /// a-e, b-f: both reversed as one
+    ReverseBoth,
+    /// a-c, b-e, d-f: both reversed in place
+    ReverseEach,
+    /// a-d, e-b, c-f: segments swapped
+    Swap,
+    /// a-d, e-c, b-f: swapped, the first reversed
+    SwapReverseFirst,
+    /// a-e, d-b, c-f: swapped, the second reversed
+    SwapReverseSecond,
+}
+
+impl Reconnection {
+    const ALL: [Reconnection; 7] = [
+        Reconnection::ReverseFirst,
+        Reconnection::ReverseSecond,
+        Reconnection::ReverseBoth,
+        Reconnection::ReverseEach,
+        Reconnection::Swap,
+        Reconnection::SwapReverseFirst,
+        Reconnection::SwapReverseSecond,
+    ];
+
+    /// rearranges `tour[i + 1..=k]`, the first segment ends at `j`
+    fn apply(self, tour: &mut [Node], i: usize, j: usize, k: usize) {
+        let (first, second) = (j - i, k - j);
+        let segments = &mut tour[i + 1..=k];
+        match self {
+            Reconnection::ReverseFirst => segments[..first].reverse(),
+            Reconnection::ReverseSecond => segments[first..].reverse(),
+            Reconnection::ReverseBoth => segments.reverse(),
+            Reconnection::ReverseEach => {
+                segments[..first].reverse();
+                segments[first..].reverse();
+            }
+            Reconnection::Swap => segments.rotate_left(first),
+            Reconnection::SwapReverseFirst => {
+                segments.rotate_left(first);
+                segments[second..].reverse();
+            }
+            Reconnection::SwapReverseSecond => {
+                segments.rotate_left(first);
+                segments[..second].reverse();
+            }
+        }
+    }
+}
+
+impl MoveOperator for ThreeOpt {
+    fn name(&self) -> &str {
+        match self.improvement {
+            Improvement::First => "3-opt first",
+            Improvement::Best => "3-opt",
+        }
+    }
+
+    fn improve_at(&self, tour: &mut [Node], i: usize, metric: &dyn Metric) -> Option<f64> {
+        let n = tour.len();
+        if n < 6 || i + 1 >= n {
+            // the closing edge is covered as the last edge of the others
+            return None;
+        }
+        let d = |x: usize, y: usize| metric.distance(&tour[x], &tour[y % n]);
+        let (a, b) = (i, i + 1);
+        let ab = d(a, b);
+        let reversals = Reversals::new(tour, metric);
+        let reversed = |from: usize, to: usize| Reversals::cost(&reversals, from, to);
+
+        let mut best: Option<(f64, usize, usize, Reconnection)> = None;
+        'search: for c in b..n - 1 {
+            let (dd, cd) = (c + 1, d(c, c + 1));
+            for e in dd..n {
+                // the first and the last edge would share a node
+                if a == 0 && e == n - 1 {
+                    continue;
+                }
+                let f = e + 1;
+                let removed = ab + cd + d(e, f);
+                for kind in Reconnection::ALL {
+                    let added = match kind {
+                        Reconnection::ReverseFirst => d(a, c) + d(b, dd) + d(e, f) + reversed(b, c),
+                        Reconnection::ReverseSecond => ab + d(c, e) + d(dd, f) + reversed(dd, e),
+                        Reconnection::ReverseBoth => d(a, e) + cd + d(b, f) + reversed(b, e),
+                        Reconnection::ReverseEach => {
+                            d(a, c) + d(b, e) + d(dd, f) + reversed(b, c) + reversed(dd, e)
+                        }
+                        Reconnection::Swap => d(a, dd) + d(e, b) + d(c, f),
+                        Reconnection::SwapReverseFirst => {
+                            d(a, dd) + d(e, c) + d(b, f) + reversed(b, c)
+                        }
+                        Reconnection::SwapReverseSecond => {
+                            d(a, e) + d(dd, b) + d(c, f) + reversed(dd, e)
+                        }
+                    };
+                    let gain = removed - added;
+                    if gain > best.map_or(EPSILON, |(g, ..)| g) {
+                        best = Some((gain, c, e, kind));
+                        if self.improvement == Improvement::First {
+                            break 'search;
+                        }
+                    }
+                }
+            }
+        }
+
+        let (gain, c, e, kind) = best?;
+        kind.apply(tour, a, c, e);
+        Some(gain)
+    }
+
+    /// only tries the moves that join a to one of its candidates, which ends the second edge,
+    /// with a third edge at a candidate of b or of the other end of the second edge.
+    /// edge i can be any of the three, candidates are in all directions
+    fn improve_near(
+        &self,
+        tour: &mut [Node],
+        i: usize,
+        candidates: &mut Candidates,
+        metric: &dyn Metric,
+    ) -> Option<f64> {
+        let n = tour.len();
+        if n < 6 || i + 1 >= n {
+            return None;
+        }
+        let d = |x: usize, y: usize| metric.distance(&tour[x], &tour[y % n]);
+        let ab = d(i, i + 1);
+
+        // positions of the three edges, sorted
+        let mut edges = Vec::new();
+        for x in candidates.of(&tour[i]) {
+            if d(i, x) >= ab {
+                break;
+            }
+            for (second, other) in [(x, (x + 1) % n), ((x + n - 1) % n, (x + n - 1) % n)] {
+                for y in candidates
+                    .of(&tour[i + 1])
+                    .chain(candidates.of(&tour[other]))
+                {
+                    for third in [y, (y + n - 1) % n] {
+                        let mut triple = [i, second, third];
+                        triple.sort_unstable();
+                        // distinct, and the first and the last edge wouldn't share a node
+                        if triple[0] < triple[1]
+                            && triple[1] < triple[2]
+                            && !(triple[0] == 0 && triple[2] == n - 1)
+                        {
+                            edges.push(triple);
+                        }
+                    }
+                }
+            }
+        }
+        edges.sort_unstable();
+        edges.dedup();
+
+        let mut best: Option<(f64, usize, usize, usize, Reconnection)> = None;
+        'search: for [a, c, e] in edges {
+            let (b, dd, f) = (a + 1, c + 1, e + 1);
+            let ab = d(a, b);
+            let (cd, ef) = (d(c, dd), d(e, f));
+            let removed = ab + cd + ef;
+            for kind in Reconnection::ALL {
+                let added = match kind {
+                    Reconnection::ReverseFirst => d(a, c) + d(b, dd) + ef,
+                    Reconnection::ReverseSecond => ab + d(c, e) + d(dd, f),
+                    Reconnection::ReverseBoth => d(a, e) + cd + d(b, f),
+                    Reconnection::ReverseEach => d(a, c) + d(b, e) + d(dd, f),
+                    Reconnection::Swap => d(a, dd) + d(e, b) + d(c, f),
+                    Reconnection::SwapReverseFirst => d(a, dd) + d(e, c) + d(b, f),
+                    Reconnection::SwapReverseSecond => d(a, e) + d(dd, b) + d(c, f),
+                };
+                let gain = removed - added;
+                if gain > best.map_or(EPSILON, |(g, ..)| g) {
+                    best = Some((gain, a, c, e, kind));
+                    if self.improvement == Improvement::First {
+                        break 'search;
+                    }
+                }
+            }
+        }
+
+        let (gain, a, c, e, kind) = best?;
+        kind.apply(tour, a, c, e);
+        candidates.update(tour, a + 1..=e);
+        Some(gain)
+    }
+
+    fn random_move(&self, tour: &mut [Node], rng: &mut StdRng) {
+        if tour.len() < 6 {
+            return;
+        }
+        let positions = random_positions(tour.len() - 1, 3, rng);
+        let kind = *Reconnection::ALL
+            .choose(rng)
+            .expect("there are 7 reconnections");
+        kind.apply(tour, positions[0], positions[1], positions[2]);
+    }
+}
+
+/// or-opt: moves the chain of up to `max_segment` nodes after an edge
+/// between two other neighbors, forwards or reversed, the best such move for every edge
+///
+/// a quadratic pass per segment length, so cheap enough to run after 2-opt,
+/// and it catches misplaced runs of nodes that 2-opt would have to fix in several worsening steps
+#[derive(Debug, Clone, Copy)]
+pub struct OrOpt {
+    pub max_segment: usize,
+}
+
+impl Default for OrOpt {
+    fn default() -> Self {
+        OrOpt { max_segment: 3 }
+    }
+}
+
+impl MoveOperator for OrOpt {
+    fn name(&self) -> &str {
+        "or-opt"
+    }
+
+    fn improve_at(&self, tour: &mut [Node], i: usize, metric: &dyn Metric) -> Option<f64> {
+        let n = tour.len();
+        let best = self.best_move(tour, i, metric, |_, a, next| (0..a).chain(next..n));
+        best.map(|found| OrOpt::apply(tour, i, found).0)
+    }
+
+    fn random_move(&self, tour: &mut [Node], rng: &mut StdRng) {
+        let n = tour.len();
+        if n < 4 {
+            return;
+        }
+        let length = rng.gen_range(1..=self.max_segment.clamp(1, n - 2));
+        // the chain at `from`, afterwards at `to`
+        let positions = random_positions(n - length + 1, 2, rng);
+        let (from, to) = if rng.gen_bool(0.5) {
+            (positions[0], positions[1])
+        } else {
+            (positions[1], positions[0])
+        };
+        if to > from {
+            tour[from..to + length].rotate_left(length);
+        } else {
+            tour[to..from + length].rotate_right(length);
+        }
+        if rng.gen_bool(0.5) {
+            tour[to..to + length].reverse();
+        }
+    }
+
+    /// only inserts the chain next to a candidate of one of its ends
+    fn improve_near(
+        &self,
+        tour: &mut [Node],
+        i: usize,
+        candidates: &mut Candidates,
+        metric: &dyn Metric,
+    ) -> Option<f64> {
+        let n = tour.len();
+        let found = self.best_move(tour, i, metric, |length, a, next| {
+            let ends = [&tour[a + 1], &tour[a + length]];
+            ends.into_iter()
+                .flat_map(|end| candidates.of(end))
+                .flat_map(|c| [c, (c + n - 1) % n])
+                .filter(|&p| p < a || p >= next)
+                .collect::<Vec<_>>()
+        });
+        let (gain, changed) = OrOpt::apply(tour, i, found?);
+        candidates.update(tour, changed);
+        Some(gain)
+    }
+
+    /// moves the chains that start at `node` in either direction next to a candidate of one of
+    /// their ends, as long as the edge to it is shorter than what taking the chain out saves.
+    /// a linked list does that without touching the rest of the tour
+    fn improve_order(
+        &self,
+        order: &mut dyn TourOrder,
+        node: usize,
+        nodes: &[Node],
+        candidates: &[Vec<usize>],
+        metric: &dyn Metric,
+    ) -> Option<Vec<usize>> {
+        let d = |x: usize, y: usize| metric.distance(&nodes[x], &nodes[y]);
+        // gain, the direction, the chain with the nodes around it, the edge from p to q, reversed
+        let mut best = (EPSILON, None);
+        for forward in [true, false] {
+            let step = |x: usize| {
+                if forward {
+                    order.next(x)
+                } else {
+                    order.prev(x)
+                }
+            };
+            let back = |x: usize| {
+                if forward {
+                    order.prev(x)
+                } else {
+                    order.next(x)
+                }
+            };
+            let before = back(node);
+            let mut chain = vec![node];
+            for length in 1..=self.max_segment.min(order.len().saturating_sub(3)) {
+                if length > 1 {
+                    chain.push(step(chain[length - 2]));
+                }
+                let end = chain[length - 1];
+                let after = step(end);
+                let removed = d(before, node) + d(end, after) - d(before, after);
+                for (e, c) in [node, end]
+                    .into_iter()
+                    .flat_map(|e| candidates[e].iter().map(move |&c| (e, c)))
+                {
+                    if d(e, c) >= removed {
+                        continue;
+                    }
+                    for (p, q) in [(c, step(c)), (back(c), c)] {
+                        if chain.contains(&p) || chain.contains(&q) {
+                            continue;
+                        }
+                        let added = d(p, node) + d(end, q) - d(p, q);
+                        if removed - added > best.0 {
+                            best = (
+                                removed - added,
+                                Some((forward, [before, node, end, after], p, q, false)),
+                            );
+                        }
+                        let added = d(p, end) + d(node, q) - d(p, q);
+                        if removed - added > best.0 {
+                            best = (
+                                removed - added,
+                                Some((forward, [before, node, end, after], p, q, true)),
+                            );
+                        }
+                    }
+                }
+            }
+        }
+        let (forward, [before, start, end, after], p, q, reverse) = best.1?;
+        if forward {
+            order.move_chain(start, end, p, reverse);
+        } else {
+            // forwards the chain runs from end to start and the edge from q to p
+            order.move_chain(end, start, q, reverse);
+        }
+        // chains within reach of the new edges have new nodes around them
+        let mut woken = Vec::new();
+        for touched in [before, start, end, after, p, q] {
+            let (mut back, mut ahead) = (touched, touched);
+            woken.push(touched);
+            for _ in 0..self.max_segment {
+                back = order.prev(back);
+                ahead = order.next(ahead);
+                woken.extend([back, ahead]);
+            }
+        }
+        Some(woken)
+    }
+}
+
+/// an or-opt move: gain, segment length, edge to insert after, reversed
+type Insertion = (f64, usize, usize, bool);
+
+impl OrOpt {
+    /// the best move of the chain after the edge at `i` to the edge after one of the `places`,
+    /// which get the segment length and the positions before and after the chain
+    fn best_move<P: IntoIterator<Item = usize>>(
+        &self,
+        tour: &[Node],
+        i: usize,
+        metric: &dyn Metric,
+        mut places: impl FnMut(usize, usize, usize) -> P,
+    ) -> Option<Insertion> {
+        let n = tour.len();
+        let d = |x: usize, y: usize| metric.distance(&tour[x], &tour[y % n]);
+        let (a, first) = (i, i + 1);
+        let reversals = Reversals::new(tour, metric);
+
+        let mut best = (EPSILON, 0, 0, false);
+        for length in 1..=self.max_segment {
+            let last = first + length - 1;
+            // the segment would wrap around, or leave nowhere else to go
+            if last >= n || n < length + 3 {
+                break;
+            }
+            let next = last + 1;
+            let removed = d(a, first) + d(last, next) - d(a, next);
+            for p in places(length, a, next) {
+                let pq = d(p, p + 1);
+                let forward = removed + pq - d(p, first) - d(last, p + 1);
+                let reversed = removed + pq
+                    - d(p, last)
+                    - d(first, p + 1)
+                    - Reversals::cost(&reversals, first, last);
+                for (gain, reverse) in [(forward, false), (reversed, true)] {
+                    if gain > best.0 {
+                        best = (gain, length, p, reverse);
+                    }
+                }
+            }
+        }
+        (best.1 > 0).then_some(best)
+    }
+
+    /// applies the move of the chain after `a`, returns its gain and the positions it changed
+    fn apply(
+        tour: &mut [Node],
+        a: usize,
+        (gain, length, p, reverse): Insertion,
+    ) -> (f64, std::ops::RangeInclusive<usize>) {
+        let first = a + 1;
+        let (moved, changed) = if p > a {
+            tour[first..=p].rotate_left(length);
+            (p + 1 - length..=p, first..=p)
+        } else {
+            tour[p + 1..first + length].rotate_right(length);
+            (p + 1..=p + length, p + 1..=first + length - 1)
+        };
+        if reverse {
+            tour[moved].reverse();
+        }
+        (gain, changed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::local_search::*;
+    use crate::metric::Euclidean;
+    use crate::random_nodes;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_two_h_opt_gains_match_lengths() {
+        let mut rng = StdRng::seed_from_u64(5);
+        let nodes = random_nodes(60, &mut rng);
+        let mut tour = nodes.clone();
+        for i in 0..tour.len() {
+            let before = Euclidean.tour_length(&tour);
+            if let Some(gain) = TwoHOpt.improve_at(&mut tour, i, &Euclidean) {
+                let after = Euclidean.tour_length(&tour);
+                assert!((before - after - gain).abs() < 1e-9);
+            }
+        }
+
+        local_search(&mut tour, &TwoHOpt, &Euclidean);
+        let ids: Vec<usize> = tour.iter().map(|n| n.id).collect();
+        assert!(crate::tour::Tour::from_ids(&ids, &nodes).is_ok());
+        let nn = crate::solvers::tsp_nearest_neighbor(&nodes, &Euclidean);
+        assert!(Euclidean.tour_length(&tour) < Euclidean.tour_length(&nn));
+        // a local optimum, another sweep finds nothing
+        assert_eq!(local_search(&mut tour, &TwoHOpt, &Euclidean), 0);
+    }
+
+    #[test]
+    fn test_swap_gains_and_random_moves() {
+        let mut rng = StdRng::seed_from_u64(28);
+        let nodes = random_nodes(40, &mut rng);
+        let mut tour = nodes.clone();
+        for i in 0..tour.len() {
+            let before = Euclidean.tour_length(&tour);
+            if let Some(gain) = Swap.improve_at(&mut tour, i, &Euclidean) {
+                let after = Euclidean.tour_length(&tour);
+                assert!((before - after - gain).abs() < 1e-9);
+            }
+        }
+
+        let operators: [&dyn MoveOperator; 5] = [
+            &Swap,
+            &TwoOpt,
+            &TwoHOpt,
+            &ThreeOpt::default(),
+            &OrOpt::default(),
+        ];
+        for operator in operators {
+            for _ in 0..20 {
+                operator.random_move(&mut tour, &mut rng);
+                let ids: Vec<usize> = tour.iter().map(|n| n.id).collect();
+                assert!(crate::tour::Tour::from_ids(&ids, &nodes).is_ok());
+            }
+        }
+    }
+
+    #[test]
+    fn test_two_opt_removes_crossings() {
+        let mut rng = StdRng::seed_from_u64(6);
+        let nodes = random_nodes(80, &mut rng);
+        let mut tour = nodes.clone();
+        let before = Euclidean.tour_length(&tour);
+        assert!(local_search(&mut tour, &TwoOpt, &Euclidean) > 0);
+        assert!(Euclidean.tour_length(&tour) < before);
+        assert!(crate::geometry::crossings(&tour).is_empty());
+        // a local optimum
+        assert_eq!(local_search(&mut tour, &TwoOpt, &Euclidean), 0);
+    }
+
+    #[test]
+    fn test_three_opt_beats_two_opt() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let nodes = random_nodes(40, &mut rng);
+        let mut two = nodes.clone();
+        local_search(&mut two, &TwoOpt, &Euclidean);
+
+        for improvement in [Improvement::First, Improvement::Best] {
+            let operator = ThreeOpt { improvement };
+            let mut tour = two.clone();
+            for i in 0..tour.len() {
+                let before = Euclidean.tour_length(&tour);
+                if let Some(gain) = operator.improve_at(&mut tour, i, &Euclidean) {
+                    let after = Euclidean.tour_length(&tour);
+                    assert!((before - after - gain).abs() < 1e-9);
+                }
+            }
+            local_search(&mut tour, &operator, &Euclidean);
+            let ids: Vec<usize> = tour.iter().map(|n| n.id).collect();
+            assert!(crate::tour::Tour::from_ids(&ids, &nodes).is_ok());
+            assert!(Euclidean.tour_length(&tour) < Euclidean.tour_length(&two));
+            // 3-opt optima are 2-opt optima
+            assert_eq!(local_search(&mut tour, &TwoOpt, &Euclidean), 0);
+        }
+    }
+
+    #[test]
+    fn test_or_opt_gains_match_lengths() {
+        let mut rng = StdRng::seed_from_u64(8);
+        let nodes = random_nodes(50, &mut rng);
+        let mut tour = nodes.clone();
+        local_search(&mut tour, &TwoOpt, &Euclidean);
+        let two = Euclidean.tour_length(&tour);
+
+        let operator = OrOpt::default();
+        for i in 0..tour.len() {
+            let before = Euclidean.tour_length(&tour);
+            if let Some(gain) = operator.improve_at(&mut tour, i, &Euclidean) {
+                let after = Euclidean.tour_length(&tour);
+                assert!((before - after - gain).abs() < 1e-9);
+            }
+        }
+        local_search(&mut tour, &operator, &Euclidean);
+        let ids: Vec<usize> = tour.iter().map(|n| n.id).collect();
+        assert!(crate::tour::Tour::from_ids(&ids, &nodes).is_ok());
+        assert!(Euclidean.tour_length(&tour) < two);
+    }
+
+    #[test]
+    fn test_candidate_moves_match_lengths() {
+        let mut rng = StdRng::seed_from_u64(54);
+        let nodes = random_nodes(120, &mut rng);
+        let operators: [&dyn MoveOperator; 5] = [
+            &TwoOpt,
+            &ThreeOpt::default(),
+            &ThreeOpt {
+                improvement: Improvement::First,
+            },
+            &OrOpt::default(),
+            &TwoHOpt,
+        ];
+        for operator in operators {
+            let mut tour = crate::solvers::tsp_nearest_neighbor(&nodes, &Euclidean);
+            let mut candidates = Candidates::new(&tour, DEFAULT_NEIGHBORS);
+            let mut improved = 0;
+            for i in 0..tour.len() {
+                let before = Euclidean.tour_length(&tour);
+                if let Some(gain) = operator.improve_near(&mut tour, i, &mut candidates, &Euclidean)
+                {
+                    let after = Euclidean.tour_length(&tour);
+                    assert!((before - after - gain).abs() < 1e-9);
+                    improved += 1;
+                }
+            }
+            assert!(improved > 0, "{}", operator.name());
+            assert!(tour
+                .iter()
+                .enumerate()
+                .all(|(i, node)| candidates.positions[node.id] == i));
+
+            let mut full = tour.clone();
+            local_search_near(&mut tour, operator, &Euclidean, DEFAULT_NEIGHBORS);
+            local_search(&mut full, operator, &Euclidean);
+            let ids: Vec<usize> = tour.iter().map(|n| n.id).collect();
+            assert!(crate::tour::Tour::from_ids(&ids, &nodes).is_ok());
+            let (near, full) = (Euclidean.tour_length(&tour), Euclidean.tour_length(&full));
+            assert!(near < full * 1.03, "{}", operator.name());
+        }
+    }
+
+    #[test]
+    fn test_moves_wake_the_nodes_with_new_neighbors() {
+        let mut rng = StdRng::seed_from_u64(55);
+        let mut tour = random_nodes(20, &mut rng);
+        let mut candidates = Candidates::new(&tour, 4);
+        assert_eq!(candidates.take_woken().len(), 20);
+
+        tour[5..=9].reverse();
+        candidates.update(&tour, 5..=9);
+        let mut woken = candidates.take_woken();
+        woken.sort_unstable();
+        let mut ends: Vec<usize> = [4, 5, 9, 10].iter().map(|&i| tour[i].id).collect();
+        ends.sort_unstable();
+        assert_eq!(woken, ends);
+        assert!(candidates.take_woken().is_empty());
+    }
+
+    #[test]
+    fn test_order_moves_shorten_the_tour_on_every_representation() {
+        let mut rng = StdRng::seed_from_u64(55);
+        let nodes = random_nodes(200, &mut rng);
+        let start = crate::solvers::tsp_nearest_neighbor(&nodes, &Euclidean);
+        let candidates = crate::spatial::neighbor_lists(&start, DEFAULT_NEIGHBORS, 2);
+        let length = |order: &dyn TourOrder| {
+            let order = order.order();
+            let tour: Vec<Node> = order.iter().map(|&i| start[i].clone()).collect();
+            Euclidean.tour_length(&tour)
+        };
+        let operators: [&dyn MoveOperator; 2] = [&TwoOpt, &OrOpt::default()];
+        for operator in operators {
+            let mut tours = Vec::new();
+            for representation in [
+                Representation::Array,
+                Representation::List,
+                Representation::TwoLevel,
+            ] {
+                let mut order = representation.new_order(start.len());
+                let mut improved = 0;
+                for node in 0..start.len() {
+                    let before = length(order.as_ref());
+                    if operator
+                        .improve_order(order.as_mut(), node, &start, &candidates, &Euclidean)
+                        .is_some()
+                    {
+                        assert!(length(order.as_ref()) < before - 1e-12);
+                        improved += 1;
+                    }
+                }
+                assert!(improved > 0, "{}", operator.name());
+
+                let mut tour = start.clone();
+                local_search_order(
+                    &mut tour,
+                    operator,
+                    &Euclidean,
+                    DEFAULT_NEIGHBORS,
+                    representation,
+                );
+                let ids: Vec<usize> = tour.iter().map(|n| n.id).collect();
+                tours.push(crate::tour::Tour::from_ids(&ids, &nodes).unwrap());
+            }
+            // the same cycle, whichever way round
+            assert_eq!(tours[0], tours[1], "{}", operator.name());
+            assert_eq!(tours[0], tours[2], "{}", operator.name());
+        }
+    }
+
+    #[test]
+    fn test_gains_follow_the_direction_of_asymmetric_costs() {
+        let mut rng = StdRng::seed_from_u64(309);
+        let n = 24;
+        // one-way streets: going up in id costs more than coming back
+        let rows: Vec<Vec<f64>> = (0..n)
+            .map(|i| {
+                (0..n)
+                    .map(|j| match j {
+                        _ if j == i => 0.0,
+                        _ if j > i => rng.gen_range(5.0..10.0),
+                        _ => rng.gen_range(0.0..5.0),
+                    })
+                    .collect()
+            })
+            .collect();
+        let matrix = crate::explicit::ExplicitMatrix::directed(&rows).unwrap();
+        assert!(!matrix.is_symmetric());
+        let nodes = matrix.nodes();
+        let operators: [&dyn MoveOperator; 5] = [
+            &TwoOpt,
+            &TwoHOpt,
+            &ThreeOpt::default(),
+            &OrOpt::default(),
+            &Swap,
+        ];
+        for operator in operators {
+            let mut tour = nodes.clone();
+            tour.shuffle(&mut rng);
+            for i in 0..n {
+                let before = matrix.tour_length(&tour);
+                if let Some(gain) = operator.improve_at(&mut tour, i, &matrix) {
+                    let after = matrix.tour_length(&tour);
+                    assert!((before - after - gain).abs() < 1e-9, "{}", operator.name());
+                }
+            }
+            // the candidate search falls back to trying every move
+            let before = matrix.tour_length(&tour);
+            local_search_near(&mut tour, operator, &matrix, 5);
+            assert!(matrix.tour_length(&tour) <= before, "{}", operator.name());
+        }
+
+        // the tour going down the ids the cheap way round beats the same cycle going up
+        let down: Vec<Node> = nodes.iter().rev().cloned().collect();
+        assert!(matrix.tour_length(&down) < matrix.tour_length(&nodes));
+        let mut tour = nodes.clone();
+        local_search(&mut tour, &TwoOpt, &matrix);
+        assert!(matrix.tour_length(&tour) < matrix.tour_length(&nodes));
+    }
+}
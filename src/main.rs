@@ -1,346 +1,1615 @@
 #![allow(non_snake_case)]
 
-use std::hash::{Hash, Hasher};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 
-use itertools::Itertools;
-use rand::Rng;
+use tsp::metric::{CachedMetric, DistanceMatrix, Euclidean, MatrixLayout, Metric, MetricKind};
+use tsp::plot::{
+    draw_convergence, draw_generations, draw_path, draw_ring, draw_routes, draw_tour,
+    draw_tour_diff,
+};
+use tsp::solvers::{tsp_nearest_neighbor, tsp_simulated_annealing, Solver};
+use tsp::tour::Tour;
+use tsp::*;
 
-use plotters::coord::types::RangedCoordf64;
-use plotters::prelude::*;
+/// `stem.svg` or `stem.png`
+fn image_name(stem: &str, svg: bool) -> String {
+    format!("{}.{}", stem, if svg { "svg" } else { "png" })
+}
 
-#[derive(Debug, Clone)]
-struct Node {
-    id: usize,
-    x: f64,
-    y: f64,
+/// prints how many edges of the tour cross and the first few pairs, by node ids
+fn print_crossings(tour: &[Node]) {
+    const LISTED: usize = 10;
+    if tour.len() > geometry::CROSSINGS_MAX_NODES {
+        println!(
+            "crossings: not checked above {} nodes",
+            geometry::CROSSINGS_MAX_NODES
+        );
+        return;
+    }
+    let pairs = geometry::crossings(tour);
+    let edge = |k: usize| format!("{}-{}", tour[k].id, tour[(k + 1) % tour.len()].id);
+    let listed: Vec<String> = pairs
+        .iter()
+        .take(LISTED)
+        .map(|&(i, j)| format!("{} x {}", edge(i), edge(j)))
+        .collect();
+    match pairs.len() {
+        0 => println!("crossings: 0"),
+        count if count > LISTED => {
+            println!("crossings: {} ({}, ...)", count, listed.join(", "))
+        }
+        count => println!("crossings: {} ({})", count, listed.join(", ")),
+    }
 }
 
-impl Hash for Node {
-    fn hash<H: Hasher>(&self, state: &mut H) {
-        self.id.hash(state);
+/// `replay` writes its frames and the convergence plot in here
+const REPLAY_DIR: &str = "replay";
+
+/// `som` writes the ring of every frame and the tour in here
+const SOM_DIR: &str = "som";
+
+/// snapshots of the ring `som` draws before the trained one
+const SOM_FRAMES: usize = 20;
+
+/// instance files from this size on report their loading progress
+const PROGRESS_MIN_BYTES: u64 = 16 << 20;
+
+/// `generate --integer` places nodes on the grid 0..=INTEGER_SIDE
+const INTEGER_SIDE: i64 = 1000;
+
+const USAGE: &str = "commands:
+    (none)                              solve a random instance with every algorithm
+    tournament [rounds]                 play all solvers on many instance families
+    solve <stages> [nodes]              run a chain of solvers, e.g. nn,sa
+                                        (bf, hk, bb, nn, nn-kd, greedy, ci, fi, hull, cw,
+                                        hilbert, sa, ga, memetic, tabu, grasp, ils, vns, alns,
+                                        gls, rr, som, ce, dc, swap, 2opt, 2h, 3opt, oropt, lk,
+                                        bf-exact with --features exact),
+                                        8xsa keeps the best of 8 restarts of a stage,
+                                        nn:start=all of nearest neighbor from every node
+    diff <stages> <stages> [nodes]      compare the tours of two chains edge by edge
+    compare <stages> <stages> [nodes] [runs]
+                                        solve an instance with two chains from many seeds
+                                        and test whether one finds shorter tours
+    multi-start <stages> <starts> [nodes]
+                                        restart a chain from rotated start tours, or random ones
+                                        with --shuffle, on --threads threads, print every start
+    genetic [nodes] [parameters]        run the genetic algorithm, e.g. population=200, and plot
+                                        the best and mean length per generation to genetic.png
+    som [nodes] [parameters]            train a self-organizing map, e.g. neurons=4, and draw its
+                                        ring as it goes to som/ring_NNNN.png, the tour to som/tour.png
+    branch-and-bound [nodes]            solve exactly by branch and bound, print how much of the
+                                        search tree the bound cut off
+    multi-depot <depots> <salesmen> [nodes]
+                                        routes from the first nodes as depots to all others,
+                                        --assign fixed or optimized (default) depots per salesman,
+                                        --capacity <k> customers per salesman at most,
+                                        --objective total (default) or longest route to minimize
+    mtsp <salesmen> [nodes]             multi-depot with one depot they share, node 0 or --start <id>
+    online [nodes] [arrivals] [departures]
+                                        keep a tour up to date while nodes come and go
+    dot <stages> [nodes]                write tour, --knn <k> graph, --mst and --hull to tsp.dot
+    tikz <stages> [nodes]               write the tour as a standalone tikz picture to tsp.tex
+    export-model <file> [nodes]         write the milp of an instance as .lp or .mps,
+                                        --formulation mtz (default) or dfj
+    generate <file> [nodes]             write a random instance, on an integer grid with --integer
+    evaluate <instance> <solution>      validate, measure and draw a tour found elsewhere
+                                        (a tsplib .tour under the metric of its .tsp or .atsp)
+    convert <input> <output> [--tour]   translate an instance, or a tour, between plain text,
+                                        csv (.csv), json (.json) and tsplib (.tsp, .atsp, .tour),
+                                        or from geojson (.geojson)
+    geocode <addresses.csv> <stops.csv>
+                                        look up street addresses for --instance, in a --gazetteer
+                                        <file> of known ones or on nominatim (--features nominatim)
+    replay <log>                        draw the checkpoints of a run recorded with
+                                        solve --record <log> into replay/, without solving again
+flags:
+    --seed <seed>, --threads <threads>, --cache-distances,
+    --matrix full|f32|triangular|triangular-f32 to compute all distances before solve,
+    --persist-distances to also keep the distance cache of solve on disk for the next run,
+    --no-cache to solve again instead of reusing the cached result of the same configuration,
+    --instance <file> to use an instance file instead of random nodes: plain, .csv, .json, .tsp,
+    .atsp, .geojson or a .csv matrix of costs,
+    --integer for rounded integer distances on integer coordinates (solve, for files without their own),
+    --svg to draw tours as svg instead of png,
+    --record <file> to write the checkpoints of solve to an event log,
+    --nav google|osm to write directions links for the tour of a stops --instance (solve),
+    --path to solve a one-way route instead of a round trip, --start <id> and --end <id> fix its ends,
+    --start <id> without --path for the depot every stage of solve starts the round trip at,
+    --alpha <level> for the significance tests of tournament and compare (default 0.05),
+    --profile <file.svg> to write a flamegraph of the run (needs the profiling feature),
+    --assert-max-length <length> and --assert-gap <percent> to exit with status 3
+    if the tour of solve is longer, the gap is to --reference <tour file> or the mst lower bound";
+
+// this https://www.youtube.com/watch?v=GiDsjIBOVoA
+// has been super helpful to get me started
+fn main() {
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    // every run is reproducible from the printed seed
+    let seed = take_flag(&mut args, "--seed").unwrap_or_else(rng::random_seed);
+    let threads = take_flag(&mut args, "--threads").unwrap_or(1);
+    let persist_distances = take_switch(&mut args, "--persist-distances");
+    let cache_distances = take_switch(&mut args, "--cache-distances") || persist_distances;
+    let no_cache = take_switch(&mut args, "--no-cache");
+    let matrix: Option<MatrixLayout> = take_flag(&mut args, "--matrix");
+    let knn = take_flag(&mut args, "--knn");
+    let mst = take_switch(&mut args, "--mst");
+    let hull = take_switch(&mut args, "--hull");
+    let formulation = take_flag(&mut args, "--formulation").unwrap_or(model::Formulation::Mtz);
+    let instance: Option<String> = take_flag(&mut args, "--instance");
+    let integer = take_switch(&mut args, "--integer");
+    let convert_tour = take_switch(&mut args, "--tour");
+    let svg = take_switch(&mut args, "--svg");
+    let gate = quality::QualityGate {
+        max_length: take_flag(&mut args, "--assert-max-length"),
+        max_gap: take_flag(&mut args, "--assert-gap"),
+    };
+    let reference: Option<String> = take_flag(&mut args, "--reference");
+    let record: Option<String> = take_flag(&mut args, "--record");
+    let gazetteer: Option<String> = take_flag(&mut args, "--gazetteer");
+    let navigation: Option<navigation::Provider> = take_flag(&mut args, "--nav");
+    let assignment = take_flag(&mut args, "--assign").unwrap_or(mtsp::Assignment::Optimized);
+    let capacity: Option<usize> = take_flag(&mut args, "--capacity");
+    let objective = take_flag(&mut args, "--objective").unwrap_or(mtsp::Objective::Total);
+    let alpha = take_flag(&mut args, "--alpha").unwrap_or(stats::DEFAULT_ALPHA);
+    let profile: Option<String> = take_flag(&mut args, "--profile");
+    let shuffle = take_switch(&mut args, "--shuffle");
+    let path_mode = take_switch(&mut args, "--path");
+    let path_ends = path::PathEnds {
+        start: take_flag(&mut args, "--start"),
+        end: take_flag(&mut args, "--end"),
+    };
+    // without --path the start is the depot of a round trip, which ends there too
+    if !path_mode && path_ends.end.is_some() {
+        println!("Error parsing --end:\nthe end of a path needs --path");
+        std::process::exit(2);
+    }
+    if !(alpha > 0.0 && alpha < 1.0) {
+        println!("Error parsing --alpha:\nsignificance level has to be between 0 and 1");
+        std::process::exit(2);
+    }
+    let profiler = match profiling::start(profile) {
+        Ok(profiler) => profiler,
+        Err(err) => {
+            println!("Error parsing --profile:\n{}", err);
+            std::process::exit(2);
+        }
+    };
+    println!("seed: {}", seed);
+    let mut rng = StdRng::seed_from_u64(seed);
+
+    match args.first().map(String::as_str) {
+        Some("tournament") => {
+            let rounds = parse_arg(&args, 1, "rounds").unwrap_or(3);
+            let entries =
+                tournament::run_tournament(&solvers::registered_solvers(), rounds, seed, threads);
+            tournament::print_scoreboard(&entries, alpha);
+        }
+        Some("solve") => {
+            let Some(stages) = args.get(1) else {
+                println!("Usage: solve <stage,stage,...> [nodes]");
+                std::process::exit(2);
+            };
+            let N = parse_arg(&args, 2, "nodes").unwrap_or(13);
+            let (nodes, costs, instance_metric) =
+                match instance.as_deref().and_then(instance_with_metric) {
+                    Some((nodes, metric, name)) => {
+                        (nodes, objective::NodeCosts::default(), Some((name, metric)))
+                    }
+                    None => {
+                        let (nodes, costs) = instance_with_costs(&instance, N, &mut rng);
+                        (nodes, costs, None)
+                    }
+                };
+            if integer && instance_metric.is_some() {
+                println!("--integer is ignored, the instance file defines its distances");
+            }
+            let stops = navigation.and(instance.as_deref().and_then(read_stops));
+            let navigation = navigation.map(|provider| match stops {
+                Some(stops) => (provider, stops),
+                None => {
+                    println!("Error parsing --nav:\nneeds a stops file from geocode as --instance");
+                    std::process::exit(2);
+                }
+            });
+            let options = SolveOptions {
+                seed,
+                cache_distances,
+                matrix,
+                persist_distances,
+                cache_results: !no_cache,
+                integer,
+                instance_metric,
+                threads,
+                svg,
+                gate,
+                reference,
+                record,
+                navigation,
+                path: path_mode.then_some(path_ends),
+                depot: path_ends.start.filter(|_| !path_mode),
+            };
+            solve(stages, &nodes, &costs, &mut rng, &options);
+        }
+        Some("diff") => {
+            let (Some(from), Some(to)) = (args.get(1), args.get(2)) else {
+                println!("Usage: diff <stage,stage,...> <stage,stage,...> [nodes]");
+                std::process::exit(2);
+            };
+            let N = parse_arg(&args, 3, "nodes").unwrap_or(13);
+            let (nodes, metric) = instance_nodes_and_metric(&instance, N, &mut rng);
+            diff(from, to, &nodes, metric.as_ref(), &mut rng);
+        }
+        Some("compare") => {
+            let (Some(first), Some(second)) = (args.get(1), args.get(2)) else {
+                println!("Usage: compare <stage,stage,...> <stage,stage,...> [nodes] [runs]");
+                std::process::exit(2);
+            };
+            let N = parse_arg(&args, 3, "nodes").unwrap_or(50);
+            let runs = parse_arg(&args, 4, "runs").unwrap_or(20);
+            let (nodes, metric) = instance_nodes_and_metric(&instance, N, &mut rng);
+            let chains = [first.as_str(), second.as_str()];
+            compare(chains, &nodes, metric.as_ref(), runs, seed, threads, alpha);
+        }
+        Some("multi-start") => {
+            let (Some(stages), Some(starts)) = (args.get(1), parse_arg(&args, 2, "starts")) else {
+                println!("Usage: multi-start <stage,stage,...> <starts> [nodes]");
+                std::process::exit(2);
+            };
+            let N = parse_arg(&args, 3, "nodes").unwrap_or(50);
+            let (nodes, metric) = instance_nodes_and_metric(&instance, N, &mut rng);
+            let metric = metric.as_ref();
+            multi_start(stages, starts, shuffle, &nodes, metric, &mut rng, threads);
+        }
+        Some("genetic") => {
+            let N = parse_arg(&args, 1, "nodes").unwrap_or(50);
+            let params = match args.get(2).map_or(Ok(Default::default()), |p| p.parse()) {
+                Ok(params) => params,
+                Err(err) => {
+                    println!("Error parsing genetic parameters:\n{}", err);
+                    std::process::exit(2);
+                }
+            };
+            let (nodes, metric) = instance_nodes_and_metric(&instance, N, &mut rng);
+            genetic(&nodes, metric.as_ref(), &params, &mut rng, svg);
+        }
+        Some("som") => {
+            let N = parse_arg(&args, 1, "nodes").unwrap_or(100);
+            let params = match args.get(2).map_or(Ok(Default::default()), |p| p.parse()) {
+                Ok(params) => params,
+                Err(err) => {
+                    println!("Error parsing som parameters:\n{}", err);
+                    std::process::exit(2);
+                }
+            };
+            let (nodes, metric) = instance_nodes_and_metric(&instance, N, &mut rng);
+            som(&nodes, metric.as_ref(), &params, seed, &mut rng, svg);
+        }
+        Some("branch-and-bound") => {
+            let N = parse_arg(&args, 1, "nodes").unwrap_or(12);
+            let (nodes, metric) = instance_nodes_and_metric(&instance, N, &mut rng);
+            branch_and_bound(&nodes, metric.as_ref());
+        }
+        Some("multi-depot") => {
+            let (Some(depots), Some(salesmen)) = (
+                parse_arg(&args, 1, "depots"),
+                parse_arg(&args, 2, "salesmen"),
+            ) else {
+                println!(
+                    "Usage: multi-depot <depots> <salesmen> [nodes] [--assign fixed|optimized]"
+                );
+                std::process::exit(2);
+            };
+            let N = parse_arg(&args, 3, "nodes").unwrap_or(50);
+            let (nodes, metric) = instance_nodes_and_metric(&instance, N, &mut rng);
+            let metric = metric.as_ref();
+            if let Err(err) = multi_depot(
+                &nodes, metric, depots, salesmen, assignment, capacity, objective,
+            ) {
+                println!("Error in multi-depot:\n{}", err);
+                std::process::exit(1);
+            }
+        }
+        Some("mtsp") => {
+            let Some(salesmen) = parse_arg(&args, 1, "salesmen") else {
+                println!(
+                    "Usage: mtsp <salesmen> [nodes] [--start <depot>] [--objective total|longest]"
+                );
+                std::process::exit(2);
+            };
+            let N = parse_arg(&args, 2, "nodes").unwrap_or(50);
+            let (mut nodes, metric) = instance_nodes_and_metric(&instance, N, &mut rng);
+            let depot = path_ends.start.unwrap_or(0);
+            let Some(i) = nodes.iter().position(|node| node.id == depot) else {
+                println!("Error in --start:\nnode {} isn't in the instance", depot);
+                std::process::exit(2);
+            };
+            nodes.swap(0, i);
+            let fixed = mtsp::Assignment::Fixed;
+            let metric = metric.as_ref();
+            if let Err(err) = multi_depot(&nodes, metric, 1, salesmen, fixed, capacity, objective) {
+                println!("Error in mtsp:\n{}", err);
+                std::process::exit(1);
+            }
+        }
+        Some("online") => {
+            let N = parse_arg(&args, 1, "nodes").unwrap_or(50);
+            let arrivals = parse_arg(&args, 2, "arrivals").unwrap_or(50);
+            let departures = parse_arg(&args, 3, "departures").unwrap_or(0);
+            online(N, arrivals, departures, seed, svg);
+        }
+        Some("dot") => {
+            let Some(stages) = args.get(1) else {
+                println!("Usage: dot <stage,stage,...> [nodes] [--knn <k>] [--mst] [--hull]");
+                std::process::exit(2);
+            };
+            let N = parse_arg(&args, 2, "nodes").unwrap_or(13);
+            let (nodes, metric) = instance_nodes_and_metric(&instance, N, &mut rng);
+            let layers = dot::DotLayers {
+                tour: None,
+                knn,
+                mst,
+                hull,
+            };
+            export_dot(stages, &nodes, metric.as_ref(), &mut rng, layers);
+        }
+        Some("tikz") => {
+            let Some(stages) = args.get(1) else {
+                println!("Usage: tikz <stage,stage,...> [nodes]");
+                std::process::exit(2);
+            };
+            let N = parse_arg(&args, 2, "nodes").unwrap_or(13);
+            let (nodes, metric) = instance_nodes_and_metric(&instance, N, &mut rng);
+            export_tikz(stages, &nodes, metric.as_ref(), &mut rng, seed);
+        }
+        Some("export-model") => {
+            let Some(filename) = args.get(1) else {
+                println!("Usage: export-model <file.lp|file.mps> [nodes] [--formulation mtz|dfj]");
+                std::process::exit(2);
+            };
+            let N = parse_arg(&args, 2, "nodes").unwrap_or(13);
+            let (nodes, metric) = instance_nodes_and_metric(&instance, N, &mut rng);
+            export_model(filename, &nodes, metric.as_ref(), formulation);
+        }
+        Some("generate") => {
+            let Some(filename) = args.get(1) else {
+                println!("Usage: generate <file> [nodes]");
+                std::process::exit(2);
+            };
+            let N = parse_arg(&args, 2, "nodes").unwrap_or(13);
+            let nodes = if integer {
+                integer::random_nodes(N, INTEGER_SIDE, &mut rng)
+            } else {
+                random_nodes(N, &mut rng)
+            };
+            let mut text = formats::format_nodes(&nodes);
+            if integer {
+                // lets `convert` write the instance as EUC_2D
+                text.insert_str(0, "# metric: integer\n");
+            }
+            match std::fs::write(filename, text) {
+                Ok(()) => println!("wrote {}", filename),
+                Err(err) => println!("Error writing {}:\n{}", filename, err),
+            }
+        }
+        Some("evaluate") => {
+            let (Some(instance), Some(solution)) = (args.get(1), args.get(2)) else {
+                println!("Usage: evaluate <instance> <solution>");
+                std::process::exit(2);
+            };
+            // checked under the distances of the instance, if the file has its own
+            if let Some((nodes, metric, _)) = instance_with_metric(instance) {
+                evaluate(&nodes, metric.as_ref(), solution, seed, svg);
+            } else {
+                let nodes = instance_nodes(&Some(instance.clone()), 0, &mut rng);
+                evaluate(&nodes, &Euclidean, solution, seed, svg);
+            }
+        }
+        Some("convert") => {
+            let (Some(input), Some(output)) = (args.get(1), args.get(2)) else {
+                println!("Usage: convert <input> <output> [--tour]");
+                std::process::exit(2);
+            };
+            // tsplib tours have their own extension
+            let tour = convert_tour || input.ends_with(".tour");
+            match convert::convert_file(input, output, tour) {
+                Ok(warnings) => {
+                    for warning in warnings {
+                        println!("warning: {}", warning);
+                    }
+                    println!("wrote {}", output);
+                }
+                Err(err) => {
+                    println!("Error converting {}:\n{}", input, err);
+                    std::process::exit(1);
+                }
+            }
+        }
+        Some("geocode") => {
+            let (Some(input), Some(output)) = (args.get(1), args.get(2)) else {
+                println!("Usage: geocode <addresses.csv> <stops.csv> [--gazetteer <file>]");
+                std::process::exit(2);
+            };
+            geocode_addresses(input, output, &gazetteer);
+        }
+        Some("replay") => {
+            let Some(log) = args.get(1) else {
+                println!("Usage: replay <log> [--instance <file>] [--svg]");
+                std::process::exit(2);
+            };
+            replay(log, &instance, svg);
+        }
+        Some(other) => {
+            println!("unknown command '{}'\n{}", other, USAGE);
+            std::process::exit(2);
+        }
+        None => demo(seed, svg),
+    }
+
+    if let Some(profiler) = profiler {
+        if let Err(err) = profiler.finish() {
+            println!("Error writing flamegraph:\n{}", err);
+            std::process::exit(1);
+        }
     }
 }
 
-impl PartialEq for Node {
-    fn eq(&self, other: &Self) -> bool {
-        self.id == other.id
+/// the stops of a stops file written by `geocode` or a geojson collection of points,
+/// None for other files. exits on bad stops files
+fn read_stops(path: &str) -> Option<Vec<geo::GeoStop>> {
+    // only the header is needed to tell, instance files can be huge
+    let mut header = String::new();
+    let file = std::fs::File::open(path).ok()?;
+    std::io::Read::read_to_string(&mut std::io::Read::take(file, 4096), &mut header).ok()?;
+    let parse = if geo::is_stops_file(&header) {
+        geo::parse_stops
+    } else if path.ends_with(".geojson") || geo::is_geojson(&header) {
+        geo::parse_geojson
+    } else {
+        return None;
+    };
+    match std::fs::read_to_string(path)
+        .map_err(|err| err.to_string())
+        .and_then(|text| parse(&text))
+    {
+        Ok(stops) => Some(stops),
+        Err(err) => {
+            println!("Error reading stops:\n{}", err);
+            std::process::exit(1);
+        }
     }
 }
-impl Eq for Node {}
 
-fn random_nodes(N: usize) -> Vec<Node> {
-    let mut rng = rand::thread_rng();
-    (0..N)
-        .map(|i| Node {
-            id: i,
-            x: rng.gen::<f64>(),
-            y: rng.gen::<f64>(),
+/// looks up the addresses of an address csv and writes them as a stops file,
+/// with the gazetteer if there is one and nominatim otherwise
+fn geocode_addresses(input: &str, output: &str, gazetteer: &Option<String>) {
+    let read = |path: &str| {
+        std::fs::read_to_string(path).unwrap_or_else(|err| {
+            println!("Error reading {}:\n{}", path, err);
+            std::process::exit(1);
         })
-        .collect()
+    };
+    let geocoder: Box<dyn geocode::Geocoder> = match gazetteer {
+        Some(path) => match geocode::Gazetteer::parse(&read(path)) {
+            Ok(gazetteer) => Box::new(gazetteer),
+            Err(err) => {
+                println!("Error reading gazetteer:\n{}", err);
+                std::process::exit(1);
+            }
+        },
+        #[cfg(feature = "nominatim")]
+        None => Box::new(geocode::Nominatim::default()),
+        #[cfg(not(feature = "nominatim"))]
+        None => {
+            println!("no geocoder: pass --gazetteer <file> or build with --features nominatim");
+            std::process::exit(2);
+        }
+    };
+    let addresses = match geocode::parse_addresses(&read(input)) {
+        Ok(addresses) => addresses,
+        Err(err) => {
+            println!("Error reading addresses:\n{}", err);
+            std::process::exit(1);
+        }
+    };
+
+    println!(
+        "geocoding {} addresses with {}",
+        addresses.len(),
+        geocoder.name()
+    );
+    let total = addresses.len();
+    let progress = |i: usize| eprint!("\rgeocoding {}/{}", i + 1, total);
+    let (stops, missing) = match geocode::geocode_all(&addresses, geocoder.as_ref(), progress) {
+        Ok(result) => result,
+        Err(err) => {
+            eprintln!();
+            println!("Error geocoding:\n{}", err);
+            std::process::exit(1);
+        }
+    };
+    eprintln!();
+    for message in &missing {
+        println!("warning: {}", message);
+    }
+    match std::fs::write(output, geo::format_stops(&stops)) {
+        Ok(()) => println!(
+            "wrote {} of {} addresses to {}, use it with --instance {}",
+            stops.len(),
+            total,
+            output,
+            output
+        ),
+        Err(err) => println!("Error writing {}:\n{}", output, err),
+    }
 }
 
-/// euclidian distance between 2 nodes
-fn node_distance(node1: &Node, node2: &Node) -> f64 {
-    ((node2.x - node1.x).powi(2) + (node2.y - node1.y).powi(2)).sqrt()
+/// nodes of the instance file if there is one, N random ones otherwise. exits on bad files
+fn instance_nodes(instance: &Option<String>, N: usize, rng: &mut StdRng) -> Vec<Node> {
+    instance_with_costs(instance, N, rng).0
 }
 
-/// traverses pairs of nodes in order and sums the distances
-fn get_tour_length(nodes: &[&Node]) -> f64 {
-    let mut length: f64 = 0.0;
-    for window_slice in nodes.windows(2) {
-        match window_slice {
-            [n1, n2] => length += node_distance(n1, n2),
-            _ => unreachable!(".windows should guarantee slices of 2 always"),
+/// `instance_nodes` with the distances the instance file defines, euclidean ones otherwise
+fn instance_nodes_and_metric(
+    instance: &Option<String>,
+    N: usize,
+    rng: &mut StdRng,
+) -> (Vec<Node>, Box<dyn Metric>) {
+    match instance.as_deref().and_then(instance_with_metric) {
+        Some((nodes, metric, _)) => (nodes, metric),
+        None => (instance_nodes(instance, N, rng), Box::new(Euclidean)),
+    }
+}
+
+/// the tsplib instance at `path`, exits on bad files
+fn read_tsplib(path: &str) -> tsplib::TsplibInstance {
+    match tsplib::read_tsp(path) {
+        Ok(instance) => instance,
+        Err(err) => {
+            println!("Error reading instance:\n{}", err);
+            std::process::exit(1);
         }
     }
-    // if vector has at least 1 element, add distance from last to first node
-    if let Some(first) = nodes.iter().nth(0) {
-        if let Some(last) = nodes.iter().last() {
-            length += node_distance(last, first);
+}
+
+/// the cost matrix of a csv file that isn't an `id,x,y` instance, None for other files.
+/// exits on bad matrices
+fn read_matrix(path: &str) -> Option<explicit::ExplicitMatrix> {
+    if convert::Format::from_path(path) != convert::Format::Csv {
+        return None;
+    }
+    let text = std::fs::read_to_string(path).ok()?;
+    if !explicit::is_matrix_csv(&text) {
+        return None;
+    }
+    match explicit::parse_matrix_csv(&text)
+        .and_then(|rows| explicit::ExplicitMatrix::directed(&rows))
+    {
+        Ok(matrix) => Some(matrix),
+        Err(err) => {
+            println!("Error reading cost matrix:\n{}: {}", path, err);
+            std::process::exit(1);
         }
     }
-    length
 }
 
-/// considers every possible unique permutation (n-1)!
-///
-/// (some permutations are the same, e.g. [0, 1, 2] = [1, 2, 0])
-/// can be optimized by
-/// keeping the first node the same
-/// checking uniqueness
-/// not the point tho, just getting my feet wet here
-#[allow(dead_code)]
-fn tsp_brute_force(nodes: &Vec<Node>) -> Vec<Node> {
-    let mut optimal_tour: Vec<&Node> = Vec::new();
-    let mut optimal_length = f64::INFINITY;
-    // loop over all possible unique tours
-    for tour in nodes.iter().permutations(nodes.len()).unique() {
-        let new_length = get_tour_length(&tour);
-        if new_length < optimal_length {
-            optimal_length = new_length;
-            optimal_tour = tour.clone();
+/// an instance file that defines its own distances, tsplib's edge weights, great circles
+/// between stops or a cost matrix: its nodes, the metric and the metric's name for cache keys.
+/// None for the files of coordinates
+fn instance_with_metric(path: &str) -> Option<(Vec<Node>, Box<dyn Metric>, String)> {
+    if tsplib::is_instance_path(path) {
+        let tsplib = read_tsplib(path);
+        let name = tsplib.metric.name();
+        return Some((tsplib.nodes, Box::new(tsplib.metric), name));
+    }
+    if let Some(stops) = read_stops(path) {
+        let metric = Box::new(geo::Haversine::new(&stops));
+        return Some((geo::project(&stops), metric, "haversine".to_string()));
+    }
+    let matrix = read_matrix(path)?;
+    let name = matrix.name();
+    Some((matrix.nodes(), Box::new(matrix), name))
+}
+
+/// like `instance_nodes`, plus the service times and weights of the file
+fn instance_with_costs(
+    instance: &Option<String>,
+    N: usize,
+    rng: &mut StdRng,
+) -> (Vec<Node>, objective::NodeCosts) {
+    let Some(path) = instance else {
+        return (random_nodes(N, rng), objective::NodeCosts::default());
+    };
+    if let Some(stops) = read_stops(path) {
+        return (geo::project(&stops), objective::NodeCosts::default());
+    }
+    if tsplib::is_instance_path(path) {
+        return (read_tsplib(path).nodes, objective::NodeCosts::default());
+    }
+    if let Some(matrix) = read_matrix(path) {
+        return (matrix.nodes(), objective::NodeCosts::default());
+    }
+    if matches!(
+        convert::Format::from_path(path),
+        convert::Format::Csv | convert::Format::Json
+    ) {
+        match convert::read_instance(path) {
+            Ok(instance) => return (instance.nodes, instance.costs),
+            Err(err) => {
+                println!("Error reading instance:\n{}", err);
+                std::process::exit(1);
+            }
+        }
+    }
+    // big files take a while, show how far along loading is
+    let mut last_percent = None;
+    let progress = |bytes: u64, size: u64| {
+        if size < PROGRESS_MIN_BYTES {
+            return;
+        }
+        let percent = bytes * 100 / size;
+        if last_percent != Some(percent) {
+            eprint!("\rloading {}: {}%", path, percent);
+            last_percent = Some(percent);
+        }
+        if bytes == size {
+            eprintln!();
+        }
+    };
+    match formats::read_instance_with_progress(path, progress) {
+        Ok(instance) => instance,
+        Err(err) => {
+            println!("Error reading instance:\n{}", err);
+            std::process::exit(1);
         }
     }
+}
 
-    let mut return_tour = Vec::new();
-    for n in optimal_tour {
-        return_tour.push(n.clone());
+/// removes `name value` from args and parses value, exits on garbage
+fn take_flag<T: std::str::FromStr>(args: &mut Vec<String>, name: &str) -> Option<T>
+where
+    T::Err: std::fmt::Display,
+{
+    let position = args.iter().position(|arg| arg == name)?;
+    args.remove(position);
+    if position >= args.len() {
+        println!("Error parsing {}:\nmissing value", name);
+        std::process::exit(2);
+    }
+    match args.remove(position).parse() {
+        Ok(value) => Some(value),
+        Err(err) => {
+            println!("Error parsing {}:\n{}", name, err);
+            std::process::exit(2);
+        }
     }
-    return_tour
 }
 
-/// start at first node and always choose closest next node
-fn tsp_nearest_neighbor(nodes: &Vec<Node>) -> Vec<Node> {
-    let mut nearest_neighbor: Vec<Node> = Vec::new();
-    let mut leftovers: Vec<Node> = nodes.clone();
+/// removes `name` from args, true if it was there
+fn take_switch(args: &mut Vec<String>, name: &str) -> bool {
+    let before = args.len();
+    args.retain(|arg| arg != name);
+    args.len() != before
+}
 
-    while !leftovers.is_empty() {
-        if nearest_neighbor.is_empty() {
-            // leftovers isn't empty per loop cond
-            if let Some(first) = leftovers.pop() {
-                nearest_neighbor.push(first);
+/// parses the positional argument at index, exits on garbage
+fn parse_arg<T: std::str::FromStr>(args: &[String], index: usize, name: &str) -> Option<T>
+where
+    T::Err: std::fmt::Display,
+{
+    match args.get(index).map(|arg| arg.parse()) {
+        Some(Ok(value)) => Some(value),
+        Some(Err(err)) => {
+            println!("Error parsing {}:\n{}", name, err);
+            std::process::exit(2);
+        }
+        None => None,
+    }
+}
+
+/// pipeline of a comma separated chain of solvers for `nodes`, e.g. "nn,sa:iterations=5000",
+/// exits on unknown solvers and ones that don't fit the instance
+fn build_pipeline(stages: &str, nodes: &[Node]) -> pipeline::Pipeline {
+    let mut pipeline = pipeline::Pipeline::new();
+    for stage in stages.split(',') {
+        let solver = solvers::solver_by_name(stage).and_then(|solver| {
+            solver.check(nodes)?;
+            Ok(solver)
+        });
+        match solver {
+            Ok(solver) => pipeline = pipeline.then_boxed(solver),
+            Err(err) => {
+                println!("Error parsing stage '{}':\n{}", stage, err);
+                std::process::exit(2);
             }
-        } else {
-            // nearest_neighbor isn't empty per if cond above
-            if let Some(last_neighbor) = nearest_neighbor.last() {
-                let mut smallest_distance: f64 = f64::INFINITY;
-                let mut nn_position: usize = 0;
-                for (i, node) in leftovers.iter().enumerate() {
-                    let new_distance = node_distance(node, last_neighbor);
-                    if new_distance < smallest_distance {
-                        smallest_distance = new_distance;
-                        nn_position = i;
-                    }
+        }
+    }
+    pipeline
+}
+
+/// how `solve` measures and whether it may reuse old results
+struct SolveOptions {
+    seed: u64,
+    cache_distances: bool,
+    /// compute all distances before solving
+    matrix: Option<MatrixLayout>,
+    /// load the distance cache from disk before solving and save it after, implies `cache_distances`
+    persist_distances: bool,
+    /// look up and store runs in the result cache
+    cache_results: bool,
+    integer: bool,
+    /// distances the instance file defines, with their name for the cache keys, instead of `integer`.
+    /// tsplib's edge weights and great circles between stops
+    instance_metric: Option<(String, Box<dyn Metric>)>,
+    /// for building the distance matrix
+    threads: usize,
+    /// draw svg instead of png
+    svg: bool,
+    gate: quality::QualityGate,
+    /// tour file the gap is measured against, the mst lower bound without one
+    reference: Option<String>,
+    /// file the checkpoints of the run are written to, for `replay`
+    record: Option<String>,
+    /// directions links for the stops of the instance
+    navigation: Option<(navigation::Provider, Vec<geo::GeoStop>)>,
+    /// solve a one-way route between these ends instead of a tour
+    path: Option<path::PathEnds>,
+    /// id of the node the tour has to start at
+    depot: Option<usize>,
+}
+
+/// run a chain of solvers on the instance, or read the run from the result cache
+fn solve(
+    stages: &str,
+    nodes: &[Node],
+    costs: &objective::NodeCosts,
+    rng: &mut StdRng,
+    options: &SolveOptions,
+) {
+    let mut pipeline = build_pipeline(stages, nodes);
+    if let Some(depot) = options.depot {
+        if !nodes.iter().any(|node| node.id == depot) {
+            println!("Error in --start:\nnode {} isn't in the instance", depot);
+            std::process::exit(2);
+        }
+        pipeline = pipeline.starting_at(depot);
+    }
+    let integer = options.integer && options.instance_metric.is_none();
+    let metric_kind = if integer {
+        MetricKind::Integer
+    } else {
+        MetricKind::Euclidean
+    };
+    let mut metric_name = match &options.instance_metric {
+        Some((name, _)) => name.clone(),
+        None => metric_kind.name().to_string(),
+    };
+
+    let integer_metric = integer.then(|| match integer::IntegerEuclidean::new(nodes) {
+        Ok(metric) => metric,
+        Err(err) => {
+            println!("Error in --integer mode:\n{}", err);
+            std::process::exit(1);
+        }
+    });
+    let distance: &dyn Metric = match (&options.instance_metric, &integer_metric) {
+        (Some((_, metric)), _) => metric.as_ref(),
+        (None, Some(integer_metric)) => integer_metric,
+        (None, None) => &Euclidean,
+    };
+    // a path is the tour through a dummy node between its ends, see `path`
+    let inner = distance;
+    let open = options.path.map(|ends| {
+        if let Err(err) = ends.check(nodes) {
+            println!("Error in --path:\n{}", err);
+            std::process::exit(2);
+        }
+        metric_name = format!("{}, {}", metric_name, ends.name());
+        path::OpenPath::new(nodes, distance, ends)
+    });
+    let with_dummy = open.as_ref().map(|open| open.nodes_with_dummy(nodes));
+    let nodes = with_dummy.as_deref().unwrap_or(nodes);
+    let distance: &dyn Metric = match &open {
+        Some(open) => open,
+        None => distance,
+    };
+    // service times and weights from the instance file
+    let weighted = (!costs.is_neutral()).then(|| objective::Weighted::new(distance, costs.clone()));
+    let base: &dyn Metric = match &weighted {
+        Some(weighted) => weighted,
+        None => distance,
+    };
+    let start = std::time::Instant::now();
+    let matrix = options
+        .matrix
+        .map(|layout| DistanceMatrix::new(nodes, base, layout, options.threads));
+    let base: &dyn Metric = match &matrix {
+        Some(matrix) => {
+            println!(
+                "distance matrix: {}, {} in {:?}",
+                matrix.layout().name(),
+                memory::format_bytes(matrix.memory()),
+                start.elapsed()
+            );
+            matrix
+        }
+        None => base,
+    };
+    let cache_size = nodes.iter().map(|n| n.id + 1).max().unwrap_or(0);
+    let cached = options
+        .cache_distances
+        .then(|| CachedMetric::new(base, cache_size));
+    let metric: &dyn Metric = match &cached {
+        Some(cached) => cached,
+        None => base,
+    };
+    let matrices = matrix_cache::MatrixCache::new(result_cache::DEFAULT_DIR);
+    let matrix_key = matrix_cache::matrix_key(nodes, costs, &metric_name);
+    if let (Some(cached), true) = (&cached, options.persist_distances) {
+        if let Some(snapshot) = matrices.load(matrix_key, cache_size) {
+            match cached.preload(&snapshot) {
+                Ok(known) => println!(
+                    "loaded {} distances from {}",
+                    known,
+                    matrices.path(matrix_key).display()
+                ),
+                Err(err) => println!("Error loading distance matrix:\n{}", err),
+            }
+        }
+    }
+
+    // everything the run depends on, the distance cache and f64 matrices don't change results
+    let mut key = result_cache::KeyBuilder::new();
+    key.nodes(nodes).costs(nodes, costs).text(&metric_name);
+    if options.matrix.is_some_and(|layout| layout.f32) {
+        key.text("f32");
+    }
+    if let Some(depot) = options.depot {
+        key.text(&format!("depot {}", depot));
+    }
+    let key = key.text(stages).number(options.seed).finish();
+    let results = result_cache::ResultCache::new(result_cache::DEFAULT_DIR);
+    // a cached result has no checkpoints to record
+    let cached_run = (options.cache_results && options.record.is_none())
+        .then(|| results.load(key))
+        .flatten()
+        .and_then(|(ids, mut run)| {
+            run.tour = Tour::from_ids(&ids, nodes).ok()?.into_nodes();
+            Some(run)
+        });
+    let run = match cached_run {
+        Some(run) => {
+            println!("cached result: {}", results.path(key).display());
+            run
+        }
+        None => {
+            if let Err(err) = cancel::install_interrupt_handler() {
+                println!("Error installing ctrl-c handler:\n{}", err);
+            }
+            if options.record.is_some() {
+                events::start_recording();
+            }
+            let run = pipeline.run(nodes, metric, rng);
+            if let Some(path) = &options.record {
+                let names = run.stages.iter().map(|stage| stage.name.clone()).collect();
+                write_event_log(path, names, nodes.len(), options.seed);
+            }
+            // an interrupted run isn't what this configuration produces
+            if options.cache_results && !run.interrupted {
+                if let Err(err) = results.store(key, &run) {
+                    println!("Error caching result:\n{}", err);
                 }
+            }
+            run
+        }
+    };
+    let interrupted = run.interrupted;
+    let mut tour = match &open {
+        // the dummy goes back between the ends, so the tour measures as long as the path
+        Some(open) => {
+            let mut path = open.cut(&run.tour);
+            path.extend(nodes.last().cloned());
+            Tour::from(path)
+        }
+        None => Tour::from(run.tour),
+    };
+    // turning the tour around changes its length on asymmetric metrics
+    match options.depot {
+        _ if open.is_some() => {}
+        Some(depot) => {
+            tour.start_at(depot);
+        }
+        None if metric.is_symmetric() => tour.canonicalize(),
+        None => {
+            let first = tour.nodes().iter().map(|node| node.id).min();
+            tour.start_at(first.unwrap_or(0));
+        }
+    }
+    // what gets printed and drawn, the path without its dummy
+    let shown = &tour.nodes()[..tour.len() - open.is_some() as usize];
+    println!("initial length: {:?}", run.initial_length);
+    for stage in &run.stages {
+        println!(
+            "{:<22} length: {:<20?} time: {:?}",
+            stage.name, stage.length, stage.time
+        );
+    }
+    if let Some(open) = &open {
+        // the stages measure the tour through the dummy, not the path that's cut out of it
+        if options.path != Some(path::PathEnds::default()) {
+            println!(
+                "stage lengths count {:?} for every end a stage left out of place",
+                open.penalty()
+            );
+        }
+        println!("path length: {:?}", path::path_length(inner, shown));
+    }
+    if let Some(cached) = &cached {
+        let stats = cached.stats();
+        println!(
+            "distance cache: {} hits, {} misses ({:.1}% hit rate)",
+            stats.hits,
+            stats.misses,
+            100.0 * stats.hit_rate()
+        );
+        // only worth writing if the run computed something new
+        if options.persist_distances && stats.misses > 0 {
+            match matrices.store(matrix_key, cache_size, &cached.snapshot()) {
+                Ok(path) => println!("saved distance matrix to {}", path.display()),
+                Err(err) => println!("Error saving distance matrix:\n{}", err),
+            }
+        }
+    }
+
+    println!(
+        "{}: {:?}",
+        if open.is_some() { "path" } else { "tour" },
+        shown.iter().map(|n| n.id).collect::<Vec<_>>()
+    );
+    println!("fingerprint: {:016x}", tour.fingerprint().0);
+    if open.is_none() {
+        print_crossings(tour.nodes());
+    }
+
+    let metadata =
+        image_meta::RunMetadata::new(pipeline.name(), options.seed, shown, tour.length(metric))
+            .with("stages", stages)
+            .with("metric", &metric_name);
+    let filename = image_name("pipeline", options.svg);
+    let drawn = match open {
+        Some(_) => draw_path(&filename, shown, &metadata),
+        None => draw_tour(&filename, shown, &metadata),
+    };
+    if let Err(err) = drawn {
+        println!("Error drawing:\n{}", err);
+    }
+
+    if let Some((provider, stops)) = &options.navigation {
+        write_navigation(&Tour::from(shown.to_vec()), stops, *provider);
+    }
+
+    if interrupted {
+        let manifest = format!(
+            "# interrupted run, the tour is the best one found before ctrl-c\n\
+             seed {}\nstages {}\ncompleted {} of {}\nlength {:?}\ntour interrupted.tour\n",
+            options.seed,
+            stages,
+            run.stages.len(),
+            stages.split(',').count(),
+            tour.length(metric),
+        );
+        let ids: Vec<String> = shown.iter().map(|n| n.id.to_string()).collect();
+        match std::fs::write("interrupted.tour", ids.join("\n") + "\n")
+            .and_then(|()| std::fs::write("interrupted.manifest", manifest))
+        {
+            Ok(()) => println!("interrupted, wrote interrupted.tour and interrupted.manifest"),
+            Err(err) => println!("Error writing interrupted run:\n{}", err),
+        }
+        std::process::exit(cancel::INTERRUPTED_EXIT_CODE);
+    }
+
+    if options.gate.is_active() {
+        check_quality(&tour, nodes, metric, options);
+    }
+}
+
+/// writes the checkpoints recorded so far, see `replay`
+fn write_event_log(path: &str, stages: Vec<String>, nodes: usize, seed: u64) {
+    let log = events::EventLog {
+        seed,
+        nodes,
+        stages,
+        checkpoints: events::take_checkpoints(),
+    };
+    match std::fs::write(path, log.format()) {
+        Ok(()) => println!("wrote {} checkpoints to {}", log.checkpoints.len(), path),
+        Err(err) => println!("Error writing event log:\n{}", err),
+    }
+}
+
+/// draws every checkpoint of a recorded run and how the length converged,
+/// without solving again. the instance is the `--instance` file or the random one of the logged seed
+fn replay(path: &str, instance: &Option<String>, svg: bool) {
+    let log = match std::fs::read_to_string(path)
+        .map_err(|err| err.to_string())
+        .and_then(|text| events::EventLog::parse(&text))
+    {
+        Ok(log) => log,
+        Err(err) => {
+            println!("Error reading event log:\n{}", err);
+            std::process::exit(1);
+        }
+    };
+    // `main` draws random instances from a fresh rng of the seed too
+    let nodes = instance_nodes(instance, log.nodes, &mut StdRng::seed_from_u64(log.seed));
+    if nodes.len() != log.nodes {
+        println!(
+            "Error replaying {}:\nthe run had {} nodes, the instance has {}",
+            path,
+            log.nodes,
+            nodes.len()
+        );
+        std::process::exit(1);
+    }
 
-                nearest_neighbor.push(leftovers.swap_remove(nn_position));
+    if let Err(err) = std::fs::create_dir_all(REPLAY_DIR) {
+        println!("Error creating {}:\n{}", REPLAY_DIR, err);
+        std::process::exit(1);
+    }
+    for (k, checkpoint) in log.checkpoints.iter().enumerate() {
+        let tour = match Tour::from_ids(&checkpoint.tour, &nodes) {
+            Ok(tour) => tour,
+            Err(err) => {
+                println!("Error in checkpoint {}:\n{}", k, err);
+                std::process::exit(1);
             }
+        };
+        let stage = match checkpoint.stage {
+            0 => "initial",
+            stage => log.stages.get(stage - 1).map_or("unknown", String::as_str),
+        };
+        let mut metadata =
+            image_meta::RunMetadata::new(stage, log.seed, tour.nodes(), checkpoint.length);
+        if let Some(iteration) = checkpoint.iteration {
+            metadata = metadata.with("iteration", iteration);
+        }
+        let filename = image_name(&format!("{}/frame_{:04}", REPLAY_DIR, k), svg);
+        if let Err(err) = draw_tour(&filename, tour.nodes(), &metadata) {
+            println!("Error drawing:\n{}", err);
         }
     }
 
-    nearest_neighbor
+    let filename = image_name(&format!("{}/convergence", REPLAY_DIR), svg);
+    if let Err(err) = draw_convergence(&filename, &log) {
+        println!("Error drawing:\n{}", err);
+    }
+    println!(
+        "wrote {} frames to {}/ and {}",
+        log.checkpoints.len(),
+        REPLAY_DIR,
+        filename
+    );
+}
+
+/// writes the directions links of the tour to navigation.txt, the tour starts at the first stop
+fn write_navigation(tour: &Tour, stops: &[geo::GeoStop], provider: navigation::Provider) {
+    let ordered: Option<Vec<&geo::GeoStop>> = tour
+        .nodes()
+        .iter()
+        .map(|node| stops.iter().find(|stop| stop.id == node.id))
+        .collect();
+    let Some(ordered) = ordered else {
+        println!("Error writing navigation links:\nthe tour visits nodes that aren't stops");
+        return;
+    };
+    let mut text = String::new();
+    for leg in navigation::legs(&ordered, provider) {
+        let address = |position: usize| &ordered[position % ordered.len()].address;
+        text.push_str(&format!(
+            "# stops {} to {}: {} -> {}\n{}\n",
+            leg.from + 1,
+            leg.to + 1,
+            address(leg.from),
+            address(leg.to),
+            leg.url
+        ));
+    }
+    match std::fs::write("navigation.txt", text) {
+        Ok(()) => println!("wrote directions links to navigation.txt"),
+        Err(err) => println!("Error writing navigation.txt:\n{}", err),
+    }
 }
 
-/// swap two random nodes, returning the swapped indices
-/// indices are never equal
-fn random_swap(nodes: &mut Vec<Node>) -> (usize, usize) {
-    let mut rng = rand::thread_rng();
-    let a = rng.gen_range(0..nodes.len());
-    let b = loop {
-        let random = rng.gen_range(0..nodes.len());
-        if random != a {
-            break random;
+/// prints whether the tour meets the quality gate of the options, exits if it doesn't
+fn check_quality(tour: &Tour, nodes: &[Node], metric: &dyn Metric, options: &SolveOptions) {
+    let reference = match &options.reference {
+        Some(path) => {
+            let reference = formats::read_tour_ids(path)
+                .map_err(|err| err.to_string())
+                .and_then(|ids| Tour::from_ids(&ids, nodes));
+            match reference {
+                Ok(reference) => quality::Reference {
+                    name: format!("reference tour {}", path),
+                    length: reference.length(metric),
+                },
+                Err(err) => {
+                    println!("Error reading reference tour:\n{}", err);
+                    std::process::exit(1);
+                }
+            }
         }
+        None => quality::mst_bound(nodes, metric),
     };
+    let failures = options.gate.check(tour.length(metric), &reference);
+    if failures.is_empty() {
+        println!("quality gate passed");
+        return;
+    }
+    for failure in failures {
+        println!("quality gate failed: {}", failure);
+    }
+    std::process::exit(quality::FAILED_EXIT_CODE);
+}
+
+/// solve the same instance with two chains of solvers and compare the tours edge by edge
+fn diff(from: &str, to: &str, nodes: &[Node], metric: &dyn Metric, rng: &mut StdRng) {
+    let (from, to) = (build_pipeline(from, nodes), build_pipeline(to, nodes));
+    let from_tour = Tour::from(from.solve(nodes, metric, rng));
+    let to_tour = Tour::from(to.solve(nodes, metric, rng));
+
+    let diff = from_tour.diff(&to_tour, metric);
+    println!("{} length: {:?}", from.name(), from_tour.length(metric));
+    println!("{} length: {:?}", to.name(), to_tour.length(metric));
+    println!("shared edges: {}", diff.shared);
+    println!("removed edges: {:?}", diff.removed);
+    println!("added edges: {:?}", diff.added);
+    println!("length delta: {:?}", diff.length_delta);
+    if from_tour == to_tour {
+        println!("tours are identical up to rotation and direction");
+    }
+
+    if let Err(err) = draw_tour_diff("diff.png", &from_tour, &to_tour, metric) {
+        println!("Error drawing:\n{}", err);
+    }
+}
+
+/// solve the instance `runs` times with each of two chains, every run from its own seed,
+/// and test whether one chain finds shorter tours, see `stats`
+fn compare(
+    chains: [&str; 2],
+    nodes: &[Node],
+    metric: &dyn Metric,
+    runs: usize,
+    seed: u64,
+    threads: usize,
+    alpha: f64,
+) {
+    let pipelines = chains.map(|chain| build_pipeline(chain, nodes));
+    let lengths: Vec<[f64; 2]> = rng::parallel_map(runs, threads, |run| {
+        [0, 1].map(|chain| {
+            let mut rng = rng::stream_rng(seed, (2 * run + chain) as u64);
+            metric.tour_length(&pipelines[chain].solve(nodes, metric, &mut rng))
+        })
+    });
+    let samples = [0, 1].map(|chain| lengths.iter().map(|l| l[chain]).collect::<Vec<_>>());
 
-    nodes.swap(a, b);
-    (a, b)
-}
-
-/// searches for best tour by randomly swapping Nodes,
-/// accepting swaps with shorter tours.
-/// swaps that beget longer tours are accepted based on a
-/// probability function that decreases over time
-///
-/// parameters open for more tuning
-fn tsp_simulated_annealing(nodes: &Vec<Node>) -> Vec<Node> {
-    const ITERATIONS: u32 = 10000;
-    const TEMP_0_FACTOR: f64 = 3.3;
-    const COOLING_FACTOR: f64 = 0.88;
-
-    let mut rng = rand::thread_rng();
-    let mut annealed = nodes.clone();
-    //different graph sizes seem to call for different beginning temperaturs?
-    let mut temp = nodes.len() as f64 / TEMP_0_FACTOR;
-    let mut current_length = get_tour_length(&annealed.iter().collect::<Vec<_>>());
-
-    for _ in 0..ITERATIONS {
-        let (a, b) = random_swap(&mut annealed);
-        let new_length = get_tour_length(&annealed.iter().collect::<Vec<_>>());
-        let delta = new_length - current_length;
-
-        // probability to accept swap
-        let probability = if delta > 0.0 {
-            f64::exp(-(delta / temp))
+    for (pipeline, sample) in pipelines.iter().zip(&samples) {
+        let mean = sample.iter().sum::<f64>() / runs as f64;
+        let best = sample.iter().copied().fold(f64::INFINITY, f64::min);
+        println!(
+            "{}: mean {:.6}, median {:.6}, best {:.6}",
+            pipeline.name(),
+            mean,
+            stats::median(sample),
+            best
+        );
+    }
+    let Some(test) = stats::mann_whitney_u(&samples[0], &samples[1]) else {
+        return;
+    };
+    println!(
+        "mann-whitney u: {}, p-value: {:.4} over {} runs each",
+        test.statistic, test.p_value, runs
+    );
+    if test.p_value > alpha {
+        println!("no significant difference at alpha {}", alpha);
+    } else {
+        // U counts the pairs the first chain lost, more than half means it's usually longer
+        let better = if test.statistic < (runs * runs) as f64 / 2.0 {
+            &pipelines[0]
         } else {
-            1.0
+            &pipelines[1]
         };
+        println!(
+            "{} finds shorter tours, significant at alpha {}",
+            better.name(),
+            alpha
+        );
+    }
+}
 
-        // debugging
-        // println!("length: {:.7}, temp: {:.7}, delta: {:.7} prob: {:.7}", current_length, temp, delta, probability);
+/// restart a chain of solvers from `starts` starts and print how every start did
+fn multi_start(
+    stages: &str,
+    starts: usize,
+    shuffle: bool,
+    nodes: &[Node],
+    metric: &dyn Metric,
+    rng: &mut StdRng,
+    threads: usize,
+) {
+    let mut solver =
+        multistart::MultiStart::new(build_pipeline(stages, nodes), starts).threads(threads);
+    if shuffle {
+        solver = solver.shuffled();
+    }
+    let run = solver.run(nodes, metric, rng);
+    for (k, start) in run.starts.iter().enumerate() {
+        println!(
+            "{} start {:<4} length: {:<20} time: {:<12?} seed: {}",
+            if k == run.best { "*" } else { " " },
+            k,
+            start.length,
+            start.time,
+            start.seed
+        );
+    }
+    println!(
+        "best: {} (start {}), mean: {:.6}, std dev: {:.6}",
+        run.starts[run.best].length,
+        run.best,
+        run.mean_length(),
+        run.std_dev()
+    );
+    println!(
+        "tour: {:?}",
+        run.tour.iter().map(|n| n.id).collect::<Vec<_>>()
+    );
+}
 
-        // swap back if longer + failed probability test
-        if rng.gen::<f64>() > probability {
-            annealed.swap(a, b);
-        } else {
-            current_length = new_length;
+/// the genetic algorithm from the instance order, with its history plotted
+fn genetic(
+    nodes: &[Node],
+    metric: &dyn Metric,
+    params: &tsp::genetic::GaParams,
+    rng: &mut StdRng,
+    svg: bool,
+) {
+    let start = std::time::Instant::now();
+    let run = tsp::genetic::tsp_genetic(nodes, metric, params, rng);
+    if let (Some(first), Some(last)) = (run.history.first(), run.history.last()) {
+        println!(
+            "generations: {}, best: {} -> {}, mean: {:.6} -> {:.6}, time: {:?}",
+            run.history.len() - 1,
+            first.best,
+            last.best,
+            first.mean,
+            last.mean,
+            start.elapsed()
+        );
+    }
+    let filename = image_name("genetic", svg);
+    match draw_generations(&filename, &run.history) {
+        Ok(()) => println!("wrote {}", filename),
+        Err(err) => println!("Error drawing:\n{}", err),
+    }
+}
+
+/// trains a self-organizing map, draws its ring every few iterations and the tour at the end
+fn som(
+    nodes: &[Node],
+    metric: &dyn Metric,
+    params: &tsp::som::SomParams,
+    seed: u64,
+    rng: &mut StdRng,
+    svg: bool,
+) {
+    let start = std::time::Instant::now();
+    let run = tsp::som::tsp_som(nodes, params, SOM_FRAMES, rng);
+    let length = metric.tour_length(&run.tour);
+    println!("length: {:<20} time: {:?}", length, start.elapsed());
+
+    if let Err(err) = std::fs::create_dir_all(SOM_DIR) {
+        println!("Error creating {}:\n{}", SOM_DIR, err);
+        std::process::exit(1);
+    }
+    for (k, ring) in run.rings.iter().enumerate() {
+        let filename = image_name(&format!("{}/ring_{:04}", SOM_DIR, k), svg);
+        if let Err(err) = draw_ring(&filename, nodes, ring) {
+            println!("Error drawing:\n{}", err);
         }
+    }
+    let filename = image_name(&format!("{}/tour", SOM_DIR), svg);
+    let metadata = image_meta::RunMetadata::new("som", seed, &run.tour, length);
+    if let Err(err) = draw_tour(&filename, &run.tour, &metadata) {
+        println!("Error drawing:\n{}", err);
+    }
+    println!(
+        "wrote {} rings to {}/ and {}",
+        run.rings.len(),
+        SOM_DIR,
+        filename
+    );
+}
 
-        // cooling
-        temp = COOLING_FACTOR * temp;
+/// the optimal tour by branch and bound, with the size of the search tree
+fn branch_and_bound(nodes: &[Node], metric: &dyn Metric) {
+    let start = std::time::Instant::now();
+    let run = tsp::branch_and_bound::solve(nodes, metric);
+    println!(
+        "length: {:<20} time: {:<12?} expanded: {} pruned: {}",
+        run.length,
+        start.elapsed(),
+        run.expanded,
+        run.pruned
+    );
+    println!(
+        "tour: {:?}",
+        run.tour.iter().map(|n| n.id).collect::<Vec<_>>()
+    );
+}
 
-        // add to history
+/// solve the instance and write the tour plus candidate graphs as graphviz dot
+fn export_dot(
+    stages: &str,
+    nodes: &[Node],
+    metric: &dyn Metric,
+    rng: &mut StdRng,
+    layers: dot::DotLayers,
+) {
+    let pipeline = build_pipeline(stages, nodes);
+    let tour = Tour::from(pipeline.solve(nodes, metric, rng));
+
+    let layers = dot::DotLayers {
+        tour: Some(&tour),
+        ..layers
+    };
+    match std::fs::write("tsp.dot", dot::to_dot(nodes, &layers, metric)) {
+        Ok(()) => println!("wrote tsp.dot, render with 'neato -n -Tpng tsp.dot -o tsp.png'"),
+        Err(err) => println!("Error writing tsp.dot:\n{}", err),
     }
+}
 
-    annealed
+/// solve the instance and write the tour as a latex figure
+fn export_tikz(stages: &str, nodes: &[Node], metric: &dyn Metric, rng: &mut StdRng, seed: u64) {
+    let pipeline = build_pipeline(stages, nodes);
+    let tour = Tour::from(pipeline.solve(nodes, metric, rng));
+    let caption = format!(
+        "{}, {} nodes, length {:.4}, seed {}",
+        pipeline.name(),
+        tour.nodes().len(),
+        tour.length(metric),
+        seed
+    );
+    match std::fs::write("tsp.tex", tikz::to_tikz(&tour, &caption)) {
+        Ok(()) => println!("wrote tsp.tex, compile with 'pdflatex tsp.tex'"),
+        Err(err) => println!("Error writing tsp.tex:\n{}", err),
+    }
 }
 
-/// draw tour with plotters to filename
-fn draw_tour(filename: &str, nodes: &Vec<Node>) -> Result<(), Box<dyn std::error::Error>> {
-    if nodes.is_empty() {
-        return Err("can't draw empty tour".into());
+/// write the milp formulation of the instance for external solvers
+fn export_model(
+    filename: &str,
+    nodes: &[Node],
+    metric: &dyn Metric,
+    formulation: model::Formulation,
+) {
+    let model = model::Model::tsp(nodes, metric, formulation);
+
+    let contents = if filename.ends_with(".mps") {
+        model.to_mps()
+    } else if filename.ends_with(".lp") {
+        model.to_lp()
+    } else {
+        println!("unknown model format of '{}', use .lp or .mps", filename);
+        std::process::exit(2);
+    };
+    match std::fs::write(filename, contents) {
+        Ok(()) => println!("wrote {}", filename),
+        Err(err) => println!("Error writing {}:\n{}", filename, err),
     }
-    let root = BitMapBackend::new(filename, (1111, 1111)).into_drawing_area();
-    let root = root.titled(
-        &format!(
-            "'{}', tour length: {}",
-            filename,
-            get_tour_length(&nodes.iter().collect::<Vec<_>>())
-        ),
-        TextStyle::from(("sans-serif", 24).into_font()).color(&WHITE),
-    )?;
+}
 
-    root.fill(&RGBColor(245, 245, 245))?;
-
-    let root = root.apply_coord_spec(Cartesian2d::<RangedCoordf64, RangedCoordf64>::new(
-        0f64..1f64,
-        0f64..1f64,
-        (0..1000, 0..1000),
-    ));
-
-    let dot_and_id = |node: &Node| {
-        return EmptyElement::at((node.x, node.y))
-            + Circle::new((0, 0), 7, ShapeStyle::from(&BLACK).filled())
-            + Text::new(
-                format!("{}", node.id),
-                (13, 0),
-                ("sans-serif", 23.0).into_font(),
-            );
+/// check a tour found elsewhere against the instance and the crate's own solvers
+fn evaluate(nodes: &[Node], metric: &dyn Metric, solution: &str, seed: u64, svg: bool) {
+    let ids = match formats::read_tour_ids(solution) {
+        Ok(ids) => ids,
+        Err(err) => {
+            println!("Error reading solution:\n{}", err);
+            std::process::exit(1);
+        }
+    };
+    let tour = match Tour::from_ids(&ids, nodes) {
+        Ok(tour) => tour,
+        Err(err) => {
+            println!("invalid solution:\n{}", err);
+            std::process::exit(1);
+        }
     };
 
-    //
-    // draw nodes
-    //
-    for node in nodes {
-        root.draw(&dot_and_id(node))?;
+    let length = tour.length(metric);
+    println!("{} length: {:?}", solution, length);
+    print_crossings(tour.nodes());
+    for (i, solver) in solvers::registered_solvers().iter().enumerate() {
+        if nodes.len() > solver.max_nodes() {
+            continue;
+        }
+        let own = Tour::from(solver.solve(nodes, metric, &mut rng::stream_rng(seed, i as u64)));
+        let own_length = own.length(metric);
+        println!(
+            "{:<40} length: {:<20?} {:+.2}%",
+            solver.name(),
+            own_length,
+            100.0 * (own_length / length - 1.0)
+        );
     }
 
-    //
-    // draw edges
-    //
-    let mut edge_points = nodes
-        .iter()
-        .map(|n| (n.x, n.y))
-        .collect::<Vec<(f64, f64)>>();
-    // edge_points is just transformed nodes, which can't be empty
-    edge_points.insert(0, *edge_points.last().unwrap());
-    root.draw(&PathElement::new(
-        edge_points,
-        ShapeStyle::from(&BLACK).filled(),
-    ))?;
-
-    root.present()?;
-    Ok(())
+    let metadata = image_meta::RunMetadata::new("external", seed, tour.nodes(), length)
+        .with("solution", solution);
+    if let Err(err) = draw_tour(&image_name("evaluate", svg), tour.nodes(), &metadata) {
+        println!("Error drawing:\n{}", err);
+    }
 }
 
-// this https://www.youtube.com/watch?v=GiDsjIBOVoA
-// has been super helpful to get me started
-fn main() {
-    let N = 13;
-    let nodes = random_nodes(N);
+/// keeps a nearest neighbor tour up to date while new random nodes arrive one by one
+/// and random visited ones drop out, then compares it to solving the remaining nodes from scratch
+fn online(N: usize, arrivals: usize, departures: usize, seed: u64, svg: bool) {
+    const REPAIR_WINDOW: usize = 3;
+
+    let mut rng = StdRng::seed_from_u64(seed);
+    let nodes = random_nodes(N + arrivals, &mut rng);
+    let mut tour = Tour::from(tsp_nearest_neighbor(&nodes[..N], &Euclidean));
+    println!("initial length: {:?}", tour.length(&Euclidean));
+
+    let start = std::time::Instant::now();
+    let mut relocations = 0;
+    let mut departed = 0;
+    // one round without an arrival if there are none, so the departures still happen
+    for i in 0..arrivals.max(1) {
+        if let Some(node) = nodes.get(N + i) {
+            tour.insert_node(node.clone(), &Euclidean);
+            relocations += tour.repair_around(node.id, REPAIR_WINDOW, &Euclidean);
+        }
+        // spread the departures evenly over the arrivals, all of them are due after the last
+        let due = (i + 1) * departures / arrivals.max(1);
+        while departed < due && tour.nodes().len() > 3 {
+            let id = tour.nodes()[rng.gen_range(0..tour.nodes().len())].id;
+            if let Some((_, repairs)) = tour.remove_node(id, REPAIR_WINDOW, &Euclidean) {
+                relocations += repairs;
+            }
+            departed += 1;
+        }
+    }
     println!(
-        "random tour length: {:?}",
-        get_tour_length(&nodes.iter().collect::<Vec<_>>())
+        "after {} arrivals and {} departures: {:?} ({} repair moves, {:?})",
+        arrivals,
+        departed,
+        tour.length(&Euclidean),
+        relocations,
+        start.elapsed()
     );
 
-    let nn_tour = tsp_nearest_neighbor(&nodes);
+    let start = std::time::Instant::now();
+    let scratch = Tour::from(tsp_nearest_neighbor(tour.nodes(), &Euclidean));
     println!(
-        "nearest neighbor length: {:?}",
-        get_tour_length(&nn_tour.iter().collect::<Vec<_>>())
+        "nearest neighbor from scratch: {:?} ({:?})",
+        scratch.length(&Euclidean),
+        start.elapsed()
     );
 
-    let sa_tour = tsp_simulated_annealing(&nodes);
+    let metadata = image_meta::RunMetadata::new(
+        "online nearest neighbor",
+        seed,
+        tour.nodes(),
+        tour.length(&Euclidean),
+    )
+    .with(
+        "parameters",
+        format!(
+            "nodes={} arrivals={} departures={} repair window={}",
+            N, arrivals, departures, REPAIR_WINDOW
+        ),
+    );
+    if let Err(err) = draw_tour(&image_name("online", svg), tour.nodes(), &metadata) {
+        println!("Error drawing:\n{}", err);
+    }
+}
+
+/// routes for `salesmen` salesmen from the first `depots` nodes of the instance to all others.
+/// with fixed assignment salesman i works from depot i mod depots. errors if they don't fit
+fn multi_depot(
+    nodes: &[Node],
+    metric: &dyn Metric,
+    depots: usize,
+    salesmen: usize,
+    assignment: mtsp::Assignment,
+    capacity: Option<usize>,
+    objective: mtsp::Objective,
+) -> Result<(), String> {
+    if depots == 0 || depots >= nodes.len() {
+        return Err("needs at least one depot and one customer".to_string());
+    }
+    let (depot_nodes, customers) = nodes.split_at(depots);
+    let assigned: Vec<usize> = (0..salesmen).map(|i| i % depots).collect();
+    let start = std::time::Instant::now();
+    let solution = mtsp::solve(
+        depot_nodes,
+        customers,
+        &assigned,
+        assignment,
+        capacity,
+        objective,
+        metric,
+    )?;
+    let time = start.elapsed();
+
+    for (r, route) in solution.routes.iter().enumerate() {
+        let depot = &solution.depots[route.depot];
+        println!(
+            "salesman {} from depot {}: {} customers, length {:?}, {:?}",
+            r,
+            depot.id,
+            route.customers.len(),
+            mtsp::route_length(depot, &route.customers, metric),
+            route.customers.iter().map(|n| n.id).collect::<Vec<_>>()
+        );
+    }
     println!(
-        "sa length: {:?}",
-        get_tour_length(&sa_tour.iter().collect::<Vec<_>>())
+        "total length: {:?}, longest route: {:?}, time: {:?}",
+        solution.length(metric),
+        solution.longest(metric),
+        time
     );
+    if let Err(err) = draw_routes("multi-depot.png", &solution, metric) {
+        println!("Error drawing:\n{}", err);
+    }
+    Ok(())
+}
 
-    // let optimal_tour = tsp_brute_force(&nodes);
+/// solve a random instance with every algorithm and draw the tours
+fn demo(seed: u64, svg: bool) {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let N = 13;
+    let nodes = random_nodes(N, &mut rng);
+    println!("random tour length: {:?}", get_tour_length(&nodes));
+
+    let nn_tour = tsp_nearest_neighbor(&nodes, &Euclidean);
+    println!("nearest neighbor length: {:?}", get_tour_length(&nn_tour));
+
+    let sa_params = annealing::SaParams::preset(N);
+    let sa_tour = tsp_simulated_annealing(&nodes, &Euclidean, &sa_params, &mut rng);
+    println!("sa length: {:?}", get_tour_length(&sa_tour));
+
+    // let optimal_tour = tsp_brute_force(&nodes, &Euclidean);
     // println!(
     //     "optimal length: {:?}",
-    //     get_tour_length(&optimal_tour.iter().collect::<Vec<_>>())
+    //     get_tour_length(&optimal_tour)
     // );
 
-    if let Err(err) = draw_tour("random.png", &nodes) {
+    let metadata = |algorithm: &str, tour: &[Node]| {
+        image_meta::RunMetadata::new(algorithm, seed, tour, get_tour_length(tour))
+    };
+    if let Err(err) = draw_tour(
+        &image_name("random", svg),
+        &nodes,
+        &metadata("random", &nodes),
+    ) {
         println!("Error drawing:\n{}", err);
     }
-    if let Err(err) = draw_tour("nn.png", &nn_tour) {
+    if let Err(err) = draw_tour(
+        &image_name("nn", svg),
+        &nn_tour,
+        &metadata("nearest neighbor", &nn_tour),
+    ) {
         println!("Error drawing:\n{}", err);
     }
-    if let Err(err) = draw_tour("sa.png", &sa_tour) {
+    let sa_metadata =
+        metadata("simulated annealing", &sa_tour).with("parameters", format!("{:?}", sa_params));
+    if let Err(err) = draw_tour(&image_name("sa", svg), &sa_tour, &sa_metadata) {
         println!("Error drawing:\n{}", err);
     }
-    // if let Err(err) = draw_tour("optimal.png", &optimal_tour) {
+    // if let Err(err) = draw_tour("optimal.png", &optimal_tour, &metadata("brute force", &optimal_tour)) {
     //     println!("Error drawing:\n{}", err);
     // }
 }
-
-#[cfg(test)]
-mod tests {
-    use crate::*;
-
-    #[test]
-    fn test_node_distance_simple() {
-        assert_eq!(
-            node_distance(
-                &Node {
-                    id: 0,
-                    x: 0.0,
-                    y: 0.0,
-                },
-                &Node {
-                    id: 0,
-                    x: 0.0,
-                    y: 1.0,
-                }
-            ),
-            1.0
-        );
-    }
-
-    #[test]
-    fn test_tour_length_simple() {
-        let tour = vec![
-            Node {
-                id: 0,
-                x: 0.0,
-                y: 0.0,
-            },
-            Node {
-                id: 0,
-                x: 1.0,
-                y: 0.0,
-            },
-            Node {
-                id: 0,
-                x: 1.0,
-                y: 1.0,
-            },
-            Node {
-                id: 0,
-                x: 0.0,
-                y: 1.0,
-            },
-        ];
-        assert_eq!(get_tour_length(&tour.iter().collect::<Vec<_>>()), 4.0);
-    }
-
-    #[test]
-    fn test_random_swap_back() {
-        let mut tour = random_nodes(7);
-        let check = tour.clone();
-        let (a, b) = random_swap(&mut tour);
-        tour.swap(a, b);
-        assert_eq!(tour, check);
-    }
-}
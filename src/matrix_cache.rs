@@ -0,0 +1,113 @@
+//! distance matrices saved to disk, so expensive metrics are only evaluated once per instance
+//!
+//! a matrix belongs to the instance, not to a run: the key is the instance hash
+//! with the metric and node costs, independent of the node order and the crate version.
+//! matrices are binary, an 8 byte magic, the side as u64 and then side² f64,
+//! all little endian, NaN for distances that were never computed
+
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::PathBuf;
+
+use crate::image_meta::instance_hash;
+use crate::objective::NodeCosts;
+use crate::result_cache::{CacheKey, KeyBuilder};
+use crate::Node;
+
+const MAGIC: &[u8; 8] = b"TSPDIST1";
+
+/// key of the matrix of the instance under the metric, the same for every order of `nodes`.
+/// the metric is its name, e.g. `MetricKind::name` or `TsplibMetric::name`
+pub fn matrix_key(nodes: &[Node], costs: &NodeCosts, metric: &str) -> CacheKey {
+    let mut sorted = nodes.to_vec();
+    sorted.sort_by_key(|node| node.id);
+    KeyBuilder::unversioned()
+        .number(instance_hash(nodes))
+        .text(metric)
+        .costs(&sorted, costs)
+        .finish()
+}
+
+/// directory of saved matrices, one file per key
+pub struct MatrixCache {
+    dir: PathBuf,
+}
+
+impl MatrixCache {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        MatrixCache { dir: dir.into() }
+    }
+
+    pub fn path(&self, key: CacheKey) -> PathBuf {
+        self.dir.join(format!("{:016x}.matrix", key.0))
+    }
+
+    /// the saved matrix if there is one with side `size`, see `CachedMetric::snapshot`
+    pub fn load(&self, key: CacheKey, size: usize) -> Option<Vec<f64>> {
+        let file = std::fs::File::open(self.path(key)).ok()?;
+        let mut reader = BufReader::new(file);
+        let mut word = [0; 8];
+        reader.read_exact(&mut word).ok()?;
+        if &word != MAGIC {
+            return None;
+        }
+        reader.read_exact(&mut word).ok()?;
+        if u64::from_le_bytes(word) != size as u64 {
+            return None;
+        }
+        let mut distances = Vec::with_capacity(size * size);
+        for _ in 0..size * size {
+            reader.read_exact(&mut word).ok()?;
+            distances.push(f64::from_le_bytes(word));
+        }
+        Some(distances)
+    }
+
+    pub fn store(&self, key: CacheKey, size: usize, distances: &[f64]) -> std::io::Result<PathBuf> {
+        std::fs::create_dir_all(&self.dir)?;
+        let path = self.path(key);
+        // a run killed while writing leaves a temporary file, never a truncated matrix
+        let partial = path.with_extension("matrix.partial");
+        let mut writer = BufWriter::new(std::fs::File::create(&partial)?);
+        writer.write_all(MAGIC)?;
+        writer.write_all(&(size as u64).to_le_bytes())?;
+        for distance in distances {
+            writer.write_all(&distance.to_le_bytes())?;
+        }
+        writer.flush()?;
+        drop(writer);
+        std::fs::rename(&partial, &path)?;
+        Ok(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::matrix_cache::*;
+    use crate::metric::{CachedMetric, Euclidean, Metric, MetricKind};
+
+    #[test]
+    fn test_matrix_round_trip() {
+        let nodes = crate::random_nodes(7, &mut rand::thread_rng());
+        let costs = NodeCosts::default();
+        let key = matrix_key(&nodes, &costs, MetricKind::Euclidean.name());
+        let mut reversed = nodes.clone();
+        reversed.reverse();
+        assert_eq!(
+            key,
+            matrix_key(&reversed, &costs, MetricKind::Euclidean.name())
+        );
+        assert_ne!(key, matrix_key(&nodes, &costs, MetricKind::Integer.name()));
+
+        let cached = CachedMetric::new(Euclidean, nodes.len());
+        let length = cached.tour_length(&nodes);
+        let cache = MatrixCache::new(std::env::temp_dir().join("tsp_test_matrix_cache"));
+        cache.store(key, nodes.len(), &cached.snapshot()).unwrap();
+        assert!(cache.load(key, nodes.len() + 1).is_none());
+
+        let reloaded = CachedMetric::new(Euclidean, nodes.len());
+        let snapshot = cache.load(key, nodes.len()).unwrap();
+        assert_eq!(reloaded.preload(&snapshot), Ok(nodes.len()));
+        assert_eq!(reloaded.tour_length(&nodes), length);
+        assert_eq!(reloaded.stats().misses, 0);
+    }
+}
@@ -0,0 +1,120 @@
+//! heap usage of solver runs
+//!
+//! with the `track-memory` feature every allocation goes through a counting
+//! allocator that keeps the live and peak heap size of each thread,
+//! without it `measure` just runs the closure and reports nothing
+
+/// runs `f` and returns its result with the peak heap growth of this thread while it ran, in bytes
+///
+/// memory allocated by threads `f` spawns isn't counted,
+/// memory `f` frees that was allocated before it started can make the peak look smaller
+#[cfg(feature = "track-memory")]
+pub fn measure<R>(f: impl FnOnce() -> R) -> (R, Option<usize>) {
+    let (result, peak) = tracking::measure(f);
+    (result, Some(peak))
+}
+
+#[cfg(not(feature = "track-memory"))]
+pub fn measure<R>(f: impl FnOnce() -> R) -> (R, Option<usize>) {
+    (f(), None)
+}
+
+/// bytes with a binary unit, e.g. "1.5 MiB"
+pub fn format_bytes(bytes: usize) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit + 1 < UNITS.len() {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+#[cfg(feature = "track-memory")]
+mod tracking {
+    use std::alloc::{GlobalAlloc, Layout, System};
+    use std::cell::Cell;
+
+    thread_local! {
+        // const initialized and without destructors, so the allocator can use them
+        static LIVE: Cell<isize> = const { Cell::new(0) };
+        static PEAK: Cell<isize> = const { Cell::new(0) };
+    }
+
+    fn record(delta: isize) {
+        // try_with, thread locals are gone while a thread shuts down
+        let _ = LIVE.try_with(|live| {
+            let now = live.get() + delta;
+            live.set(now);
+            let _ = PEAK.try_with(|peak| peak.set(peak.get().max(now)));
+        });
+    }
+
+    /// the system allocator, counting bytes per thread
+    pub struct TrackingAllocator;
+
+    unsafe impl GlobalAlloc for TrackingAllocator {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            let ptr = System.alloc(layout);
+            if !ptr.is_null() {
+                record(layout.size() as isize);
+            }
+            ptr
+        }
+
+        unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+            let ptr = System.alloc_zeroed(layout);
+            if !ptr.is_null() {
+                record(layout.size() as isize);
+            }
+            ptr
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            System.dealloc(ptr, layout);
+            record(-(layout.size() as isize));
+        }
+
+        unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+            let new = System.realloc(ptr, layout, new_size);
+            if !new.is_null() {
+                record(new_size as isize - layout.size() as isize);
+            }
+            new
+        }
+    }
+
+    #[global_allocator]
+    static ALLOCATOR: TrackingAllocator = TrackingAllocator;
+
+    pub fn measure<R>(f: impl FnOnce() -> R) -> (R, usize) {
+        let start = LIVE.with(Cell::get);
+        PEAK.with(|peak| peak.set(start));
+        let result = f();
+        let peak = PEAK.with(Cell::get);
+        (result, (peak - start).max(0) as usize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::memory::*;
+
+    #[test]
+    fn test_measure() {
+        let (length, peak) = measure(|| std::hint::black_box(vec![0u8; 1 << 20]).len());
+        assert_eq!(length, 1 << 20);
+        if cfg!(feature = "track-memory") {
+            assert!(peak.unwrap() >= 1 << 20);
+        } else {
+            assert_eq!(peak, None);
+        }
+        assert_eq!(format_bytes(512), "512 B");
+        assert_eq!(format_bytes(3 << 19), "1.5 MiB");
+    }
+}
@@ -0,0 +1,499 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::{node_distance, Node};
+
+/// cost of travelling between two nodes
+///
+/// solvers only ever ask the metric, never compute distances themselves,
+/// so swapping in another metric (or wrapping one) changes every algorithm at once
+pub trait Metric: Sync {
+    fn distance(&self, from: &Node, to: &Node) -> f64;
+
+    /// traverses pairs of nodes in order, including last to first, and sums the distances
+    fn tour_length(&self, nodes: &[Node]) -> f64 {
+        closed_length(self, nodes.iter())
+    }
+
+    /// `tour_length` of borrowed nodes, e.g. permutations
+    fn tour_length_refs(&self, nodes: &[&Node]) -> f64 {
+        closed_length(self, nodes.iter().copied())
+    }
+
+    /// distances from `from` to every node of `to`, in `out`, for scans over many nodes.
+    /// metrics with a faster batch than one distance at a time override it
+    fn distances(&self, from: &Node, to: &[Node], out: &mut Vec<f64>) {
+        out.clear();
+        out.extend(to.iter().map(|node| self.distance(from, node)));
+    }
+
+    /// whether every distance is the same both ways. a move that reverses part of the tour
+    /// only changes the edges at its ends then, on asymmetric metrics the ones inside it
+    /// point the other way too
+    fn is_symmetric(&self) -> bool {
+        true
+    }
+}
+
+/// sum of the distances between consecutive nodes, including last to first
+pub fn closed_length<'a, M: Metric + ?Sized>(
+    metric: &M,
+    mut nodes: impl Iterator<Item = &'a Node>,
+) -> f64 {
+    let Some(first) = nodes.next() else {
+        return 0.0;
+    };
+    let mut length: f64 = 0.0;
+    let mut previous = first;
+    for node in nodes {
+        length += metric.distance(previous, node);
+        previous = node;
+    }
+    length + metric.distance(previous, first)
+}
+
+/// lets borrowed metrics, e.g. `&dyn Metric`, be wrapped by other metrics
+impl<M: Metric + ?Sized> Metric for &M {
+    fn distance(&self, from: &Node, to: &Node) -> f64 {
+        (**self).distance(from, to)
+    }
+
+    fn tour_length(&self, nodes: &[Node]) -> f64 {
+        (**self).tour_length(nodes)
+    }
+
+    fn tour_length_refs(&self, nodes: &[&Node]) -> f64 {
+        (**self).tour_length_refs(nodes)
+    }
+
+    fn distances(&self, from: &Node, to: &[Node], out: &mut Vec<f64>) {
+        (**self).distances(from, to, out)
+    }
+
+    fn is_symmetric(&self) -> bool {
+        (**self).is_symmetric()
+    }
+}
+
+/// straight line distance, see `node_distance`
+pub struct Euclidean;
+
+impl Metric for Euclidean {
+    fn distance(&self, from: &Node, to: &Node) -> f64 {
+        node_distance(from, to)
+    }
+
+    #[cfg(feature = "simd")]
+    fn distances(&self, from: &Node, to: &[Node], out: &mut Vec<f64>) {
+        euclidean_lanes(from, to, out)
+    }
+}
+
+/// `node_distance` to 4 nodes at a time: the coordinates go into arrays first, so the compiler
+/// computes the lanes, square roots included, with vector instructions. the same operations
+/// in the same order as `node_distance`, so the same results to the bit
+#[cfg(feature = "simd")]
+fn euclidean_lanes(from: &Node, to: &[Node], out: &mut Vec<f64>) {
+    const LANES: usize = 4;
+    out.clear();
+    out.reserve(to.len());
+    let chunks = to.chunks_exact(LANES);
+    let rest = chunks.remainder();
+    for chunk in chunks {
+        let mut lanes = [0.0; LANES];
+        for (lane, node) in lanes.iter_mut().zip(chunk) {
+            let (dx, dy) = (node.x - from.x, node.y - from.y);
+            *lane = dx * dx + dy * dy;
+        }
+        for lane in &mut lanes {
+            *lane = lane.sqrt();
+        }
+        out.extend_from_slice(&lanes);
+    }
+    out.extend(rest.iter().map(|node| node_distance(from, node)));
+}
+
+/// which distance an instance is meant to be measured with, for files and cache keys
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetricKind {
+    /// `Euclidean`
+    Euclidean,
+    /// `IntegerEuclidean`, rounded like tsplib's EUC_2D
+    Integer,
+}
+
+impl MetricKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            MetricKind::Euclidean => "euclidean",
+            MetricKind::Integer => "integer",
+        }
+    }
+}
+
+impl std::str::FromStr for MetricKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "euclidean" => Ok(MetricKind::Euclidean),
+            "integer" => Ok(MetricKind::Integer),
+            _ => Err(format!(
+                "unknown metric '{}', expected euclidean or integer",
+                s
+            )),
+        }
+    }
+}
+
+/// remembers every distance the wrapped metric computed
+///
+/// backed by a full matrix over node ids that is filled on demand,
+/// nodes with ids outside of the matrix are passed through uncached.
+/// safe to share between threads, racing misses just compute the same value twice
+pub struct CachedMetric<M: Metric> {
+    inner: M,
+    size: usize,
+    /// f64 bits, NaN means not computed yet
+    cache: Vec<AtomicU64>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+/// hit/miss counters of a `CachedMetric`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+}
+
+impl CacheStats {
+    pub fn hit_rate(&self) -> f64 {
+        let total = self.hits + self.misses;
+        if total == 0 {
+            0.0
+        } else {
+            self.hits as f64 / total as f64
+        }
+    }
+}
+
+impl<M: Metric> CachedMetric<M> {
+    /// cache for nodes with ids in 0..size
+    pub fn new(inner: M, size: usize) -> Self {
+        CachedMetric {
+            inner,
+            size,
+            cache: (0..size * size)
+                .map(|_| AtomicU64::new(f64::NAN.to_bits()))
+                .collect(),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// every slot of the matrix by `from.id * size + to.id`, NaN where nothing was computed yet
+    pub fn snapshot(&self) -> Vec<f64> {
+        self.cache
+            .iter()
+            .map(|slot| f64::from_bits(slot.load(Ordering::Relaxed)))
+            .collect()
+    }
+
+    /// fills the matrix from a `snapshot` of a cache of the same size, returns the number of
+    /// distances that are known now. the distances have to come from the same metric
+    pub fn preload(&self, snapshot: &[f64]) -> Result<usize, String> {
+        if snapshot.len() != self.cache.len() {
+            return Err(format!(
+                "matrix has {} entries, the cache {}",
+                snapshot.len(),
+                self.cache.len()
+            ));
+        }
+        for (slot, distance) in self.cache.iter().zip(snapshot) {
+            if !distance.is_nan() {
+                slot.store(distance.to_bits(), Ordering::Relaxed);
+            }
+        }
+        Ok(snapshot.iter().filter(|d| !d.is_nan()).count())
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl<M: Metric> Metric for CachedMetric<M> {
+    fn distance(&self, from: &Node, to: &Node) -> f64 {
+        if from.id >= self.size || to.id >= self.size {
+            self.misses.fetch_add(1, Ordering::Relaxed);
+            return self.inner.distance(from, to);
+        }
+
+        let slot = &self.cache[from.id * self.size + to.id];
+        let cached = f64::from_bits(slot.load(Ordering::Relaxed));
+        if !cached.is_nan() {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return cached;
+        }
+
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        let distance = self.inner.distance(from, to);
+        slot.store(distance.to_bits(), Ordering::Relaxed);
+        distance
+    }
+
+    fn is_symmetric(&self) -> bool {
+        self.inner.is_symmetric()
+    }
+}
+
+/// how a `DistanceMatrix` stores its distances
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct MatrixLayout {
+    /// f32 entries, half the memory, distances are off by up to about 1e-7 of their length
+    pub f32: bool,
+    /// only the lower triangle with the diagonal, half the memory, for symmetric metrics.
+    /// asymmetric ones get the full matrix anyway
+    pub triangular: bool,
+}
+
+impl MatrixLayout {
+    pub fn name(self) -> &'static str {
+        match (self.triangular, self.f32) {
+            (false, false) => "full",
+            (false, true) => "f32",
+            (true, false) => "triangular",
+            (true, true) => "triangular-f32",
+        }
+    }
+}
+
+impl std::str::FromStr for MatrixLayout {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (triangular, f32) = match s {
+            "full" => (false, false),
+            "f32" => (false, true),
+            "triangular" => (true, false),
+            "triangular-f32" => (true, true),
+            _ => {
+                return Err(format!(
+                    "unknown matrix layout '{}', expected full, f32, triangular or triangular-f32",
+                    s
+                ))
+            }
+        };
+        Ok(MatrixLayout { f32, triangular })
+    }
+}
+
+enum Entries {
+    F64(Vec<f64>),
+    F32(Vec<f32>),
+}
+
+/// every distance between the nodes of an instance, computed once up front
+///
+/// indexed by node id like `CachedMetric`, so the subsets of the instance that decomposition
+/// and friends solve use the same matrix. nodes with ids outside of it go to the wrapped metric
+pub struct DistanceMatrix<M: Metric> {
+    inner: M,
+    size: usize,
+    layout: MatrixLayout,
+    entries: Entries,
+}
+
+impl<M: Metric> DistanceMatrix<M> {
+    /// on `threads` threads, the entries are the same for any thread count
+    pub fn new(nodes: &[Node], inner: M, mut layout: MatrixLayout, threads: usize) -> Self {
+        layout.triangular &= inner.is_symmetric();
+        let size = nodes.iter().map(|n| n.id + 1).max().unwrap_or(0);
+        let mut by_id: Vec<Option<&Node>> = vec![None; size];
+        for node in nodes {
+            by_id[node.id] = Some(node);
+        }
+        let len = if layout.triangular {
+            size * (size + 1) / 2
+        } else {
+            size * size
+        };
+        let entries = if layout.f32 {
+            let mut entries = vec![0.0; len];
+            fill(&mut entries, &by_id, layout, &inner, threads, |d| d as f32);
+            Entries::F32(entries)
+        } else {
+            let mut entries = vec![0.0; len];
+            fill(&mut entries, &by_id, layout, &inner, threads, |d| d);
+            Entries::F64(entries)
+        };
+        DistanceMatrix {
+            inner,
+            size,
+            layout,
+            entries,
+        }
+    }
+
+    pub fn layout(&self) -> MatrixLayout {
+        self.layout
+    }
+
+    /// bytes taken by the entries
+    pub fn memory(&self) -> usize {
+        match &self.entries {
+            Entries::F64(entries) => entries.len() * std::mem::size_of::<f64>(),
+            Entries::F32(entries) => entries.len() * std::mem::size_of::<f32>(),
+        }
+    }
+}
+
+/// computes the rows of the matrix, each thread a band of consecutive rows with about as many
+/// entries as the others
+fn fill<T: Send>(
+    entries: &mut [T],
+    by_id: &[Option<&Node>],
+    layout: MatrixLayout,
+    inner: &impl Metric,
+    threads: usize,
+    entry: impl Fn(f64) -> T + Sync,
+) {
+    let size = by_id.len();
+    // without gaps in the ids a row is a slice of nodes, for `Metric::distances`
+    let dense: Option<Vec<Node>> = by_id.iter().map(|node| node.cloned()).collect();
+    let dense = dense.as_deref();
+    let row_length = |from: usize| if layout.triangular { from + 1 } else { size };
+    let band = entries.len().div_ceil(threads.max(1)).max(1);
+    let entry = &entry;
+    std::thread::scope(|scope| {
+        let (mut rest, mut from) = (entries, 0);
+        while from < size {
+            let (start, mut count) = (from, 0);
+            while from < size && count < band {
+                count += row_length(from);
+                from += 1;
+            }
+            let (rows, tail) = std::mem::take(&mut rest).split_at_mut(count);
+            rest = tail;
+            let end = from;
+            scope.spawn(move || {
+                let mut cells = rows.iter_mut();
+                let mut distances = Vec::new();
+                for row in start..end {
+                    match (by_id[row], dense) {
+                        (Some(a), Some(dense)) => {
+                            inner.distances(a, &dense[..row_length(row)], &mut distances);
+                            for (&distance, cell) in distances.iter().zip(&mut cells) {
+                                *cell = entry(distance);
+                            }
+                        }
+                        _ => {
+                            for (to, cell) in (0..row_length(row)).zip(&mut cells) {
+                                if let (Some(a), Some(b)) = (by_id[row], by_id[to]) {
+                                    *cell = entry(inner.distance(a, b));
+                                }
+                            }
+                        }
+                    }
+                }
+            });
+        }
+    });
+}
+
+/// position of the distance from id `from` to id `to` in the entries
+fn index(layout: MatrixLayout, size: usize, from: usize, to: usize) -> usize {
+    if layout.triangular {
+        let (row, column) = if from >= to { (from, to) } else { (to, from) };
+        row * (row + 1) / 2 + column
+    } else {
+        from * size + to
+    }
+}
+
+impl<M: Metric> Metric for DistanceMatrix<M> {
+    fn distance(&self, from: &Node, to: &Node) -> f64 {
+        if from.id >= self.size || to.id >= self.size {
+            return self.inner.distance(from, to);
+        }
+        let i = index(self.layout, self.size, from.id, to.id);
+        match &self.entries {
+            Entries::F64(entries) => entries[i],
+            Entries::F32(entries) => entries[i] as f64,
+        }
+    }
+
+    fn is_symmetric(&self) -> bool {
+        self.inner.is_symmetric()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::metric::*;
+    use crate::random_nodes;
+
+    #[test]
+    fn test_cached_metric_counts_hits() {
+        let nodes = random_nodes(5, &mut rand::thread_rng());
+        let cached = CachedMetric::new(Euclidean, nodes.len());
+
+        let first = cached.tour_length(&nodes);
+        assert_eq!(cached.stats(), CacheStats { hits: 0, misses: 5 });
+        assert_eq!(cached.tour_length(&nodes), first);
+        assert_eq!(cached.stats(), CacheStats { hits: 5, misses: 5 });
+        assert_eq!(first, Euclidean.tour_length(&nodes));
+        let refs = nodes.iter().collect::<Vec<_>>();
+        assert_eq!(first, cached.tour_length_refs(&refs));
+    }
+
+    #[test]
+    fn test_batch_distances_match() {
+        use rand::SeedableRng;
+
+        let nodes = random_nodes(103, &mut rand::rngs::StdRng::seed_from_u64(57));
+        let mut out = vec![1.0; 7];
+        for from in &nodes[..10] {
+            for to in [&nodes[..], &nodes[..3], &nodes[50..54], &[]] {
+                Euclidean.distances(from, to, &mut out);
+                let one_by_one: Vec<f64> = to.iter().map(|n| Euclidean.distance(from, n)).collect();
+                // to the bit, with or without the simd feature
+                assert_eq!(out, one_by_one);
+            }
+        }
+    }
+
+    #[test]
+    fn test_distance_matrix_layouts() {
+        use rand::SeedableRng;
+
+        let nodes = random_nodes(30, &mut rand::rngs::StdRng::seed_from_u64(51));
+        let tour_length = Euclidean.tour_length(&nodes);
+        for layout in ["full", "f32", "triangular", "triangular-f32"] {
+            let layout: MatrixLayout = layout.parse().unwrap();
+            let matrix = DistanceMatrix::new(&nodes[..20], Euclidean, layout, 3);
+            for a in &nodes {
+                for b in &nodes {
+                    let distance = matrix.distance(a, b);
+                    let exact = Euclidean.distance(a, b);
+                    if layout.f32 && a.id < 20 && b.id < 20 {
+                        assert!((distance - exact).abs() <= exact * 1e-6);
+                    } else {
+                        assert_eq!(distance, exact);
+                    }
+                }
+            }
+            if !layout.f32 {
+                assert_eq!(matrix.tour_length(&nodes), tour_length);
+            }
+        }
+        let full = DistanceMatrix::new(&nodes, Euclidean, MatrixLayout::default(), 1);
+        assert_eq!(full.memory(), 30 * 30 * 8);
+        let small = DistanceMatrix::new(&nodes, Euclidean, "triangular-f32".parse().unwrap(), 1);
+        assert_eq!(small.memory(), 30 * 31 / 2 * 4);
+        assert!("half".parse::<MatrixLayout>().is_err());
+    }
+}
@@ -0,0 +1,345 @@
+//! milp formulations of an instance for external solvers (gurobi, cplex, highs, ...)
+//!
+//! the tour is encoded with binaries `x_i_j`, 1 if the tour travels from node i to node j
+
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use crate::metric::Metric;
+use crate::Node;
+
+/// how subtours are kept out of the solution
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Formulation {
+    /// miller-tucker-zemlin: order variables `u_i`, polynomial size
+    Mtz,
+    /// dantzig-fulkerson-johnson: one cut per node subset.
+    /// written out completely for small instances, otherwise
+    /// they have to be added lazily and are only described in comments
+    Dfj,
+}
+
+impl std::str::FromStr for Formulation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "mtz" => Ok(Formulation::Mtz),
+            "dfj" => Ok(Formulation::Dfj),
+            _ => Err(format!("unknown formulation '{}', try mtz or dfj", s)),
+        }
+    }
+}
+
+/// biggest instance the dfj subset constraints are written out for
+pub const DFJ_EXPLICIT_MAX_NODES: usize = 12;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Sense {
+    Equal,
+    LessEqual,
+}
+
+#[derive(Debug, Clone)]
+struct Constraint {
+    name: String,
+    terms: Vec<(String, f64)>,
+    sense: Sense,
+    rhs: f64,
+}
+
+/// continuous variables with bounds, everything not listed is binary
+#[derive(Debug, Clone)]
+struct Bounded {
+    name: String,
+    lower: f64,
+    upper: f64,
+}
+
+/// formulation independent model, written as lp or mps
+#[derive(Debug, Clone)]
+pub struct Model {
+    comments: Vec<String>,
+    objective: Vec<(String, f64)>,
+    constraints: Vec<Constraint>,
+    binaries: Vec<String>,
+    continuous: Vec<Bounded>,
+}
+
+fn x(i: usize, j: usize) -> String {
+    format!("x_{}_{}", i, j)
+}
+
+impl Model {
+    /// model of the tsp over `nodes`, variables are named after node ids
+    pub fn tsp(nodes: &[Node], metric: &dyn Metric, formulation: Formulation) -> Self {
+        let n = nodes.len();
+        let mut model = Model {
+            comments: vec![format!(
+                "tsp over {} nodes, {:?} formulation, x_i_j = 1 if the tour goes from i to j",
+                n, formulation
+            )],
+            objective: Vec::new(),
+            constraints: Vec::new(),
+            binaries: Vec::new(),
+            continuous: Vec::new(),
+        };
+
+        for a in nodes {
+            for b in nodes {
+                if a.id != b.id {
+                    model.objective.push((x(a.id, b.id), metric.distance(a, b)));
+                    model.binaries.push(x(a.id, b.id));
+                }
+            }
+        }
+
+        // leave and enter every node exactly once
+        for a in nodes {
+            let others = || nodes.iter().filter(|b| b.id != a.id);
+            model.constraints.push(Constraint {
+                name: format!("out_{}", a.id),
+                terms: others().map(|b| (x(a.id, b.id), 1.0)).collect(),
+                sense: Sense::Equal,
+                rhs: 1.0,
+            });
+            model.constraints.push(Constraint {
+                name: format!("in_{}", a.id),
+                terms: others().map(|b| (x(b.id, a.id), 1.0)).collect(),
+                sense: Sense::Equal,
+                rhs: 1.0,
+            });
+        }
+
+        match formulation {
+            Formulation::Mtz => model.add_mtz(nodes),
+            Formulation::Dfj => model.add_dfj(nodes),
+        }
+        model
+    }
+
+    /// u_a - u_b + (n - 1) x_a_b <= n - 2 for all a != b except the first node,
+    /// which forces u to increase along the tour, so every cycle has to pass the first node
+    fn add_mtz(&mut self, nodes: &[Node]) {
+        let n = nodes.len() as f64;
+        let rest = nodes.iter().skip(1);
+        for a in rest.clone() {
+            self.continuous.push(Bounded {
+                name: format!("u_{}", a.id),
+                lower: 1.0,
+                upper: n - 1.0,
+            });
+            for b in rest.clone().filter(|b| b.id != a.id) {
+                self.constraints.push(Constraint {
+                    name: format!("mtz_{}_{}", a.id, b.id),
+                    terms: vec![
+                        (format!("u_{}", a.id), 1.0),
+                        (format!("u_{}", b.id), -1.0),
+                        (x(a.id, b.id), n - 1.0),
+                    ],
+                    sense: Sense::LessEqual,
+                    rhs: n - 2.0,
+                });
+            }
+        }
+    }
+
+    /// sum of x inside S <= |S| - 1 for every subset S of size 2..=n/2,
+    /// bigger subsets are implied by the complement through the degree constraints
+    fn add_dfj(&mut self, nodes: &[Node]) {
+        let n = nodes.len();
+        self.comments.push(
+            "dfj subtour elimination: sum_{i,j in S, i != j} x_i_j <= |S| - 1 for every subset S"
+                .to_string(),
+        );
+        if n > DFJ_EXPLICIT_MAX_NODES {
+            self.comments.push(format!(
+                "not written out for {} nodes, add them as lazy constraints:",
+                n
+            ));
+            self.comments.push(
+                "solve, find the cycles of the solution, cut every cycle shorter than the tour, repeat"
+                    .to_string(),
+            );
+            return;
+        }
+
+        for subset in 1u32..(1 << n) {
+            let size = subset.count_ones() as usize;
+            if size < 2 || size > n / 2 {
+                continue;
+            }
+            let members: Vec<&Node> = (0..n)
+                .filter(|i| subset & (1 << i) != 0)
+                .map(|i| &nodes[i])
+                .collect();
+            let mut terms = Vec::new();
+            for a in &members {
+                for b in &members {
+                    if a.id != b.id {
+                        terms.push((x(a.id, b.id), 1.0));
+                    }
+                }
+            }
+            self.constraints.push(Constraint {
+                name: format!("dfj_{}", subset),
+                terms,
+                sense: Sense::LessEqual,
+                rhs: size as f64 - 1.0,
+            });
+        }
+    }
+
+    /// cplex lp format
+    pub fn to_lp(&self) -> String {
+        fn terms(out: &mut String, terms: &[(String, f64)]) {
+            for (i, (name, coefficient)) in terms.iter().enumerate() {
+                // long rows are fine for solvers but not for people, wrap them
+                if i > 0 && i % 8 == 0 {
+                    out.push_str("\n   ");
+                }
+                let sign = if *coefficient < 0.0 { '-' } else { '+' };
+                let _ = write!(out, " {} {} {}", sign, coefficient.abs(), name);
+            }
+        }
+
+        let mut lp = String::new();
+        for comment in &self.comments {
+            let _ = writeln!(lp, "\\ {}", comment);
+        }
+        lp.push_str("Minimize\n obj:");
+        terms(&mut lp, &self.objective);
+        lp.push_str("\nSubject To\n");
+        for constraint in &self.constraints {
+            let _ = write!(lp, " {}:", constraint.name);
+            terms(&mut lp, &constraint.terms);
+            let sense = match constraint.sense {
+                Sense::Equal => "=",
+                Sense::LessEqual => "<=",
+            };
+            let _ = writeln!(lp, " {} {}", sense, constraint.rhs);
+        }
+        lp.push_str("Bounds\n");
+        for bounded in &self.continuous {
+            let _ = writeln!(
+                lp,
+                " {} <= {} <= {}",
+                bounded.lower, bounded.name, bounded.upper
+            );
+        }
+        lp.push_str("Binaries\n");
+        for binary in &self.binaries {
+            let _ = writeln!(lp, " {}", binary);
+        }
+        lp.push_str("End\n");
+        lp
+    }
+
+    /// free mps format
+    pub fn to_mps(&self) -> String {
+        let mut mps = String::new();
+        for comment in &self.comments {
+            let _ = writeln!(mps, "* {}", comment);
+        }
+        mps.push_str("NAME tsp\nROWS\n N obj\n");
+        for constraint in &self.constraints {
+            let sense = match constraint.sense {
+                Sense::Equal => 'E',
+                Sense::LessEqual => 'L',
+            };
+            let _ = writeln!(mps, " {} {}", sense, constraint.name);
+        }
+
+        // mps is column major, collect the coefficients of every variable
+        let mut columns: Vec<(&str, Vec<(&str, f64)>)> = self
+            .binaries
+            .iter()
+            .chain(self.continuous.iter().map(|b| &b.name))
+            .map(|name| (name.as_str(), Vec::new()))
+            .collect();
+        let indices: HashMap<&str, usize> = columns
+            .iter()
+            .enumerate()
+            .map(|(i, (name, _))| (*name, i))
+            .collect();
+        let index = |name: &str| {
+            *indices
+                .get(name)
+                .expect("constraints only use declared variables")
+        };
+        for (name, coefficient) in &self.objective {
+            columns[index(name)].1.push(("obj", *coefficient));
+        }
+        for constraint in &self.constraints {
+            for (name, coefficient) in &constraint.terms {
+                columns[index(name)]
+                    .1
+                    .push((&constraint.name, *coefficient));
+            }
+        }
+
+        mps.push_str("COLUMNS\n");
+        let binary_count = self.binaries.len();
+        for (i, (name, rows)) in columns.iter().enumerate() {
+            if i == 0 && binary_count > 0 {
+                mps.push_str(" MARKER 'MARKER' 'INTORG'\n");
+            }
+            if i == binary_count && binary_count > 0 {
+                mps.push_str(" MARKER 'MARKER' 'INTEND'\n");
+            }
+            for (row, coefficient) in rows {
+                let _ = writeln!(mps, " {} {} {}", name, row, coefficient);
+            }
+        }
+        if binary_count == columns.len() && binary_count > 0 {
+            mps.push_str(" MARKER 'MARKER' 'INTEND'\n");
+        }
+
+        mps.push_str("RHS\n");
+        for constraint in &self.constraints {
+            let _ = writeln!(mps, " rhs {} {}", constraint.name, constraint.rhs);
+        }
+        mps.push_str("BOUNDS\n");
+        for binary in &self.binaries {
+            let _ = writeln!(mps, " BV bnd {}", binary);
+        }
+        for bounded in &self.continuous {
+            let _ = writeln!(mps, " LO bnd {} {}", bounded.name, bounded.lower);
+            let _ = writeln!(mps, " UP bnd {} {}", bounded.name, bounded.upper);
+        }
+        mps.push_str("ENDATA\n");
+        mps
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::metric::Euclidean;
+    use crate::model::*;
+
+    fn triangle() -> Vec<Node> {
+        [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0)]
+            .iter()
+            .enumerate()
+            .map(|(id, &(x, y))| Node { id, x, y })
+            .collect()
+    }
+
+    #[test]
+    fn test_model_sizes() {
+        let mtz = Model::tsp(&triangle(), &Euclidean, Formulation::Mtz);
+        assert_eq!(mtz.binaries.len(), 6);
+        // 2 degree constraints per node, u_1 - u_2 and u_2 - u_1
+        assert_eq!(mtz.constraints.len(), 6 + 2);
+        let lp = mtz.to_lp();
+        assert!(lp.contains(" out_0: + 1 x_0_1 + 1 x_0_2 = 1\n"));
+        assert!(lp.contains(" 1 <= u_2 <= 2\n"));
+
+        let dfj = Model::tsp(&triangle(), &Euclidean, Formulation::Dfj);
+        // subsets of size 2..=1 don't exist for 3 nodes
+        assert_eq!(dfj.constraints.len(), 6);
+        let mps = dfj.to_mps();
+        assert!(mps.contains(" x_1_0 obj 1\n x_1_0 in_0 1\n x_1_0 out_1 1\n"));
+        assert!(mps.ends_with("ENDATA\n"));
+    }
+}
@@ -0,0 +1,586 @@
+//! several salesmen with their own depots, together visiting every customer once
+//!
+//! every salesman leaves from a depot and returns to it, the total length of all routes is minimized
+//! and salesmen without customers stay home. without a capacity that's often a single salesman,
+//! a capacity limits the customers per salesman. depots are either assigned to the salesmen up front
+//! or free, then a route moves to whichever depot serves it best. a single depot they all share is
+//! the classic mTSP. minimizing the longest route instead, the salesmen split the work between them.
+//!
+//! the routes are built by cheapest insertion over all of them, then improved with 2.5-opt
+//! inside the routes, relocations and exchanges of customers between routes and,
+//! with free depots, moving routes to other depots, until none of those helps anymore.
+//! for the longest route, insertions go where they leave the shortest route behind and
+//! customers only move off the longest route, to wherever that shortens it the most
+
+use crate::local_search::{local_search, TwoHOpt};
+use crate::metric::Metric;
+use crate::Node;
+
+/// gains below this are float noise
+const EPSILON: f64 = 1e-12;
+
+/// which depot each salesman works from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Assignment {
+    /// keep the given depots
+    Fixed,
+    /// move salesmen to other depots where that shortens their route
+    Optimized,
+}
+
+impl std::str::FromStr for Assignment {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "fixed" => Ok(Assignment::Fixed),
+            "optimized" => Ok(Assignment::Optimized),
+            _ => Err(format!(
+                "unknown depot assignment '{}', expected fixed or optimized",
+                s
+            )),
+        }
+    }
+}
+
+/// what `solve` minimizes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Objective {
+    /// the length of all routes together
+    Total,
+    /// the length of the longest route, the time until the last salesman is back
+    Longest,
+}
+
+impl std::str::FromStr for Objective {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "total" => Ok(Objective::Total),
+            "longest" => Ok(Objective::Longest),
+            _ => Err(format!(
+                "unknown objective '{}', expected total or longest",
+                s
+            )),
+        }
+    }
+}
+
+/// the customers of one salesman in visiting order, from the depot back to it
+#[derive(Debug, Clone)]
+pub struct Route {
+    /// index into the depots
+    pub depot: usize,
+    pub customers: Vec<Node>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Solution {
+    pub depots: Vec<Node>,
+    pub routes: Vec<Route>,
+    /// most customers a route may have
+    pub capacity: usize,
+}
+
+impl Solution {
+    pub fn length(&self, metric: &dyn Metric) -> f64 {
+        self.routes
+            .iter()
+            .map(|route| route_length(&self.depots[route.depot], &route.customers, metric))
+            .sum()
+    }
+
+    /// length of the longest route, 0 without customers
+    pub fn longest(&self, metric: &dyn Metric) -> f64 {
+        self.route_lengths(metric).into_iter().fold(0.0, f64::max)
+    }
+
+    /// length of every route, in the order of `routes`
+    pub fn route_lengths(&self, metric: &dyn Metric) -> Vec<f64> {
+        self.routes
+            .iter()
+            .map(|route| route_length(&self.depots[route.depot], &route.customers, metric))
+            .collect()
+    }
+}
+
+/// from the depot through the customers and back, 0 without customers
+pub fn route_length(depot: &Node, customers: &[Node], metric: &dyn Metric) -> f64 {
+    let (Some(first), Some(last)) = (customers.first(), customers.last()) else {
+        return 0.0;
+    };
+    let inner: f64 = customers
+        .windows(2)
+        .map(|pair| metric.distance(&pair[0], &pair[1]))
+        .sum();
+    metric.distance(depot, first) + inner + metric.distance(last, depot)
+}
+
+/// the nodes before and after position `i` of the route, the depot at both ends.
+/// `i == customers.len()` is the gap before the return to the depot
+fn gap<'a>(depot: &'a Node, customers: &'a [Node], i: usize) -> (&'a Node, &'a Node) {
+    let before = if i == 0 { depot } else { &customers[i - 1] };
+    (before, customers.get(i).unwrap_or(depot))
+}
+
+/// cheapest position to insert `node` into the route, with its cost
+fn cheapest_insertion(
+    depot: &Node,
+    customers: &[Node],
+    node: &Node,
+    metric: &dyn Metric,
+) -> (f64, usize) {
+    (0..=customers.len())
+        .map(|i| {
+            let (a, b) = gap(depot, customers, i);
+            let cost = if customers.is_empty() {
+                2.0 * metric.distance(depot, node)
+            } else {
+                metric.distance(a, node) + metric.distance(node, b) - metric.distance(a, b)
+            };
+            (cost, i)
+        })
+        .fold(
+            (f64::INFINITY, 0),
+            |best, c| if c.0 < best.0 { c } else { best },
+        )
+}
+
+/// routes for `salesmen`, the depot index of every salesman, visiting all `customers`
+/// with at most `capacity` customers per route and the shortest `objective`.
+/// errors if they don't fit
+pub fn solve(
+    depots: &[Node],
+    customers: &[Node],
+    salesmen: &[usize],
+    assignment: Assignment,
+    capacity: Option<usize>,
+    objective: Objective,
+    metric: &dyn Metric,
+) -> Result<Solution, String> {
+    if salesmen.is_empty() {
+        return Err("needs at least one salesman".to_string());
+    }
+    if let Some(capacity) = capacity.filter(|&k| salesmen.len().saturating_mul(k) < customers.len())
+    {
+        return Err(format!(
+            "{} salesmen can't visit {} customers with a capacity of {}",
+            salesmen.len(),
+            customers.len(),
+            capacity
+        ));
+    }
+    let capacity = capacity.unwrap_or(usize::MAX);
+    if let Some(depot) = salesmen.iter().find(|&&d| d >= depots.len()) {
+        return Err(format!("there is no depot {}", depot));
+    }
+    let mut solution = Solution {
+        capacity,
+        depots: depots.to_vec(),
+        routes: salesmen
+            .iter()
+            .map(|&depot| Route {
+                depot,
+                customers: Vec::new(),
+            })
+            .collect(),
+    };
+    if solution.routes.is_empty() {
+        return Ok(solution);
+    }
+
+    // far customers first, they're the ones that decide where routes go
+    let nearest_depot = |node: &Node| {
+        depots
+            .iter()
+            .map(|depot| metric.distance(depot, node))
+            .fold(f64::INFINITY, f64::min)
+    };
+    let mut order = customers.to_vec();
+    order.sort_by(|a, b| nearest_depot(b).total_cmp(&nearest_depot(a)));
+    let mut lengths = vec![0.0; solution.routes.len()];
+    for customer in order {
+        let (cost, r, i) = match objective {
+            Objective::Total => best_insertion(&solution, &customer, None, metric),
+            Objective::Longest => most_even_insertion(&solution, &lengths, &customer, metric),
+        };
+        solution.routes[r].customers.insert(i, customer);
+        lengths[r] += cost;
+    }
+
+    loop {
+        let mut improved = false;
+        for route in &mut solution.routes {
+            improve_route(&solution.depots[route.depot], &mut route.customers, metric);
+        }
+        match objective {
+            Objective::Total => {
+                improved |= relocate(&mut solution, metric);
+                improved |= exchange(&mut solution, metric);
+                improved |= swap_tails(&mut solution, metric);
+            }
+            Objective::Longest => improved |= unload_longest(&mut solution, metric),
+        }
+        if assignment == Assignment::Optimized {
+            improved |= move_depots(&mut solution, metric);
+        }
+        if !improved {
+            return Ok(solution);
+        }
+    }
+}
+
+/// (cost, route, position) where `node` is cheapest to insert, apart from route `except`
+/// and full routes
+fn best_insertion(
+    solution: &Solution,
+    node: &Node,
+    except: Option<usize>,
+    metric: &dyn Metric,
+) -> (f64, usize, usize) {
+    let mut best = (f64::INFINITY, 0, 0);
+    for (r, route) in solution.routes.iter().enumerate() {
+        if Some(r) == except || route.customers.len() >= solution.capacity {
+            continue;
+        }
+        let (cost, i) = cheapest_insertion(
+            &solution.depots[route.depot],
+            &route.customers,
+            node,
+            metric,
+        );
+        if cost < best.0 {
+            best = (cost, r, i);
+        }
+    }
+    best
+}
+
+/// (cost, route, position) of the insertion of `node` that leaves the route with the
+/// shortest length, given the `lengths` of all routes
+fn most_even_insertion(
+    solution: &Solution,
+    lengths: &[f64],
+    node: &Node,
+    metric: &dyn Metric,
+) -> (f64, usize, usize) {
+    let mut best = (f64::INFINITY, 0, 0);
+    let mut shortest = f64::INFINITY;
+    for (r, route) in solution.routes.iter().enumerate() {
+        if route.customers.len() >= solution.capacity {
+            continue;
+        }
+        let (cost, i) = cheapest_insertion(
+            &solution.depots[route.depot],
+            &route.customers,
+            node,
+            metric,
+        );
+        if lengths[r] + cost < shortest {
+            shortest = lengths[r] + cost;
+            best = (cost, r, i);
+        }
+    }
+    best
+}
+
+/// moves the customer of the longest route to another route where that makes the longer
+/// of the two shortest, if it's shorter than the longest was. returns whether one moved
+fn unload_longest(solution: &mut Solution, metric: &dyn Metric) -> bool {
+    let lengths = solution.route_lengths(metric);
+    let Some(r) = (0..lengths.len()).max_by(|&a, &b| lengths[a].total_cmp(&lengths[b])) else {
+        return false;
+    };
+    let route = &solution.routes[r];
+    let depot = &solution.depots[route.depot];
+    // (longer of the two routes after the move, customer, target route, position)
+    let mut best = (lengths[r] - EPSILON, 0, r, 0);
+    for (i, node) in route.customers.iter().enumerate() {
+        let (a, _) = gap(depot, &route.customers, i);
+        let (_, b) = gap(depot, &route.customers, i + 1);
+        let saving = metric.distance(a, node) + metric.distance(node, b) - metric.distance(a, b);
+        for (s, target) in solution.routes.iter().enumerate() {
+            if s == r || target.customers.len() >= solution.capacity {
+                continue;
+            }
+            let (cost, j) = cheapest_insertion(
+                &solution.depots[target.depot],
+                &target.customers,
+                node,
+                metric,
+            );
+            let longer = (lengths[r] - saving).max(lengths[s] + cost);
+            if longer < best.0 {
+                best = (longer, i, s, j);
+            }
+        }
+    }
+    let (_, i, s, j) = best;
+    if s == r {
+        return false;
+    }
+    let node = solution.routes[r].customers.remove(i);
+    solution.routes[s].customers.insert(j, node);
+    true
+}
+
+/// 2.5-opt on the cycle through the depot and the customers
+fn improve_route(depot: &Node, customers: &mut Vec<Node>, metric: &dyn Metric) {
+    let mut cycle = Vec::with_capacity(customers.len() + 1);
+    cycle.push(depot.clone());
+    cycle.append(customers);
+    local_search(&mut cycle, &TwoHOpt, metric);
+    // the moves may have shifted the depot, the route starts at it again
+    let start = cycle
+        .iter()
+        .position(|node| node.id == depot.id)
+        .expect("moves keep every node");
+    cycle.rotate_left(start);
+    customers.extend(cycle.drain(1..));
+}
+
+/// moves customers to other routes where that's shorter, returns whether any moved
+fn relocate(solution: &mut Solution, metric: &dyn Metric) -> bool {
+    let mut moved = false;
+    for r in 0..solution.routes.len() {
+        let mut i = 0;
+        while i < solution.routes[r].customers.len() {
+            let route = &solution.routes[r];
+            let depot = &solution.depots[route.depot];
+            let node = route.customers[i].clone();
+            let (a, _) = gap(depot, &route.customers, i);
+            let (_, b) = gap(depot, &route.customers, i + 1);
+            let saving =
+                metric.distance(a, &node) + metric.distance(&node, b) - metric.distance(a, b);
+
+            // with no other route that has room the cost stays infinite
+            let (cost, target, j) = best_insertion(solution, &node, Some(r), metric);
+            if cost < saving - EPSILON {
+                let node = solution.routes[r].customers.remove(i);
+                solution.routes[target].customers.insert(j, node);
+                moved = true;
+            } else {
+                i += 1;
+            }
+        }
+    }
+    moved
+}
+
+/// swaps customers of different routes where that's shorter, which works with full routes too.
+/// returns whether any were swapped
+fn exchange(solution: &mut Solution, metric: &dyn Metric) -> bool {
+    // change of the route if its customer at i is replaced by `node`
+    let replace = |solution: &Solution, r: usize, i: usize, node: &Node| {
+        let route = &solution.routes[r];
+        let depot = &solution.depots[route.depot];
+        let (a, current) = gap(depot, &route.customers, i);
+        let (_, b) = gap(depot, &route.customers, i + 1);
+        metric.distance(a, node) + metric.distance(node, b)
+            - metric.distance(a, current)
+            - metric.distance(current, b)
+    };
+    let mut swapped = false;
+    for r in 0..solution.routes.len() {
+        for s in r + 1..solution.routes.len() {
+            for i in 0..solution.routes[r].customers.len() {
+                for j in 0..solution.routes[s].customers.len() {
+                    let u = solution.routes[r].customers[i].clone();
+                    let v = solution.routes[s].customers[j].clone();
+                    if replace(solution, r, i, &v) + replace(solution, s, j, &u) < -EPSILON {
+                        solution.routes[r].customers[i] = v;
+                        solution.routes[s].customers[j] = u;
+                        swapped = true;
+                    }
+                }
+            }
+        }
+    }
+    swapped
+}
+
+/// 2-opt*: two routes trade the ends after some customer, which untangles crossing routes.
+/// returns whether any traded
+fn swap_tails(solution: &mut Solution, metric: &dyn Metric) -> bool {
+    // from `from` through `tail` to the depot, the edges inside the tail don't change
+    let join = |from: &Node, tail: &[Node], depot: &Node| match (tail.first(), tail.last()) {
+        (Some(first), Some(last)) => metric.distance(from, first) + metric.distance(last, depot),
+        _ => metric.distance(from, depot),
+    };
+    let mut traded = false;
+    for r in 0..solution.routes.len() {
+        for s in r + 1..solution.routes.len() {
+            let (dr, ds) = (
+                &solution.depots[solution.routes[r].depot],
+                &solution.depots[solution.routes[s].depot],
+            );
+            let (mut i, mut j) = (0, 0);
+            while i <= solution.routes[r].customers.len() {
+                let (a, b) = (&solution.routes[r].customers, &solution.routes[s].customers);
+                if j > b.len() {
+                    i += 1;
+                    j = 0;
+                    continue;
+                }
+                let fits =
+                    i + b.len() - j <= solution.capacity && j + a.len() - i <= solution.capacity;
+                let (before_r, _) = gap(dr, a, i);
+                let (before_s, _) = gap(ds, b, j);
+                let old = join(before_r, &a[i..], dr) + join(before_s, &b[j..], ds);
+                let new = join(before_r, &b[j..], dr) + join(before_s, &a[i..], ds);
+                if fits && new < old - EPSILON {
+                    let tail_r = solution.routes[r].customers.split_off(i);
+                    let tail_s = solution.routes[s].customers.split_off(j);
+                    solution.routes[r].customers.extend(tail_s);
+                    solution.routes[s].customers.extend(tail_r);
+                    traded = true;
+                }
+                j += 1;
+            }
+        }
+    }
+    traded
+}
+
+/// moves routes to the depot they're shortest from, returns whether any moved
+fn move_depots(solution: &mut Solution, metric: &dyn Metric) -> bool {
+    let mut moved = false;
+    for route in &mut solution.routes {
+        if route.customers.is_empty() {
+            continue;
+        }
+        let current = route_length(&solution.depots[route.depot], &route.customers, metric);
+        // (length, depot, position in the cycle the depot goes before)
+        let mut best = (current - EPSILON, route.depot, 0);
+        let n = route.customers.len();
+        for (d, depot) in solution.depots.iter().enumerate() {
+            if d == route.depot {
+                continue;
+            }
+            // the route is a cycle, the new depot can break it open at any edge
+            let cycle = current
+                - metric.distance(&solution.depots[route.depot], &route.customers[0])
+                - metric.distance(&route.customers[n - 1], &solution.depots[route.depot])
+                + metric.distance(&route.customers[n - 1], &route.customers[0]);
+            for i in 0..n {
+                let (a, b) = (&route.customers[(i + n - 1) % n], &route.customers[i]);
+                let length = if n == 1 {
+                    2.0 * metric.distance(depot, b)
+                } else {
+                    cycle - metric.distance(a, b)
+                        + metric.distance(a, depot)
+                        + metric.distance(depot, b)
+                };
+                if length < best.0 {
+                    best = (length, d, i);
+                }
+            }
+        }
+        if best.1 != route.depot {
+            route.depot = best.1;
+            route.customers.rotate_left(best.2);
+            moved = true;
+        }
+    }
+    moved
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::metric::Euclidean;
+    use crate::mtsp::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_multi_depot_routes() {
+        let mut rng = StdRng::seed_from_u64(2);
+        let nodes = crate::random_nodes(83, &mut rng);
+        let (depots, customers) = nodes.split_at(3);
+        // every salesman starts at the first depot
+        let salesmen = [0, 0, 0, 0];
+
+        let run = |assignment, capacity| {
+            solve(
+                depots,
+                customers,
+                &salesmen,
+                assignment,
+                capacity,
+                Objective::Total,
+                &Euclidean,
+            )
+        };
+
+        let fixed = run(Assignment::Fixed, None).unwrap();
+        assert!(fixed.routes.iter().all(|route| route.depot == 0));
+        let mut ids: Vec<usize> = fixed
+            .routes
+            .iter()
+            .flat_map(|route| route.customers.iter().map(|n| n.id))
+            .collect();
+        ids.sort_unstable();
+        assert_eq!(ids, (3..83).collect::<Vec<_>>());
+
+        let optimized = run(Assignment::Optimized, Some(25)).unwrap();
+        assert!(optimized
+            .routes
+            .iter()
+            .all(|route| route.customers.len() <= 25));
+        assert!(optimized.routes.iter().any(|route| route.depot != 0));
+        let capped = run(Assignment::Fixed, Some(25)).unwrap();
+        assert!(optimized.length(&Euclidean) < capped.length(&Euclidean));
+        assert_eq!(
+            run(Assignment::Fixed, Some(19)).unwrap_err(),
+            "4 salesmen can't visit 80 customers with a capacity of 19"
+        );
+        assert_eq!(
+            solve(
+                depots,
+                customers,
+                &[],
+                Assignment::Fixed,
+                None,
+                Objective::Total,
+                &Euclidean
+            )
+            .unwrap_err(),
+            "needs at least one salesman"
+        );
+        let customers: usize = optimized.routes.iter().map(|r| r.customers.len()).sum();
+        assert_eq!(customers, 80);
+    }
+
+    #[test]
+    fn test_shortest_longest_route_from_a_shared_depot() {
+        let mut rng = StdRng::seed_from_u64(312);
+        let nodes = crate::random_nodes(61, &mut rng);
+        let (depot, customers) = nodes.split_at(1);
+        let run = |objective| {
+            solve(
+                depot,
+                customers,
+                &[0; 4],
+                Assignment::Fixed,
+                None,
+                objective,
+                &Euclidean,
+            )
+            .unwrap()
+        };
+
+        let total = run(Objective::Total);
+        let longest = run(Objective::Longest);
+        assert!(longest
+            .routes
+            .iter()
+            .all(|route| !route.customers.is_empty()));
+        assert!(longest.longest(&Euclidean) < 0.5 * total.longest(&Euclidean));
+        assert!(longest.length(&Euclidean) > total.length(&Euclidean));
+        let visited: usize = longest.routes.iter().map(|r| r.customers.len()).sum();
+        assert_eq!(visited, 60);
+        assert_eq!("longest".parse(), Ok(Objective::Longest));
+        assert!("shortest".parse::<Objective>().is_err());
+    }
+}
@@ -0,0 +1,183 @@
+//! restarts for any solver: run it from several starts and keep the best tour
+//!
+//! start k gets its own rng stream, see `rng`, and the given tour rotated by k/K of its length,
+//! so constructors like nearest neighbor begin at different nodes and improvers still get
+//! the tour they were given. `shuffled` starts every run from a random tour instead
+
+use std::time::{Duration, Instant};
+
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+use crate::cancel;
+use crate::metric::Metric;
+use crate::rng;
+use crate::solvers::Solver;
+use crate::Node;
+
+/// ```ignore
+/// let solver = MultiStart::new(SimulatedAnnealing::default(), 8).threads(4);
+/// ```
+pub struct MultiStart<S: Solver> {
+    inner: S,
+    name: String,
+    starts: usize,
+    threads: usize,
+    shuffled: bool,
+}
+
+/// how one start went
+#[derive(Debug, Clone)]
+pub struct StartReport {
+    /// seed of the rng the start ran with
+    pub seed: u64,
+    pub length: f64,
+    pub time: Duration,
+}
+
+/// best tour of all starts plus how every start did
+#[derive(Debug, Clone)]
+pub struct MultiStartRun {
+    pub tour: Vec<Node>,
+    /// index of the start that found `tour`
+    pub best: usize,
+    /// in start order, starts skipped after ctrl-c are missing
+    pub starts: Vec<StartReport>,
+}
+
+impl MultiStartRun {
+    pub fn mean_length(&self) -> f64 {
+        self.starts.iter().map(|s| s.length).sum::<f64>() / self.starts.len() as f64
+    }
+
+    /// sample standard deviation of the lengths, 0 for a single start
+    pub fn std_dev(&self) -> f64 {
+        if self.starts.len() < 2 {
+            return 0.0;
+        }
+        let mean = self.mean_length();
+        let squares: f64 = self.starts.iter().map(|s| (s.length - mean).powi(2)).sum();
+        (squares / (self.starts.len() - 1) as f64).sqrt()
+    }
+}
+
+impl<S: Solver> MultiStart<S> {
+    pub fn new(inner: S, starts: usize) -> Self {
+        let name = format!("{}x {}", starts, inner.name());
+        MultiStart {
+            inner,
+            name,
+            starts: starts.max(1),
+            threads: 1,
+            shuffled: false,
+        }
+    }
+
+    /// spread the starts over threads, the result doesn't depend on how many
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    /// start every run from a random tour instead of the given one
+    pub fn shuffled(mut self) -> Self {
+        self.shuffled = true;
+        self
+    }
+
+    pub fn run(&self, nodes: &[Node], metric: &dyn Metric, rng: &mut StdRng) -> MultiStartRun {
+        let master: u64 = rng.gen();
+        let results = rng::parallel_map(self.starts, self.threads, |k| {
+            // the first start always runs, so there's a tour to return
+            if k > 0 && cancel::is_cancelled() {
+                return None;
+            }
+            let seed = rng::stream_seed(master, k as u64);
+            let mut start_rng = rng::stream_rng(master, k as u64);
+            let mut tour = nodes.to_vec();
+            if self.shuffled {
+                tour.shuffle(&mut start_rng);
+            } else if !tour.is_empty() {
+                tour.rotate_left(k * nodes.len() / self.starts);
+            }
+
+            let start = Instant::now();
+            let tour = self.inner.solve(&tour, metric, &mut start_rng);
+            let report = StartReport {
+                seed,
+                length: metric.tour_length(&tour),
+                time: start.elapsed(),
+            };
+            Some((tour, report))
+        });
+
+        let mut best: Option<(usize, Vec<Node>)> = None;
+        let mut starts: Vec<StartReport> = Vec::new();
+        for (tour, report) in results.into_iter().flatten() {
+            if best
+                .as_ref()
+                .is_none_or(|(b, _)| report.length < starts[*b].length)
+            {
+                best = Some((starts.len(), tour));
+            }
+            starts.push(report);
+        }
+        let (best, tour) = best.expect("the first start always runs");
+        MultiStartRun { tour, best, starts }
+    }
+}
+
+impl<S: Solver> Solver for MultiStart<S> {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn solve(&self, nodes: &[Node], metric: &dyn Metric, rng: &mut StdRng) -> Vec<Node> {
+        self.run(nodes, metric, rng).tour
+    }
+
+    fn max_nodes(&self) -> usize {
+        self.inner.max_nodes()
+    }
+
+    fn check(&self, nodes: &[Node]) -> Result<(), String> {
+        self.inner.check(nodes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::metric::Euclidean;
+    use crate::multistart::*;
+    use crate::random_nodes;
+    use crate::solvers::{NearestNeighbor, SimulatedAnnealing};
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_best_of_starts_independent_of_threads() {
+        let nodes = random_nodes(30, &mut StdRng::seed_from_u64(1));
+        let run = |threads| {
+            MultiStart::new(SimulatedAnnealing::default(), 6)
+                .threads(threads)
+                .shuffled()
+                .run(&nodes, &Euclidean, &mut StdRng::seed_from_u64(2))
+        };
+        let (single, parallel) = (run(1), run(3));
+        assert_eq!(single.starts.len(), 6);
+        assert_eq!(single.tour, parallel.tour);
+        assert_eq!(single.best, parallel.best);
+        let best = single.starts[single.best].length;
+        assert!(single.starts.iter().all(|s| s.length >= best));
+        assert!(single.std_dev() > 0.0);
+
+        // rotated starts give nearest neighbor different first nodes
+        let nn = MultiStart::new(NearestNeighbor, 5).run(
+            &nodes,
+            &Euclidean,
+            &mut StdRng::seed_from_u64(2),
+        );
+        assert!(nn.starts.iter().any(|s| s.length != nn.starts[0].length));
+        assert_eq!(nn.tour.len(), nodes.len());
+    }
+}
@@ -0,0 +1,145 @@
+//! directions links for driving a solved tour of stops, see `geo`
+//!
+//! map services only take a few stops per link, so the tour is split into consecutive legs,
+//! each link starting at the stop the previous one ended at
+
+use std::fmt::Write;
+
+use crate::geo::GeoStop;
+
+/// browsers and map apps cut urls beyond this
+const MAX_URL_LENGTH: usize = 2048;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Provider {
+    /// origin, destination and up to 9 waypoints per link
+    Google,
+    /// openstreetmap.org routes from one point to another, a link per leg
+    Osm,
+}
+
+impl Provider {
+    /// most stops a single link can cover, origin and destination included
+    fn max_stops(self) -> usize {
+        match self {
+            Provider::Google => 11,
+            Provider::Osm => 2,
+        }
+    }
+
+    fn url(self, stops: &[&GeoStop]) -> String {
+        let position = |stop: &GeoStop| format!("{:.6},{:.6}", stop.lat, stop.lon);
+        let (first, last) = (stops[0], stops[stops.len() - 1]);
+        match self {
+            Provider::Google => {
+                let mut url = format!(
+                    "https://www.google.com/maps/dir/?api=1&origin={}&destination={}&travelmode=driving",
+                    position(first),
+                    position(last)
+                );
+                let waypoints: Vec<String> = stops[1..stops.len() - 1]
+                    .iter()
+                    .map(|stop| position(stop))
+                    .collect();
+                if !waypoints.is_empty() {
+                    // `|` separates the waypoints, percent encoded
+                    let _ = write!(url, "&waypoints={}", waypoints.join("%7C"));
+                }
+                url
+            }
+            Provider::Osm => format!(
+                "https://www.openstreetmap.org/directions?engine=fossgis_osrm_car&route={}%3B{}",
+                position(first),
+                position(last)
+            ),
+        }
+    }
+}
+
+impl std::str::FromStr for Provider {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "google" => Ok(Provider::Google),
+            "osm" | "openstreetmap" => Ok(Provider::Osm),
+            _ => Err(format!(
+                "unknown map provider '{}', expected google or osm",
+                s
+            )),
+        }
+    }
+}
+
+/// one directions link of a tour
+#[derive(Debug, Clone, PartialEq)]
+pub struct Leg {
+    /// positions in the tour of the first and the last stop of the link
+    pub from: usize,
+    pub to: usize,
+    pub url: String,
+}
+
+/// links covering the tour through `stops` in order and back to the first stop,
+/// as few as the provider and the url length allow
+pub fn legs(stops: &[&GeoStop], provider: Provider) -> Vec<Leg> {
+    if stops.len() < 2 {
+        return Vec::new();
+    }
+    // driving the tour ends where it started
+    let mut route = stops.to_vec();
+    route.push(stops[0]);
+
+    let mut legs = Vec::new();
+    let mut start = 0;
+    while start + 1 < route.len() {
+        let mut end = (start + provider.max_stops()).min(route.len());
+        while end > start + 2 && provider.url(&route[start..end]).len() > MAX_URL_LENGTH {
+            end -= 1;
+        }
+        legs.push(Leg {
+            from: start,
+            to: end - 1,
+            url: provider.url(&route[start..end]),
+        });
+        start = end - 1;
+    }
+    legs
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::navigation::*;
+
+    #[test]
+    fn test_legs_cover_the_tour() {
+        let stops: Vec<GeoStop> = (0..25)
+            .map(|id| GeoStop {
+                id,
+                lat: 52.0 + id as f64 / 100.0,
+                lon: 13.0,
+                address: String::new(),
+            })
+            .collect();
+        let refs: Vec<&GeoStop> = stops.iter().collect();
+
+        // 26 stops with the return, 11 per link overlapping by one
+        let google = legs(&refs, Provider::Google);
+        assert_eq!(
+            google
+                .iter()
+                .map(|leg| (leg.from, leg.to))
+                .collect::<Vec<_>>(),
+            vec![(0, 10), (10, 20), (20, 25)]
+        );
+        assert!(google[2].url.contains("origin=52.200000,13.000000"));
+        assert!(google[2].url.contains("destination=52.000000,13.000000"));
+        assert_eq!(google[0].url.matches("%7C").count(), 8);
+
+        let osm = legs(&refs, Provider::Osm);
+        assert_eq!(osm.len(), 25);
+        assert!(osm[0]
+            .url
+            .ends_with("route=52.000000,13.000000%3B52.010000,13.000000"));
+    }
+}
@@ -0,0 +1,95 @@
+//! per-node service times and weights on top of a distance metric
+//!
+//! the tour cost becomes
+//! `sum over edges (a, b) of w_a * w_b * d(a, b)` plus the service time of every node,
+//! so solvers optimize it like any other metric
+
+use crate::metric::Metric;
+use crate::Node;
+
+/// service time and weight of every node, by id
+///
+/// nodes without an entry take no service time and have weight 1
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NodeCosts {
+    service: Vec<f64>,
+    weight: Vec<f64>,
+}
+
+impl NodeCosts {
+    pub fn set(&mut self, id: usize, service: f64, weight: f64) {
+        if id >= self.service.len() {
+            self.service.resize(id + 1, 0.0);
+            self.weight.resize(id + 1, 1.0);
+        }
+        self.service[id] = service;
+        self.weight[id] = weight;
+    }
+
+    pub fn service(&self, id: usize) -> f64 {
+        self.service.get(id).copied().unwrap_or(0.0)
+    }
+
+    pub fn weight(&self, id: usize) -> f64 {
+        self.weight.get(id).copied().unwrap_or(1.0)
+    }
+
+    /// true if the costs don't change anything
+    pub fn is_neutral(&self) -> bool {
+        self.service.iter().all(|&s| s == 0.0) && self.weight.iter().all(|&w| w == 1.0)
+    }
+}
+
+/// `inner` with every edge scaled by the weights of its ends
+/// and half the service time of each end added to it
+///
+/// a closed tour enters and leaves every node once, so it pays every service time exactly once
+pub struct Weighted<M: Metric> {
+    inner: M,
+    costs: NodeCosts,
+}
+
+impl<M: Metric> Weighted<M> {
+    pub fn new(inner: M, costs: NodeCosts) -> Self {
+        Weighted { inner, costs }
+    }
+}
+
+impl<M: Metric> Metric for Weighted<M> {
+    fn distance(&self, from: &Node, to: &Node) -> f64 {
+        let weight = self.costs.weight(from.id) * self.costs.weight(to.id);
+        let service = (self.costs.service(from.id) + self.costs.service(to.id)) / 2.0;
+        weight * self.inner.distance(from, to) + service
+    }
+
+    fn is_symmetric(&self) -> bool {
+        self.inner.is_symmetric()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::metric::Euclidean;
+    use crate::objective::*;
+
+    #[test]
+    fn test_service_times_and_weights() {
+        let nodes: Vec<Node> = [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)]
+            .iter()
+            .enumerate()
+            .map(|(id, &(x, y))| Node { id, x, y })
+            .collect();
+
+        let mut costs = NodeCosts::default();
+        assert!(costs.is_neutral());
+        costs.set(1, 0.5, 1.0);
+        costs.set(3, 0.25, 1.0);
+        let serviced = Weighted::new(Euclidean, costs.clone());
+        assert_eq!(serviced.tour_length(&nodes), 4.0 + 0.75);
+
+        // edges 1-2 and 2-3 touch node 2
+        costs.set(2, 0.0, 2.0);
+        let weighted = Weighted::new(Euclidean, costs);
+        assert_eq!(weighted.tour_length(&nodes), 2.0 + 2.0 * 2.0 + 0.75);
+    }
+}
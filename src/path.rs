@@ -0,0 +1,279 @@
+//! one-way routes: hamiltonian paths instead of closed tours
+//!
+//! a path is solved as the tour through one more node, the dummy of an `OpenPath` metric,
+//! which costs nothing to get to from the end of the path and to leave for its start.
+//! with a fixed start or end every other way out of or into the dummy costs a penalty that's
+//! longer than any path, so every solver puts the dummy between the right ends by itself and
+//! the tour's length is the path's. `cut` opens the solved tour at the dummy again.
+//! free ends keep a symmetric metric symmetric, fixed ones make it asymmetric: exact solvers
+//! still find the shortest path then, solvers that only look at coordinates may misplace an
+//! end, which `cut` repairs
+
+use rand::rngs::StdRng;
+
+use crate::metric::Metric;
+use crate::solvers::Solver;
+use crate::Node;
+
+/// which ends of the path are given, by node id, None for whichever is shortest
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PathEnds {
+    pub start: Option<usize>,
+    pub end: Option<usize>,
+}
+
+impl PathEnds {
+    /// for cache keys and printouts, e.g. `path 3 to any`
+    pub fn name(&self) -> String {
+        let end = |id: Option<usize>| id.map_or("any".to_string(), |id| id.to_string());
+        format!("path {} to {}", end(self.start), end(self.end))
+    }
+
+    /// that the ends are nodes of the instance and not the same one
+    pub fn check(&self, nodes: &[Node]) -> Result<(), String> {
+        for id in [self.start, self.end].into_iter().flatten() {
+            if !nodes.iter().any(|node| node.id == id) {
+                return Err(format!("node {} isn't in the instance", id));
+            }
+        }
+        if self.start.is_some() && self.start == self.end {
+            return Err("a path that starts where it ends is a tour".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// `inner` with a dummy node that joins the ends of the path, see the module docs
+pub struct OpenPath<M: Metric> {
+    inner: M,
+    ends: PathEnds,
+    dummy: usize,
+    penalty: f64,
+}
+
+impl<M: Metric> OpenPath<M> {
+    /// the dummy gets the id after the last one of `nodes`. the penalty is twice the distance
+    /// to the node farthest from the first one for every node, which is longer than any path
+    /// on metrics that keep to the triangle inequality
+    pub fn new(nodes: &[Node], inner: M, ends: PathEnds) -> Self {
+        let dummy = nodes.iter().map(|node| node.id + 1).max().unwrap_or(0);
+        let radius = nodes.first().map_or(0.0, |first| {
+            nodes
+                .iter()
+                .map(|node| inner.distance(first, node).max(inner.distance(node, first)))
+                .fold(0.0, f64::max)
+        });
+        OpenPath {
+            inner,
+            ends,
+            dummy,
+            penalty: 2.0 * radius * nodes.len() as f64 + 1.0,
+        }
+    }
+
+    /// `nodes` and the dummy at the fixed start, or the fixed end, or the centroid, so that
+    /// geometric neighbor lists find it close to where the path opens
+    pub fn nodes_with_dummy(&self, nodes: &[Node]) -> Vec<Node> {
+        let at = |id: Option<usize>| id.and_then(|id| nodes.iter().find(|node| node.id == id));
+        let (x, y) = match at(self.ends.start).or(at(self.ends.end)) {
+            Some(node) => (node.x, node.y),
+            None => {
+                let n = nodes.len().max(1) as f64;
+                (
+                    nodes.iter().map(|node| node.x).sum::<f64>() / n,
+                    nodes.iter().map(|node| node.y).sum::<f64>() / n,
+                )
+            }
+        };
+        let mut with_dummy = nodes.to_vec();
+        with_dummy.push(Node {
+            id: self.dummy,
+            x,
+            y,
+        });
+        with_dummy
+    }
+
+    /// what the tour length counts for every way into or out of the dummy that misses a fixed end
+    pub fn penalty(&self) -> f64 {
+        self.penalty
+    }
+
+    /// the path of a tour through the nodes and the dummy, see `cut`
+    pub fn cut(&self, tour: &[Node]) -> Vec<Node> {
+        cut(tour, self.dummy, self.ends, &self.inner)
+    }
+}
+
+impl<M: Metric> Metric for OpenPath<M> {
+    fn distance(&self, from: &Node, to: &Node) -> f64 {
+        let free = |end: Option<usize>, node: &Node| end.is_none_or(|id| id == node.id);
+        match (from.id == self.dummy, to.id == self.dummy) {
+            (true, true) => 0.0,
+            (true, false) if free(self.ends.start, to) => 0.0,
+            (false, true) if free(self.ends.end, from) => 0.0,
+            (false, false) => self.inner.distance(from, to),
+            _ => self.penalty,
+        }
+    }
+
+    fn is_symmetric(&self) -> bool {
+        self.inner.is_symmetric() && self.ends == PathEnds::default()
+    }
+}
+
+/// the path after `dummy` in the tour, up to it. stages that hardly look at lengths, like
+/// a plain construction, can leave a fixed end elsewhere: then the path is turned around
+/// if that puts it in place on a symmetric metric, or the end is moved there
+pub fn cut(tour: &[Node], dummy: usize, ends: PathEnds, metric: &dyn Metric) -> Vec<Node> {
+    let at = tour
+        .iter()
+        .position(|node| node.id == dummy)
+        .map_or(0, |i| i + 1);
+    let mut path: Vec<Node> = tour[at..]
+        .iter()
+        .chain(&tour[..at])
+        .filter(|node| node.id != dummy)
+        .cloned()
+        .collect();
+    let first_is = |path: &[Node], id: Option<usize>| id.is_none_or(|id| path[0].id == id);
+    let last_is =
+        |path: &[Node], id: Option<usize>| id.is_none_or(|id| path[path.len() - 1].id == id);
+    if path.is_empty() || (first_is(&path, ends.start) && last_is(&path, ends.end)) {
+        return path;
+    }
+    if metric.is_symmetric() {
+        path.reverse();
+        if first_is(&path, ends.start) && last_is(&path, ends.end) {
+            return path;
+        }
+        path.reverse();
+    }
+    if let Some(i) = ends
+        .start
+        .and_then(|id| path.iter().position(|node| node.id == id))
+    {
+        path[..=i].rotate_right(1);
+    }
+    if let Some(i) = ends
+        .end
+        .and_then(|id| path.iter().position(|node| node.id == id))
+    {
+        path[i..].rotate_left(1);
+    }
+    path
+}
+
+/// length of `nodes` as a path, without the edge back to the first
+pub fn path_length(metric: &dyn Metric, nodes: &[Node]) -> f64 {
+    nodes
+        .windows(2)
+        .map(|pair| metric.distance(&pair[0], &pair[1]))
+        .sum()
+}
+
+/// the shortest path `solver` finds through `nodes` between the `ends`
+pub fn solve(
+    solver: &dyn Solver,
+    nodes: &[Node],
+    metric: &dyn Metric,
+    ends: PathEnds,
+    rng: &mut StdRng,
+) -> Vec<Node> {
+    let open = OpenPath::new(nodes, metric, ends);
+    let tour = solver.solve(&open.nodes_with_dummy(nodes), &open, rng);
+    open.cut(&tour)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::local_search::{OrOpt, TwoOpt};
+    use crate::metric::Euclidean;
+    use crate::path::*;
+    use crate::pipeline::Pipeline;
+    use crate::solvers::{LocalSearch, NearestNeighbor};
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_paths_between_fixed_ends() {
+        // a line of nodes, the tour has to come back but the path doesn't
+        let nodes: Vec<Node> = [0.0, 3.0, 1.0, 2.0, 4.0]
+            .iter()
+            .enumerate()
+            .map(|(id, &x)| Node { id, x, y: 0.0 })
+            .collect();
+        let solver = Pipeline::new()
+            .then(NearestNeighbor)
+            .then(LocalSearch::new(TwoOpt))
+            .then(LocalSearch::new(OrOpt::default()));
+        let mut rng = StdRng::seed_from_u64(310);
+        let ids = |path: &[Node]| path.iter().map(|node| node.id).collect::<Vec<_>>();
+
+        let free = solve(&solver, &nodes, &Euclidean, PathEnds::default(), &mut rng);
+        assert_eq!(path_length(&Euclidean, &free), 4.0);
+        assert!(ids(&free) == [0, 2, 3, 1, 4] || ids(&free) == [4, 1, 3, 2, 0]);
+
+        let ends = PathEnds {
+            start: Some(2),
+            end: None,
+        };
+        let from_two = solve(&solver, &nodes, &Euclidean, ends, &mut rng);
+        assert_eq!(ids(&from_two)[0], 2);
+        assert_eq!(path_length(&Euclidean, &from_two), 5.0);
+
+        let ends = PathEnds {
+            start: Some(0),
+            end: Some(1),
+        };
+        let between = solve(&solver, &nodes, &Euclidean, ends, &mut rng);
+        assert_eq!(ids(&between), [0, 2, 3, 4, 1]);
+        assert_eq!(path_length(&Euclidean, &between), 5.0);
+        let open = OpenPath::new(&nodes, &Euclidean, ends);
+        let mut tour = between.clone();
+        tour.push(open.nodes_with_dummy(&nodes)[5].clone());
+        assert_eq!(open.tour_length(&tour), 5.0);
+        assert!(!open.is_symmetric());
+
+        // a construction alone may leave the ends anywhere, the cut puts them in place
+        let mut shuffled = between.clone();
+        shuffled.swap(0, 3);
+        assert_eq!(ids(&cut(&shuffled, 9, ends, &Euclidean)), [0, 4, 2, 3, 1]);
+
+        assert!(ends.check(&nodes).is_ok());
+        let same = PathEnds {
+            start: Some(1),
+            end: Some(1),
+        };
+        assert!(same.check(&nodes).is_err());
+        assert_eq!(
+            PathEnds {
+                start: Some(7),
+                end: None
+            }
+            .check(&nodes)
+            .unwrap_err(),
+            "node 7 isn't in the instance"
+        );
+        assert_eq!(ends.name(), "path 0 to 1");
+    }
+
+    #[test]
+    fn test_exact_solvers_agree_on_fixed_ends() {
+        let mut rng = StdRng::seed_from_u64(5);
+        let nodes = crate::random_nodes(9, &mut rng);
+        let ends = PathEnds {
+            start: Some(0),
+            end: Some(3),
+        };
+        let length = |name: &str, rng: &mut StdRng| {
+            let solver = crate::solvers::solver_by_name(name).unwrap();
+            let path = solve(solver.as_ref(), &nodes, &Euclidean, ends, rng);
+            assert_eq!((path[0].id, path[8].id), (0, 3));
+            path_length(&Euclidean, &path)
+        };
+        let optimum = length("hk", &mut rng);
+        for name in ["bf", "bb"] {
+            assert!((length(name, &mut rng) - optimum).abs() < 1e-9, "{}", name);
+        }
+    }
+}
@@ -0,0 +1,54 @@
+//! random kicks that knock a tour out of its local optimum, for metaheuristics to build on
+
+use rand::rngs::StdRng;
+use rand::seq::index;
+
+use crate::Node;
+
+/// the double-bridge kick: cuts the tour into A B C D at three random positions and reconnects
+/// it as A C B D. 2-opt can't undo it without first making the tour longer,
+/// so a local search after it ends up in another optimum
+pub fn double_bridge(tour: &mut [Node], rng: &mut StdRng) {
+    let n = tour.len();
+    if n < 4 {
+        return;
+    }
+    // cuts before positions 1..n, so every part has at least one node
+    let mut cuts = index::sample(rng, n - 1, 3).into_vec();
+    cuts.sort_unstable();
+    let (b, c, d) = (cuts[0] + 1, cuts[1] + 1, cuts[2] + 1);
+    // B C -> C B
+    tour[b..d].rotate_left(c - b);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::perturbation::*;
+    use crate::random_nodes;
+    use rand::SeedableRng;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_double_bridge_swaps_middle_parts() {
+        let mut rng = StdRng::seed_from_u64(23);
+        let nodes = random_nodes(30, &mut rng);
+        let edges = |tour: &[Node]| -> HashSet<(usize, usize)> {
+            (0..tour.len())
+                .map(|i| {
+                    let (a, b) = (tour[i].id, tour[(i + 1) % tour.len()].id);
+                    (a.min(b), a.max(b))
+                })
+                .collect()
+        };
+        for _ in 0..50 {
+            let mut tour = nodes.clone();
+            double_bridge(&mut tour, &mut rng);
+            let ids: Vec<usize> = tour.iter().map(|n| n.id).collect();
+            assert!(crate::tour::Tour::from_ids(&ids, &nodes).is_ok());
+            assert_eq!(tour[0], nodes[0]);
+            // A-B, B-C and C-D become A-C, C-B and B-D, one may stay when B and C are single nodes
+            let replaced = edges(&nodes).difference(&edges(&tour)).count();
+            assert!(replaced == 2 || replaced == 3);
+        }
+    }
+}
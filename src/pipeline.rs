@@ -0,0 +1,217 @@
+use std::time::{Duration, Instant};
+
+use rand::rngs::StdRng;
+
+use crate::cancel;
+use crate::events;
+use crate::metric::Metric;
+use crate::solvers::Solver;
+use crate::tour::start_at;
+use crate::Node;
+
+/// chain of solvers, each stage starts from the tour the previous one produced
+///
+/// ```ignore
+/// let pipeline = Pipeline::new().then(NearestNeighbor).then(SimulatedAnnealing::default());
+/// ```
+pub struct Pipeline {
+    name: String,
+    stages: Vec<Box<dyn Solver>>,
+    /// id of the node every stage gets first and the tour starts at, see `starting_at`
+    start: Option<usize>,
+}
+
+/// what a single stage did to the tour
+#[derive(Debug, Clone)]
+pub struct StageReport {
+    pub name: String,
+    pub time: Duration,
+    /// tour length after the stage ran
+    pub length: f64,
+}
+
+/// final tour plus the history of how the pipeline got there
+#[derive(Debug, Clone)]
+pub struct PipelineRun {
+    pub tour: Vec<Node>,
+    /// length of the tour the pipeline was given
+    pub initial_length: f64,
+    pub stages: Vec<StageReport>,
+    /// stopped by ctrl-c, later stages didn't run and `tour` is the best one so far
+    pub interrupted: bool,
+}
+
+impl Pipeline {
+    pub fn new() -> Self {
+        Pipeline {
+            name: String::new(),
+            stages: Vec::new(),
+            start: None,
+        }
+    }
+
+    /// pins a depot: every stage gets the tour rotated to start at node `id`, and so does
+    /// the result. the stages are free to move it around in between, a round trip is as
+    /// long from any of its nodes
+    pub fn starting_at(mut self, id: usize) -> Self {
+        self.start = Some(id);
+        self
+    }
+
+    /// append a stage
+    pub fn then<S: Solver + 'static>(self, stage: S) -> Self {
+        self.then_boxed(Box::new(stage))
+    }
+
+    /// append an already boxed stage, e.g. one picked at runtime
+    pub fn then_boxed(mut self, stage: Box<dyn Solver>) -> Self {
+        if !self.name.is_empty() {
+            self.name.push_str(" -> ");
+        }
+        self.name.push_str(stage.name());
+        self.stages.push(stage);
+        self
+    }
+
+    /// runs all stages in order, timing each of them
+    pub fn run(&self, nodes: &[Node], metric: &dyn Metric, rng: &mut StdRng) -> PipelineRun {
+        self.run_with_progress(nodes, metric, rng, |_| {})
+    }
+
+    /// like `run`, calling `progress` with the report of every stage once it finished
+    pub fn run_with_progress(
+        &self,
+        nodes: &[Node],
+        metric: &dyn Metric,
+        rng: &mut StdRng,
+        mut progress: impl FnMut(&StageReport),
+    ) -> PipelineRun {
+        let initial_length = metric.tour_length(nodes);
+        let mut tour = nodes.to_vec();
+        let pin = |tour: &mut Vec<Node>| {
+            if let Some(id) = self.start {
+                start_at(tour, id);
+            }
+        };
+        pin(&mut tour);
+        let mut stages = Vec::new();
+        // a stage stopped early can leave a worse tour than the one it got
+        let mut best = (initial_length, tour.clone());
+        let mut interrupted = false;
+        events::set_stage(0);
+        events::record(None, initial_length, None, &tour);
+
+        for (k, stage) in self.stages.iter().enumerate() {
+            let start = Instant::now();
+            events::set_stage(k + 1);
+            tour = stage.solve(&tour, metric, rng);
+            pin(&mut tour);
+            let length = metric.tour_length(&tour);
+            events::record(None, length, None, &tour);
+            let report = StageReport {
+                name: stage.name().to_string(),
+                time: start.elapsed(),
+                length,
+            };
+            progress(&report);
+            stages.push(report);
+            if length < best.0 {
+                best = (length, tour.clone());
+            }
+            if cancel::is_cancelled() {
+                interrupted = true;
+                tour = best.1;
+                break;
+            }
+        }
+
+        PipelineRun {
+            tour,
+            initial_length,
+            stages,
+            interrupted,
+        }
+    }
+}
+
+impl Default for Pipeline {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Solver for Pipeline {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn solve(&self, nodes: &[Node], metric: &dyn Metric, rng: &mut StdRng) -> Vec<Node> {
+        self.run(nodes, metric, rng).tour
+    }
+
+    /// a pipeline is only as fast as its slowest stage
+    fn max_nodes(&self) -> usize {
+        self.stages
+            .iter()
+            .map(|stage| stage.max_nodes())
+            .min()
+            .unwrap_or(usize::MAX)
+    }
+
+    /// the first stage that doesn't fit
+    fn check(&self, nodes: &[Node]) -> Result<(), String> {
+        self.stages.iter().try_for_each(|stage| stage.check(nodes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::metric::Euclidean;
+    use crate::pipeline::*;
+    use crate::solvers::{NearestNeighbor, SimulatedAnnealing};
+    use crate::{get_tour_length, random_nodes};
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_pipeline_reports_every_stage() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let nodes = random_nodes(20, &mut rng);
+        let pipeline = Pipeline::new()
+            .then(NearestNeighbor)
+            .then(SimulatedAnnealing::default());
+        assert_eq!(pipeline.name(), "nearest neighbor -> simulated annealing");
+
+        let run = pipeline.run(&nodes, &Euclidean, &mut rng);
+        assert_eq!(run.stages.len(), 2);
+        assert_eq!(run.tour.len(), nodes.len());
+        assert_eq!(run.stages[1].length, get_tour_length(&run.tour));
+    }
+
+    /// turns the tour around, after checking it was handed the depot first
+    struct Reverse;
+
+    impl Solver for Reverse {
+        fn name(&self) -> &str {
+            "reverse"
+        }
+
+        fn solve(&self, nodes: &[Node], _metric: &dyn Metric, _rng: &mut StdRng) -> Vec<Node> {
+            assert_eq!(nodes[0].id, 7);
+            nodes.iter().rev().cloned().collect()
+        }
+    }
+
+    #[test]
+    fn test_every_stage_starts_at_the_depot() {
+        let mut rng = StdRng::seed_from_u64(311);
+        let nodes = random_nodes(20, &mut rng);
+        let pipeline = Pipeline::new()
+            .then(Reverse)
+            .then(SimulatedAnnealing::default())
+            .then(Reverse)
+            .starting_at(7);
+        let run = pipeline.run(&nodes, &Euclidean, &mut rng);
+        assert_eq!(run.tour[0].id, 7);
+        assert_eq!(run.stages[2].length, get_tour_length(&run.tour));
+    }
+}
@@ -0,0 +1,604 @@
+//! drawing tours, tour diffs, multi-depot routes and convergence plots with plotters
+//!
+//! tours are drawn as png or svg, with the metadata of the run embedded, see `image_meta`
+
+use plotters::coord::types::RangedCoordf64;
+use plotters::prelude::*;
+
+use crate::events;
+use crate::genetic;
+use crate::geometry;
+use crate::get_tour_length;
+use crate::image_meta;
+use crate::metric::{Euclidean, Metric};
+use crate::mtsp;
+use crate::path;
+use crate::profiling;
+use crate::tour::Tour;
+use crate::Node;
+
+/// the unit square, or a square around the nodes if they don't fit in there
+pub fn plot_ranges(nodes: &[Node]) -> (std::ops::Range<f64>, std::ops::Range<f64>) {
+    let fold = |f: fn(f64, f64) -> f64, start: f64, coordinate: fn(&Node) -> f64| {
+        nodes.iter().map(coordinate).fold(start, f)
+    };
+    let (min_x, max_x) = (fold(f64::min, 0.0, |n| n.x), fold(f64::max, 1.0, |n| n.x));
+    let (min_y, max_y) = (fold(f64::min, 0.0, |n| n.y), fold(f64::max, 1.0, |n| n.y));
+    if (min_x, max_x, min_y, max_y) == (0.0, 1.0, 0.0, 1.0) {
+        return (0.0..1.0, 0.0..1.0);
+    }
+    // same scale on both axes, with some room for the labels
+    let side = (max_x - min_x).max(max_y - min_y) * 1.05;
+    (min_x..min_x + side, min_y..min_y + side)
+}
+
+/// draw tour with plotters to filename, an svg if it ends with .svg and a png otherwise,
+/// and embed the metadata of the run in the image
+pub fn draw_tour(
+    filename: &str,
+    nodes: &[Node],
+    metadata: &image_meta::RunMetadata,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let _scope = profiling::scope("drawing");
+    let title = format!("'{}', tour length: {}", filename, get_tour_length(nodes));
+    if filename.ends_with(".svg") {
+        std::fs::write(filename, render_svg(nodes, &title, metadata)?)?;
+    } else {
+        std::fs::write(filename, render_png(nodes, &title, metadata)?)?;
+    }
+    Ok(())
+}
+
+/// `draw_tour` of a one-way route, without the edge from the last node back to the first
+pub fn draw_path(
+    filename: &str,
+    nodes: &[Node],
+    metadata: &image_meta::RunMetadata,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let _scope = profiling::scope("drawing");
+    let title = format!(
+        "'{}', path length: {}",
+        filename,
+        path::path_length(&Euclidean, nodes)
+    );
+    if filename.ends_with(".svg") {
+        std::fs::write(filename, svg_of(nodes, &title, metadata, false)?)?;
+    } else {
+        std::fs::write(filename, png_of(nodes, &title, metadata, false)?)?;
+    }
+    Ok(())
+}
+
+/// side of the drawn images in pixels
+pub const IMAGE_SIZE: u32 = 1111;
+
+/// the tour drawn as png file contents, with the metadata embedded
+pub fn render_png(
+    nodes: &[Node],
+    title: &str,
+    metadata: &image_meta::RunMetadata,
+) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    png_of(nodes, title, metadata, true)
+}
+
+fn png_of(
+    nodes: &[Node],
+    title: &str,
+    metadata: &image_meta::RunMetadata,
+    closed: bool,
+) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    if nodes.is_empty() {
+        return Err("can't draw empty tour".into());
+    }
+    let mut pixels = vec![0; (IMAGE_SIZE * IMAGE_SIZE * 3) as usize];
+    draw_tour_on(
+        BitMapBackend::with_buffer(&mut pixels, (IMAGE_SIZE, IMAGE_SIZE)).into_drawing_area(),
+        title,
+        nodes,
+        closed,
+    )?;
+
+    let mut png = Vec::new();
+    let mut encoder = png::Encoder::new(&mut png, IMAGE_SIZE, IMAGE_SIZE);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&pixels)?;
+    writer.finish()?;
+    Ok(image_meta::png_with_metadata(&png, metadata)?)
+}
+
+/// the tour drawn as an svg document, with the metadata embedded
+pub fn render_svg(
+    nodes: &[Node],
+    title: &str,
+    metadata: &image_meta::RunMetadata,
+) -> Result<String, Box<dyn std::error::Error>> {
+    svg_of(nodes, title, metadata, true)
+}
+
+fn svg_of(
+    nodes: &[Node],
+    title: &str,
+    metadata: &image_meta::RunMetadata,
+    closed: bool,
+) -> Result<String, Box<dyn std::error::Error>> {
+    if nodes.is_empty() {
+        return Err("can't draw empty tour".into());
+    }
+    let mut svg = String::new();
+    draw_tour_on(
+        SVGBackend::with_string(&mut svg, (IMAGE_SIZE, IMAGE_SIZE)).into_drawing_area(),
+        title,
+        nodes,
+        closed,
+    )?;
+    Ok(image_meta::svg_with_metadata(&svg, metadata)?)
+}
+
+/// the tour, or the path without its closing edge unless `closed`, with its crossings in red
+pub fn draw_tour_on<DB: DrawingBackend>(
+    root: DrawingArea<DB, plotters::coord::Shift>,
+    title: &str,
+    nodes: &[Node],
+    closed: bool,
+) -> Result<(), Box<dyn std::error::Error>>
+where
+    DB::ErrorType: 'static,
+{
+    let root = root.titled(
+        title,
+        TextStyle::from(("sans-serif", 24).into_font()).color(&WHITE),
+    )?;
+
+    root.fill(&RGBColor(245, 245, 245))?;
+
+    let (x_range, y_range) = plot_ranges(nodes);
+    let root = root.apply_coord_spec(Cartesian2d::<RangedCoordf64, RangedCoordf64>::new(
+        x_range,
+        y_range,
+        (0..1000, 0..1000),
+    ));
+
+    let dot_and_id = |node: &Node| {
+        EmptyElement::at((node.x, node.y))
+            + Circle::new((0, 0), 7, ShapeStyle::from(&BLACK).filled())
+            + Text::new(
+                format!("{}", node.id),
+                (13, 0),
+                ("sans-serif", 23.0).into_font(),
+            )
+    };
+
+    //
+    // draw nodes
+    //
+    for node in nodes {
+        root.draw(&dot_and_id(node))?;
+    }
+
+    //
+    // draw edges
+    //
+    let mut edge_points = nodes
+        .iter()
+        .map(|n| (n.x, n.y))
+        .collect::<Vec<(f64, f64)>>();
+    if closed {
+        // edge_points is just transformed nodes, which can't be empty
+        edge_points.insert(0, *edge_points.last().unwrap());
+    }
+    root.draw(&PathElement::new(
+        edge_points,
+        ShapeStyle::from(&BLACK).filled(),
+    ))?;
+
+    //
+    // highlight crossing edges
+    //
+    if nodes.len() <= geometry::CROSSINGS_MAX_NODES {
+        let last = nodes.len() - 1;
+        for (i, j) in geometry::crossings(nodes) {
+            if !closed && (i == last || j == last) {
+                continue;
+            }
+            for k in [i, j] {
+                let (a, b) = (&nodes[k], &nodes[(k + 1) % nodes.len()]);
+                root.draw(&PathElement::new(
+                    vec![(a.x, a.y), (b.x, b.y)],
+                    ShapeStyle::from(&RED).stroke_width(3),
+                ))?;
+            }
+        }
+    }
+
+    root.present()?;
+    Ok(())
+}
+
+/// length at every checkpoint of a recorded run, one colored line per stage
+pub fn draw_convergence(
+    filename: &str,
+    log: &events::EventLog,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if filename.ends_with(".svg") {
+        draw_convergence_on(
+            SVGBackend::new(filename, (1111, 600)).into_drawing_area(),
+            log,
+        )
+    } else {
+        draw_convergence_on(
+            BitMapBackend::new(filename, (1111, 600)).into_drawing_area(),
+            log,
+        )
+    }
+}
+
+pub fn draw_convergence_on<DB: DrawingBackend>(
+    root: DrawingArea<DB, plotters::coord::Shift>,
+    log: &events::EventLog,
+) -> Result<(), Box<dyn std::error::Error>>
+where
+    DB::ErrorType: 'static,
+{
+    if log.checkpoints.is_empty() {
+        return Err("the log has no checkpoints".into());
+    }
+    root.fill(&WHITE)?;
+    let lengths = log.checkpoints.iter().map(|c| c.length);
+    let (min, max) = lengths.fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), l| {
+        (min.min(l), max.max(l))
+    });
+    // some room above and below, and a range even if the length never changed
+    let margin = ((max - min) * 0.05).max(1e-9);
+    let mut chart = ChartBuilder::on(&root)
+        .caption("tour length per checkpoint", ("sans-serif", 24))
+        .margin(15)
+        .x_label_area_size(40)
+        .y_label_area_size(70)
+        .build_cartesian_2d(0..log.checkpoints.len(), min - margin..max + margin)?;
+    chart
+        .configure_mesh()
+        .x_desc("checkpoint")
+        .y_desc("length")
+        .draw()?;
+
+    let last_stage = log.checkpoints.iter().map(|c| c.stage).max().unwrap_or(0);
+    for stage in 0..=last_stage {
+        let points: Vec<(usize, f64)> = log
+            .checkpoints
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| c.stage == stage)
+            .map(|(k, c)| (k, c.length))
+            .collect();
+        if points.is_empty() {
+            continue;
+        }
+        let name = match stage {
+            0 => "initial".to_string(),
+            stage => log.stages.get(stage - 1).cloned().unwrap_or_default(),
+        };
+        let color = Palette99::pick(stage).to_rgba();
+        chart
+            .draw_series(LineSeries::new(points.clone(), color.stroke_width(2)))?
+            .label(name)
+            .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], color));
+        chart.draw_series(
+            points
+                .into_iter()
+                .map(|point| Circle::new(point, 3, color.filled())),
+        )?;
+    }
+    chart
+        .configure_series_labels()
+        .background_style(WHITE.mix(0.8))
+        .border_style(BLACK)
+        .draw()?;
+
+    root.present()?;
+    Ok(())
+}
+
+/// best and mean length of the population per generation of a genetic algorithm run
+pub fn draw_generations(
+    filename: &str,
+    history: &[genetic::Generation],
+) -> Result<(), Box<dyn std::error::Error>> {
+    if filename.ends_with(".svg") {
+        draw_generations_on(
+            SVGBackend::new(filename, (1111, 600)).into_drawing_area(),
+            history,
+        )
+    } else {
+        draw_generations_on(
+            BitMapBackend::new(filename, (1111, 600)).into_drawing_area(),
+            history,
+        )
+    }
+}
+
+pub fn draw_generations_on<DB: DrawingBackend>(
+    root: DrawingArea<DB, plotters::coord::Shift>,
+    history: &[genetic::Generation],
+) -> Result<(), Box<dyn std::error::Error>>
+where
+    DB::ErrorType: 'static,
+{
+    if history.is_empty() {
+        return Err("the run has no generations".into());
+    }
+    root.fill(&WHITE)?;
+    let (min, max) = history
+        .iter()
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), g| {
+            (min.min(g.best), max.max(g.mean))
+        });
+    let margin = ((max - min) * 0.05).max(1e-9);
+    let mut chart = ChartBuilder::on(&root)
+        .caption("tour length per generation", ("sans-serif", 24))
+        .margin(15)
+        .x_label_area_size(40)
+        .y_label_area_size(70)
+        .build_cartesian_2d(0..history.len(), min - margin..max + margin)?;
+    chart
+        .configure_mesh()
+        .x_desc("generation")
+        .y_desc("length")
+        .draw()?;
+
+    let best: Vec<(usize, f64)> = history
+        .iter()
+        .enumerate()
+        .map(|(k, g)| (k, g.best))
+        .collect();
+    let mean: Vec<(usize, f64)> = history
+        .iter()
+        .enumerate()
+        .map(|(k, g)| (k, g.mean))
+        .collect();
+    for (name, points, color) in [("best", best, BLUE), ("mean", mean, RED)] {
+        chart
+            .draw_series(LineSeries::new(points, color.stroke_width(2)))?
+            .label(name)
+            .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], color));
+    }
+    chart
+        .configure_series_labels()
+        .background_style(WHITE.mix(0.8))
+        .border_style(BLACK)
+        .draw()?;
+
+    root.present()?;
+    Ok(())
+}
+
+/// the nodes with a ring of neurons of a self-organizing map through them
+pub fn draw_ring(
+    filename: &str,
+    nodes: &[Node],
+    ring: &[Node],
+) -> Result<(), Box<dyn std::error::Error>> {
+    if filename.ends_with(".svg") {
+        draw_ring_on(
+            SVGBackend::new(filename, (IMAGE_SIZE, IMAGE_SIZE)).into_drawing_area(),
+            nodes,
+            ring,
+        )
+    } else {
+        draw_ring_on(
+            BitMapBackend::new(filename, (IMAGE_SIZE, IMAGE_SIZE)).into_drawing_area(),
+            nodes,
+            ring,
+        )
+    }
+}
+
+pub fn draw_ring_on<DB: DrawingBackend>(
+    root: DrawingArea<DB, plotters::coord::Shift>,
+    nodes: &[Node],
+    ring: &[Node],
+) -> Result<(), Box<dyn std::error::Error>>
+where
+    DB::ErrorType: 'static,
+{
+    if ring.is_empty() {
+        return Err("can't draw an empty ring".into());
+    }
+    root.fill(&RGBColor(245, 245, 245))?;
+    let (x_range, y_range) = plot_ranges(nodes);
+    let root = root.apply_coord_spec(Cartesian2d::<RangedCoordf64, RangedCoordf64>::new(
+        x_range,
+        y_range,
+        (0..IMAGE_SIZE as i32, 0..IMAGE_SIZE as i32),
+    ));
+
+    let mut points: Vec<(f64, f64)> = ring.iter().map(|n| (n.x, n.y)).collect();
+    points.push(points[0]);
+    root.draw(&PathElement::new(points, BLUE.stroke_width(2)))?;
+    for node in nodes {
+        root.draw(&Circle::new(
+            (node.x, node.y),
+            6,
+            ShapeStyle::from(&BLACK).filled(),
+        ))?;
+    }
+
+    root.present()?;
+    Ok(())
+}
+
+/// draw the routes of several salesmen in different colors, depots as squares
+pub fn draw_routes(
+    filename: &str,
+    solution: &mtsp::Solution,
+    metric: &dyn Metric,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let all: Vec<Node> = solution
+        .depots
+        .iter()
+        .chain(solution.routes.iter().flat_map(|route| &route.customers))
+        .cloned()
+        .collect();
+    let root = BitMapBackend::new(filename, (1111, 1111)).into_drawing_area();
+    let root = root.titled(
+        &format!(
+            "'{}', {} routes, total length: {}, longest: {}",
+            filename,
+            solution
+                .routes
+                .iter()
+                .filter(|r| !r.customers.is_empty())
+                .count(),
+            solution.length(metric),
+            solution.longest(metric)
+        ),
+        TextStyle::from(("sans-serif", 24).into_font()).color(&WHITE),
+    )?;
+    root.fill(&RGBColor(245, 245, 245))?;
+
+    let (x_range, y_range) = plot_ranges(&all);
+    let root = root.apply_coord_spec(Cartesian2d::<RangedCoordf64, RangedCoordf64>::new(
+        x_range,
+        y_range,
+        (0..1000, 0..1000),
+    ));
+
+    //
+    // draw routes
+    //
+    for (r, route) in solution.routes.iter().enumerate() {
+        if route.customers.is_empty() {
+            continue;
+        }
+        let depot = &solution.depots[route.depot];
+        let color = Palette99::pick(r).to_rgba();
+        let points = std::iter::once(depot)
+            .chain(&route.customers)
+            .chain(std::iter::once(depot))
+            .map(|n| (n.x, n.y))
+            .collect::<Vec<_>>();
+        root.draw(&PathElement::new(points, color.stroke_width(2)))?;
+        for node in &route.customers {
+            root.draw(&Circle::new((node.x, node.y), 6, color.filled()))?;
+        }
+    }
+
+    //
+    // draw depots
+    //
+    for depot in &solution.depots {
+        root.draw(
+            &(EmptyElement::at((depot.x, depot.y))
+                + Rectangle::new([(-9, -9), (9, 9)], ShapeStyle::from(&BLACK).filled())
+                + Text::new(
+                    format!("{}", depot.id),
+                    (13, 0),
+                    ("sans-serif", 23.0).into_font(),
+                )),
+        )?;
+    }
+
+    root.present()?;
+    Ok(())
+}
+
+/// draw two tours over each other, shared edges black,
+/// edges only `from` has red and edges only `to` has green
+pub fn draw_tour_diff(
+    filename: &str,
+    from: &Tour,
+    to: &Tour,
+    metric: &dyn Metric,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if from.is_empty() {
+        return Err("can't draw empty tour".into());
+    }
+    let diff = from.diff(to, metric);
+    let root = BitMapBackend::new(filename, (1111, 1111)).into_drawing_area();
+    let root = root.titled(
+        &format!(
+            "'{}', {} shared, {} removed, {} added, length delta: {}",
+            filename,
+            diff.shared,
+            diff.removed.len(),
+            diff.added.len(),
+            diff.length_delta
+        ),
+        TextStyle::from(("sans-serif", 24).into_font()).color(&WHITE),
+    )?;
+
+    root.fill(&RGBColor(245, 245, 245))?;
+
+    let (x_range, y_range) = plot_ranges(from.nodes());
+    let root = root.apply_coord_spec(Cartesian2d::<RangedCoordf64, RangedCoordf64>::new(
+        x_range,
+        y_range,
+        (0..1000, 0..1000),
+    ));
+
+    let position = |id: usize| {
+        from.nodes()
+            .iter()
+            .find(|n| n.id == id)
+            .map(|n| (n.x, n.y))
+            .ok_or("tours don't visit the same nodes")
+    };
+    let edge_style = |color: &RGBColor, width: u32| ShapeStyle::from(color).stroke_width(width);
+
+    //
+    // draw edges, changes on top
+    //
+    for (a, b) in from.edges().intersection(&to.edges()) {
+        root.draw(&PathElement::new(
+            vec![position(*a)?, position(*b)?],
+            edge_style(&BLACK, 1),
+        ))?;
+    }
+    for ((a, b), color) in diff
+        .removed
+        .iter()
+        .map(|e| (e, RGBColor(220, 40, 40)))
+        .chain(diff.added.iter().map(|e| (e, RGBColor(40, 160, 40))))
+    {
+        root.draw(&PathElement::new(
+            vec![position(*a)?, position(*b)?],
+            edge_style(&color, 3),
+        ))?;
+    }
+
+    //
+    // draw nodes
+    //
+    for node in from.nodes() {
+        root.draw(
+            &(EmptyElement::at((node.x, node.y))
+                + Circle::new((0, 0), 7, ShapeStyle::from(&BLACK).filled())
+                + Text::new(
+                    format!("{}", node.id),
+                    (13, 0),
+                    ("sans-serif", 23.0).into_font(),
+                )),
+        )?;
+    }
+
+    root.present()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::plot::*;
+    use crate::random_nodes;
+
+    #[test]
+    fn test_render_in_memory() {
+        let nodes = random_nodes(5, &mut rand::thread_rng());
+        let metadata = image_meta::RunMetadata::new("random", 1, &nodes, get_tour_length(&nodes));
+        let png = render_png(&nodes, "title", &metadata).unwrap();
+        assert!(png.starts_with(b"\x89PNG"));
+        let svg = render_svg(&nodes, "title", &metadata).unwrap();
+        assert!(svg.contains("<entry key=\"algorithm\">random</entry>"));
+        assert!(render_svg(&[], "title", &metadata).is_err());
+    }
+}
@@ -0,0 +1,131 @@
+//! built-in profiling, with the `profiling` feature
+//!
+//! `--profile <file.svg>` samples the whole run with pprof and writes a flamegraph of it,
+//! no external tools needed. the parts of a solve worth telling apart are marked with `scope`,
+//! their calls and total time are printed next to the flamegraph.
+//! without the feature scopes compile to nothing
+
+#[cfg(feature = "profiling")]
+use std::sync::Mutex;
+#[cfg(feature = "profiling")]
+use std::time::{Duration, Instant};
+
+/// name, calls and total time of every scope that ran
+#[cfg(feature = "profiling")]
+static TOTALS: Mutex<Vec<(&'static str, u64, Duration)>> = Mutex::new(Vec::new());
+
+/// samples per second
+#[cfg(feature = "profiling")]
+const FREQUENCY: i32 = 997;
+
+/// adds the time until it's dropped to the scope's total
+#[must_use = "the scope ends when this is dropped"]
+pub struct Scope {
+    #[cfg(feature = "profiling")]
+    name: &'static str,
+    #[cfg(feature = "profiling")]
+    start: Instant,
+}
+
+/// times everything until the end of the enclosing block as `name`
+#[inline(always)]
+pub fn scope(#[allow(unused_variables)] name: &'static str) -> Scope {
+    Scope {
+        #[cfg(feature = "profiling")]
+        name,
+        #[cfg(feature = "profiling")]
+        start: Instant::now(),
+    }
+}
+
+#[cfg(feature = "profiling")]
+impl Drop for Scope {
+    fn drop(&mut self) {
+        let elapsed = self.start.elapsed();
+        let mut totals = TOTALS.lock().unwrap_or_else(|p| p.into_inner());
+        match totals.iter_mut().find(|(name, _, _)| *name == self.name) {
+            Some((_, calls, total)) => {
+                *calls += 1;
+                *total += elapsed;
+            }
+            None => totals.push((self.name, 1, elapsed)),
+        }
+    }
+}
+
+/// a running sampler, `finish` writes what it saw
+pub struct Profiler {
+    #[cfg_attr(not(feature = "profiling"), allow(dead_code))]
+    path: String,
+    #[cfg(feature = "profiling")]
+    guard: pprof::ProfilerGuard<'static>,
+}
+
+/// starts sampling if there's a flamegraph to write, errors without the feature
+pub fn start(path: Option<String>) -> Result<Option<Profiler>, String> {
+    let Some(path) = path else {
+        return Ok(None);
+    };
+    #[cfg(feature = "profiling")]
+    {
+        let guard = pprof::ProfilerGuardBuilder::default()
+            .frequency(FREQUENCY)
+            .blocklist(&["libc", "libgcc", "pthread", "vdso"])
+            .build()
+            .map_err(|err| err.to_string())?;
+        Ok(Some(Profiler { path, guard }))
+    }
+    #[cfg(not(feature = "profiling"))]
+    {
+        Err(format!(
+            "can't write {}, built without profiling, rebuild with --features profiling",
+            path
+        ))
+    }
+}
+
+impl Profiler {
+    /// writes the flamegraph and prints the scopes, slowest first
+    pub fn finish(self) -> Result<(), Box<dyn std::error::Error>> {
+        #[cfg(feature = "profiling")]
+        {
+            let report = self.guard.report().build()?;
+            report.flamegraph(std::fs::File::create(&self.path)?)?;
+            println!("wrote flamegraph to {}", self.path);
+
+            let mut totals = TOTALS.lock().unwrap_or_else(|p| p.into_inner()).clone();
+            totals.sort_by_key(|(_, _, total)| std::cmp::Reverse(*total));
+            if !totals.is_empty() {
+                println!(
+                    "{:<20}{:>12}{:>14}{:>14}",
+                    "scope", "calls", "total", "mean"
+                );
+            }
+            for (name, calls, total) in totals {
+                println!(
+                    "{:<20}{:>12}{:>14.3?}{:>14.3?}",
+                    name,
+                    calls,
+                    total,
+                    total / calls as u32
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "profiling"))]
+mod tests {
+    use crate::profiling::*;
+
+    #[test]
+    fn test_scopes_add_up() {
+        for _ in 0..3 {
+            let _scope = scope("test scope");
+        }
+        let totals = TOTALS.lock().unwrap();
+        let (_, calls, _) = totals.iter().find(|(n, _, _)| *n == "test scope").unwrap();
+        assert_eq!(*calls, 3);
+    }
+}
@@ -0,0 +1,123 @@
+//! pass/fail thresholds on the produced tour, for scripts and ci pipelines
+//!
+//! a run that misses a threshold exits with `FAILED_EXIT_CODE`,
+//! so callers don't have to parse the output to notice a bad tour
+
+use crate::geometry::mst_length;
+use crate::metric::Metric;
+use crate::Node;
+
+/// exit status of runs whose tour missed a threshold,
+/// apart from 1 (bad input), 2 (bad arguments) and 130 (interrupted)
+pub const FAILED_EXIT_CODE: i32 = 3;
+
+/// a percentage, `5%` or just `5`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Percent(pub f64);
+
+impl std::str::FromStr for Percent {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let number = s.strip_suffix('%').unwrap_or(s);
+        match number.trim().parse::<f64>() {
+            Ok(percent) if percent >= 0.0 => Ok(Percent(percent)),
+            Ok(_) => Err(format!("'{}' is negative", s)),
+            Err(err) => Err(format!("'{}' isn't a percentage: {}", s, err)),
+        }
+    }
+}
+
+/// what the tour length is compared to for the gap
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reference {
+    /// e.g. "reference tour" or "mst lower bound"
+    pub name: String,
+    pub length: f64,
+}
+
+/// thresholds a tour has to meet, unset ones always pass
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct QualityGate {
+    pub max_length: Option<f64>,
+    /// how much longer than the reference the tour may be
+    pub max_gap: Option<Percent>,
+}
+
+impl QualityGate {
+    pub fn is_active(&self) -> bool {
+        self.max_length.is_some() || self.max_gap.is_some()
+    }
+
+    /// one message per missed threshold, empty if the tour passed
+    pub fn check(&self, length: f64, reference: &Reference) -> Vec<String> {
+        let mut failures = Vec::new();
+        if let Some(max_length) = self.max_length {
+            // NaN lengths fail too
+            if length.is_nan() || length > max_length {
+                failures.push(format!(
+                    "length {:?} is above the maximum of {:?}",
+                    length, max_length
+                ));
+            }
+        }
+        if let Some(Percent(max_gap)) = self.max_gap {
+            let gap = gap_percent(length, reference.length);
+            if gap.is_nan() || gap > max_gap {
+                failures.push(format!(
+                    "gap {:.3}% to the {} ({:?}) is above the maximum of {}%",
+                    gap, reference.name, reference.length, max_gap
+                ));
+            }
+        }
+        failures
+    }
+}
+
+/// how much longer `length` is than `reference`, in percent
+pub fn gap_percent(length: f64, reference: f64) -> f64 {
+    100.0 * (length / reference - 1.0)
+}
+
+/// the minimum spanning tree, no tour is shorter
+pub fn mst_bound(nodes: &[Node], metric: &dyn Metric) -> Reference {
+    Reference {
+        name: "mst lower bound".to_string(),
+        length: mst_length(nodes, metric),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::metric::Euclidean;
+    use crate::quality::*;
+
+    #[test]
+    fn test_gate() {
+        assert_eq!("5%".parse::<Percent>(), Ok(Percent(5.0)));
+        assert_eq!("2.5".parse::<Percent>(), Ok(Percent(2.5)));
+        assert!("-1%".parse::<Percent>().is_err());
+        assert!("five".parse::<Percent>().is_err());
+
+        // unit square, the mst is three of its sides
+        let nodes: Vec<Node> = [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)]
+            .iter()
+            .enumerate()
+            .map(|(id, &(x, y))| Node { id, x, y })
+            .collect();
+        let bound = mst_bound(&nodes, &Euclidean);
+        assert_eq!(bound.length, 3.0);
+
+        let gate = QualityGate {
+            max_length: Some(4.5),
+            max_gap: Some(Percent(40.0)),
+        };
+        assert!(gate.is_active());
+        assert!(gate.check(4.0, &bound).is_empty());
+        let failures = gate.check(5.0, &bound);
+        assert_eq!(failures.len(), 2);
+        assert!(failures[1].starts_with("gap 66.667% to the mst lower bound"));
+        assert_eq!(gate.check(f64::NAN, &bound).len(), 2);
+        assert!(!QualityGate::default().is_active());
+    }
+}
@@ -0,0 +1,217 @@
+//! on-disk cache of solver runs, addressed by the hash of everything that went into them
+//!
+//! a run is fully determined by the instance, the metric, the solvers with their parameters
+//! and the seed, so running the same configuration again can just read the old result.
+//! the crate version is part of the key, results of older builds are never reused
+
+use std::fmt::Write as _;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::objective::NodeCosts;
+use crate::pipeline::{PipelineRun, StageReport};
+use crate::Node;
+
+/// hash of a run configuration, 64 bit fnv-1a over a canonical description
+///
+/// unlike `DefaultHasher` it's guaranteed to be the same on every platform and rust version
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CacheKey(pub u64);
+
+/// collects the parts of a configuration, floats are hashed by their bits
+pub struct KeyBuilder {
+    hash: u64,
+}
+
+impl KeyBuilder {
+    pub fn new() -> Self {
+        let mut builder = Self::unversioned();
+        builder.text(env!("CARGO_PKG_VERSION"));
+        builder
+    }
+
+    /// without the crate version, for hashes that have to stay the same across releases
+    pub fn unversioned() -> Self {
+        KeyBuilder {
+            hash: 0xcbf2_9ce4_8422_2325,
+        }
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.hash ^= byte as u64;
+            self.hash = self.hash.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+
+    /// length prefixed, so `("ab", "c")` and `("a", "bc")` differ
+    pub fn text(&mut self, text: &str) -> &mut Self {
+        self.bytes(&(text.len() as u64).to_le_bytes());
+        self.bytes(text.as_bytes());
+        self
+    }
+
+    pub fn number(&mut self, number: u64) -> &mut Self {
+        self.bytes(&number.to_le_bytes());
+        self
+    }
+
+    /// ids and exact coordinates in order, the order is the starting tour
+    pub fn nodes(&mut self, nodes: &[Node]) -> &mut Self {
+        self.number(nodes.len() as u64);
+        for node in nodes {
+            self.number(node.id as u64)
+                .number(node.x.to_bits())
+                .number(node.y.to_bits());
+        }
+        self
+    }
+
+    pub fn costs(&mut self, nodes: &[Node], costs: &NodeCosts) -> &mut Self {
+        for node in nodes {
+            self.number(costs.service(node.id).to_bits())
+                .number(costs.weight(node.id).to_bits());
+        }
+        self
+    }
+
+    pub fn finish(&self) -> CacheKey {
+        CacheKey(self.hash)
+    }
+}
+
+impl Default for KeyBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// directory of cached runs, one text file per key
+pub struct ResultCache {
+    dir: PathBuf,
+}
+
+/// directory the cli caches runs in
+pub const DEFAULT_DIR: &str = ".tsp-cache";
+
+impl ResultCache {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        ResultCache { dir: dir.into() }
+    }
+
+    pub fn path(&self, key: CacheKey) -> PathBuf {
+        self.dir.join(format!("{:016x}.run", key.0))
+    }
+
+    /// the cached run, with node ids instead of nodes. None if there is none or it's unreadable
+    pub fn load(&self, key: CacheKey) -> Option<(Vec<usize>, PipelineRun)> {
+        let text = std::fs::read_to_string(self.path(key)).ok()?;
+        parse_run(&text)
+    }
+
+    pub fn store(&self, key: CacheKey, run: &PipelineRun) -> std::io::Result<PathBuf> {
+        std::fs::create_dir_all(&self.dir)?;
+        let path = self.path(key);
+        std::fs::write(&path, format_run(run))?;
+        Ok(path)
+    }
+}
+
+/// `initial <length>`, one `stage <nanos> <length> <name>` line per stage and `tour <ids>`
+fn format_run(run: &PipelineRun) -> String {
+    // writing to a String never fails
+    let mut text = String::new();
+    let _ = writeln!(text, "initial {:?}", run.initial_length);
+    for stage in &run.stages {
+        let _ = writeln!(
+            text,
+            "stage {} {:?} {}",
+            stage.time.as_nanos(),
+            stage.length,
+            stage.name
+        );
+    }
+    text.push_str("tour");
+    for node in &run.tour {
+        let _ = write!(text, " {}", node.id);
+    }
+    text.push('\n');
+    text
+}
+
+fn parse_run(text: &str) -> Option<(Vec<usize>, PipelineRun)> {
+    let mut initial_length = None;
+    let mut stages = Vec::new();
+    let mut ids = None;
+    for line in text.lines() {
+        let (kind, rest) = line.split_once(' ').unwrap_or((line, ""));
+        match kind {
+            "initial" => initial_length = Some(rest.parse().ok()?),
+            "stage" => {
+                let mut fields = rest.splitn(3, ' ');
+                let nanos: u64 = fields.next()?.parse().ok()?;
+                let length = fields.next()?.parse().ok()?;
+                stages.push(StageReport {
+                    name: fields.next()?.to_string(),
+                    time: Duration::from_nanos(nanos),
+                    length,
+                });
+            }
+            "tour" => {
+                ids = Some(
+                    rest.split_whitespace()
+                        .map(|id| id.parse().ok())
+                        .collect::<Option<Vec<usize>>>()?,
+                )
+            }
+            _ => return None,
+        }
+    }
+    let run = PipelineRun {
+        tour: Vec::new(),
+        initial_length: initial_length?,
+        stages,
+        interrupted: false,
+    };
+    Some((ids?, run))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::result_cache::*;
+
+    #[test]
+    fn test_keys_and_round_trip() {
+        let nodes = crate::random_nodes(6, &mut rand::thread_rng());
+        let key = |stages: &str, seed: u64| {
+            KeyBuilder::new()
+                .nodes(&nodes)
+                .text(stages)
+                .number(seed)
+                .finish()
+        };
+        assert_eq!(key("nn,sa", 1), key("nn,sa", 1));
+        assert_ne!(key("nn,sa", 1), key("nn,sa", 2));
+        assert_ne!(key("nn,sa", 1), key("nn", 1));
+
+        let run = PipelineRun {
+            tour: nodes.clone(),
+            initial_length: 3.25,
+            stages: vec![StageReport {
+                name: "nearest neighbor -> x".to_string(),
+                time: Duration::from_micros(1500),
+                length: 0.1 + 0.2,
+            }],
+            interrupted: false,
+        };
+        let cache = ResultCache::new(std::env::temp_dir().join("tsp_test_result_cache"));
+        cache.store(key("test", 0), &run).unwrap();
+        let (ids, cached) = cache.load(key("test", 0)).unwrap();
+        assert_eq!(ids, (0..6).collect::<Vec<_>>());
+        assert_eq!(cached.initial_length, 3.25);
+        assert_eq!(cached.stages[0].name, run.stages[0].name);
+        assert_eq!(cached.stages[0].time, run.stages[0].time);
+        assert_eq!(cached.stages[0].length, 0.1 + 0.2);
+        assert!(cache.load(key("missing", 0)).is_none());
+    }
+}
@@ -0,0 +1,93 @@
+//! reproducible randomness
+//!
+//! everything random is driven by a single master seed.
+//! independent pieces of work (threads, tournament games, restarts, ...)
+//! get their own stream derived from the master seed and a stable stream id,
+//! never from the order in which threads happen to pick up work.
+//! that way the same seed gives the same result for any thread count
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::cancel;
+
+/// splitmix64 finalizer, scrambles similar inputs into unrelated outputs
+pub fn splitmix64(mut z: u64) -> u64 {
+    z = z.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+/// seed of stream `stream` of the master seed
+pub fn stream_seed(master: u64, stream: u64) -> u64 {
+    splitmix64(master ^ splitmix64(stream))
+}
+
+/// rng of stream `stream` of the master seed
+pub fn stream_rng(master: u64, stream: u64) -> StdRng {
+    StdRng::seed_from_u64(stream_seed(master, stream))
+}
+
+/// fresh master seed for runs where the user didn't pick one
+pub fn random_seed() -> u64 {
+    rand::thread_rng().gen()
+}
+
+/// `f(i)` for every i in 0..count, spread over `threads` threads
+///
+/// results are returned in index order no matter which thread computed them,
+/// so as long as `f` only depends on `i` the output doesn't depend on `threads`
+pub fn parallel_map<R, F>(count: usize, threads: usize, f: F) -> Vec<R>
+where
+    R: Send,
+    F: Fn(usize) -> R + Sync,
+{
+    let next = AtomicUsize::new(0);
+    let results: Mutex<Vec<Option<R>>> = Mutex::new((0..count).map(|_| None).collect());
+    // the workers stop with the solve that started them
+    let token = cancel::current_token();
+
+    std::thread::scope(|scope| {
+        for _ in 0..threads.clamp(1, count.max(1)) {
+            scope.spawn(|| {
+                cancel::with_token(token.clone(), || loop {
+                    let i = next.fetch_add(1, Ordering::Relaxed);
+                    if i >= count {
+                        break;
+                    }
+                    let result = f(i);
+                    results.lock().unwrap()[i] = Some(result);
+                })
+            });
+        }
+    });
+
+    results
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .map(|r| r.expect("every index is computed exactly once"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::rng::*;
+
+    #[test]
+    fn test_streams_differ() {
+        assert_ne!(stream_seed(7, 0), stream_seed(7, 1));
+        assert_ne!(stream_seed(7, 0), stream_seed(8, 0));
+        assert_eq!(stream_seed(7, 3), stream_seed(7, 3));
+    }
+
+    #[test]
+    fn test_parallel_map_independent_of_threads() {
+        let work = |i: usize| stream_rng(42, i as u64).gen::<u64>();
+        assert_eq!(parallel_map(50, 1, work), parallel_map(50, 7, work));
+    }
+}
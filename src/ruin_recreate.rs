@@ -0,0 +1,149 @@
+//! ruin and recreate: take a handful of nodes out of the tour, a spatial cluster or a random
+//! subset, put each back at its cheapest place and keep the result unless it's longer
+//!
+//! an iteration costs a pass over the tour per removed node, not per node of the tour squared
+//! like a 2-opt sweep, so it keeps improving large instances where the sweeps get slow
+
+use std::str::FromStr;
+
+use rand::rngs::StdRng;
+use rand::seq::{index, SliceRandom};
+use rand::Rng;
+
+use crate::cancel;
+use crate::insertion::insert_each;
+use crate::metric::Metric;
+use crate::Node;
+
+/// everything `tsp_ruin_recreate` can be tuned with
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RuinParams {
+    pub iterations: usize,
+    /// nodes every ruin removes
+    pub removal: usize,
+    /// chance a ruin removes a cluster instead of random nodes
+    pub cluster: f64,
+}
+
+impl Default for RuinParams {
+    fn default() -> Self {
+        RuinParams {
+            iterations: 20000,
+            removal: 10,
+            cluster: 0.5,
+        }
+    }
+}
+
+/// `key=value` pairs separated by `:` over the defaults, e.g. `removal=30:cluster=1`
+impl FromStr for RuinParams {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        fn value<T: FromStr>(key: &str, value: &str) -> Result<T, String>
+        where
+            T::Err: std::fmt::Display,
+        {
+            value
+                .parse()
+                .map_err(|err| format!("invalid {} '{}': {}", key, value, err))
+        }
+
+        let mut params = RuinParams::default();
+        for pair in s.split(':').filter(|p| !p.is_empty()) {
+            let Some((key, v)) = pair.split_once('=') else {
+                return Err(format!("expected key=value, got '{}'", pair));
+            };
+            match key {
+                "iterations" => params.iterations = value(key, v)?,
+                "removal" => params.removal = value(key, v)?,
+                "cluster" => params.cluster = value(key, v)?,
+                _ => {
+                    return Err(format!(
+                        "unknown parameter '{}', try iterations, removal or cluster",
+                        key
+                    ))
+                }
+            }
+        }
+        if !(0.0..=1.0).contains(&params.cluster) {
+            return Err(format!("cluster {} isn't between 0 and 1", params.cluster));
+        }
+        Ok(params)
+    }
+}
+
+/// positions of a random node and the `count - 1` nodes closest to it
+fn cluster(tour: &[Node], count: usize, metric: &dyn Metric, rng: &mut StdRng) -> Vec<usize> {
+    let center = &tour[rng.gen_range(0..tour.len())];
+    let mut positions: Vec<usize> = (0..tour.len()).collect();
+    positions.select_nth_unstable_by(count - 1, |&a, &b| {
+        metric
+            .distance(center, &tour[a])
+            .total_cmp(&metric.distance(center, &tour[b]))
+    });
+    positions.truncate(count);
+    positions
+}
+
+/// the shortest tour `iterations` ruins of the given one led to
+pub fn tsp_ruin_recreate(
+    nodes: &[Node],
+    metric: &dyn Metric,
+    params: &RuinParams,
+    rng: &mut StdRng,
+) -> Vec<Node> {
+    let n = nodes.len();
+    if n < 4 {
+        return nodes.to_vec();
+    }
+    let count = params.removal.clamp(1, n - 2);
+    let mut current = nodes.to_vec();
+    let mut length = metric.tour_length(&current);
+    for _ in 0..params.iterations {
+        if cancel::is_cancelled() {
+            break;
+        }
+        let mut positions = if rng.gen_bool(params.cluster) {
+            cluster(&current, count, metric, rng)
+        } else {
+            index::sample(rng, n, count).into_vec()
+        };
+        // removing from the back keeps the other positions valid
+        positions.sort_unstable_by(|a, b| b.cmp(a));
+        let mut tour = current.clone();
+        let mut removed: Vec<Node> = positions.iter().map(|&p| tour.remove(p)).collect();
+        removed.shuffle(rng);
+        insert_each(&mut tour, removed, metric);
+
+        let recreated = metric.tour_length(&tour);
+        if recreated <= length {
+            current = tour;
+            length = recreated;
+        }
+    }
+    current
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::metric::Euclidean;
+    use crate::random_nodes;
+    use crate::ruin_recreate::*;
+    use crate::solvers::tsp_nearest_neighbor;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_improves_nearest_neighbor() {
+        let nodes = random_nodes(100, &mut StdRng::seed_from_u64(36));
+        let nn = tsp_nearest_neighbor(&nodes, &Euclidean);
+        let mut rng = StdRng::seed_from_u64(37);
+        assert_eq!(cluster(&nn, 5, &Euclidean, &mut rng).len(), 5);
+
+        let params: RuinParams = "iterations=3000".parse().unwrap();
+        let tour = tsp_ruin_recreate(&nn, &Euclidean, &params, &mut rng);
+        let ids: Vec<usize> = tour.iter().map(|n| n.id).collect();
+        assert!(crate::tour::Tour::from_ids(&ids, &nodes).is_ok());
+        assert!(Euclidean.tour_length(&tour) < Euclidean.tour_length(&nn) * 0.95);
+    }
+}
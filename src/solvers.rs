@@ -0,0 +1,1677 @@
+//! the tsp algorithms, and the `Solver` trait that makes them interchangeable
+//!
+//! every solver the command line knows is in `registered_solvers` and `solver_by_name`
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use rand::rngs::StdRng;
+use rand::Rng;
+
+use crate::alns::AlnsParams;
+use crate::annealing::{MoveType, SaOverrides, SaParams, Temperature, REHEAT_WINDOW};
+use crate::cross_entropy::CeParams;
+use crate::genetic::GaParams;
+use crate::gls::GlsParams;
+use crate::grasp::GraspParams;
+use crate::local_search::{
+    local_search, local_search_near, local_search_order, MoveOperator, OrOpt, Swap, ThreeOpt,
+    TwoHOpt, TwoOpt, DEFAULT_NEIGHBORS,
+};
+use crate::metric::Metric;
+use crate::multistart::MultiStart;
+use crate::pipeline::Pipeline;
+use crate::ruin_recreate::RuinParams;
+use crate::som::SomParams;
+use crate::tabu::TabuParams;
+use crate::tour_order::Representation;
+use crate::{annealing, cancel, events, profiling, rng, Node};
+
+/// considers every tour once, (n-1)!/2 of them
+///
+/// the first node stays first, rotations of a tour are the same tour, and on symmetric
+/// metrics node 1 comes before node 2, which leaves one of every tour and its reflection.
+/// a partial tour that's already longer than the best one so far isn't extended
+pub fn tsp_brute_force(nodes: &[Node], metric: &dyn Metric) -> Vec<Node> {
+    tsp_brute_force_parallel(nodes, metric, 1)
+}
+
+/// `tsp_brute_force` on `threads` threads, every start of three nodes is a piece of work.
+/// they share the length of the best tour so far for pruning, but only cut what's longer,
+/// so ties go to the same tour for any thread count
+pub fn tsp_brute_force_parallel(nodes: &[Node], metric: &dyn Metric, threads: usize) -> Vec<Node> {
+    let n = nodes.len();
+    if n < 4 {
+        return shortest_direction(nodes, metric);
+    }
+    let symmetric = metric.is_symmetric();
+    let starts: Vec<(usize, usize)> = (1..n)
+        .flat_map(|second| (1..n).map(move |third| (second, third)))
+        .filter(|&(second, third)| {
+            second != third && (!symmetric || second != 2 && (third != 2 || second == 1))
+        })
+        .collect();
+    let bound = AtomicU64::new(f64::INFINITY.to_bits());
+    let bests = rng::parallel_map(starts.len(), threads, |k| {
+        let (second, third) = starts[k];
+        let mut search = BruteForceSearch {
+            nodes,
+            metric,
+            tour: vec![0, second, third],
+            visited: vec![false; n],
+            symmetric,
+            best: (f64::INFINITY, Vec::new()),
+            bound: &bound,
+        };
+        for &i in &search.tour {
+            search.visited[i] = true;
+        }
+        let distance = |a: usize, b: usize| metric.distance(&nodes[a], &nodes[b]);
+        search.extend(distance(0, second) + distance(second, third));
+        search.best
+    });
+    // the first of equally short tours, like a single thread finds it
+    match bests.into_iter().min_by(|a, b| a.0.total_cmp(&b.0)) {
+        Some((_, tour)) if !tour.is_empty() => tour.iter().map(|&i| nodes[i].clone()).collect(),
+        // interrupted before any tour was complete
+        _ => nodes.to_vec(),
+    }
+}
+
+/// the partial tour of `tsp_brute_force` by positions in `nodes`
+struct BruteForceSearch<'a> {
+    nodes: &'a [Node],
+    metric: &'a dyn Metric,
+    tour: Vec<usize>,
+    visited: Vec<bool>,
+    /// whether reflections can be skipped
+    symmetric: bool,
+    best: (f64, Vec<usize>),
+    /// bits of the shortest length any thread found, positive floats order like their bits
+    bound: &'a AtomicU64,
+}
+
+impl BruteForceSearch<'_> {
+    fn bound(&self) -> f64 {
+        f64::from_bits(self.bound.load(Ordering::Relaxed))
+    }
+
+    fn extend(&mut self, length: f64) {
+        let n = self.nodes.len();
+        let last = self.tour[self.tour.len() - 1];
+        if self.tour.len() == n {
+            let closed = length + self.metric.distance(&self.nodes[last], &self.nodes[0]);
+            if closed < self.best.0 && closed <= self.bound() {
+                self.best = (closed, self.tour.clone());
+                self.bound.fetch_min(closed.to_bits(), Ordering::Relaxed);
+            }
+            return;
+        }
+        // stop with the best tour so far, once there is one
+        if self.bound().is_finite() && cancel::is_cancelled() {
+            return;
+        }
+        for next in 1..n {
+            // node 2 before node 1 is the reflection of a tour that has them the other way
+            if self.visited[next] || (self.symmetric && next == 2 && !self.visited[1]) {
+                continue;
+            }
+            let extended = length + self.metric.distance(&self.nodes[last], &self.nodes[next]);
+            if extended >= self.best.0 || extended > self.bound() {
+                continue;
+            }
+            self.visited[next] = true;
+            self.tour.push(next);
+            self.extend(extended);
+            self.tour.pop();
+            self.visited[next] = false;
+        }
+    }
+}
+
+/// the only tour of up to 3 nodes, the way round that's shorter on asymmetric metrics
+pub fn shortest_direction(nodes: &[Node], metric: &dyn Metric) -> Vec<Node> {
+    let mut backwards = nodes.to_vec();
+    if let Some(rest) = backwards.get_mut(1..) {
+        rest.reverse();
+    }
+    if metric.tour_length(&backwards) < metric.tour_length(nodes) {
+        backwards
+    } else {
+        nodes.to_vec()
+    }
+}
+
+/// exact dynamic program over subsets, O(n²·2ⁿ) time and O(n·2ⁿ) memory.
+/// returns the optimal tour starting at the first node and its length
+///
+/// the cheapest path from the first node through every subset of the others, ending at each
+/// node of the subset, is built from the paths through the subset without that node.
+/// 20 nodes take about a second and 80 MB, every extra node doubles both.
+/// an interrupted run returns the tour it was given
+pub fn tsp_held_karp(nodes: &[Node], metric: &dyn Metric) -> (Vec<Node>, f64) {
+    let n = nodes.len();
+    if n <= 3 {
+        let tour = shortest_direction(nodes, metric);
+        let length = metric.tour_length(&tour);
+        return (tour, length);
+    }
+    let distance: Vec<Vec<f64>> = nodes
+        .iter()
+        .map(|a| nodes.iter().map(|b| metric.distance(a, b)).collect())
+        .collect();
+
+    // node 0 is the start, bit j of a subset and column j stand for node j + 1
+    let m = n - 1;
+    let mut cost = vec![f64::INFINITY; m << m];
+    let mut parent = vec![0u8; m << m];
+    for j in 0..m {
+        cost[(1 << j) * m + j] = distance[0][j + 1];
+    }
+    for subset in 1usize..1 << m {
+        if subset % 4096 == 0 && cancel::is_cancelled() {
+            return (nodes.to_vec(), metric.tour_length(nodes));
+        }
+        for last in (0..m).filter(|&j| subset & 1 << j != 0) {
+            let before = subset & !(1 << last);
+            if before == 0 {
+                continue;
+            }
+            let mut best = (f64::INFINITY, 0);
+            for previous in (0..m).filter(|&j| before & 1 << j != 0) {
+                let length = cost[before * m + previous] + distance[previous + 1][last + 1];
+                if length < best.0 {
+                    best = (length, previous);
+                }
+            }
+            cost[subset * m + last] = best.0;
+            parent[subset * m + last] = best.1 as u8;
+        }
+    }
+
+    let full = (1 << m) - 1;
+    let mut last = (0..m)
+        .min_by(|&a, &b| {
+            let close = |j: usize| cost[full * m + j] + distance[j + 1][0];
+            close(a).total_cmp(&close(b))
+        })
+        .expect("there are nodes besides the first");
+    let mut order = Vec::with_capacity(n);
+    let mut subset = full;
+    while subset != 0 {
+        order.push(last + 1);
+        let previous = parent[subset * m + last] as usize;
+        subset &= !(1 << last);
+        last = previous;
+    }
+    order.push(0);
+    order.reverse();
+
+    let tour: Vec<Node> = order.into_iter().map(|i| nodes[i].clone()).collect();
+    let length = metric.tour_length(&tour);
+    (tour, length)
+}
+
+/// start at the last node and always choose closest next node
+pub fn tsp_nearest_neighbor(nodes: &[Node], metric: &dyn Metric) -> Vec<Node> {
+    tsp_nearest_neighbor_from(nodes, metric, nodes.len().saturating_sub(1))
+}
+
+/// nearest neighbor from the node at position `start`
+pub fn tsp_nearest_neighbor_from(nodes: &[Node], metric: &dyn Metric, start: usize) -> Vec<Node> {
+    let _scope = profiling::scope("nearest neighbor");
+    let mut leftovers: Vec<Node> = nodes.to_vec();
+    if leftovers.is_empty() {
+        return leftovers;
+    }
+    let mut nearest_neighbor: Vec<Node> = Vec::with_capacity(nodes.len());
+    nearest_neighbor.push(leftovers.swap_remove(start));
+
+    let mut distances = Vec::with_capacity(leftovers.len());
+    while let Some(last_neighbor) = nearest_neighbor.last() {
+        if leftovers.is_empty() {
+            break;
+        }
+        metric.distances(last_neighbor, &leftovers, &mut distances);
+        let mut smallest_distance: f64 = f64::INFINITY;
+        let mut nn_position: usize = 0;
+        for (i, &new_distance) in distances.iter().enumerate() {
+            if new_distance < smallest_distance {
+                smallest_distance = new_distance;
+                nn_position = i;
+            }
+        }
+
+        nearest_neighbor.push(leftovers.swap_remove(nn_position));
+    }
+
+    nearest_neighbor
+}
+
+/// nearest neighbor from every node on `threads` threads, the shortest tour wins and ties go to
+/// the earlier start, so the result doesn't depend on the thread count
+pub fn tsp_nearest_neighbor_all(nodes: &[Node], metric: &dyn Metric, threads: usize) -> Vec<Node> {
+    let tours = rng::parallel_map(nodes.len(), threads, |start| {
+        let tour = tsp_nearest_neighbor_from(nodes, metric, start);
+        (metric.tour_length(&tour), tour)
+    });
+    tours
+        .into_iter()
+        .min_by(|a, b| a.0.total_cmp(&b.0))
+        .map_or_else(Vec::new, |(_, tour)| tour)
+}
+
+/// nearest neighbor from the last node with a k-d tree of the leftovers, O(n log n) on uniform
+/// instances. it looks for the closest node by euclidean distance, whatever the metric,
+/// and takes the earlier node of the instance between equally close ones
+pub fn tsp_nearest_neighbor_kd(nodes: &[Node]) -> Vec<Node> {
+    let _scope = profiling::scope("nearest neighbor");
+    let Some(mut current) = nodes.len().checked_sub(1) else {
+        return Vec::new();
+    };
+    let mut tree = crate::spatial::KdTree::new(nodes);
+    let mut tour = Vec::with_capacity(nodes.len());
+    loop {
+        tree.remove(current);
+        tour.push(nodes[current].clone());
+        match tree.nearest(nodes[current].x, nodes[current].y) {
+            Some(next) => current = next,
+            None => break,
+        }
+    }
+    tour
+}
+
+/// two random indices, never equal
+fn random_indices(len: usize, rng: &mut impl Rng) -> (usize, usize) {
+    let a = rng.gen_range(0..len);
+    let b = loop {
+        let random = rng.gen_range(0..len);
+        if random != a {
+            break random;
+        }
+    };
+    (a, b)
+}
+
+/// swaps the nodes at a and b, reverses the nodes between them (a has to be the smaller)
+/// or moves the node at a to b
+fn apply_move(nodes: &mut [Node], moves: MoveType, a: usize, b: usize) {
+    match moves {
+        MoveType::Reversal => nodes[a..=b].reverse(),
+        MoveType::Insertion => move_node(nodes, a, b),
+        _ => nodes.swap(a, b),
+    }
+}
+
+/// position the node at `i` comes from once `apply_move` ran, without running it
+fn moved_from(moves: MoveType, a: usize, b: usize, i: usize) -> usize {
+    match moves {
+        MoveType::Reversal if (a..=b).contains(&i) => a + b - i,
+        MoveType::Insertion if i == b => a,
+        MoveType::Insertion if a < b && (a..b).contains(&i) => i + 1,
+        MoveType::Insertion if b < a && (b + 1..=a).contains(&i) => i - 1,
+        MoveType::Swap | MoveType::Mixed if i == a => b,
+        MoveType::Swap | MoveType::Mixed if i == b => a,
+        _ => i,
+    }
+}
+
+/// shifts the nodes in between by one
+fn move_node(nodes: &mut [Node], from: usize, to: usize) {
+    if from < to {
+        nodes[from..=to].rotate_left(1);
+    } else {
+        nodes[to..=from].rotate_right(1);
+    }
+}
+
+/// positions of the edges a move between a and b changes, before and after the move,
+/// an edge at i goes from node i to the next. a reversal flips the edges inside its
+/// segment too, which doesn't change their lengths on a symmetric metric, see `move_delta`
+fn changed_edges(moves: MoveType, a: usize, b: usize, n: usize) -> ([usize; 4], [usize; 4]) {
+    let before = |i: usize| (i + n - 1) % n;
+    match moves {
+        // the nodes in between shift towards where the moved node was
+        MoveType::Insertion if a < b => ([before(a), a, b, b], [before(a), before(b), b, b]),
+        MoveType::Insertion => ([before(b), before(a), a, a], [before(b), b, a, a]),
+        _ => {
+            let edges = [before(a), a, before(b), b];
+            (edges, edges)
+        }
+    }
+}
+
+/// length of the edges at the positions, each counted once
+fn edge_length(nodes: &[Node], starts: [usize; 4], metric: &dyn Metric) -> f64 {
+    moved_edge_length(nodes, starts, |i| i, metric)
+}
+
+/// `edge_length` of the tour the nodes would make with the node from `from(i)` at every `i`
+fn moved_edge_length(
+    nodes: &[Node],
+    mut starts: [usize; 4],
+    from: impl Fn(usize) -> usize,
+    metric: &dyn Metric,
+) -> f64 {
+    let n = nodes.len();
+    starts.sort_unstable();
+    let mut length = 0.0;
+    for (k, &i) in starts.iter().enumerate() {
+        if k == 0 || starts[k - 1] != i {
+            length += metric.distance(&nodes[from(i)], &nodes[from((i + 1) % n)]);
+        }
+    }
+    length
+}
+
+/// the kind of move `params` ask for and its indices
+fn random_move(params: &SaParams, n: usize, rng: &mut impl Rng) -> (MoveType, usize, usize) {
+    let moves = match params.moves {
+        MoveType::Mixed if rng.gen::<f64>() < params.reversal_share => MoveType::Reversal,
+        MoveType::Mixed => MoveType::Swap,
+        moves => moves,
+    };
+    let (a, b) = random_indices(n, rng);
+    match moves {
+        MoveType::Reversal => (moves, a.min(b), a.max(b)),
+        _ => (moves, a, b),
+    }
+}
+
+/// how much longer the move would make the tour, without making it.
+/// only the edges around the move change, O(1) instead of a pass over the tour,
+/// except for reversals on asymmetric metrics, whose segment is measured both ways
+fn move_delta(nodes: &[Node], moves: MoveType, a: usize, b: usize, metric: &dyn Metric) -> f64 {
+    let _scope = profiling::scope("move evaluation");
+    let from = |i| moved_from(moves, a, b, i);
+    if moves == MoveType::Reversal && !metric.is_symmetric() {
+        let n = nodes.len();
+        let ends = [(a + n - 1) % n, b, b, b];
+        let backwards: f64 = nodes[a..=b]
+            .windows(2)
+            .map(|pair| metric.distance(&pair[1], &pair[0]))
+            .sum();
+        let removed = edge_length(nodes, ends, metric) + open_length(&nodes[a..=b], metric);
+        return moved_edge_length(nodes, ends, from, metric) + backwards - removed;
+    }
+    let (before, after) = changed_edges(moves, a, b, nodes.len());
+    moved_edge_length(nodes, after, from, metric) - edge_length(nodes, before, metric)
+}
+
+/// length of the path through `nodes`, without going back to the first
+fn open_length(nodes: &[Node], metric: &dyn Metric) -> f64 {
+    nodes
+        .windows(2)
+        .map(|pair| metric.distance(&pair[0], &pair[1]))
+        .sum()
+}
+
+/// the temperature at which a typical longer move out of the tour is taken with
+/// `probability`, from the mean of `AUTO_SAMPLES` random moves that make it longer
+fn sampled_temperature(
+    nodes: &[Node],
+    params: &SaParams,
+    probability: f64,
+    metric: &dyn Metric,
+    rng: &mut impl Rng,
+) -> Option<f64> {
+    let (mut worse, mut count) = (0.0, 0);
+    for _ in 0..annealing::AUTO_SAMPLES {
+        let (moves, a, b) = random_move(params, nodes.len(), rng);
+        let delta = move_delta(nodes, moves, a, b, metric);
+        if delta > 0.0 {
+            worse += delta;
+            count += 1;
+        }
+    }
+    let probability = probability.clamp(f64::MIN_POSITIVE, 1.0 - f64::EPSILON);
+    (count > 0).then(|| -(worse / count as f64) / probability.ln())
+}
+
+/// searches for best tour by randomly swapping Nodes, reversing segments
+/// or moving single nodes, see `annealing::MoveType`, accepting moves with shorter tours.
+/// moves that beget longer tours are accepted based on a
+/// probability function that decreases over time
+///
+/// parameters default to the preset for the instance size, see `annealing`
+pub fn tsp_simulated_annealing(
+    nodes: &[Node],
+    metric: &dyn Metric,
+    params: &annealing::SaParams,
+    rng: &mut impl Rng,
+) -> Vec<Node> {
+    let mut acceptance = params.acceptance.build();
+    tsp_simulated_annealing_with(nodes, metric, params, acceptance.as_mut(), rng)
+}
+
+/// simulated annealing deciding moves with any acceptance rule, `params.acceptance` aside
+pub fn tsp_simulated_annealing_with(
+    nodes: &[Node],
+    metric: &dyn Metric,
+    params: &annealing::SaParams,
+    acceptance: &mut dyn annealing::Acceptance,
+    rng: &mut impl Rng,
+) -> Vec<Node> {
+    let mut annealed = nodes.to_vec();
+    if annealed.len() < 2 {
+        return annealed;
+    }
+    let mut current_length = metric.tour_length(&annealed);
+    // temperatures are relative to the edge lengths, whatever the scale of the coordinates
+    let relative = params.start_temperature * current_length / nodes.len() as f64;
+    let start_temp = match params.auto_temperature {
+        Some(probability) => {
+            sampled_temperature(&annealed, params, probability, metric, rng).unwrap_or(relative)
+        }
+        None => relative,
+    };
+    // every restart gets the same share of the iterations, the last one the rest
+    let runs = params
+        .restarts
+        .saturating_add(1)
+        .min(params.iterations.max(1));
+    let run_length = params.iterations / runs;
+    let mut temp = Temperature::over(params, start_temp, run_length);
+    acceptance.start(current_length);
+    // checked once, recording is off for nearly every run
+    let recording = events::is_recording();
+    let record_every = (params.iterations / events::CHECKPOINTS_PER_RUN).max(1);
+    // tours before reheats and restarts, the shortest one is the result unless the end beats it
+    let mut best: Option<(f64, Vec<Node>)> = None;
+    let keep = |best: &mut Option<(f64, Vec<Node>)>, length: f64, tour: &[Node]| {
+        if best.as_ref().is_none_or(|(shortest, _)| length < *shortest) {
+            *best = Some((length, tour.to_vec()));
+        }
+    };
+    let mut taken = 0;
+
+    for iteration in 0..params.iterations {
+        // stop early on ctrl-c, polled every now and then
+        if iteration % 1024 == 0 && cancel::is_cancelled() {
+            break;
+        }
+        let run = iteration / run_length;
+        if iteration > 0 && iteration % run_length == 0 && run < runs {
+            keep(&mut best, current_length, &annealed);
+            if let Some((length, tour)) = &best {
+                annealed.clone_from(tour);
+                current_length = *length;
+            }
+            let moves = if run + 1 == runs {
+                params.iterations - iteration
+            } else {
+                run_length
+            };
+            temp = Temperature::over(params, start_temp, moves);
+            acceptance.start(current_length);
+        }
+        let (moves, a, b) = random_move(params, annealed.len(), rng);
+        let new_length = current_length + move_delta(&annealed, moves, a, b, metric);
+
+        // debugging
+        // println!("length: {:.7}, temp: {:.7}, new length: {:.7}", current_length, temp, new_length);
+
+        // only moves the acceptance rule takes touch the tour
+        let accepted = acceptance.accept(current_length, new_length, temp.get(), rng);
+        if accepted {
+            apply_move(&mut annealed, moves, a, b);
+            current_length = new_length;
+        }
+
+        // cooling
+        temp.update(accepted);
+
+        // reheat once the moves hardly get taken, less the later in the run
+        if params.reheat > 0.0 {
+            taken += accepted as u32;
+            if (iteration + 1) % REHEAT_WINDOW == 0 {
+                if (taken as f64) < params.reheat * REHEAT_WINDOW as f64 {
+                    keep(&mut best, current_length, &annealed);
+                    temp.reheat(start_temp * temp.remaining());
+                }
+                taken = 0;
+            }
+        }
+
+        // add to history
+        if recording && iteration % record_every == 0 {
+            events::record(
+                Some(iteration as u64),
+                current_length,
+                Some(temp.get()),
+                &annealed,
+            );
+        }
+    }
+
+    match best {
+        Some((length, tour)) if length < current_length => tour,
+        _ => annealed,
+    }
+}
+
+/// 2-opt from the given tour, reversing segments until no reversal shortens it.
+/// on its own from any order, or as a polish after a constructor, e.g. `nn,2opt`
+pub fn tsp_two_opt(nodes: &[Node], metric: &dyn Metric) -> Vec<Node> {
+    let mut tour = nodes.to_vec();
+    local_search(&mut tour, &TwoOpt, metric);
+    tour
+}
+
+/// common interface of all tsp algorithms
+///
+/// the order of `nodes` is the starting tour, so improvers
+/// (like simulated annealing) can be fed the output of constructors.
+/// all distances have to come from `metric`,
+/// all randomness has to come from `rng` to keep runs reproducible,
+/// solvers are shared between threads by the parallel harnesses
+pub trait Solver: Send + Sync {
+    /// short name used in printouts
+    fn name(&self) -> &str;
+
+    fn solve(&self, nodes: &[Node], metric: &dyn Metric, rng: &mut StdRng) -> Vec<Node>;
+
+    /// largest instance the solver is able to handle in reasonable time
+    fn max_nodes(&self) -> usize {
+        usize::MAX
+    }
+
+    /// whether the solver's parameters fit the instance, like a start node that's in it
+    fn check(&self, _nodes: &[Node]) -> Result<(), String> {
+        Ok(())
+    }
+
+    /// `solve`, and the lengths it went through: the given tour, whatever the solver
+    /// records along the way, like annealing's checkpoints, and the result
+    fn solve_result(&self, nodes: &[Node], metric: &dyn Metric, rng: &mut StdRng) -> SolveResult {
+        let start = Instant::now();
+        let initial_length = metric.tour_length(nodes);
+        let (tour, recorded) = events::collect_history(|| self.solve(nodes, metric, rng));
+        let time = start.elapsed();
+        let length = metric.tour_length(&tour);
+
+        // pipelines record the tour they were given and every stage's already
+        let mut history = Vec::with_capacity(recorded.len() + 2);
+        if recorded.first() != Some(&initial_length) {
+            history.push(initial_length);
+        }
+        history.extend(recorded);
+        if history.last() != Some(&length) {
+            history.push(length);
+        }
+        SolveResult {
+            tour,
+            history,
+            stats: SolveStats {
+                initial_length,
+                length,
+                time,
+            },
+        }
+    }
+}
+
+/// the tour a solver found and how it got there
+#[derive(Debug, Clone)]
+pub struct SolveResult {
+    pub tour: Vec<Node>,
+    /// tour lengths from the given tour to the result, see `Solver::solve_result`
+    pub history: Vec<f64>,
+    pub stats: SolveStats,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SolveStats {
+    pub initial_length: f64,
+    pub length: f64,
+    pub time: Duration,
+}
+
+/// boxed solvers picked at runtime can be wrapped like any other, e.g. by `MultiStart`
+impl<S: Solver + ?Sized> Solver for Box<S> {
+    fn name(&self) -> &str {
+        (**self).name()
+    }
+
+    fn solve(&self, nodes: &[Node], metric: &dyn Metric, rng: &mut StdRng) -> Vec<Node> {
+        (**self).solve(nodes, metric, rng)
+    }
+
+    fn max_nodes(&self) -> usize {
+        (**self).max_nodes()
+    }
+
+    fn check(&self, nodes: &[Node]) -> Result<(), String> {
+        (**self).check(nodes)
+    }
+
+    fn solve_result(&self, nodes: &[Node], metric: &dyn Metric, rng: &mut StdRng) -> SolveResult {
+        (**self).solve_result(nodes, metric, rng)
+    }
+}
+
+/// every tour, see `tsp_brute_force_parallel`
+pub struct BruteForce {
+    pub threads: usize,
+}
+
+impl Solver for BruteForce {
+    fn name(&self) -> &str {
+        "brute force"
+    }
+
+    fn solve(&self, nodes: &[Node], metric: &dyn Metric, _rng: &mut StdRng) -> Vec<Node> {
+        tsp_brute_force_parallel(nodes, metric, self.threads)
+    }
+
+    /// 13 nodes take well under a second on uniform instances, 14 already a couple,
+    /// how much the pruning cuts depends on the instance
+    fn max_nodes(&self) -> usize {
+        13
+    }
+}
+
+/// exact optimum by dynamic programming, see `tsp_held_karp`
+pub struct HeldKarp;
+
+impl Solver for HeldKarp {
+    fn name(&self) -> &str {
+        "held-karp"
+    }
+
+    fn solve(&self, nodes: &[Node], metric: &dyn Metric, _rng: &mut StdRng) -> Vec<Node> {
+        tsp_held_karp(nodes, metric).0
+    }
+
+    /// about a second, and the memory doubles with every node beyond
+    fn max_nodes(&self) -> usize {
+        20
+    }
+}
+
+/// exact optimum by branch and bound, see `branch_and_bound`
+pub struct BranchAndBound;
+
+impl Solver for BranchAndBound {
+    fn name(&self) -> &str {
+        "branch and bound"
+    }
+
+    fn solve(&self, nodes: &[Node], metric: &dyn Metric, _rng: &mut StdRng) -> Vec<Node> {
+        crate::branch_and_bound::solve(nodes, metric).tour
+    }
+
+    /// uniform instances of this size take about a second, the bound does worse on others
+    fn max_nodes(&self) -> usize {
+        25
+    }
+}
+
+/// brute force with exact length comparisons, see `exact`
+#[cfg(feature = "exact")]
+pub struct ExactBruteForce;
+
+#[cfg(feature = "exact")]
+impl Solver for ExactBruteForce {
+    fn name(&self) -> &str {
+        "exact brute force"
+    }
+
+    fn solve(&self, nodes: &[Node], metric: &dyn Metric, _rng: &mut StdRng) -> Vec<Node> {
+        // non finite coordinates have no exact value, floats are all there is for them
+        crate::exact::tsp_brute_force_exact(nodes, metric)
+            .unwrap_or_else(|_| tsp_brute_force(nodes, metric))
+    }
+
+    /// the exact comparisons only run on near-ties, so it's about as fast as plain brute force
+    fn max_nodes(&self) -> usize {
+        9
+    }
+}
+
+pub struct NearestNeighbor;
+
+impl Solver for NearestNeighbor {
+    fn name(&self) -> &str {
+        "nearest neighbor"
+    }
+
+    fn solve(&self, nodes: &[Node], metric: &dyn Metric, _rng: &mut StdRng) -> Vec<Node> {
+        tsp_nearest_neighbor(nodes, metric)
+    }
+}
+
+/// nearest neighbor from the node with this id, or from the last node like `NearestNeighbor`
+/// if there's none, which `check` reports
+pub struct NearestNeighborFrom {
+    pub id: usize,
+}
+
+impl Solver for NearestNeighborFrom {
+    fn name(&self) -> &str {
+        "nearest neighbor"
+    }
+
+    fn solve(&self, nodes: &[Node], metric: &dyn Metric, _rng: &mut StdRng) -> Vec<Node> {
+        match nodes.iter().position(|node| node.id == self.id) {
+            Some(start) => tsp_nearest_neighbor_from(nodes, metric, start),
+            None => tsp_nearest_neighbor(nodes, metric),
+        }
+    }
+
+    fn check(&self, nodes: &[Node]) -> Result<(), String> {
+        match nodes.iter().any(|node| node.id == self.id) {
+            true => Ok(()),
+            false => Err(format!("start node {} isn't in the instance", self.id)),
+        }
+    }
+}
+
+/// the shortest nearest neighbor tour of all starts, see `tsp_nearest_neighbor_all`
+pub struct AllStartsNearestNeighbor {
+    pub threads: usize,
+}
+
+impl Solver for AllStartsNearestNeighbor {
+    fn name(&self) -> &str {
+        "nearest neighbor from all starts"
+    }
+
+    fn solve(&self, nodes: &[Node], metric: &dyn Metric, _rng: &mut StdRng) -> Vec<Node> {
+        tsp_nearest_neighbor_all(nodes, metric, self.threads)
+    }
+
+    /// n tours of n² steps each, 1000 nodes take about 2 seconds on one core
+    fn max_nodes(&self) -> usize {
+        1000
+    }
+}
+
+/// nearest neighbor with a k-d tree, see `tsp_nearest_neighbor_kd`
+pub struct KdNearestNeighbor;
+
+impl Solver for KdNearestNeighbor {
+    fn name(&self) -> &str {
+        "nearest neighbor (k-d tree)"
+    }
+
+    fn solve(&self, nodes: &[Node], _metric: &dyn Metric, _rng: &mut StdRng) -> Vec<Node> {
+        tsp_nearest_neighbor_kd(nodes)
+    }
+
+    /// a million nodes take about a second and a half
+    fn max_nodes(&self) -> usize {
+        1_000_000
+    }
+}
+
+/// greedy edge matching, see `construction::tsp_greedy_edge`
+pub struct GreedyEdge;
+
+impl Solver for GreedyEdge {
+    fn name(&self) -> &str {
+        "greedy edge"
+    }
+
+    fn solve(&self, nodes: &[Node], metric: &dyn Metric, _rng: &mut StdRng) -> Vec<Node> {
+        crate::construction::tsp_greedy_edge(nodes, metric)
+    }
+
+    /// the list of all edges takes about 200 MB here
+    fn max_nodes(&self) -> usize {
+        5000
+    }
+}
+
+/// cheapest insertion, see `construction::tsp_cheapest_insertion`
+pub struct CheapestInsertion;
+
+impl Solver for CheapestInsertion {
+    fn name(&self) -> &str {
+        "cheapest insertion"
+    }
+
+    fn solve(&self, nodes: &[Node], metric: &dyn Metric, _rng: &mut StdRng) -> Vec<Node> {
+        crate::construction::tsp_cheapest_insertion(nodes, metric)
+    }
+}
+
+/// clarke-wright savings from the first node, see `construction::tsp_savings`
+pub struct Savings {
+    pub threads: usize,
+}
+
+impl Solver for Savings {
+    fn name(&self) -> &str {
+        "savings"
+    }
+
+    fn solve(&self, nodes: &[Node], metric: &dyn Metric, _rng: &mut StdRng) -> Vec<Node> {
+        crate::construction::tsp_savings(nodes, metric, self.threads)
+    }
+
+    /// the list of all savings takes about 200 MB here
+    fn max_nodes(&self) -> usize {
+        5000
+    }
+}
+
+/// hilbert curve order, see `construction::tsp_space_filling_curve`
+pub struct SpaceFillingCurve;
+
+impl Solver for SpaceFillingCurve {
+    fn name(&self) -> &str {
+        "hilbert curve"
+    }
+
+    fn solve(&self, nodes: &[Node], _metric: &dyn Metric, _rng: &mut StdRng) -> Vec<Node> {
+        crate::construction::tsp_space_filling_curve(nodes)
+    }
+}
+
+/// convex hull insertion, see `construction::tsp_hull_insertion`
+pub struct HullInsertion;
+
+impl Solver for HullInsertion {
+    fn name(&self) -> &str {
+        "hull insertion"
+    }
+
+    fn solve(&self, nodes: &[Node], metric: &dyn Metric, _rng: &mut StdRng) -> Vec<Node> {
+        crate::construction::tsp_hull_insertion(nodes, metric)
+    }
+}
+
+/// farthest insertion, see `construction::tsp_farthest_insertion`
+pub struct FarthestInsertion;
+
+impl Solver for FarthestInsertion {
+    fn name(&self) -> &str {
+        "farthest insertion"
+    }
+
+    fn solve(&self, nodes: &[Node], metric: &dyn Metric, _rng: &mut StdRng) -> Vec<Node> {
+        crate::construction::tsp_farthest_insertion(nodes, metric)
+    }
+}
+
+/// uses the preset for the instance size, except for what the user overrides
+#[derive(Default)]
+pub struct SimulatedAnnealing {
+    pub overrides: SaOverrides,
+}
+
+impl Solver for SimulatedAnnealing {
+    fn name(&self) -> &str {
+        "simulated annealing"
+    }
+
+    fn solve(&self, nodes: &[Node], metric: &dyn Metric, rng: &mut StdRng) -> Vec<Node> {
+        let params = self.overrides.apply(SaParams::preset(nodes.len()));
+        tsp_simulated_annealing(nodes, metric, &params, rng)
+    }
+}
+
+/// grid decomposition for huge instances, nearest neighbor in cells of about `cell_size` nodes.
+/// see `decomposition`
+pub struct Decomposition {
+    pub cell_size: usize,
+}
+
+impl Default for Decomposition {
+    fn default() -> Self {
+        Decomposition { cell_size: 256 }
+    }
+}
+
+impl Solver for Decomposition {
+    fn name(&self) -> &str {
+        "decomposition"
+    }
+
+    fn solve(&self, nodes: &[Node], metric: &dyn Metric, rng: &mut StdRng) -> Vec<Node> {
+        crate::decomposition::solve(nodes, metric, self.cell_size, &NearestNeighbor, rng)
+    }
+}
+
+/// lin-kernighan from the given tour, chains of up to `depth` exchanges
+/// among the `neighbors` closest nodes of every node. see `lin_kernighan`
+pub struct LinKernighan {
+    pub depth: usize,
+    pub neighbors: usize,
+}
+
+impl Default for LinKernighan {
+    fn default() -> Self {
+        LinKernighan {
+            depth: 10,
+            neighbors: 8,
+        }
+    }
+}
+
+impl Solver for LinKernighan {
+    fn name(&self) -> &str {
+        "lin-kernighan"
+    }
+
+    fn solve(&self, nodes: &[Node], metric: &dyn Metric, _rng: &mut StdRng) -> Vec<Node> {
+        crate::lin_kernighan::solve(nodes, metric, self.depth, self.neighbors)
+    }
+}
+
+/// genetic algorithm, see `genetic`
+#[derive(Default)]
+pub struct Genetic {
+    pub params: GaParams,
+}
+
+impl Solver for Genetic {
+    fn name(&self) -> &str {
+        "genetic"
+    }
+
+    fn solve(&self, nodes: &[Node], metric: &dyn Metric, rng: &mut StdRng) -> Vec<Node> {
+        crate::genetic::tsp_genetic(nodes, metric, &self.params, rng).tour
+    }
+}
+
+/// the genetic algorithm breeding 2-opt optima, see `genetic`
+pub struct Memetic {
+    pub params: GaParams,
+}
+
+impl Default for Memetic {
+    fn default() -> Self {
+        Memetic {
+            params: GaParams::memetic(),
+        }
+    }
+}
+
+impl Solver for Memetic {
+    fn name(&self) -> &str {
+        "memetic"
+    }
+
+    fn solve(&self, nodes: &[Node], metric: &dyn Metric, rng: &mut StdRng) -> Vec<Node> {
+        crate::genetic::tsp_memetic(nodes, metric, &self.params, rng).tour
+    }
+}
+
+/// adaptive large neighborhood search with all the destroy and repair operators, see `alns`
+#[derive(Default)]
+pub struct Alns {
+    pub params: AlnsParams,
+}
+
+impl Solver for Alns {
+    fn name(&self) -> &str {
+        "alns"
+    }
+
+    fn solve(&self, nodes: &[Node], metric: &dyn Metric, rng: &mut StdRng) -> Vec<Node> {
+        crate::alns::tsp_alns(
+            nodes,
+            metric,
+            &crate::alns::destroy_operators(),
+            &crate::alns::repair_operators(),
+            &self.params,
+            rng,
+        )
+    }
+}
+
+/// 2-opt with penalties on the edges that keep showing up, see `gls`
+#[derive(Default)]
+pub struct GuidedLocalSearch {
+    pub params: GlsParams,
+}
+
+impl Solver for GuidedLocalSearch {
+    fn name(&self) -> &str {
+        "guided local search"
+    }
+
+    fn solve(&self, nodes: &[Node], metric: &dyn Metric, _rng: &mut StdRng) -> Vec<Node> {
+        crate::gls::tsp_guided_local_search(nodes, metric, &self.params)
+    }
+
+    /// the penalties are a full matrix, 36MB at this size
+    fn max_nodes(&self) -> usize {
+        3000
+    }
+}
+
+/// removes a few nodes and inserts them back, over and over, see `ruin_recreate`
+#[derive(Default)]
+pub struct RuinRecreate {
+    pub params: RuinParams,
+}
+
+impl Solver for RuinRecreate {
+    fn name(&self) -> &str {
+        "ruin and recreate"
+    }
+
+    fn solve(&self, nodes: &[Node], metric: &dyn Metric, rng: &mut StdRng) -> Vec<Node> {
+        crate::ruin_recreate::tsp_ruin_recreate(nodes, metric, &self.params, rng)
+    }
+}
+
+/// a self-organizing map read off as a tour, see `som`
+#[derive(Default)]
+pub struct SelfOrganizingMap {
+    pub params: SomParams,
+}
+
+impl Solver for SelfOrganizingMap {
+    fn name(&self) -> &str {
+        "som"
+    }
+
+    fn solve(&self, nodes: &[Node], _metric: &dyn Metric, rng: &mut StdRng) -> Vec<Node> {
+        crate::som::tsp_som(nodes, &self.params, 0, rng).tour
+    }
+
+    /// every iteration looks at every neuron, so the run is quadratic in the nodes
+    fn max_nodes(&self) -> usize {
+        2000
+    }
+}
+
+/// the cross-entropy method over transition probabilities, see `cross_entropy`
+pub struct CrossEntropy {
+    pub params: CeParams,
+    pub threads: usize,
+}
+
+impl Solver for CrossEntropy {
+    fn name(&self) -> &str {
+        "cross-entropy"
+    }
+
+    fn solve(&self, nodes: &[Node], metric: &dyn Metric, rng: &mut StdRng) -> Vec<Node> {
+        crate::cross_entropy::tsp_cross_entropy(nodes, metric, &self.params, self.threads, rng).tour
+    }
+
+    /// every sample is quadratic in the nodes, and it takes more rounds to converge
+    fn max_nodes(&self) -> usize {
+        200
+    }
+}
+
+/// tabu search over 2-opt moves, see `tabu`
+#[derive(Default)]
+pub struct TabuSearch {
+    pub params: TabuParams,
+}
+
+impl Solver for TabuSearch {
+    fn name(&self) -> &str {
+        "tabu search"
+    }
+
+    fn solve(&self, nodes: &[Node], metric: &dyn Metric, _rng: &mut StdRng) -> Vec<Node> {
+        crate::tabu::tsp_tabu_search(nodes, metric, &self.params)
+    }
+}
+
+/// randomized greedy constructions plus 2-opt, the best of them, see `grasp`
+#[derive(Default)]
+pub struct Grasp {
+    pub params: GraspParams,
+}
+
+impl Solver for Grasp {
+    fn name(&self) -> &str {
+        "grasp"
+    }
+
+    fn solve(&self, nodes: &[Node], metric: &dyn Metric, rng: &mut StdRng) -> Vec<Node> {
+        crate::grasp::tsp_grasp(nodes, metric, &self.params, rng)
+    }
+}
+
+/// 2-opt with double-bridge kicks, see `ils`
+pub struct IteratedLocalSearch {
+    pub iterations: usize,
+}
+
+impl Default for IteratedLocalSearch {
+    fn default() -> Self {
+        IteratedLocalSearch { iterations: 200 }
+    }
+}
+
+impl Solver for IteratedLocalSearch {
+    fn name(&self) -> &str {
+        "iterated local search"
+    }
+
+    fn solve(&self, nodes: &[Node], metric: &dyn Metric, rng: &mut StdRng) -> Vec<Node> {
+        crate::ils::tsp_iterated_local_search(nodes, metric, self.iterations, rng)
+    }
+}
+
+/// variable neighborhood search, see `vns`
+pub struct VariableNeighborhoodSearch {
+    pub iterations: usize,
+}
+
+impl Default for VariableNeighborhoodSearch {
+    fn default() -> Self {
+        VariableNeighborhoodSearch { iterations: 100 }
+    }
+}
+
+impl Solver for VariableNeighborhoodSearch {
+    fn name(&self) -> &str {
+        "variable neighborhood"
+    }
+
+    fn solve(&self, nodes: &[Node], metric: &dyn Metric, rng: &mut StdRng) -> Vec<Node> {
+        crate::vns::tsp_vns(nodes, metric, self.iterations, rng)
+    }
+}
+
+/// runs a move operator from the given tour until it's a local optimum, see `local_search`
+pub struct LocalSearch {
+    pub operator: Box<dyn MoveOperator>,
+    /// only tries moves to this many closest nodes of every node, see `local_search_near`.
+    /// unset unless asked for, with `near` or `neighbors=`: the lists are the closest nodes by
+    /// coordinates, and the nodes of cost matrices are only placeholders on a circle
+    pub neighbors: Option<usize>,
+    /// runs on this tour representation instead of the array, see `local_search_order`.
+    /// only for operators with `improve_order`
+    pub representation: Option<Representation>,
+}
+
+impl LocalSearch {
+    pub fn new(operator: impl MoveOperator + 'static) -> Self {
+        LocalSearch {
+            operator: Box::new(operator),
+            neighbors: None,
+            representation: None,
+        }
+    }
+
+    /// with `DEFAULT_NEIGHBORS` candidates per node
+    pub fn near(operator: impl MoveOperator + 'static) -> Self {
+        LocalSearch {
+            operator: Box::new(operator),
+            neighbors: Some(DEFAULT_NEIGHBORS),
+            representation: None,
+        }
+    }
+}
+
+impl Solver for LocalSearch {
+    fn name(&self) -> &str {
+        self.operator.name()
+    }
+
+    fn solve(&self, nodes: &[Node], metric: &dyn Metric, _rng: &mut StdRng) -> Vec<Node> {
+        let mut tour = nodes.to_vec();
+        let operator = self.operator.as_ref();
+        match (self.representation, self.neighbors) {
+            (Some(representation), neighbors) => {
+                let neighbors = neighbors.unwrap_or(DEFAULT_NEIGHBORS);
+                local_search_order(&mut tour, operator, metric, neighbors, representation)
+            }
+            (None, Some(neighbors)) => local_search_near(&mut tour, operator, metric, neighbors),
+            (None, None) => local_search(&mut tour, operator, metric),
+        };
+        tour
+    }
+}
+
+/// a local search stage from `key=value` parameters, `neighbors` for every operator, `tour` for
+/// the ones with `orders` and the others for `configure`, which tells whether it knew the key.
+/// without `neighbors` every move is tried, see `LocalSearch::neighbors` for why
+fn local_search_stage<O: MoveOperator + 'static>(
+    name: &str,
+    mut operator: O,
+    parameters: &str,
+    orders: bool,
+    mut configure: impl FnMut(&mut O, &str, &str) -> Result<bool, String>,
+) -> Result<Box<dyn Solver>, String> {
+    let (mut neighbors, mut representation) = (None, None);
+    for parameter in parameters.split(':').filter(|p| !p.is_empty()) {
+        let (key, value) = parameter
+            .split_once('=')
+            .ok_or_else(|| format!("expected key=value, got '{}'", parameter))?;
+        if key == "neighbors" {
+            let k: usize = value
+                .parse()
+                .map_err(|err| format!("invalid {} '{}': {}", key, value, err))?;
+            if k == 0 {
+                return Err("candidate lists need at least one node".to_string());
+            }
+            neighbors = Some(k);
+        } else if key == "tour" && orders {
+            representation = Some(value.parse()?);
+        } else if !configure(&mut operator, key, value)? {
+            return Err(format!("unknown {} parameter '{}'", name, key));
+        }
+    }
+    Ok(Box::new(LocalSearch {
+        operator: Box::new(operator),
+        neighbors,
+        representation,
+    }))
+}
+
+/// every solver the crate knows about, e.g. for tournaments
+pub fn registered_solvers() -> Vec<Box<dyn Solver>> {
+    vec![
+        // tournaments already play their games in parallel
+        Box::new(BruteForce { threads: 1 }),
+        Box::new(NearestNeighbor),
+        Box::new(SimulatedAnnealing::default()),
+        Box::new(
+            Pipeline::new()
+                .then(NearestNeighbor)
+                .then(SimulatedAnnealing::default()),
+        ),
+        Box::new(
+            Pipeline::new()
+                .then(NearestNeighbor)
+                .then(LocalSearch::new(TwoHOpt)),
+        ),
+    ]
+}
+
+/// looks up a solver by its short or long cli name, e.g. "nn" or "nearest-neighbor"
+///
+/// simulated annealing takes parameters after the name, e.g. "sa:iterations=5000:cooling=0.999",
+/// decomposition its cell size, e.g. "dc:cell=500",
+/// 3-opt its improvement strategy, e.g. "3opt:improvement=first",
+/// or-opt its longest segment, e.g. "oropt:segment=5",
+/// iterated local search and variable neighborhood search their iterations,
+/// e.g. "ils:iterations=500" or "vns:iterations=500",
+/// lin-kernighan its depth and candidates, e.g. "lk:depth=20:neighbors=10",
+/// the genetic and memetic algorithms, tabu search, grasp, alns, guided local search,
+/// ruin and recreate, the self-organizing map and cross-entropy just like annealing,
+/// e.g. "ga:population=200", "memetic:generations=100", "tabu:tenure=30", "grasp:alpha=0.05",
+/// "alns:removal=0.3", "gls:alpha=0.1", "rr:removal=30", "som:neurons=4" or "ce:samples=500".
+/// a count and an x in front restart the solver, e.g. "8xsa" keeps the best of 8 annealings
+pub fn solver_by_name(name: &str) -> Result<Box<dyn Solver>, String> {
+    if let Some((starts, inner)) = name.split_once('x') {
+        if let Ok(starts) = starts.parse::<usize>() {
+            if starts == 0 {
+                return Err("a multi-start needs at least one start".to_string());
+            }
+            let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+            return Ok(Box::new(
+                MultiStart::new(solver_by_name(inner)?, starts).threads(threads),
+            ));
+        }
+    }
+    let (name, parameters) = name.split_once(':').unwrap_or((name, ""));
+    let solver: Box<dyn Solver> = match name {
+        "sa" | "simulated-annealing" => {
+            return Ok(Box::new(SimulatedAnnealing {
+                overrides: parameters.parse()?,
+            }))
+        }
+        "dc" | "decomposition" => {
+            let mut solver = Decomposition::default();
+            if !parameters.is_empty() {
+                let size = parameters
+                    .strip_prefix("cell=")
+                    .ok_or_else(|| format!("unknown decomposition parameter '{}'", parameters))?;
+                solver.cell_size = size
+                    .parse()
+                    .map_err(|err| format!("invalid cell size '{}': {}", size, err))?;
+            }
+            return Ok(Box::new(solver));
+        }
+        "3opt" | "3-opt" => {
+            return local_search_stage("3-opt", ThreeOpt::default(), parameters, false, |op, key, value| {
+                match key {
+                    "improvement" => op.improvement = value.parse()?,
+                    _ => return Ok(false),
+                }
+                Ok(true)
+            })
+        }
+        "tabu" | "tabu-search" => {
+            return Ok(Box::new(TabuSearch {
+                params: parameters.parse()?,
+            }))
+        }
+        "ils" | "iterated-local-search" => {
+            let mut solver = IteratedLocalSearch::default();
+            if !parameters.is_empty() {
+                let iterations = parameters
+                    .strip_prefix("iterations=")
+                    .ok_or_else(|| format!("unknown ils parameter '{}'", parameters))?;
+                solver.iterations = iterations
+                    .parse()
+                    .map_err(|err| format!("invalid iterations '{}': {}", iterations, err))?;
+            }
+            return Ok(Box::new(solver));
+        }
+        "vns" | "variable-neighborhood-search" => {
+            let mut solver = VariableNeighborhoodSearch::default();
+            if !parameters.is_empty() {
+                let iterations = parameters
+                    .strip_prefix("iterations=")
+                    .ok_or_else(|| format!("unknown vns parameter '{}'", parameters))?;
+                solver.iterations = iterations
+                    .parse()
+                    .map_err(|err| format!("invalid iterations '{}': {}", iterations, err))?;
+            }
+            return Ok(Box::new(solver));
+        }
+        "gls" | "guided-local-search" => {
+            return Ok(Box::new(GuidedLocalSearch {
+                params: parameters.parse()?,
+            }))
+        }
+        "rr" | "ruin-recreate" => {
+            return Ok(Box::new(RuinRecreate {
+                params: parameters.parse()?,
+            }))
+        }
+        "som" | "self-organizing-map" => {
+            return Ok(Box::new(SelfOrganizingMap {
+                params: parameters.parse()?,
+            }))
+        }
+        "ce" | "cross-entropy" => {
+            return Ok(Box::new(CrossEntropy {
+                params: parameters.parse()?,
+                threads: std::thread::available_parallelism().map_or(1, |n| n.get()),
+            }))
+        }
+        "alns" => {
+            return Ok(Box::new(Alns {
+                params: parameters.parse()?,
+            }))
+        }
+        "grasp" => {
+            return Ok(Box::new(Grasp {
+                params: parameters.parse()?,
+            }))
+        }
+        "ga" | "genetic" => {
+            return Ok(Box::new(Genetic {
+                params: parameters.parse()?,
+            }))
+        }
+        "memetic" => {
+            return Ok(Box::new(Memetic {
+                params: GaParams::memetic().overridden(parameters)?,
+            }))
+        }
+        "lk" | "lin-kernighan" => {
+            let mut solver = LinKernighan::default();
+            for parameter in parameters.split(':').filter(|p| !p.is_empty()) {
+                let (key, value) = parameter
+                    .split_once('=')
+                    .ok_or_else(|| format!("expected key=value, got '{}'", parameter))?;
+                let value = value
+                    .parse()
+                    .map_err(|err| format!("invalid {} '{}': {}", key, value, err))?;
+                match key {
+                    "depth" => solver.depth = value,
+                    "neighbors" => solver.neighbors = value,
+                    _ => return Err(format!("unknown lin-kernighan parameter '{}'", key)),
+                }
+            }
+            return Ok(Box::new(solver));
+        }
+        "oropt" | "or-opt" => {
+            return local_search_stage("or-opt", OrOpt::default(), parameters, true, |op, key, value| {
+                match key {
+                    "segment" => {
+                        op.max_segment = value
+                            .parse()
+                            .map_err(|err| format!("invalid segment length '{}': {}", value, err))?
+                    }
+                    _ => return Ok(false),
+                }
+                Ok(true)
+            })
+        }
+        "bf" | "brute-force" => Box::new(BruteForce {
+            threads: std::thread::available_parallelism().map_or(1, |n| n.get()),
+        }),
+        "hk" | "held-karp" => Box::new(HeldKarp),
+        "bb" | "branch-and-bound" => Box::new(BranchAndBound),
+        #[cfg(feature = "exact")]
+        "bf-exact" | "exact-brute-force" => Box::new(ExactBruteForce),
+        "nn" | "nearest-neighbor" => {
+            if parameters.is_empty() {
+                return Ok(Box::new(NearestNeighbor));
+            }
+            let start = parameters
+                .strip_prefix("start=")
+                .ok_or_else(|| format!("unknown nearest neighbor parameter '{}'", parameters))?;
+            if start == "all" {
+                return Ok(Box::new(AllStartsNearestNeighbor {
+                    threads: std::thread::available_parallelism().map_or(1, |n| n.get()),
+                }));
+            }
+            let id = start
+                .parse()
+                .map_err(|err| format!("invalid start node '{}': {}", start, err))?;
+            return Ok(Box::new(NearestNeighborFrom { id }));
+        }
+        "nn-kd" | "kd-nearest-neighbor" => Box::new(KdNearestNeighbor),
+        "greedy" | "greedy-edge" => Box::new(GreedyEdge),
+        "ci" | "cheapest-insertion" => Box::new(CheapestInsertion),
+        "fi" | "farthest-insertion" => Box::new(FarthestInsertion),
+        "hull" | "hull-insertion" => Box::new(HullInsertion),
+        "hilbert" | "space-filling-curve" => Box::new(SpaceFillingCurve),
+        "cw" | "savings" => Box::new(Savings {
+            threads: std::thread::available_parallelism().map_or(1, |n| n.get()),
+        }),
+        "swap" => Box::new(LocalSearch::new(Swap)),
+        "2opt" | "2-opt" => {
+            return local_search_stage("2-opt", TwoOpt, parameters, true, |_, _, _| Ok(false))
+        }
+        "2h" | "2h-opt" | "2.5-opt" => Box::new(LocalSearch::new(TwoHOpt)),
+        _ => return Err(format!(
+            "unknown solver '{}', try bf, hk, bb, nn, nn-kd, greedy, ci, fi, hull, cw, hilbert, sa, ga, memetic, tabu, grasp, ils, vns, alns, gls, rr, som, ce, dc, swap, 2opt, 2h, 3opt, oropt or lk",
+            name
+        )),
+    };
+    if !parameters.is_empty() {
+        return Err(format!("{} doesn't take parameters", solver.name()));
+    }
+    Ok(solver)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::random_nodes;
+    use crate::solvers::*;
+
+    #[test]
+    fn test_deltas_match_lengths_of_the_moved_tours() {
+        use crate::metric::Euclidean;
+
+        let mut rng = rand::thread_rng();
+        let mut tour = random_nodes(7, &mut rng);
+        let mut check = tour.clone();
+        for moves in [MoveType::Swap, MoveType::Reversal, MoveType::Insertion] {
+            for _ in 0..50 {
+                let (a, b) = random_indices(tour.len(), &mut rng);
+                let (a, b) = match moves {
+                    MoveType::Reversal => (a.min(b), a.max(b)),
+                    _ => (a, b),
+                };
+                let length = Euclidean.tour_length(&tour);
+                let delta = move_delta(&tour, moves, a, b, &Euclidean);
+                assert_eq!(tour, check, "measuring a move leaves the tour alone");
+                apply_move(&mut tour, moves, a, b);
+                assert!((length + delta - Euclidean.tour_length(&tour)).abs() < 1e-9);
+                check.clone_from(&tour);
+            }
+        }
+
+        // reversals turn the edges inside around, which matters when they cost more one way
+        let rows: Vec<Vec<f64>> = (0..7)
+            .map(|i| {
+                (0..7)
+                    .map(|j| {
+                        if j > i {
+                            2.0 * j as f64
+                        } else {
+                            (i - j) as f64
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+        let matrix = crate::explicit::ExplicitMatrix::directed(&rows).unwrap();
+        let mut tour = matrix.nodes();
+        for _ in 0..50 {
+            let (moves, a, b) = random_move(&SaParams::preset(7), 7, &mut rng);
+            let length = matrix.tour_length(&tour);
+            let delta = move_delta(&tour, moves, a, b, &matrix);
+            apply_move(&mut tour, moves, a, b);
+            assert!((length + delta - matrix.tour_length(&tour)).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn test_restarts_and_reheats_share_the_iterations() {
+        use crate::metric::Euclidean;
+        use rand::SeedableRng;
+
+        let nodes = random_nodes(30, &mut StdRng::seed_from_u64(42));
+        for overrides in ["restarts=3", "restarts=500:iterations=100", "reheat=1"] {
+            let overrides: SaOverrides = overrides.parse().unwrap();
+            let params = overrides.apply(SaParams::preset(nodes.len()));
+            let tour = tsp_simulated_annealing(
+                &nodes,
+                &Euclidean,
+                &params,
+                &mut StdRng::seed_from_u64(43),
+            );
+            let ids: Vec<usize> = tour.iter().map(|n| n.id).collect();
+            assert!(crate::tour::Tour::from_ids(&ids, &nodes).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_sampled_temperature_follows_the_scale() {
+        use crate::metric::Euclidean;
+        use rand::SeedableRng;
+
+        let mut nodes = random_nodes(50, &mut StdRng::seed_from_u64(44));
+        let mut scaled: Vec<Node> = nodes
+            .iter()
+            .map(|n| Node {
+                x: n.x * 10.0,
+                y: n.y * 10.0,
+                ..n.clone()
+            })
+            .collect();
+        let check = nodes.clone();
+        let params = SaParams::preset(nodes.len());
+        let sample = |nodes: &mut [Node]| {
+            let mut rng = StdRng::seed_from_u64(45);
+            sampled_temperature(nodes, &params, 0.8, &Euclidean, &mut rng).unwrap()
+        };
+        let (t, t_scaled) = (sample(&mut nodes), sample(&mut scaled));
+        assert_eq!(nodes, check);
+        assert!((t_scaled / t - 10.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_every_solver_reports_history() {
+        use crate::metric::Euclidean;
+        use rand::SeedableRng;
+
+        let nodes = random_nodes(30, &mut StdRng::seed_from_u64(46));
+        let mut rng = StdRng::seed_from_u64(47);
+        let nn = NearestNeighbor.solve_result(&nodes, &Euclidean, &mut rng);
+        assert_eq!(nn.history, vec![nn.stats.initial_length, nn.stats.length]);
+
+        let sa = solver_by_name("sa:iterations=10000")
+            .unwrap()
+            .solve_result(&nn.tour, &Euclidean, &mut rng);
+        assert!(sa.history.len() as u32 > events::CHECKPOINTS_PER_RUN);
+        assert_eq!(sa.history[0], nn.stats.length);
+        assert_eq!(sa.history.last(), Some(&sa.stats.length));
+        assert_eq!(sa.stats.length, Euclidean.tour_length(&sa.tour));
+    }
+
+    #[test]
+    fn test_held_karp_matches_brute_force() {
+        use crate::metric::Euclidean;
+        use rand::SeedableRng;
+
+        let nodes = random_nodes(8, &mut StdRng::seed_from_u64(3));
+        let (tour, length) = tsp_held_karp(&nodes, &Euclidean);
+        assert_eq!(tour.len(), nodes.len());
+        assert_eq!(tour[0], nodes[0]);
+        let optimum = Euclidean.tour_length(&tsp_brute_force(&nodes, &Euclidean));
+        assert!((length - optimum).abs() < 1e-9);
+
+        let nodes = random_nodes(11, &mut StdRng::seed_from_u64(48));
+        let (_, length) = tsp_held_karp(&nodes, &Euclidean);
+        let tour = tsp_brute_force(&nodes, &Euclidean);
+        assert_eq!(tour[0], nodes[0]);
+        let ids: Vec<usize> = tour.iter().map(|n| n.id).collect();
+        assert!(crate::tour::Tour::from_ids(&ids, &nodes).is_ok());
+        assert!((length - Euclidean.tour_length(&tour)).abs() < 1e-9);
+        assert_eq!(tsp_brute_force_parallel(&nodes, &Euclidean, 4), tour);
+    }
+
+    #[test]
+    fn test_nearest_neighbor_starts() {
+        use crate::metric::Euclidean;
+        use rand::SeedableRng;
+
+        let nodes = random_nodes(40, &mut StdRng::seed_from_u64(49));
+        let last = nodes.len() - 1;
+        assert_eq!(
+            tsp_nearest_neighbor(&nodes, &Euclidean),
+            tsp_nearest_neighbor_from(&nodes, &Euclidean, last)
+        );
+        let from = tsp_nearest_neighbor_from(&nodes, &Euclidean, 7);
+        assert_eq!(from[0], nodes[7]);
+
+        let best = tsp_nearest_neighbor_all(&nodes, &Euclidean, 1);
+        let shortest = (0..nodes.len())
+            .map(|start| {
+                Euclidean.tour_length(&tsp_nearest_neighbor_from(&nodes, &Euclidean, start))
+            })
+            .fold(f64::INFINITY, f64::min);
+        assert_eq!(Euclidean.tour_length(&best), shortest);
+        assert_eq!(tsp_nearest_neighbor_all(&nodes, &Euclidean, 4), best);
+
+        let mut rng = StdRng::seed_from_u64(50);
+        let solver = solver_by_name(&format!("nn:start={}", nodes[7].id)).unwrap();
+        assert_eq!(solver.solve(&nodes, &Euclidean, &mut rng), from);
+        assert!(solver.check(&nodes).is_ok());
+        let missing = solver_by_name("nn:start=40").unwrap();
+        let pipeline = crate::pipeline::Pipeline::new().then_boxed(missing);
+        assert_eq!(
+            pipeline.check(&nodes).unwrap_err(),
+            "start node 40 isn't in the instance"
+        );
+        assert!(solver_by_name("nn:start=all").is_ok());
+        assert!(solver_by_name("nn:first=3").is_err());
+    }
+
+    #[test]
+    fn test_kd_nearest_neighbor_matches_the_scan() {
+        use crate::metric::Euclidean;
+        use rand::SeedableRng;
+
+        let nodes = random_nodes(500, &mut StdRng::seed_from_u64(53));
+        assert_eq!(
+            tsp_nearest_neighbor_kd(&nodes),
+            tsp_nearest_neighbor(&nodes, &Euclidean)
+        );
+        assert!(tsp_nearest_neighbor_kd(&[]).is_empty());
+    }
+}
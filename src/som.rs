@@ -0,0 +1,202 @@
+//! self-organizing map, or elastic net: a ring of neurons pulled towards the nodes
+//!
+//! every iteration takes a random node, finds the closest neuron and moves it and its neighbors
+//! on the ring towards the node, the neighbors less the further away they are. learning rate and
+//! neighborhood both shrink over the run, from a tenth of the ring to single neurons, until the
+//! ring runs through all nodes. the tour visits the nodes in the order of their closest neurons.
+//! it works on the coordinates scaled into the unit square and ignores the metric
+
+use std::str::FromStr;
+
+use rand::rngs::StdRng;
+use rand::Rng;
+
+use crate::cancel;
+use crate::Node;
+
+/// everything `tsp_som` can be tuned with
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SomParams {
+    /// neurons per node
+    pub neurons: usize,
+    /// iterations per node
+    pub epochs: usize,
+    /// how far the closest neuron moves towards its node at the start
+    pub learning_rate: f64,
+}
+
+impl Default for SomParams {
+    fn default() -> Self {
+        SomParams {
+            neurons: 8,
+            epochs: 100,
+            learning_rate: 0.8,
+        }
+    }
+}
+
+/// `key=value` pairs separated by `:` over the defaults, e.g. `neurons=4:epochs=300`
+impl FromStr for SomParams {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        fn value<T: FromStr>(key: &str, value: &str) -> Result<T, String>
+        where
+            T::Err: std::fmt::Display,
+        {
+            value
+                .parse()
+                .map_err(|err| format!("invalid {} '{}': {}", key, value, err))
+        }
+
+        let mut params = SomParams::default();
+        for pair in s.split(':').filter(|p| !p.is_empty()) {
+            let Some((key, v)) = pair.split_once('=') else {
+                return Err(format!("expected key=value, got '{}'", pair));
+            };
+            match key {
+                "neurons" => params.neurons = value(key, v)?,
+                "epochs" => params.epochs = value(key, v)?,
+                "learning" => params.learning_rate = value(key, v)?,
+                _ => {
+                    return Err(format!(
+                        "unknown parameter '{}', try neurons, epochs or learning",
+                        key
+                    ))
+                }
+            }
+        }
+        if params.neurons == 0 {
+            return Err("the ring needs at least one neuron per node".to_string());
+        }
+        Ok(params)
+    }
+}
+
+/// the decoded tour, and the ring at evenly spaced points of the run
+#[derive(Debug, Clone)]
+pub struct SomRun {
+    pub tour: Vec<Node>,
+    /// neurons as nodes in the coordinates of the instance, ids are their places on the ring
+    pub rings: Vec<Vec<Node>>,
+}
+
+/// learning rate at the end of the run
+const FINAL_LEARNING_RATE: f64 = 0.2;
+
+/// the closest neuron to (x, y)
+fn winner(ring: &[(f64, f64)], x: f64, y: f64) -> usize {
+    let d = |&(nx, ny): &(f64, f64)| (nx - x).powi(2) + (ny - y).powi(2);
+    (0..ring.len())
+        .min_by(|&a, &b| d(&ring[a]).total_cmp(&d(&ring[b])))
+        .expect("the ring isn't empty")
+}
+
+/// trains the ring and reads the tour off it, with `frames` snapshots of the ring on the way
+/// and the trained ring last
+pub fn tsp_som(nodes: &[Node], params: &SomParams, frames: usize, rng: &mut StdRng) -> SomRun {
+    let n = nodes.len();
+    if n < 4 {
+        return SomRun {
+            tour: nodes.to_vec(),
+            rings: Vec::new(),
+        };
+    }
+    let min_x = nodes.iter().map(|n| n.x).fold(f64::INFINITY, f64::min);
+    let min_y = nodes.iter().map(|n| n.y).fold(f64::INFINITY, f64::min);
+    let side = nodes
+        .iter()
+        .map(|n| (n.x - min_x).max(n.y - min_y))
+        .fold(f64::MIN_POSITIVE, f64::max);
+    let scaled: Vec<(f64, f64)> = nodes
+        .iter()
+        .map(|n| ((n.x - min_x) / side, (n.y - min_y) / side))
+        .collect();
+    let snapshot = |ring: &[(f64, f64)]| -> Vec<Node> {
+        ring.iter()
+            .enumerate()
+            .map(|(id, &(x, y))| Node {
+                id,
+                x: min_x + x * side,
+                y: min_y + y * side,
+            })
+            .collect()
+    };
+
+    // a small circle in the middle
+    let m = n * params.neurons.max(1);
+    let mut ring: Vec<(f64, f64)> = (0..m)
+        .map(|k| {
+            let angle = std::f64::consts::TAU * k as f64 / m as f64;
+            (0.5 + 0.1 * angle.cos(), 0.5 + 0.1 * angle.sin())
+        })
+        .collect();
+    let iterations = (n * params.epochs).max(1);
+    let start_radius = (m as f64 / 10.0).max(1.0);
+    let radius_decay = start_radius.powf(-1.0 / iterations as f64);
+    let learning_decay = (FINAL_LEARNING_RATE / params.learning_rate).powf(1.0 / iterations as f64);
+    let (mut radius, mut learning_rate) = (start_radius, params.learning_rate);
+
+    let mut rings = Vec::with_capacity(frames);
+    for iteration in 0..iterations {
+        if cancel::is_cancelled() {
+            break;
+        }
+        if frames > 0 && iteration % (iterations / frames).max(1) == 0 && rings.len() < frames {
+            rings.push(snapshot(&ring));
+        }
+        let (x, y) = scaled[rng.gen_range(0..n)];
+        let closest = winner(&ring, x, y);
+        // neurons beyond 3 radii would barely move
+        let reach = ((3.0 * radius) as usize).min(m / 2);
+        for offset in -(reach as isize)..=reach as isize {
+            let k = (closest as isize + offset).rem_euclid(m as isize) as usize;
+            let pull = learning_rate * (-(offset as f64).powi(2) / (2.0 * radius * radius)).exp();
+            let neuron = &mut ring[k];
+            neuron.0 += pull * (x - neuron.0);
+            neuron.1 += pull * (y - neuron.1);
+        }
+        radius *= radius_decay;
+        learning_rate *= learning_decay;
+    }
+    if frames > 0 {
+        rings.push(snapshot(&ring));
+    }
+
+    let mut order: Vec<(usize, usize)> = scaled
+        .iter()
+        .enumerate()
+        .map(|(i, &(x, y))| (winner(&ring, x, y), i))
+        .collect();
+    order.sort_unstable();
+    SomRun {
+        tour: order.iter().map(|&(_, i)| nodes[i].clone()).collect(),
+        rings,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::metric::{Euclidean, Metric};
+    use crate::random_nodes;
+    use crate::solvers::tsp_nearest_neighbor;
+    use crate::som::*;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_ring_decodes_to_short_tour() {
+        let nodes = random_nodes(60, &mut StdRng::seed_from_u64(38));
+        let run = tsp_som(
+            &nodes,
+            &SomParams::default(),
+            5,
+            &mut StdRng::seed_from_u64(39),
+        );
+        let ids: Vec<usize> = run.tour.iter().map(|n| n.id).collect();
+        assert!(crate::tour::Tour::from_ids(&ids, &nodes).is_ok());
+        assert_eq!(run.rings.len(), 6);
+        assert!(run.rings.iter().all(|ring| ring.len() == 480));
+        let nn = tsp_nearest_neighbor(&nodes, &Euclidean);
+        assert!(Euclidean.tour_length(&run.tour) < Euclidean.tour_length(&nn) * 1.1);
+    }
+}
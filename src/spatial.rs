@@ -0,0 +1,209 @@
+//! k-d tree over the coordinates of the nodes, for closest node queries in O(log n)
+//!
+//! the tree is stored in an array: the node in the middle of a range splits it, by x on even
+//! depths and by y on odd ones, and the halves left and right of it are its subtrees.
+//! nodes can be removed, so that queries only find the ones that are left, and every subtree
+//! counts the nodes left in it to skip empty ones. distances are euclidean
+
+use crate::Node;
+
+pub struct KdTree {
+    /// x, y and position in the nodes the tree was built from
+    points: Vec<(f64, f64, usize)>,
+    /// nodes left in the subtree of every slot
+    alive: Vec<usize>,
+    removed: Vec<bool>,
+    /// slot of every position
+    slots: Vec<usize>,
+}
+
+impl KdTree {
+    /// O(n log n)
+    pub fn new(nodes: &[Node]) -> Self {
+        let mut points: Vec<(f64, f64, usize)> = nodes
+            .iter()
+            .enumerate()
+            .map(|(position, node)| (node.x, node.y, position))
+            .collect();
+        let mut alive = vec![0; nodes.len()];
+        build(&mut points, &mut alive, 0, 0);
+        let mut slots = vec![0; nodes.len()];
+        for (slot, point) in points.iter().enumerate() {
+            slots[point.2] = slot;
+        }
+        KdTree {
+            points,
+            alive,
+            removed: vec![false; nodes.len()],
+            slots,
+        }
+    }
+
+    /// nodes that haven't been removed
+    pub fn len(&self) -> usize {
+        self.alive.get(self.points.len() / 2).copied().unwrap_or(0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// takes the node at `position` out of all further queries
+    pub fn remove(&mut self, position: usize) {
+        let slot = self.slots[position];
+        if self.removed[slot] {
+            return;
+        }
+        self.removed[slot] = true;
+        let (mut lo, mut hi) = (0, self.points.len());
+        loop {
+            let mid = (lo + hi) / 2;
+            self.alive[mid] -= 1;
+            if slot == mid {
+                break;
+            }
+            if slot < mid {
+                hi = mid;
+            } else {
+                lo = mid + 1;
+            }
+        }
+    }
+
+    /// position of the closest node left to (x, y), the earlier one of equally close nodes
+    pub fn nearest(&self, x: f64, y: f64) -> Option<usize> {
+        self.k_nearest(x, y, 1).first().copied()
+    }
+
+    /// positions of the `k` closest nodes left to (x, y), closest first
+    pub fn k_nearest(&self, x: f64, y: f64, k: usize) -> Vec<usize> {
+        let mut found: Vec<(f64, usize)> = Vec::with_capacity(k + 1);
+        if k > 0 {
+            self.search(0, self.points.len(), 0, x, y, k, &mut found);
+        }
+        found.into_iter().map(|(_, position)| position).collect()
+    }
+
+    /// keeps the k closest nodes of the subtree of lo..hi in `found`, sorted by squared distance
+    #[allow(clippy::too_many_arguments)]
+    fn search(
+        &self,
+        lo: usize,
+        hi: usize,
+        depth: usize,
+        x: f64,
+        y: f64,
+        k: usize,
+        found: &mut Vec<(f64, usize)>,
+    ) {
+        if lo >= hi {
+            return;
+        }
+        let mid = (lo + hi) / 2;
+        if self.alive[mid] == 0 {
+            return;
+        }
+        let (px, py, position) = self.points[mid];
+        if !self.removed[mid] {
+            let candidate = ((px - x).powi(2) + (py - y).powi(2), position);
+            let at = found.partition_point(|entry| {
+                (entry.0.total_cmp(&candidate.0))
+                    .then(entry.1.cmp(&candidate.1))
+                    .is_lt()
+            });
+            if at < k {
+                found.insert(at, candidate);
+                found.truncate(k);
+            }
+        }
+        let offset = if depth.is_multiple_of(2) {
+            x - px
+        } else {
+            y - py
+        };
+        let (near, far) = if offset < 0.0 {
+            ((lo, mid), (mid + 1, hi))
+        } else {
+            ((mid + 1, hi), (lo, mid))
+        };
+        self.search(near.0, near.1, depth + 1, x, y, k, found);
+        // equally close nodes beyond the split can still win on their position
+        if found.len() < k || offset * offset <= found[found.len() - 1].0 {
+            self.search(far.0, far.1, depth + 1, x, y, k, found);
+        }
+    }
+}
+
+/// positions of the `k` closest other nodes of every node, closest first, in O(n log n).
+/// the queries run on `threads` threads, the lists are the same for any thread count
+pub fn neighbor_lists(nodes: &[Node], k: usize, threads: usize) -> Vec<Vec<usize>> {
+    let tree = KdTree::new(nodes);
+    crate::rng::parallel_map(nodes.len(), threads, |i| {
+        tree.k_nearest(nodes[i].x, nodes[i].y, k + 1)
+            .into_iter()
+            .filter(|&j| j != i)
+            .take(k)
+            .collect()
+    })
+}
+
+/// puts the median of `points` by the axis of `depth` in the middle and recurses into the halves,
+/// `alive` gets the size of every subtree
+fn build(points: &mut [(f64, f64, usize)], alive: &mut [usize], offset: usize, depth: usize) {
+    if points.is_empty() {
+        return;
+    }
+    let mid = points.len() / 2;
+    if depth.is_multiple_of(2) {
+        points.select_nth_unstable_by(mid, |a, b| a.0.total_cmp(&b.0));
+    } else {
+        points.select_nth_unstable_by(mid, |a, b| a.1.total_cmp(&b.1));
+    }
+    alive[offset + mid] = points.len();
+    let (left, right) = points.split_at_mut(mid);
+    build(left, alive, offset, depth + 1);
+    build(&mut right[1..], alive, offset + mid + 1, depth + 1);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::random_nodes;
+    use crate::spatial::*;
+    use rand::Rng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_queries_match_a_scan() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(52);
+        let nodes = random_nodes(300, &mut rng);
+        let mut tree = KdTree::new(&nodes);
+        let mut left: Vec<usize> = (0..nodes.len()).collect();
+        while !left.is_empty() {
+            let (x, y) = (rng.gen::<f64>(), rng.gen::<f64>());
+            let mut scan: Vec<(f64, usize)> = left
+                .iter()
+                .map(|&i| ((nodes[i].x - x).powi(2) + (nodes[i].y - y).powi(2), i))
+                .collect();
+            scan.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+            let closest: Vec<usize> = scan.iter().take(5).map(|&(_, i)| i).collect();
+            assert_eq!(tree.k_nearest(x, y, 5), closest);
+            assert_eq!(tree.len(), left.len());
+
+            let gone = left.swap_remove(rng.gen_range(0..left.len()));
+            tree.remove(gone);
+        }
+        assert!(tree.is_empty());
+        assert_eq!(tree.nearest(0.5, 0.5), None);
+    }
+
+    #[test]
+    fn test_neighbor_lists_ignore_threads() {
+        let nodes = random_nodes(500, &mut rand::rngs::StdRng::seed_from_u64(56));
+        let lists = neighbor_lists(&nodes, 8, 1);
+        assert_eq!(neighbor_lists(&nodes, 8, 4), lists);
+        assert!(lists
+            .iter()
+            .enumerate()
+            .all(|(i, list)| list.len() == 8 && !list.contains(&i)));
+    }
+}
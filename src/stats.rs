@@ -0,0 +1,216 @@
+//! rank tests for telling whether one stochastic solver really beats another
+//!
+//! both tests are two-sided and make no assumption about how tour lengths are distributed.
+//! small samples without ties get exact p-values, everything else the normal approximation
+//! with tie and continuity correction
+
+/// samples up to this size without ties are tested exactly, the counts stay far below f64 precision
+const EXACT_MAX: usize = 25;
+
+/// default significance level of the reports
+pub const DEFAULT_ALPHA: f64 = 0.05;
+
+/// outcome of a two-sided test
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TestResult {
+    /// W+ for the signed-rank test, U of the first sample for mann-whitney
+    pub statistic: f64,
+    pub p_value: f64,
+}
+
+/// complementary error function, numerical recipes' `erfcc`, fractional error below 1.2e-7
+fn erfc(x: f64) -> f64 {
+    let z = x.abs();
+    let t = 1.0 / (1.0 + 0.5 * z);
+    let polynomial = -z * z - 1.26551223
+        + t * (1.00002368
+            + t * (0.37409196
+                + t * (0.09678418
+                    + t * (-0.18628806
+                        + t * (0.27886807
+                            + t * (-1.13520398
+                                + t * (1.48851587 + t * (-0.82215223 + t * 0.17087277))))))));
+    let result = t * polynomial.exp();
+    if x >= 0.0 {
+        result
+    } else {
+        2.0 - result
+    }
+}
+
+/// P(Z <= z) for a standard normal Z
+pub fn normal_cdf(z: f64) -> f64 {
+    0.5 * erfc(-z / std::f64::consts::SQRT_2)
+}
+
+/// 1-based ranks, tied values share the mean of their ranks.
+/// also returns the sum of t³ - t over all groups of t tied values
+fn ranks(values: &[f64]) -> (Vec<f64>, f64) {
+    let mut order: Vec<usize> = (0..values.len()).collect();
+    order.sort_by(|&a, &b| values[a].total_cmp(&values[b]));
+    let mut ranks = vec![0.0; values.len()];
+    let mut ties = 0.0;
+    let mut start = 0;
+    while start < order.len() {
+        let mut end = start + 1;
+        while end < order.len() && values[order[end]] == values[order[start]] {
+            end += 1;
+        }
+        let rank = (start + end + 1) as f64 / 2.0;
+        for &i in &order[start..end] {
+            ranks[i] = rank;
+        }
+        let t = (end - start) as f64;
+        ties += t * t * t - t;
+        start = end;
+    }
+    (ranks, ties)
+}
+
+/// two-sided p-value of `observed` under the exact null distribution,
+/// `counts[s]` is the number of equally likely outcomes with statistic s
+fn exact_p_value(counts: &[f64], observed: usize) -> f64 {
+    let total: f64 = counts.iter().sum();
+    let lower: f64 = counts[..=observed].iter().sum();
+    let upper: f64 = counts[observed..].iter().sum();
+    (2.0 * lower.min(upper) / total).min(1.0)
+}
+
+/// two-sided p-value of `statistic` under a normal approximation, with continuity correction
+fn normal_p_value(statistic: f64, mean: f64, variance: f64) -> f64 {
+    if variance <= 0.0 {
+        return 1.0;
+    }
+    let distance = ((statistic - mean).abs() - 0.5).max(0.0);
+    (2.0 * (1.0 - normal_cdf(distance / variance.sqrt()))).min(1.0)
+}
+
+/// wilcoxon signed-rank test of paired samples, given as their differences.
+/// zero differences carry no information and are dropped, None if nothing is left
+pub fn wilcoxon_signed_rank(differences: &[f64]) -> Option<TestResult> {
+    let nonzero: Vec<f64> = differences.iter().copied().filter(|d| *d != 0.0).collect();
+    let n = nonzero.len();
+    if n == 0 {
+        return None;
+    }
+    let magnitudes: Vec<f64> = nonzero.iter().map(|d| d.abs()).collect();
+    let (ranks, ties) = ranks(&magnitudes);
+    let statistic: f64 = ranks
+        .iter()
+        .zip(&nonzero)
+        .filter(|(_, d)| **d > 0.0)
+        .map(|(rank, _)| rank)
+        .sum();
+
+    let p_value = if n <= EXACT_MAX && ties == 0.0 {
+        // counts[s]: subsets of the ranks 1..=n summing to s, every sign pattern is equally likely
+        let mut counts = vec![0.0; n * (n + 1) / 2 + 1];
+        counts[0] = 1.0;
+        for rank in 1..=n {
+            for s in (rank..counts.len()).rev() {
+                counts[s] += counts[s - rank];
+            }
+        }
+        exact_p_value(&counts, statistic as usize)
+    } else {
+        let n = n as f64;
+        let variance = n * (n + 1.0) * (2.0 * n + 1.0) / 24.0 - ties / 48.0;
+        normal_p_value(statistic, n * (n + 1.0) / 4.0, variance)
+    };
+    Some(TestResult { statistic, p_value })
+}
+
+/// mann-whitney u test of two independent samples, None if one of them is empty
+pub fn mann_whitney_u(a: &[f64], b: &[f64]) -> Option<TestResult> {
+    let (m, n) = (a.len(), b.len());
+    if m == 0 || n == 0 {
+        return None;
+    }
+    let combined: Vec<f64> = a.iter().chain(b).copied().collect();
+    let (ranks, ties) = ranks(&combined);
+    let rank_sum: f64 = ranks[..m].iter().sum();
+    let statistic = rank_sum - (m * (m + 1)) as f64 / 2.0;
+
+    let p_value = if m + n <= EXACT_MAX && ties == 0.0 {
+        // counts[k][u]: ways to pick k of the elements seen so far with u pairs won
+        let mut counts = vec![vec![0.0; m * n + 1]; m + 1];
+        counts[0][0] = 1.0;
+        for element in 0..m + n {
+            // picking this element, it beats every unpicked one seen before it
+            for k in (1..=m.min(element + 1)).rev() {
+                let wins = element + 1 - k;
+                for u in (wins..=m * n).rev() {
+                    counts[k][u] += counts[k - 1][u - wins];
+                }
+            }
+        }
+        exact_p_value(&counts[m], statistic as usize)
+    } else {
+        let (m, n) = (m as f64, n as f64);
+        let total = m + n;
+        let variance = m * n / 12.0 * ((total + 1.0) - ties / (total * (total - 1.0)));
+        normal_p_value(statistic, m * n / 2.0, variance)
+    };
+    Some(TestResult { statistic, p_value })
+}
+
+/// which of the p-values are significant at `alpha` after holm's correction,
+/// which keeps the chance of any false positive among all of them below `alpha`
+pub fn holm(p_values: &[f64], alpha: f64) -> Vec<bool> {
+    let mut order: Vec<usize> = (0..p_values.len()).collect();
+    order.sort_by(|&a, &b| p_values[a].total_cmp(&p_values[b]));
+    let mut significant = vec![false; p_values.len()];
+    for (k, &i) in order.iter().enumerate() {
+        if p_values[i] > alpha / (p_values.len() - k) as f64 {
+            break;
+        }
+        significant[i] = true;
+    }
+    significant
+}
+
+/// the middle value, the mean of the middle two for even lengths
+pub fn median(values: &[f64]) -> f64 {
+    let mut sorted = values.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    match sorted.len() {
+        0 => f64::NAN,
+        n if n % 2 == 1 => sorted[n / 2],
+        n => (sorted[n / 2 - 1] + sorted[n / 2]) / 2.0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::stats::*;
+
+    #[test]
+    fn test_exact_tests() {
+        // all 5 differences positive: 1 of 32 sign patterns, doubled for two sides
+        let result = wilcoxon_signed_rank(&[1.0, 2.0, 0.0, 3.0, 4.0, 5.0]).unwrap();
+        assert_eq!(result.statistic, 15.0);
+        assert!((result.p_value - 2.0 / 32.0).abs() < 1e-12);
+        // complete separation: 1 of the 20 ways to split 6 ranks 3 and 3
+        let result = mann_whitney_u(&[1.0, 2.0, 3.0], &[4.0, 5.0, 6.0]).unwrap();
+        assert_eq!(result.statistic, 0.0);
+        assert!((result.p_value - 2.0 / 20.0).abs() < 1e-12);
+        assert_eq!(
+            mann_whitney_u(&[1.0, 4.0], &[2.0, 3.0]).unwrap().p_value,
+            1.0
+        );
+        assert!(wilcoxon_signed_rank(&[0.0, 0.0]).is_none());
+    }
+
+    #[test]
+    fn test_normal_approximation() {
+        assert!((normal_cdf(1.959964) - 0.975).abs() < 1e-6);
+        assert!((normal_cdf(-1.0) - 0.158655).abs() < 1e-6);
+        // ties force the approximation, clearly shifted samples are significant
+        let a: Vec<f64> = (0..30).map(|i| (i / 2) as f64).collect();
+        let b: Vec<f64> = a.iter().map(|x| x + 10.0).collect();
+        assert!(mann_whitney_u(&a, &b).unwrap().p_value < 0.001);
+        let differences: Vec<f64> = a.iter().map(|x| x + 1.0).collect();
+        assert!(wilcoxon_signed_rank(&differences).unwrap().p_value < 0.001);
+        assert_eq!(holm(&[0.01, 0.04, 0.03], 0.05), vec![true, false, false]);
+    }
+}
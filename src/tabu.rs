@@ -0,0 +1,184 @@
+//! tabu search over the 2-opt neighborhood
+//!
+//! every iteration applies the best 2-opt move there is, even when it makes the tour longer,
+//! so the search walks out of local optima instead of stopping there. to keep it from walking
+//! straight back, edges it removed may not come back for `tenure` iterations,
+//! unless the aspiration criterion lets a move through anyway
+
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use crate::cancel;
+use crate::metric::Metric;
+use crate::Node;
+
+/// when a move that adds a tabu edge is allowed after all
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Aspiration {
+    /// never
+    None,
+    /// when it leads to a tour shorter than every one so far
+    BestSoFar,
+}
+
+impl FromStr for Aspiration {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Aspiration::None),
+            "best" => Ok(Aspiration::BestSoFar),
+            _ => Err(format!(
+                "unknown aspiration criterion '{}', expected none or best",
+                s
+            )),
+        }
+    }
+}
+
+/// everything `tsp_tabu_search` can be tuned with
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TabuParams {
+    pub iterations: usize,
+    /// iterations a removed edge stays tabu
+    pub tenure: usize,
+    pub aspiration: Aspiration,
+}
+
+impl Default for TabuParams {
+    fn default() -> Self {
+        TabuParams {
+            iterations: 1000,
+            tenure: 15,
+            aspiration: Aspiration::BestSoFar,
+        }
+    }
+}
+
+/// `key=value` pairs separated by `:` over the defaults, e.g. `tenure=30:aspiration=none`
+impl FromStr for TabuParams {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        fn value<T: FromStr>(key: &str, value: &str) -> Result<T, String>
+        where
+            T::Err: std::fmt::Display,
+        {
+            value
+                .parse()
+                .map_err(|err| format!("invalid {} '{}': {}", key, value, err))
+        }
+
+        let mut params = TabuParams::default();
+        for pair in s.split(':').filter(|p| !p.is_empty()) {
+            let Some((key, v)) = pair.split_once('=') else {
+                return Err(format!("expected key=value, got '{}'", pair));
+            };
+            match key {
+                "iterations" => params.iterations = value(key, v)?,
+                "tenure" => params.tenure = value(key, v)?,
+                "aspiration" => params.aspiration = value(key, v)?,
+                _ => {
+                    return Err(format!(
+                        "unknown parameter '{}', try iterations, tenure or aspiration",
+                        key
+                    ))
+                }
+            }
+        }
+        Ok(params)
+    }
+}
+
+/// the edge between two nodes by their positions in `nodes`, smaller first
+fn edge(a: usize, b: usize) -> (usize, usize) {
+    (a.min(b), a.max(b))
+}
+
+/// the shortest tour the search walked through, from the order of `nodes`
+pub fn tsp_tabu_search(nodes: &[Node], metric: &dyn Metric, params: &TabuParams) -> Vec<Node> {
+    let n = nodes.len();
+    if n < 5 {
+        return nodes.to_vec();
+    }
+    let d = |a: usize, b: usize| metric.distance(&nodes[a], &nodes[b]);
+    let mut tour: Vec<usize> = (0..n).collect();
+    let mut length: f64 = (0..n).map(|i| d(tour[i], tour[(i + 1) % n])).sum();
+    let mut best = (length, tour.clone());
+    // edge -> first iteration it may be added again
+    let mut tabu: HashMap<(usize, usize), usize> = HashMap::new();
+
+    for iteration in 0..params.iterations {
+        if cancel::is_cancelled() {
+            break;
+        }
+        // (change in length, i, j) of reversing tour[i + 1..=j]
+        let mut chosen: Option<(f64, usize, usize)> = None;
+        for i in 0..n - 2 {
+            let (a, b) = (tour[i], tour[i + 1]);
+            for j in i + 2..n {
+                // the edges would share a node
+                if i == 0 && j == n - 1 {
+                    continue;
+                }
+                let (c, e) = (tour[j], tour[(j + 1) % n]);
+                let delta = d(a, c) + d(b, e) - d(a, b) - d(c, e);
+                if chosen.is_some_and(|(best_delta, ..)| delta >= best_delta) {
+                    continue;
+                }
+                let is_tabu = [edge(a, c), edge(b, e)]
+                    .iter()
+                    .any(|added| tabu.get(added).is_some_and(|&until| iteration < until));
+                let aspires =
+                    params.aspiration == Aspiration::BestSoFar && length + delta < best.0 - 1e-12;
+                if !is_tabu || aspires {
+                    chosen = Some((delta, i, j));
+                }
+            }
+        }
+        // everything is tabu
+        let Some((delta, i, j)) = chosen else {
+            continue;
+        };
+
+        let (a, b, c, e) = (tour[i], tour[i + 1], tour[j], tour[(j + 1) % n]);
+        tabu.insert(edge(a, b), iteration + 1 + params.tenure);
+        tabu.insert(edge(c, e), iteration + 1 + params.tenure);
+        tabu.retain(|_, &mut until| until > iteration);
+        tour[i + 1..=j].reverse();
+        length += delta;
+        if length < best.0 {
+            best = (length, tour.clone());
+        }
+    }
+    best.1.iter().map(|&i| nodes[i].clone()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::local_search::{local_search, TwoOpt};
+    use crate::metric::Euclidean;
+    use crate::random_nodes;
+    use crate::tabu::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_escapes_two_opt_optimum() {
+        let nodes = random_nodes(60, &mut StdRng::seed_from_u64(20));
+        let mut two = nodes.clone();
+        local_search(&mut two, &TwoOpt, &Euclidean);
+
+        let params: TabuParams = "iterations=300".parse().unwrap();
+        let tour = tsp_tabu_search(&two, &Euclidean, &params);
+        let ids: Vec<usize> = tour.iter().map(|n| n.id).collect();
+        assert!(crate::tour::Tour::from_ids(&ids, &nodes).is_ok());
+        assert!(Euclidean.tour_length(&tour) < Euclidean.tour_length(&two));
+
+        assert_eq!(
+            "aspiration=none".parse::<TabuParams>().unwrap().aspiration,
+            Aspiration::None
+        );
+        assert!("tenure=-1".parse::<TabuParams>().is_err());
+    }
+}
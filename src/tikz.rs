@@ -0,0 +1,115 @@
+//! tours as standalone latex documents with a tikz picture
+//!
+//! compiles on its own with `pdflatex`, or the `tikzpicture` can be pasted into a paper
+
+use std::fmt::Write;
+
+use crate::tour::Tour;
+
+/// width and height of the drawing in centimeters, the nodes are scaled to fit in there
+const SIZE_CM: f64 = 10.0;
+
+/// instances with more nodes get no id labels, they'd just cover the tour
+const MAX_LABELS: usize = 100;
+
+/// `%`, `#` and the like in the caption would break the document
+fn escape_latex(text: &str) -> String {
+    let mut escaped = String::new();
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\textbackslash{}"),
+            '&' | '%' | '$' | '#' | '_' | '{' | '}' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '~' => escaped.push_str("\\textasciitilde{}"),
+            '^' => escaped.push_str("\\textasciicircum{}"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// standalone document drawing the tour's edges, its nodes and their ids,
+/// with `caption` below the picture
+pub fn to_tikz(tour: &Tour, caption: &str) -> String {
+    let nodes = tour.nodes();
+    let (mut min_x, mut min_y) = (f64::INFINITY, f64::INFINITY);
+    let (mut max_x, mut max_y) = (f64::NEG_INFINITY, f64::NEG_INFINITY);
+    for node in nodes {
+        (min_x, max_x) = (min_x.min(node.x), max_x.max(node.x));
+        (min_y, max_y) = (min_y.min(node.y), max_y.max(node.y));
+    }
+    // same scale on both axes, like the pngs
+    let extent = (max_x - min_x).max(max_y - min_y);
+    let scale = if extent > 0.0 { SIZE_CM / extent } else { 1.0 };
+    let position =
+        |x: f64, y: f64| format!("({:.3},{:.3})", (x - min_x) * scale, (y - min_y) * scale);
+
+    // writing to a String never fails
+    let mut tex = String::new();
+    let _ = writeln!(tex, "\\documentclass[tikz,border=5mm]{{standalone}}");
+    let _ = writeln!(tex, "\\begin{{document}}");
+    let _ = writeln!(tex, "\\begin{{tikzpicture}}");
+    let _ = writeln!(
+        tex,
+        "  \\tikzset{{tsp node/.style={{circle, fill=black, inner sep=0pt, minimum size=3pt}}}}"
+    );
+    if !nodes.is_empty() {
+        let _ = write!(tex, "  \\draw[thin] ");
+        for (i, node) in nodes.iter().enumerate() {
+            // a few points per line keeps the file readable
+            let separator = if i % 6 == 5 { "\n    " } else { " " };
+            let _ = write!(tex, "{} --{}", position(node.x, node.y), separator);
+        }
+        let _ = writeln!(tex, "cycle;");
+    }
+    for node in nodes {
+        let label = if nodes.len() <= MAX_LABELS {
+            format!(" [label={{[font=\\tiny]right:{}}}]", node.id)
+        } else {
+            String::new()
+        };
+        let _ = writeln!(
+            tex,
+            "  \\node[tsp node]{} at {} {{}};",
+            label,
+            position(node.x, node.y)
+        );
+    }
+    let _ = writeln!(
+        tex,
+        "  \\node[below, align=center] at ({:.3},-0.5) {{{}}};",
+        SIZE_CM / 2.0,
+        escape_latex(caption)
+    );
+    let _ = writeln!(tex, "\\end{{tikzpicture}}");
+    let _ = writeln!(tex, "\\end{{document}}");
+    tex
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tikz::*;
+    use crate::Node;
+
+    #[test]
+    fn test_tikz_picture() {
+        let tour = Tour::from(
+            [(0.0, 0.0), (2.0, 0.0), (2.0, 1.0)]
+                .iter()
+                .enumerate()
+                .map(|(id, &(x, y))| Node { id, x, y })
+                .collect::<Vec<_>>(),
+        );
+        let tex = to_tikz(&tour, "nn: length 5.24, 100% done_");
+        assert!(tex.starts_with("\\documentclass[tikz,border=5mm]{standalone}"));
+        assert!(tex
+            .contains("\\draw[thin] (0.000,0.000) -- (10.000,0.000) -- (10.000,5.000) -- cycle;"));
+        assert!(
+            tex.contains("\\node[tsp node] [label={[font=\\tiny]right:2}] at (10.000,5.000) {};")
+        );
+        assert!(tex.contains("{nn: length 5.24, 100\\% done\\_}"));
+        assert!(tex.trim_end().ends_with("\\end{document}"));
+    }
+}
@@ -0,0 +1,655 @@
+use std::collections::{BTreeSet, HashMap};
+use std::hash::{Hash, Hasher};
+
+use crate::metric::Metric;
+use crate::rng::splitmix64;
+use crate::Node;
+
+/// closed tour visiting `nodes` in order, last node connects back to the first
+///
+/// tours compare and hash by their canonical form,
+/// so rotations and reversals of the same cycle are equal.
+/// `measure` caches the length, the moves keep it up to date as long as they're given
+/// the metric it was measured with
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Tour {
+    nodes: Vec<Node>,
+    /// length under the metric of `measure`, None until then or after a change it can't track.
+    /// the metric isn't serialized, so neither is the length
+    #[cfg_attr(feature = "serde", serde(skip))]
+    length: Option<f64>,
+}
+
+/// undirected edge between two node ids, smaller id first
+pub type Edge = (usize, usize);
+
+/// structural difference between two tours
+#[derive(Debug, Clone, PartialEq)]
+pub struct TourDiff {
+    /// edges of the other tour that this one doesn't have
+    pub added: Vec<Edge>,
+    /// edges of this tour that the other one doesn't have
+    pub removed: Vec<Edge>,
+    /// number of edges both tours have
+    pub shared: usize,
+    /// other length minus this length
+    pub length_delta: f64,
+}
+
+/// hash of the edge set of a tour
+///
+/// xor of per edge hashes, so it doesn't care about rotation or direction:
+/// tours that are equal in canonical form always share a fingerprint.
+/// a move that swaps edges updates it in O(1) by toggling them
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Fingerprint(pub u64);
+
+impl Fingerprint {
+    /// adds the edge if it's not part of the fingerprint yet, removes it otherwise
+    pub fn toggle_edge(&mut self, a: usize, b: usize) {
+        let (low, high) = (a.min(b) as u64, a.max(b) as u64);
+        self.0 ^= splitmix64(splitmix64(low) ^ high);
+    }
+}
+
+impl From<Vec<Node>> for Tour {
+    fn from(nodes: Vec<Node>) -> Self {
+        Tour {
+            nodes,
+            length: None,
+        }
+    }
+}
+
+impl Tour {
+    /// tour visiting `nodes` in the order of `ids`, which has to name every node exactly once
+    pub fn from_ids(ids: &[usize], nodes: &[Node]) -> Result<Tour, String> {
+        let by_id: HashMap<usize, &Node> = nodes.iter().map(|n| (n.id, n)).collect();
+        let mut visited = HashMap::new();
+        let mut tour = Vec::with_capacity(ids.len());
+        for (position, id) in ids.iter().enumerate() {
+            let node = by_id
+                .get(id)
+                .ok_or_else(|| format!("node {} isn't part of the instance", id))?;
+            if let Some(first) = visited.insert(*id, position) {
+                return Err(format!(
+                    "node {} is visited twice, at positions {} and {}",
+                    id, first, position
+                ));
+            }
+            tour.push((*node).clone());
+        }
+        if tour.len() != nodes.len() {
+            let missing: Vec<usize> = nodes
+                .iter()
+                .map(|n| n.id)
+                .filter(|id| !visited.contains_key(id))
+                .collect();
+            return Err(format!("tour never visits nodes {:?}", missing));
+        }
+        Ok(Tour::from(tour))
+    }
+
+    /// checks that the tour visits every node of `instance` exactly once and nothing else
+    pub fn validate(&self, instance: &[Node]) -> Result<(), String> {
+        let ids: Vec<usize> = self.nodes.iter().map(|n| n.id).collect();
+        Tour::from_ids(&ids, instance).map(|_| ())
+    }
+
+    /// the nodes in visiting order
+    pub fn nodes(&self) -> &[Node] {
+        &self.nodes
+    }
+
+    pub fn into_nodes(self) -> Vec<Node> {
+        self.nodes
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// computed from scratch, see `measure` for the cached length
+    pub fn length(&self, metric: &dyn Metric) -> f64 {
+        metric.tour_length(&self.nodes)
+    }
+
+    /// the cached length, measured with `metric` if there's none yet
+    pub fn measure(&mut self, metric: &dyn Metric) -> f64 {
+        *self
+            .length
+            .get_or_insert_with(|| metric.tour_length(&self.nodes))
+    }
+
+    pub fn cached_length(&self) -> Option<f64> {
+        self.length
+    }
+
+    /// adds `delta` to the cached length, if there is one
+    fn track(&mut self, delta: f64) -> f64 {
+        if let Some(length) = &mut self.length {
+            *length += delta;
+        }
+        delta
+    }
+
+    /// distance from position i to position j, wrapping around
+    fn leg(&self, i: usize, j: usize, metric: &dyn Metric) -> f64 {
+        let n = self.nodes.len();
+        metric.distance(&self.nodes[i % n], &self.nodes[j % n])
+    }
+
+    /// reverses positions i..=j (i <= j), the 2-opt move, returns how much longer the tour got.
+    /// O(1) to evaluate on symmetric metrics, a pass over the tour on asymmetric ones
+    pub fn reverse_segment(&mut self, i: usize, j: usize, metric: &dyn Metric) -> f64 {
+        let n = self.nodes.len();
+        assert!(
+            i <= j && j < n,
+            "segment {}..={} out of a tour of {}",
+            i,
+            j,
+            n
+        );
+        // the edges inside point the other way too, all of them for the whole cycle
+        if !metric.is_symmetric() {
+            let before = metric.tour_length(&self.nodes);
+            self.nodes[i..=j].reverse();
+            return self.track(metric.tour_length(&self.nodes) - before);
+        }
+        // reversing everything, or everything but one node, is the same cycle
+        if j - i + 2 >= n {
+            self.nodes[i..=j].reverse();
+            return 0.0;
+        }
+        let (before, after) = (i + n - 1, j + 1);
+        let delta = self.leg(before, j, metric) + self.leg(i, after, metric)
+            - self.leg(before, i, metric)
+            - self.leg(j, after, metric);
+        self.nodes[i..=j].reverse();
+        self.track(delta)
+    }
+
+    /// swaps the nodes at positions i and j, returns how much longer the tour got
+    pub fn swap(&mut self, i: usize, j: usize, metric: &dyn Metric) -> f64 {
+        let n = self.nodes.len();
+        let (i, j) = (i.min(j), i.max(j));
+        assert!(j < n, "position {} out of a tour of {}", j, n);
+        if i == j {
+            return 0.0;
+        }
+        // edges touching either position, the one between them counted once
+        let touching = |tour: &Tour| {
+            let mut legs = vec![(i + n - 1, i), (i, i + 1), (j + n - 1, j), (j, j + 1)];
+            legs.iter_mut()
+                .for_each(|(a, b)| (*a, *b) = (*a % n, *b % n));
+            legs.sort_unstable();
+            legs.dedup();
+            legs.iter()
+                .map(|&(a, b)| tour.leg(a, b, metric))
+                .sum::<f64>()
+        };
+        let before = touching(self);
+        self.nodes.swap(i, j);
+        let delta = touching(self) - before;
+        self.track(delta)
+    }
+
+    /// puts `node` at `position`, shifting the rest back, returns how much longer the tour got
+    pub fn insert(&mut self, position: usize, node: Node, metric: &dyn Metric) -> f64 {
+        let n = self.nodes.len();
+        assert!(
+            position <= n,
+            "position {} out of a tour of {}",
+            position,
+            n
+        );
+        let delta = if n == 0 {
+            0.0
+        } else {
+            let (a, b) = (
+                &self.nodes[(position + n - 1) % n],
+                &self.nodes[position % n],
+            );
+            metric.distance(a, &node) + metric.distance(&node, b) - metric.distance(a, b)
+        };
+        self.nodes.insert(position, node);
+        self.track(delta)
+    }
+
+    /// node ids starting at the smallest id, heading towards its smaller neighbor
+    pub fn canonical_ids(&self) -> Vec<usize> {
+        let n = self.nodes.len();
+        let Some(start) = (0..n).min_by_key(|&i| self.nodes[i].id) else {
+            return Vec::new();
+        };
+        let next = self.nodes[(start + 1) % n].id;
+        let previous = self.nodes[(start + n - 1) % n].id;
+        if next <= previous {
+            (0..n).map(|i| self.nodes[(start + i) % n].id).collect()
+        } else {
+            (0..n).map(|i| self.nodes[(start + n - i) % n].id).collect()
+        }
+    }
+
+    /// rotates and possibly reverses the order into the canonical form, see `canonical_ids`
+    pub fn canonicalize(&mut self) {
+        let n = self.nodes.len();
+        let Some(start) = (0..n).min_by_key(|&i| self.nodes[i].id) else {
+            return;
+        };
+        self.nodes.rotate_left(start);
+        if n > 2 && self.nodes[1].id > self.nodes[n - 1].id {
+            self.nodes[1..].reverse();
+        }
+    }
+
+    /// rotates the node with `id` to the front, keeping the direction, see `start_at`
+    pub fn start_at(&mut self, id: usize) -> bool {
+        start_at(&mut self.nodes, id)
+    }
+
+    /// puts `node` between the two consecutive nodes where it adds the least length,
+    /// returns the position it ended up at
+    pub fn insert_node(&mut self, node: Node, metric: &dyn Metric) -> usize {
+        let n = self.nodes.len();
+        if n < 2 {
+            self.insert(n, node, metric);
+            return n;
+        }
+
+        let mut best_position = n;
+        let mut best_cost = f64::INFINITY;
+        for i in 0..n {
+            let (a, b) = (&self.nodes[i], &self.nodes[(i + 1) % n]);
+            let cost =
+                metric.distance(a, &node) + metric.distance(&node, b) - metric.distance(a, b);
+            if cost < best_cost {
+                best_cost = cost;
+                best_position = i + 1;
+            }
+        }
+        self.insert(best_position, node, metric);
+        best_position
+    }
+
+    /// takes node `id` out of the tour, connecting its two neighbors directly,
+    /// then runs `repair_around` both of them to close the gap properly.
+    /// returns the node and the number of relocations the repair took
+    pub fn remove_node(
+        &mut self,
+        id: usize,
+        window: usize,
+        metric: &dyn Metric,
+    ) -> Option<(Node, usize)> {
+        let position = self.nodes.iter().position(|n| n.id == id)?;
+        let removed = self.nodes.remove(position);
+        self.length = None;
+
+        let n = self.nodes.len();
+        let mut relocations = 0;
+        if n > 0 {
+            let previous = self.nodes[(position + n - 1) % n].id;
+            let next = self.nodes[position % n].id;
+            relocations += self.repair_around(previous, window, metric);
+            relocations += self.repair_around(next, window, metric);
+        }
+        Some((removed, relocations))
+    }
+
+    /// or-opt repair around node `id`
+    ///
+    /// relocates chains of 1 to 3 nodes starting up to `window` positions away from `id`
+    /// to wherever they're cheapest, until none of them can be improved anymore.
+    /// returns the number of relocations
+    pub fn repair_around(&mut self, id: usize, window: usize, metric: &dyn Metric) -> usize {
+        const MAX_PASSES: usize = 10;
+
+        let mut relocations = 0;
+        for _ in 0..MAX_PASSES {
+            let Some(centre) = self.nodes.iter().position(|n| n.id == id) else {
+                return relocations;
+            };
+            let mut improved = false;
+            for start in centre.saturating_sub(window)..=centre + window {
+                for length in 1..=3 {
+                    if self.relocate_segment(start, length, metric) {
+                        relocations += 1;
+                        improved = true;
+                    }
+                }
+            }
+            if !improved {
+                break;
+            }
+        }
+        relocations
+    }
+
+    /// moves the chain of `length` nodes at `start` to its cheapest position,
+    /// possibly reversed. only done if it shortens the tour
+    fn relocate_segment(&mut self, start: usize, length: usize, metric: &dyn Metric) -> bool {
+        const EPSILON: f64 = 1e-12;
+
+        let n = self.nodes.len();
+        if start + length > n || n < length + 3 {
+            return false;
+        }
+        let previous = &self.nodes[(start + n - 1) % n];
+        let next = &self.nodes[(start + length) % n];
+        let (first, last) = (&self.nodes[start], &self.nodes[start + length - 1]);
+        let removal_gain = metric.distance(previous, first) + metric.distance(last, next)
+            - metric.distance(previous, next);
+
+        let segment: Vec<Node> = self.nodes.drain(start..start + length).collect();
+        let (first, last) = (&segment[0], &segment[length - 1]);
+        // reversed, the edges inside the chain point the other way too
+        let turned: f64 = if metric.is_symmetric() {
+            0.0
+        } else {
+            segment
+                .windows(2)
+                .map(|pair| {
+                    metric.distance(&pair[1], &pair[0]) - metric.distance(&pair[0], &pair[1])
+                })
+                .sum()
+        };
+        let m = self.nodes.len();
+        let mut best = (f64::INFINITY, 0, false);
+        for i in 0..m {
+            let (a, b) = (&self.nodes[i], &self.nodes[(i + 1) % m]);
+            let base = metric.distance(a, b);
+            let forward = metric.distance(a, first) + metric.distance(last, b) - base;
+            let backward = metric.distance(a, last) + metric.distance(first, b) - base + turned;
+            if forward < best.0 {
+                best = (forward, i + 1, false);
+            }
+            if backward < best.0 {
+                best = (backward, i + 1, true);
+            }
+        }
+
+        let (cost, position, reversed) = best;
+        if removal_gain - cost > EPSILON {
+            self.track(cost - removal_gain);
+            if reversed {
+                self.nodes
+                    .splice(position..position, segment.into_iter().rev());
+            } else {
+                self.nodes.splice(position..position, segment);
+            }
+            true
+        } else {
+            self.nodes.splice(start..start, segment);
+            false
+        }
+    }
+
+    /// O(n) fingerprint of the edge set, see `Fingerprint`
+    pub fn fingerprint(&self) -> Fingerprint {
+        let mut fingerprint = Fingerprint::default();
+        for (a, b) in self.edges() {
+            fingerprint.toggle_edge(a, b);
+        }
+        fingerprint
+    }
+
+    /// all edges of the tour, including the closing one
+    ///
+    /// tours of 2 nodes travel the same edge twice, it's only reported once
+    pub fn edges(&self) -> BTreeSet<Edge> {
+        let n = self.nodes.len();
+        if n < 2 {
+            return BTreeSet::new();
+        }
+        (0..n)
+            .map(|i| {
+                let (a, b) = (self.nodes[i].id, self.nodes[(i + 1) % n].id);
+                (a.min(b), a.max(b))
+            })
+            .collect()
+    }
+
+    /// what changes going from this tour to `other`
+    pub fn diff(&self, other: &Tour, metric: &dyn Metric) -> TourDiff {
+        let own = self.edges();
+        let theirs = other.edges();
+        TourDiff {
+            added: theirs.difference(&own).copied().collect(),
+            removed: own.difference(&theirs).copied().collect(),
+            shared: own.intersection(&theirs).count(),
+            length_delta: other.length(metric) - self.length(metric),
+        }
+    }
+}
+
+impl PartialEq for Tour {
+    fn eq(&self, other: &Self) -> bool {
+        self.canonical_ids() == other.canonical_ids()
+    }
+}
+impl Eq for Tour {}
+
+impl Hash for Tour {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.canonical_ids().hash(state);
+    }
+}
+
+/// rotates the node with `id` to the front of a tour, the same round trip from there.
+/// false if it isn't in the tour
+pub fn start_at(nodes: &mut [Node], id: usize) -> bool {
+    match nodes.iter().position(|node| node.id == id) {
+        Some(i) => {
+            nodes.rotate_left(i);
+            true
+        }
+        None => false,
+    }
+}
+
+/// evcxr calls `evcxr_display` on the values notebook cells evaluate to,
+/// so a tour shows up as its drawing
+#[cfg(feature = "evcxr")]
+impl Tour {
+    pub fn evcxr_display(&self) {
+        let title = format!("tour length: {}", crate::get_tour_length(&self.nodes));
+        let metadata = crate::image_meta::RunMetadata::default();
+        match crate::plot::render_svg(&self.nodes, &title, &metadata) {
+            Ok(svg) => println!(
+                "EVCXR_BEGIN_CONTENT image/svg+xml\n{}\nEVCXR_END_CONTENT",
+                svg
+            ),
+            Err(err) => println!("Error drawing:\n{}", err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::metric::Euclidean;
+    use crate::tour::*;
+
+    fn square() -> Vec<Node> {
+        [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)]
+            .iter()
+            .enumerate()
+            .map(|(id, &(x, y))| Node { id, x, y })
+            .collect()
+    }
+
+    fn tour_of(nodes: &[Node], ids: &[usize]) -> Tour {
+        Tour::from(ids.iter().map(|&id| nodes[id].clone()).collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn test_canonical_rotations_and_reversals_equal() {
+        let nodes = square();
+        let tour = tour_of(&nodes, &[2, 3, 0, 1]);
+        assert_eq!(tour, tour_of(&nodes, &[0, 1, 2, 3]));
+        assert_eq!(tour, tour_of(&nodes, &[1, 0, 3, 2]));
+        assert_ne!(tour, tour_of(&nodes, &[0, 2, 1, 3]));
+
+        let mut canonical = tour_of(&nodes, &[3, 2, 1, 0]);
+        canonical.canonicalize();
+        let ids = canonical.nodes().iter().map(|n| n.id).collect::<Vec<_>>();
+        assert_eq!(ids, vec![0, 1, 2, 3]);
+        assert_eq!(ids, tour.canonical_ids());
+    }
+
+    #[test]
+    fn test_fingerprint_tracks_edge_swaps() {
+        let nodes = square();
+        let tour = tour_of(&nodes, &[0, 1, 2, 3]);
+        assert_eq!(
+            tour.fingerprint(),
+            tour_of(&nodes, &[2, 1, 0, 3]).fingerprint()
+        );
+
+        // 2-opt style exchange of (0, 1), (2, 3) for (0, 2), (1, 3)
+        let mut fingerprint = tour.fingerprint();
+        fingerprint.toggle_edge(0, 1);
+        fingerprint.toggle_edge(3, 2);
+        fingerprint.toggle_edge(2, 0);
+        fingerprint.toggle_edge(1, 3);
+        let crossed = tour_of(&nodes, &[0, 2, 1, 3]);
+        assert_eq!(fingerprint, crossed.fingerprint());
+        assert_ne!(fingerprint, tour.fingerprint());
+    }
+
+    #[test]
+    fn test_insert_node_cheapest_position() {
+        let nodes = square();
+        let mut tour = tour_of(&nodes, &[0, 1, 2, 3]);
+        let position = tour.insert_node(
+            Node {
+                id: 4,
+                x: 0.5,
+                y: -0.1,
+            },
+            &Euclidean,
+        );
+        assert_eq!(position, 1);
+        assert_eq!(tour.canonical_ids(), vec![0, 3, 2, 1, 4]);
+    }
+
+    #[test]
+    fn test_repair_uncrosses() {
+        let nodes = square();
+        let mut tour = tour_of(&nodes, &[0, 2, 1, 3]);
+        assert!(tour.repair_around(0, 2, &Euclidean) > 0);
+        assert_eq!(tour.length(&Euclidean), 4.0);
+    }
+
+    #[test]
+    fn test_remove_node_repairs_gap() {
+        let mut nodes = square();
+        nodes.push(Node {
+            id: 4,
+            x: 0.5,
+            y: 0.5,
+        });
+        // 4 sits between 0 and 2, removing it leaves a crossed square behind
+        let mut tour = tour_of(&nodes, &[0, 4, 2, 1, 3]);
+        let (removed, relocations) = tour.remove_node(4, 2, &Euclidean).unwrap();
+        assert_eq!(removed.id, 4);
+        assert!(relocations > 0);
+        assert_eq!(tour.length(&Euclidean), 4.0);
+        assert!(tour.remove_node(4, 2, &Euclidean).is_none());
+    }
+
+    #[test]
+    fn test_from_ids_validates() {
+        let nodes = square();
+        assert_eq!(
+            Tour::from_ids(&[3, 0, 1, 2], &nodes)
+                .unwrap()
+                .canonical_ids(),
+            vec![0, 1, 2, 3]
+        );
+        assert!(Tour::from_ids(&[0, 1, 1, 2, 3], &nodes).is_err());
+        assert!(Tour::from_ids(&[0, 1, 2], &nodes).is_err());
+        assert!(Tour::from_ids(&[0, 1, 2, 4], &nodes).is_err());
+    }
+
+    #[test]
+    fn test_moves_keep_cached_length() {
+        let nodes = crate::random_nodes(12, &mut rand::thread_rng());
+        let mut tour = Tour::from(nodes[..10].to_vec());
+        assert_eq!(tour.cached_length(), None);
+        tour.measure(&Euclidean);
+
+        let check = |tour: &Tour| {
+            let cached = tour.cached_length().unwrap();
+            assert!((cached - tour.length(&Euclidean)).abs() < 1e-9);
+        };
+        for (i, j) in [(0, 3), (2, 8), (0, 9), (4, 5), (1, 8)] {
+            tour.reverse_segment(i, j, &Euclidean);
+            check(&tour);
+            tour.swap(j, i, &Euclidean);
+            check(&tour);
+        }
+        tour.insert(0, nodes[10].clone(), &Euclidean);
+        tour.insert_node(nodes[11].clone(), &Euclidean);
+        tour.repair_around(nodes[11].id, 3, &Euclidean);
+        check(&tour);
+        assert!(tour.validate(&nodes).is_ok());
+        assert!(tour.validate(&nodes[..11]).is_err());
+    }
+
+    #[test]
+    fn test_repair_measures_reversed_chains_on_directed_costs() {
+        use rand::{Rng, SeedableRng};
+        let mut rng = rand::rngs::StdRng::seed_from_u64(309);
+        for _ in 0..20 {
+            let rows: Vec<Vec<f64>> = (0..9)
+                .map(|i| {
+                    (0..9)
+                        .map(|j| {
+                            if i == j {
+                                0.0
+                            } else {
+                                rng.gen_range(1..100) as f64
+                            }
+                        })
+                        .collect()
+                })
+                .collect();
+            let matrix = crate::explicit::ExplicitMatrix::directed(&rows).unwrap();
+            let mut tour = Tour::from(matrix.nodes());
+            let before = tour.measure(&matrix);
+            for id in 0..9 {
+                tour.repair_around(id, 2, &matrix);
+            }
+            let after = tour.length(&matrix);
+            assert!(after <= before);
+            assert!((tour.cached_length().unwrap() - after).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn test_diff_crossed_square() {
+        let nodes = square();
+        let around = Tour::from(nodes.clone());
+        let crossed = Tour::from(vec![
+            nodes[0].clone(),
+            nodes[2].clone(),
+            nodes[1].clone(),
+            nodes[3].clone(),
+        ]);
+
+        let diff = around.diff(&crossed, &Euclidean);
+        assert_eq!(diff.added, vec![(0, 2), (1, 3)]);
+        assert_eq!(diff.removed, vec![(0, 1), (2, 3)]);
+        assert_eq!(diff.shared, 2);
+        assert!((diff.length_delta - (2.0 * 2f64.sqrt() - 2.0)).abs() < 1e-12);
+
+        let same = around.diff(&around, &Euclidean);
+        assert!(same.added.is_empty() && same.removed.is_empty());
+        assert_eq!(same.shared, 4);
+    }
+}
@@ -0,0 +1,168 @@
+//! edge lengths of a tour with prefix sums, for segment costs in local search
+//!
+//! positions are indices into the tour, edge `k` runs from position `k` to `k + 1`
+//! and the last one back to position 0
+
+use crate::metric::Metric;
+use crate::Node;
+
+/// edge lengths of a tour by position, any contiguous segment sums in O(1)
+///
+/// the prefix sums are rebuilt from scratch now and then, edges changed since the
+/// last rebuild go into a fenwick tree of corrections. so queries are O(1) while no edge
+/// changed and O(log n) while some did, and updates are amortized O(log n)
+#[derive(Debug, Clone)]
+pub struct TourLengths {
+    edges: Vec<f64>,
+    /// `prefix[k]` is the sum of edges `0..k` as of the last rebuild
+    prefix: Vec<f64>,
+    /// fenwick tree of how much each edge changed since the last rebuild, 1-based
+    corrections: Vec<f64>,
+    /// updates since the last rebuild
+    updates: usize,
+}
+
+impl TourLengths {
+    pub fn new(nodes: &[Node], metric: &dyn Metric) -> Self {
+        let n = nodes.len();
+        let edges = (0..n)
+            .map(|k| metric.distance(&nodes[k], &nodes[(k + 1) % n]))
+            .collect();
+        let mut lengths = TourLengths {
+            edges,
+            prefix: Vec::new(),
+            corrections: Vec::new(),
+            updates: 0,
+        };
+        lengths.rebuild();
+        lengths
+    }
+
+    /// folds the corrections into fresh prefix sums, O(n)
+    pub fn rebuild(&mut self) {
+        self.prefix.clear();
+        self.prefix.push(0.0);
+        let mut sum = 0.0;
+        for edge in &self.edges {
+            sum += edge;
+            self.prefix.push(sum);
+        }
+        self.corrections = vec![0.0; self.edges.len() + 1];
+        self.updates = 0;
+    }
+
+    pub fn len(&self) -> usize {
+        self.edges.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.edges.is_empty()
+    }
+
+    pub fn edge(&self, k: usize) -> f64 {
+        self.edges[k]
+    }
+
+    pub fn total(&self) -> f64 {
+        self.prefix_sum(self.len())
+    }
+
+    /// sum of edges `0..k`
+    fn prefix_sum(&self, k: usize) -> f64 {
+        let mut sum = self.prefix[k];
+        if self.updates > 0 {
+            let mut i = k;
+            while i > 0 {
+                sum += self.corrections[i];
+                i &= i - 1;
+            }
+        }
+        sum
+    }
+
+    /// length of the path along the tour from position `from` to position `to`,
+    /// wrapping around the end if `to` comes before `from`
+    pub fn path(&self, from: usize, to: usize) -> f64 {
+        if from <= to {
+            self.prefix_sum(to) - self.prefix_sum(from)
+        } else {
+            self.total() - self.prefix_sum(from) + self.prefix_sum(to)
+        }
+    }
+
+    /// lengths of the two paths left after removing edges `i` and `j`, `i < j`:
+    /// positions `i + 1..=j` and the rest, starting at `j + 1` and wrapping around to `i`
+    pub fn cut(&self, i: usize, j: usize) -> (f64, f64) {
+        assert!(i < j && j < self.len(), "cut needs edges i < j < n");
+        let inner = self.path(i + 1, j);
+        let outer = self.total() - self.edges[i] - self.edges[j] - inner;
+        (inner, outer)
+    }
+
+    pub fn set_edge(&mut self, k: usize, length: f64) {
+        let delta = length - self.edges[k];
+        self.edges[k] = length;
+        // rebuilding after n / log n updates keeps updates amortized O(log n)
+        let log = usize::BITS - self.len().leading_zeros();
+        if self.updates >= self.len() / log.max(1) as usize {
+            self.rebuild();
+            return;
+        }
+        self.updates += 1;
+        let mut i = k + 1;
+        while i < self.corrections.len() {
+            self.corrections[i] += delta;
+            i += i & i.wrapping_neg();
+        }
+    }
+
+    /// measures edge `k` of `nodes` again, after a move changed its ends
+    pub fn refresh_edge(&mut self, nodes: &[Node], k: usize, metric: &dyn Metric) {
+        let length = metric.distance(&nodes[k], &nodes[(k + 1) % nodes.len()]);
+        self.set_edge(k, length);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::metric::Euclidean;
+    use crate::tour_lengths::*;
+    use rand::Rng;
+
+    #[test]
+    fn test_segments_match_direct_sums() {
+        let mut rng = rand::thread_rng();
+        let mut nodes = crate::random_nodes(40, &mut rng);
+        let mut lengths = TourLengths::new(&nodes, &Euclidean);
+        let direct = |nodes: &[Node], from: usize, to: usize| {
+            let n = nodes.len();
+            let mut k = from;
+            let mut sum = 0.0;
+            while k != to {
+                sum += Euclidean.distance(&nodes[k], &nodes[(k + 1) % n]);
+                k = (k + 1) % n;
+            }
+            sum
+        };
+        let close = |a: f64, b: f64| (a - b).abs() < 1e-9;
+
+        for round in 0..200 {
+            let (from, to) = (rng.gen_range(0..40), rng.gen_range(0..40));
+            assert!(close(lengths.path(from, to), direct(&nodes, from, to)));
+            let (i, j) = (from.min(to), from.max(to));
+            if i < j {
+                let (inner, outer) = lengths.cut(i, j);
+                assert!(close(inner, direct(&nodes, i + 1, j)));
+                assert!(close(outer, direct(&nodes, (j + 1) % 40, i)));
+            }
+            // move a node, both edges at it change
+            let k = rng.gen_range(0..40);
+            nodes[k].x = rng.gen::<f64>();
+            lengths.refresh_edge(&nodes, k, &Euclidean);
+            lengths.refresh_edge(&nodes, (k + 39) % 40, &Euclidean);
+            if round % 50 == 0 {
+                assert!(close(lengths.total(), Euclidean.tour_length(&nodes)));
+            }
+        }
+    }
+}
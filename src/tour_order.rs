@@ -0,0 +1,483 @@
+//! tours as cyclic orders of node indices, with the moves local search makes on them
+//!
+//! `Tour` and the move operators keep the nodes in one array, where reversing a segment or
+//! moving a chain shifts everything in between, O(n) per move on large instances.
+//! a `TourOrder` only answers which node comes before and after another and applies 2-opt
+//! moves and chain moves, so the representation behind it can make those cheap:
+//! `ArrayOrder` reverses the shorter side of an array, `LinkedOrder` relinks a doubly linked list
+//! and moves chains in O(1), `TwoLevelOrder` reverses blocks of nodes in O(sqrt(n)).
+//! none of them needs the tour to keep its direction, a move may flip it
+
+use std::str::FromStr;
+
+/// a tour over the nodes 0..len
+pub trait TourOrder {
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn next(&self, node: usize) -> usize;
+
+    fn prev(&self, node: usize) -> usize;
+
+    /// replaces the edges (a, next(a)) and (c, next(c)) with (a, c) and (next(a), next(c))
+    fn two_opt_move(&mut self, a: usize, c: usize);
+
+    /// moves the chain from `first` forwards to `last` between `p` and `next(p)`, `last` next to
+    /// `p` if `reverse`. neither `p` nor `next(p)` may be on the chain, and it can't be all the
+    /// other nodes. as up to three 2-opt moves unless the representation has something better
+    fn move_chain(&mut self, first: usize, last: usize, p: usize, reverse: bool) {
+        let (a, z, q) = (self.prev(first), self.next(last), self.next(p));
+        // every branch puts the chain in reversed
+        if q == a {
+            exchange(self, p, a, last, z);
+        } else if p == z {
+            exchange(self, a, first, z, q);
+        } else {
+            exchange(self, a, first, p, q);
+            exchange(self, a, p, z, last);
+        }
+        if !reverse {
+            exchange(self, p, last, first, q);
+        }
+    }
+
+    /// the nodes in tour order from node 0
+    fn order(&self) -> Vec<usize> {
+        let mut order = Vec::with_capacity(self.len());
+        let mut node = 0;
+        for _ in 0..self.len() {
+            order.push(node);
+            node = self.next(node);
+        }
+        order
+    }
+}
+
+/// replaces the edges (u, v) and (w, x) with (u, w) and (v, x),
+/// where v follows u and x follows w in the same direction, whichever way the tour runs now
+pub fn exchange<O: TourOrder + ?Sized>(order: &mut O, u: usize, v: usize, w: usize, x: usize) {
+    if order.next(u) == v {
+        order.two_opt_move(u, w);
+    } else {
+        order.two_opt_move(v, x);
+    }
+}
+
+/// the array of nodes in tour order and the position of every node
+pub struct ArrayOrder {
+    order: Vec<usize>,
+    position: Vec<usize>,
+}
+
+impl ArrayOrder {
+    /// the tour 0, 1, .., n - 1
+    pub fn new(n: usize) -> Self {
+        ArrayOrder {
+            order: (0..n).collect(),
+            position: (0..n).collect(),
+        }
+    }
+}
+
+impl TourOrder for ArrayOrder {
+    fn len(&self) -> usize {
+        self.order.len()
+    }
+
+    fn next(&self, node: usize) -> usize {
+        self.order[(self.position[node] + 1) % self.order.len()]
+    }
+
+    fn prev(&self, node: usize) -> usize {
+        let n = self.order.len();
+        self.order[(self.position[node] + n - 1) % n]
+    }
+
+    /// reverses whichever side of the tour is shorter, up to n / 2 swaps
+    fn two_opt_move(&mut self, a: usize, c: usize) {
+        let n = self.order.len();
+        let (b, d) = (self.next(a), self.next(c));
+        let inner = (self.position[c] + n - self.position[b]) % n + 1;
+        let (from, length) = if 2 * inner <= n {
+            (self.position[b], inner)
+        } else {
+            (self.position[d], n - inner)
+        };
+        for k in 0..length / 2 {
+            let (i, j) = ((from + k) % n, (from + length - 1 - k) % n);
+            self.order.swap(i, j);
+            self.position[self.order[i]] = i;
+            self.position[self.order[j]] = j;
+        }
+    }
+}
+
+/// the successor and predecessor of every node
+pub struct LinkedOrder {
+    next: Vec<usize>,
+    prev: Vec<usize>,
+}
+
+impl LinkedOrder {
+    /// the tour 0, 1, .., n - 1
+    pub fn new(n: usize) -> Self {
+        LinkedOrder {
+            next: (0..n).map(|i| (i + 1) % n).collect(),
+            prev: (0..n).map(|i| (i + n - 1) % n).collect(),
+        }
+    }
+
+    /// swaps successor and predecessor along the path from `first` forwards to `last`
+    fn turn(&mut self, first: usize, last: usize) {
+        let mut node = first;
+        loop {
+            let following = self.next[node];
+            std::mem::swap(&mut self.next[node], &mut self.prev[node]);
+            if node == last {
+                break;
+            }
+            node = following;
+        }
+    }
+
+    /// turns the path from `first` forwards to `last` around in place
+    fn reverse(&mut self, first: usize, last: usize) {
+        let (before, after) = (self.prev[first], self.next[last]);
+        self.turn(first, last);
+        if before == last {
+            // the path is the whole tour
+            return;
+        }
+        self.next[before] = last;
+        self.prev[last] = before;
+        self.next[first] = after;
+        self.prev[after] = first;
+    }
+}
+
+impl TourOrder for LinkedOrder {
+    fn len(&self) -> usize {
+        self.next.len()
+    }
+
+    fn next(&self, node: usize) -> usize {
+        self.next[node]
+    }
+
+    fn prev(&self, node: usize) -> usize {
+        self.prev[node]
+    }
+
+    /// reverses whichever side of the tour is shorter, found by walking both at once,
+    /// so it costs as much as the array but no position lookups
+    fn two_opt_move(&mut self, a: usize, c: usize) {
+        let (b, d) = (self.next[a], self.next[c]);
+        let (mut x, mut y) = (b, d);
+        while x != c && y != a {
+            x = self.next[x];
+            y = self.next[y];
+        }
+        if x == c {
+            self.reverse(b, c);
+        } else {
+            self.reverse(d, a);
+        }
+    }
+
+    /// unlinks the chain and links it back in, only turning a reversed chain around
+    /// walks it, so O(1) for the short chains of or-opt
+    fn move_chain(&mut self, first: usize, last: usize, p: usize, reverse: bool) {
+        let (a, z) = (self.prev[first], self.next[last]);
+        self.next[a] = z;
+        self.prev[z] = a;
+        let q = self.next[p];
+        let (first, last) = if reverse {
+            self.turn(first, last);
+            (last, first)
+        } else {
+            (first, last)
+        };
+        self.next[p] = first;
+        self.prev[first] = p;
+        self.next[last] = q;
+        self.prev[q] = last;
+    }
+}
+
+/// a run of the tour in a `TwoLevelOrder`, its nodes in tour order unless `reversed`
+struct Block {
+    nodes: Vec<usize>,
+    reversed: bool,
+}
+
+/// the tour cut into about sqrt(n) blocks, which are listed in tour order and can each run
+/// either way
+///
+/// a 2-opt move splits at most two blocks so that the path to reverse is whole blocks, then
+/// reverses their order and flips them, O(sqrt(n)) either way. the splits leave more and smaller
+/// blocks behind, so once there are twice as many as at the start they are cut again from scratch,
+/// which is O(n) every O(sqrt(n)) moves
+pub struct TwoLevelOrder {
+    blocks: Vec<Block>,
+    /// block indices in tour order
+    order: Vec<usize>,
+    /// where every block is in `order`
+    rank: Vec<usize>,
+    /// by node, its block and where it is in the block's nodes
+    block: Vec<usize>,
+    offset: Vec<usize>,
+    /// nodes per block when they are cut
+    size: usize,
+}
+
+impl TwoLevelOrder {
+    /// the tour 0, 1, .., n - 1
+    pub fn new(n: usize) -> Self {
+        let mut order = TwoLevelOrder {
+            blocks: Vec::new(),
+            order: Vec::new(),
+            rank: Vec::new(),
+            block: vec![0; n],
+            offset: vec![0; n],
+            size: ((n as f64).sqrt().ceil() as usize).max(1),
+        };
+        order.cut((0..n).collect());
+        order
+    }
+
+    /// makes blocks of `size` nodes of the tour `nodes`
+    fn cut(&mut self, nodes: Vec<usize>) {
+        self.blocks.clear();
+        for chunk in nodes.chunks(self.size) {
+            for (offset, &node) in chunk.iter().enumerate() {
+                self.block[node] = self.blocks.len();
+                self.offset[node] = offset;
+            }
+            self.blocks.push(Block {
+                nodes: chunk.to_vec(),
+                reversed: false,
+            });
+        }
+        self.order = (0..self.blocks.len()).collect();
+        self.rank = self.order.clone();
+    }
+
+    fn first(&self, block: usize) -> usize {
+        let block = &self.blocks[block];
+        if block.reversed {
+            block.nodes[block.nodes.len() - 1]
+        } else {
+            block.nodes[0]
+        }
+    }
+
+    fn last(&self, block: usize) -> usize {
+        let block = &self.blocks[block];
+        if block.reversed {
+            block.nodes[0]
+        } else {
+            block.nodes[block.nodes.len() - 1]
+        }
+    }
+
+    /// splits the block of `node` so that `node` starts one
+    fn split_before(&mut self, node: usize) {
+        let (old, offset) = (self.block[node], self.offset[node]);
+        let reversed = self.blocks[old].reversed;
+        // the nodes from `at` on move to a new block, before the old one if it runs backwards
+        let at = if reversed { offset + 1 } else { offset };
+        if at == 0 || at == self.blocks[old].nodes.len() {
+            return;
+        }
+        let nodes = self.blocks[old].nodes.split_off(at);
+        let new = self.blocks.len();
+        for (offset, &moved) in nodes.iter().enumerate() {
+            self.block[moved] = new;
+            self.offset[moved] = offset;
+        }
+        self.blocks.push(Block { nodes, reversed });
+        self.rank.push(0);
+        let rank = self.rank[old] + usize::from(!reversed);
+        self.order.insert(rank, new);
+        for (rank, &block) in self.order.iter().enumerate().skip(rank) {
+            self.rank[block] = rank;
+        }
+    }
+}
+
+impl TourOrder for TwoLevelOrder {
+    fn len(&self) -> usize {
+        self.block.len()
+    }
+
+    fn next(&self, node: usize) -> usize {
+        let (block, offset) = (self.block[node], self.offset[node]);
+        let nodes = &self.blocks[block].nodes;
+        if self.blocks[block].reversed {
+            if offset > 0 {
+                return nodes[offset - 1];
+            }
+        } else if offset + 1 < nodes.len() {
+            return nodes[offset + 1];
+        }
+        let blocks = self.order.len();
+        self.first(self.order[(self.rank[block] + 1) % blocks])
+    }
+
+    fn prev(&self, node: usize) -> usize {
+        let (block, offset) = (self.block[node], self.offset[node]);
+        let nodes = &self.blocks[block].nodes;
+        if self.blocks[block].reversed {
+            if offset + 1 < nodes.len() {
+                return nodes[offset + 1];
+            }
+        } else if offset > 0 {
+            return nodes[offset - 1];
+        }
+        let blocks = self.order.len();
+        self.last(self.order[(self.rank[block] + blocks - 1) % blocks])
+    }
+
+    /// reverses whichever side of the tour is fewer blocks
+    fn two_opt_move(&mut self, a: usize, c: usize) {
+        let (b, d) = (self.next(a), self.next(c));
+        self.split_before(b);
+        self.split_before(d);
+        let blocks = self.order.len();
+        let (from, to) = (self.rank[self.block[b]], self.rank[self.block[d]]);
+        let inner = (to + blocks - from) % blocks;
+        let (from, length) = if 2 * inner <= blocks {
+            (from, inner)
+        } else {
+            (to, blocks - inner)
+        };
+        for k in 0..length {
+            let block = self.order[(from + k) % blocks];
+            self.blocks[block].reversed ^= true;
+        }
+        for k in 0..length / 2 {
+            let (i, j) = ((from + k) % blocks, (from + length - 1 - k) % blocks);
+            self.order.swap(i, j);
+            self.rank[self.order[i]] = i;
+            self.rank[self.order[j]] = j;
+        }
+        if self.order.len() > 2 * self.len().div_ceil(self.size) {
+            self.cut(self.order());
+        }
+    }
+}
+
+/// which `TourOrder` a local search runs on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Representation {
+    #[default]
+    Array,
+    List,
+    TwoLevel,
+}
+
+impl Representation {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Representation::Array => "array",
+            Representation::List => "list",
+            Representation::TwoLevel => "two-level",
+        }
+    }
+
+    /// the tour 0, 1, .., n - 1
+    pub fn new_order(&self, n: usize) -> Box<dyn TourOrder> {
+        match self {
+            Representation::Array => Box::new(ArrayOrder::new(n)),
+            Representation::List => Box::new(LinkedOrder::new(n)),
+            Representation::TwoLevel => Box::new(TwoLevelOrder::new(n)),
+        }
+    }
+}
+
+impl FromStr for Representation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "array" => Ok(Representation::Array),
+            "list" => Ok(Representation::List),
+            "two-level" => Ok(Representation::TwoLevel),
+            _ => Err(format!(
+                "unknown tour representation '{}', try array, list or two-level",
+                s
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tour_order::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    /// the tour from node 0 towards its smaller neighbor, to compare orders running either way
+    fn canonical(mut order: Vec<usize>) -> Vec<usize> {
+        let zero = order.iter().position(|&x| x == 0).unwrap();
+        order.rotate_left(zero);
+        if order[order.len() - 1] < order[1] {
+            order[1..].reverse();
+        }
+        order
+    }
+
+    #[test]
+    fn test_representations_agree() {
+        let n = 40;
+        let mut rng = StdRng::seed_from_u64(53);
+        let mut reference: Vec<usize> = (0..n).collect();
+        let mut orders: Vec<Box<dyn TourOrder>> = vec![
+            Representation::Array.new_order(n),
+            Representation::List.new_order(n),
+            Representation::TwoLevel.new_order(n),
+        ];
+        for _ in 0..1000 {
+            reference.rotate_left(rng.gen_range(0..n));
+            if rng.gen_bool(0.5) {
+                let i = rng.gen_range(0..n - 3);
+                let j = rng.gen_range(i + 2..n - 1);
+                let (a, b, c, d) = (
+                    reference[i],
+                    reference[i + 1],
+                    reference[j],
+                    reference[j + 1],
+                );
+                for order in orders.iter_mut() {
+                    exchange(order.as_mut(), a, b, c, d);
+                }
+                reference[i + 1..=j].reverse();
+            } else {
+                let length = rng.gen_range(1..=3);
+                let (first, last) = (reference[1], reference[length]);
+                let to = rng.gen_range(length + 1..n);
+                let (p, q) = (reference[to], reference[(to + 1) % n]);
+                let reverse = rng.gen_bool(0.5);
+                for order in orders.iter_mut() {
+                    if order.next(reference[0]) == first {
+                        order.move_chain(first, last, p, reverse);
+                    } else {
+                        order.move_chain(last, first, q, reverse);
+                    }
+                }
+                let mut chain: Vec<usize> = reference.drain(1..=length).collect();
+                if reverse {
+                    chain.reverse();
+                }
+                let at = reference.iter().position(|&x| x == q).unwrap();
+                reference.splice(at..at, chain);
+            }
+            for order in &orders {
+                assert_eq!(canonical(order.order()), canonical(reference.clone()));
+            }
+        }
+    }
+}
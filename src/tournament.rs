@@ -0,0 +1,385 @@
+use std::time::{Duration, Instant};
+
+use crate::instances::Family;
+use crate::memory::{self, format_bytes};
+use crate::metric::Euclidean;
+use crate::rng::{parallel_map, stream_rng, stream_seed};
+use crate::solvers::Solver;
+use crate::stats::{self, TestResult};
+use crate::{get_tour_length, Node};
+
+/// instance sizes every family is played at
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SizeClass {
+    Small,
+    Medium,
+    Large,
+}
+
+impl SizeClass {
+    pub const ALL: [SizeClass; 3] = [SizeClass::Small, SizeClass::Medium, SizeClass::Large];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            SizeClass::Small => "small",
+            SizeClass::Medium => "medium",
+            SizeClass::Large => "large",
+        }
+    }
+
+    /// small is kept tiny so the exact solvers get to compete too
+    pub fn node_count(&self) -> usize {
+        match self {
+            SizeClass::Small => 8,
+            SizeClass::Medium => 50,
+            SizeClass::Large => 200,
+        }
+    }
+}
+
+/// result of one solver on one instance
+#[derive(Debug, Clone)]
+pub struct Entry {
+    pub solver: String,
+    /// index of the instance, entries of the same game played the same instance
+    pub game: usize,
+    pub family: Family,
+    pub size: SizeClass,
+    /// relative excess over the best tour any solver found on the instance
+    pub gap: f64,
+    /// 1 is best, ties share a rank
+    pub rank: usize,
+    pub time: Duration,
+    /// peak heap growth of the run, with the `track-memory` feature
+    pub memory: Option<usize>,
+}
+
+/// plays every solver on `rounds` fresh instances of every family and size class
+///
+/// solvers are given all the same instance,
+/// they're skipped on instances bigger than their `max_nodes`.
+/// games are spread over `threads` threads, every game draws its instance and
+/// solver rngs from its own stream of `seed`, so the entries only depend on `seed`
+pub fn run_tournament(
+    solvers: &[Box<dyn Solver>],
+    rounds: usize,
+    seed: u64,
+    threads: usize,
+) -> Vec<Entry> {
+    let mut games = Vec::new();
+    for family in Family::ALL {
+        for size in SizeClass::ALL {
+            for _ in 0..rounds {
+                games.push((family, size));
+            }
+        }
+    }
+
+    parallel_map(games.len(), threads, |game| {
+        let (family, size) = games[game];
+        let game_seed = stream_seed(seed, game as u64);
+        let nodes = family.generate(size.node_count(), &mut stream_rng(game_seed, 0));
+        play(solvers, &nodes, game_seed)
+            .into_iter()
+            .map(|(solver, gap, rank, time, memory)| Entry {
+                solver,
+                game,
+                family,
+                size,
+                gap,
+                rank,
+                time,
+                memory,
+            })
+            .collect::<Vec<_>>()
+    })
+    .into_iter()
+    .flatten()
+    .collect()
+}
+
+/// (solver, gap, rank, time, memory) of every solver that fits the instance
+#[allow(clippy::type_complexity)]
+fn play(
+    solvers: &[Box<dyn Solver>],
+    nodes: &[Node],
+    game_seed: u64,
+) -> Vec<(String, f64, usize, Duration, Option<usize>)> {
+    let mut results: Vec<(String, f64, Duration, Option<usize>)> = Vec::new();
+    for (i, solver) in solvers.iter().enumerate() {
+        if nodes.len() > solver.max_nodes() {
+            continue;
+        }
+        let mut rng = stream_rng(game_seed, i as u64 + 1);
+        let start = Instant::now();
+        let (tour, memory) = memory::measure(|| solver.solve(nodes, &Euclidean, &mut rng));
+        let time = start.elapsed();
+        let length = get_tour_length(&tour);
+        results.push((solver.name().to_string(), length, time, memory));
+    }
+
+    let best = results
+        .iter()
+        .map(|(_, length, _, _)| *length)
+        .fold(f64::INFINITY, f64::min);
+    results
+        .iter()
+        .map(|(solver, length, time, memory)| {
+            let rank = 1 + results.iter().filter(|(_, l, _, _)| l < length).count();
+            (solver.clone(), length / best - 1.0, rank, *time, *memory)
+        })
+        .collect()
+}
+
+/// aggregated standing of one solver over a set of entries
+#[derive(Debug, Clone)]
+pub struct Standing {
+    pub solver: String,
+    pub played: usize,
+    pub wins: usize,
+    pub mean_rank: f64,
+    pub mean_gap: f64,
+    pub time: Duration,
+    /// biggest peak heap growth of any run, if memory was tracked
+    pub peak_memory: Option<usize>,
+}
+
+/// standings in scoreboard order
+///
+/// solvers that played more instances come first, so exact solvers
+/// that only played the small instances don't top the board with a 0% gap.
+/// within the same number of games the smaller mean gap wins
+pub fn standings<'a>(entries: impl Iterator<Item = &'a Entry> + Clone) -> Vec<Standing> {
+    let mut names: Vec<&str> = Vec::new();
+    for entry in entries.clone() {
+        if !names.contains(&entry.solver.as_str()) {
+            names.push(&entry.solver);
+        }
+    }
+
+    let mut standings: Vec<Standing> = names
+        .iter()
+        .map(|name| {
+            let own: Vec<&Entry> = entries.clone().filter(|e| e.solver == *name).collect();
+            let played = own.len();
+            Standing {
+                solver: name.to_string(),
+                played,
+                wins: own.iter().filter(|e| e.rank == 1).count(),
+                mean_rank: own.iter().map(|e| e.rank as f64).sum::<f64>() / played as f64,
+                mean_gap: own.iter().map(|e| e.gap).sum::<f64>() / played as f64,
+                time: own.iter().map(|e| e.time).sum(),
+                peak_memory: own.iter().filter_map(|e| e.memory).max(),
+            }
+        })
+        .collect();
+
+    standings.sort_by(|a, b| {
+        b.played
+            .cmp(&a.played)
+            .then(a.mean_gap.total_cmp(&b.mean_gap))
+    });
+    standings
+}
+
+/// signed-rank test of two solvers on the games both of them played
+#[derive(Debug, Clone)]
+pub struct Comparison {
+    pub first: String,
+    pub second: String,
+    pub games: usize,
+    /// median of the first solver's gap minus the second's, negative if the first is usually better
+    pub median_difference: f64,
+    /// None if the two found equally long tours in every game
+    pub test: Option<TestResult>,
+    /// the first solver's gaps rank lower than the second's, whether that's significant or not
+    pub first_better: bool,
+    /// at `alpha` after holm's correction over all pairs
+    pub significant: bool,
+}
+
+/// every pair of solvers in scoreboard order, the games are the pairs of the test
+pub fn pairwise(entries: &[Entry], alpha: f64) -> Vec<Comparison> {
+    let solvers: Vec<String> = standings(entries.iter())
+        .into_iter()
+        .map(|s| s.solver)
+        .collect();
+    let mut comparisons = Vec::new();
+    for (i, first) in solvers.iter().enumerate() {
+        for second in &solvers[i + 1..] {
+            let differences: Vec<f64> = entries
+                .iter()
+                .filter(|a| a.solver == *first)
+                .filter_map(|a| {
+                    entries
+                        .iter()
+                        .find(|b| b.solver == *second && b.game == a.game)
+                        .map(|b| a.gap - b.gap)
+                })
+                .collect();
+            let test = stats::wilcoxon_signed_rank(&differences);
+            // W+ below half of all ranks means the first solver's gaps are usually smaller
+            let nonzero = differences.iter().filter(|d| **d != 0.0).count() as f64;
+            let first_better = test.is_some_and(|t| t.statistic < nonzero * (nonzero + 1.0) / 4.0);
+            comparisons.push(Comparison {
+                first: first.clone(),
+                second: second.clone(),
+                games: differences.len(),
+                median_difference: stats::median(&differences),
+                test,
+                first_better,
+                significant: false,
+            });
+        }
+    }
+    let p_values: Vec<f64> = comparisons
+        .iter()
+        .map(|c| c.test.map_or(1.0, |t| t.p_value))
+        .collect();
+    for (comparison, significant) in comparisons.iter_mut().zip(stats::holm(&p_values, alpha)) {
+        comparison.significant = significant;
+    }
+    comparisons
+}
+
+/// overall ranking, the mean gap of every solver per family and size,
+/// then which differences between solvers are significant at `alpha`
+pub fn print_scoreboard(entries: &[Entry], alpha: f64) {
+    let overall = standings(entries.iter());
+    let width = 2 + overall.iter().map(|s| s.solver.len()).max().unwrap_or(0);
+
+    let tracked = overall.iter().any(|s| s.peak_memory.is_some());
+
+    println!("tournament scoreboard");
+    print!(
+        "{:>4}  {:<width$}{:>8}{:>7}{:>11}{:>11}{:>12}",
+        "#", "solver", "played", "wins", "mean rank", "mean gap", "time"
+    );
+    if tracked {
+        print!("{:>13}", "peak memory");
+    }
+    println!();
+    for (i, standing) in overall.iter().enumerate() {
+        print!(
+            "{:>4}  {:<width$}{:>8}{:>7}{:>11.2}{:>10.2}%{:>12.3?}",
+            i + 1,
+            standing.solver,
+            standing.played,
+            standing.wins,
+            standing.mean_rank,
+            100.0 * standing.mean_gap,
+            standing.time
+        );
+        if let Some(peak) = standing.peak_memory {
+            print!("{:>13}", format_bytes(peak));
+        }
+        println!();
+    }
+
+    for family in Family::ALL {
+        println!();
+        println!("{} instances, mean gap to best", family.name());
+        print!("      {:<width$}", "solver");
+        for size in SizeClass::ALL {
+            print!("{:>14}", format!("{} ({})", size.name(), size.node_count()));
+        }
+        println!();
+
+        for standing in &overall {
+            print!("      {:<width$}", standing.solver);
+            for size in SizeClass::ALL {
+                let cell = standings(entries.iter().filter(|e| {
+                    e.family == family && e.size == size && e.solver == standing.solver
+                }));
+                match cell.first() {
+                    Some(s) => print!("{:>13.2}%", 100.0 * s.mean_gap),
+                    None => print!("{:>14}", "-"),
+                }
+            }
+            println!();
+        }
+    }
+
+    println!();
+    println!(
+        "pairwise wilcoxon signed-rank tests on the gaps, alpha {} with holm's correction",
+        alpha
+    );
+    println!(
+        "      {:<width$}{:<width$}{:>7}{:>13}{:>10}  verdict",
+        "solver", "against", "games", "median diff", "p-value"
+    );
+    for comparison in pairwise(entries, alpha) {
+        let p_value = comparison
+            .test
+            .map_or("-".to_string(), |t| format!("{:.4}", t.p_value));
+        let verdict = match (comparison.significant, comparison.first_better) {
+            (false, _) => "no significant difference".to_string(),
+            (true, true) => format!("{} is better", comparison.first),
+            (true, false) => format!("{} is better", comparison.second),
+        };
+        println!(
+            "      {:<width$}{:<width$}{:>7}{:>12.2}%{:>10}  {}",
+            comparison.first,
+            comparison.second,
+            comparison.games,
+            100.0 * comparison.median_difference,
+            p_value,
+            verdict
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::solvers::{solver_by_name, NearestNeighbor, Solver};
+    use crate::tournament::*;
+
+    #[test]
+    fn test_tournament_single_solver_always_wins() {
+        let solvers: Vec<Box<dyn Solver>> = vec![Box::new(NearestNeighbor)];
+        let entries = run_tournament(&solvers, 1, 0, 1);
+        assert_eq!(entries.len(), Family::ALL.len() * SizeClass::ALL.len());
+        assert!(entries.iter().all(|e| e.rank == 1 && e.gap == 0.0));
+        assert_eq!(standings(entries.iter())[0].wins, entries.len());
+    }
+
+    #[test]
+    fn test_pairwise_significance() {
+        let solvers: Vec<Box<dyn Solver>> = vec![
+            Box::new(NearestNeighbor),
+            Box::new(crate::pipeline::Pipeline::new().then(NearestNeighbor).then(
+                crate::solvers::LocalSearch::new(crate::local_search::TwoHOpt),
+            )),
+        ];
+        let entries = run_tournament(&solvers, 2, 1, 1);
+        let comparisons = pairwise(&entries, stats::DEFAULT_ALPHA);
+        assert_eq!(comparisons.len(), 1);
+        // 2.5-opt improves every nearest neighbor tour but the tiny ones
+        let comparison = &comparisons[0];
+        assert_eq!(comparison.games, entries.len() / 2);
+        assert!(comparison.significant, "{:?}", comparison);
+        assert_eq!(comparison.first, "nearest neighbor -> 2.5-opt");
+        assert!(comparison.first_better);
+    }
+
+    #[test]
+    fn test_tournament_independent_of_threads() {
+        // short annealing runs, the presets take too long for debug builds
+        let solvers: Vec<Box<dyn Solver>> = vec![
+            Box::new(NearestNeighbor),
+            solver_by_name("sa:iterations=2000").unwrap(),
+            solver_by_name("sa:iterations=500:reversals=1").unwrap(),
+        ];
+        let summary = |entries: Vec<Entry>| {
+            entries
+                .into_iter()
+                .map(|e| (e.solver, e.gap, e.rank))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            summary(run_tournament(&solvers, 1, 5, 1)),
+            summary(run_tournament(&solvers, 1, 5, 4))
+        );
+    }
+}
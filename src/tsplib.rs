@@ -0,0 +1,704 @@
+//! tsplib `.tsp` instances and `.tour` tours
+//!
+//! tsplib numbers nodes from 1, the crate from 0: node `i` of a file is id `i - 1` here
+//! and written back as `i`
+
+use std::fmt::Write;
+
+use crate::explicit::ExplicitMatrix;
+use crate::metric::{Metric, MetricKind};
+use crate::tour::Tour;
+use crate::Node;
+
+/// name, metric and nodes of a `.tsp` file
+#[derive(Debug, Clone)]
+pub struct TsplibInstance {
+    pub name: String,
+    pub metric: TsplibMetric,
+    pub nodes: Vec<Node>,
+}
+
+/// the EDGE_WEIGHT_TYPEs tsplib's symmetric instances use, see `TsplibMetric`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EdgeWeightType {
+    /// unrounded euclidean, not tsplib's own but what other tools write
+    Exact2d,
+    /// euclidean rounded to the nearest integer
+    Euc2d,
+    /// pseudo-euclidean of att48 and att532, rounded up
+    Att,
+    /// great circle distances in km on a sphere, coordinates are degrees.minutes
+    Geo,
+    /// distances are given in the EDGE_WEIGHT_SECTION
+    Explicit,
+}
+
+impl EdgeWeightType {
+    pub fn name(self) -> &'static str {
+        match self {
+            EdgeWeightType::Exact2d => "EXACT_2D",
+            EdgeWeightType::Euc2d => "EUC_2D",
+            EdgeWeightType::Att => "ATT",
+            EdgeWeightType::Geo => "GEO",
+            EdgeWeightType::Explicit => "EXPLICIT",
+        }
+    }
+}
+
+impl std::str::FromStr for EdgeWeightType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        [
+            EdgeWeightType::Exact2d,
+            EdgeWeightType::Euc2d,
+            EdgeWeightType::Att,
+            EdgeWeightType::Geo,
+            EdgeWeightType::Explicit,
+        ]
+        .into_iter()
+        .find(|kind| kind.name() == s)
+        .ok_or_else(|| format!("unsupported edge weight type '{}'", s))
+    }
+}
+
+/// distances the way tsplib defines them, so tour lengths match the published optima.
+/// the 2d types measure the coordinates of the nodes, EXPLICIT looks the ids up
+#[derive(Debug, Clone)]
+pub struct TsplibMetric {
+    weight_type: EdgeWeightType,
+    /// the distances of EXPLICIT instances
+    matrix: Option<ExplicitMatrix>,
+}
+
+/// the value of pi and the earth radius in km of tsplib's GEO, the short pi is part of the definition
+#[allow(clippy::approx_constant)]
+const GEO_PI: f64 = 3.141592;
+const GEO_RADIUS: f64 = 6378.388;
+
+/// degrees.minutes of a GEO coordinate in radians. the degrees are truncated,
+/// not rounded like the tsplib paper says, because that's what the optima were computed with
+fn geo_radians(value: f64) -> f64 {
+    let degrees = value.trunc();
+    GEO_PI * (degrees + 5.0 * (value - degrees) / 3.0) / 180.0
+}
+
+impl TsplibMetric {
+    /// a metric of the node coordinates, None for EXPLICIT
+    pub fn new(weight_type: EdgeWeightType) -> Option<Self> {
+        (weight_type != EdgeWeightType::Explicit).then_some(TsplibMetric {
+            weight_type,
+            matrix: None,
+        })
+    }
+
+    pub fn explicit(matrix: ExplicitMatrix) -> Self {
+        TsplibMetric {
+            weight_type: EdgeWeightType::Explicit,
+            matrix: Some(matrix),
+        }
+    }
+
+    pub fn weight_type(&self) -> EdgeWeightType {
+        self.weight_type
+    }
+
+    /// the crate's own metric that measures the same, if there is one
+    pub fn kind(&self) -> Option<MetricKind> {
+        match self.weight_type {
+            EdgeWeightType::Exact2d => Some(MetricKind::Euclidean),
+            EdgeWeightType::Euc2d => Some(MetricKind::Integer),
+            _ => None,
+        }
+    }
+
+    /// tells metrics apart in cache keys, EXPLICIT ones by a hash of their weights
+    pub fn name(&self) -> String {
+        match &self.matrix {
+            Some(matrix) => format!("tsplib {}", matrix.name()),
+            None => format!("tsplib {}", self.weight_type.name()),
+        }
+    }
+}
+
+impl Metric for TsplibMetric {
+    fn distance(&self, from: &Node, to: &Node) -> f64 {
+        let (dx, dy) = (from.x - to.x, from.y - to.y);
+        match self.weight_type {
+            EdgeWeightType::Exact2d => dx.hypot(dy),
+            EdgeWeightType::Euc2d => ((dx * dx + dy * dy).sqrt() + 0.5).floor(),
+            EdgeWeightType::Att => {
+                let distance = ((dx * dx + dy * dy) / 10.0).sqrt();
+                let rounded = (distance + 0.5).floor();
+                if rounded < distance {
+                    rounded + 1.0
+                } else {
+                    rounded
+                }
+            }
+            EdgeWeightType::Geo => {
+                // acos(1) would still come out as 1 km
+                if from.id == to.id {
+                    return 0.0;
+                }
+                let (latitude, longitude) = (geo_radians(from.x), geo_radians(from.y));
+                let (other_latitude, other_longitude) = (geo_radians(to.x), geo_radians(to.y));
+                let q1 = (longitude - other_longitude).cos();
+                let q2 = (latitude - other_latitude).cos();
+                let q3 = (latitude + other_latitude).cos();
+                let cosine = (0.5 * ((1.0 + q1) * q2 - (1.0 - q1) * q3)).clamp(-1.0, 1.0);
+                (GEO_RADIUS * cosine.acos() + 1.0).floor()
+            }
+            EdgeWeightType::Explicit => self
+                .matrix
+                .as_ref()
+                .expect("EXPLICIT metrics have a matrix")
+                .distance(from, to),
+        }
+    }
+
+    fn is_symmetric(&self) -> bool {
+        self.matrix.as_ref().is_none_or(Metric::is_symmetric)
+    }
+}
+
+/// `KEY : value` header line, None for section keywords and the like
+fn header(line: &str) -> Option<(&str, &str)> {
+    let (key, value) = line.split_once(':')?;
+    Some((key.trim(), value.trim()))
+}
+
+fn edge_weight_type(metric: MetricKind) -> &'static str {
+    match metric {
+        MetricKind::Integer => "EUC_2D",
+        // tsplib has no unrounded euclidean type, EXACT_2D is what other tools use for it
+        MetricKind::Euclidean => "EXACT_2D",
+    }
+}
+
+/// which section the lines belong to
+#[derive(PartialEq)]
+enum Section {
+    Header,
+    Coordinates,
+    /// DISPLAY_DATA_SECTION, coordinates only for drawing EXPLICIT instances
+    Display,
+    Weights,
+}
+
+/// (row, column) of every weight of an EDGE_WEIGHT_SECTION in the order of `format`,
+/// for symmetric matrices the column formats are the row formats of the other triangle
+fn weight_positions(format: &str, n: usize) -> Result<Vec<(usize, usize)>, String> {
+    let (upper, diagonal) = match format {
+        "FULL_MATRIX" => {
+            return Ok((0..n).flat_map(|i| (0..n).map(move |j| (i, j))).collect());
+        }
+        "UPPER_ROW" | "LOWER_COL" => (true, false),
+        "LOWER_ROW" | "UPPER_COL" => (false, false),
+        "UPPER_DIAG_ROW" | "LOWER_DIAG_COL" => (true, true),
+        "LOWER_DIAG_ROW" | "UPPER_DIAG_COL" => (false, true),
+        _ => return Err(format!("unsupported edge weight format '{}'", format)),
+    };
+    let mut positions = Vec::new();
+    for i in 0..n {
+        let columns = if upper {
+            (if diagonal { i } else { i + 1 })..n
+        } else {
+            0..(if diagonal { i + 1 } else { i })
+        };
+        positions.extend(columns.map(|j| (i, j)));
+    }
+    Ok(positions)
+}
+
+/// the `id x y` line of a coordinate section
+fn coordinate_line(line: &str, seen: &mut Vec<bool>) -> Result<Node, String> {
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    let [id, x, y] = fields[..] else {
+        return Err(format!("expected 'id x y', got '{}'", line));
+    };
+    let id: usize = id
+        .parse()
+        .map_err(|err| format!("invalid id '{}': {}", id, err))?;
+    if id == 0 {
+        return Err("tsplib node ids start at 1".to_string());
+    }
+    if seen.len() < id {
+        seen.resize(id, false);
+    }
+    if std::mem::replace(&mut seen[id - 1], true) {
+        return Err(format!("node {} appears twice", id));
+    }
+    Ok(Node {
+        id: id - 1,
+        x: x.parse().map_err(|err| format!("invalid x: {}", err))?,
+        y: y.parse().map_err(|err| format!("invalid y: {}", err))?,
+    })
+}
+
+/// reads a symmetric instance, with coordinates or an explicit matrix, or an asymmetric
+/// ATSP one with a FULL_MATRIX of weights from row to column. errors name the line.
+/// EXPLICIT instances without DISPLAY_DATA_SECTION get their nodes placed on a circle
+pub fn parse_tsp(text: &str) -> Result<TsplibInstance, String> {
+    let mut name = String::new();
+    let mut asymmetric = false;
+    let mut weight_type = None;
+    let mut weight_format = None;
+    // with the line it's on
+    let mut dimension = None;
+    let mut nodes: Vec<Node> = Vec::new();
+    let mut display: Vec<Node> = Vec::new();
+    let (mut seen, mut seen_display) = (Vec::new(), Vec::new());
+    let mut weights: Vec<f64> = Vec::new();
+    let mut section = Section::Header;
+    let mut coordinates = false;
+
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        let error = |message: String| format!("line {}: {}", number + 1, message);
+        if line.is_empty() {
+            continue;
+        }
+        if line == "EOF" {
+            break;
+        }
+        if line.ends_with("_SECTION") {
+            section = match line {
+                "NODE_COORD_SECTION" => Section::Coordinates,
+                "DISPLAY_DATA_SECTION" => Section::Display,
+                "EDGE_WEIGHT_SECTION" => Section::Weights,
+                _ => return Err(error(format!("unsupported section '{}'", line))),
+            };
+            coordinates |= section == Section::Coordinates;
+            continue;
+        }
+        match section {
+            Section::Coordinates => nodes.push(coordinate_line(line, &mut seen).map_err(error)?),
+            Section::Display => {
+                display.push(coordinate_line(line, &mut seen_display).map_err(error)?)
+            }
+            Section::Weights => {
+                for token in line.split_whitespace() {
+                    weights.push(
+                        token
+                            .parse()
+                            .map_err(|err| error(format!("invalid weight '{}': {}", token, err)))?,
+                    );
+                }
+            }
+            Section::Header => {
+                let Some((key, value)) = header(line) else {
+                    return Err(error(format!("expected 'KEY : value', got '{}'", line)));
+                };
+                match key {
+                    "NAME" => name = value.to_string(),
+                    "TYPE" => {
+                        asymmetric = match value {
+                            "TSP" => false,
+                            "ATSP" => true,
+                            _ => {
+                                return Err(error(format!("unsupported problem type '{}'", value)))
+                            }
+                        }
+                    }
+                    "DIMENSION" => {
+                        let value = value
+                            .parse::<usize>()
+                            .map_err(|err| error(format!("invalid dimension: {}", err)))?;
+                        dimension = Some((value, number + 1));
+                    }
+                    "EDGE_WEIGHT_TYPE" => weight_type = Some(value.parse().map_err(error)?),
+                    "EDGE_WEIGHT_FORMAT" => weight_format = Some((value.to_string(), number + 1)),
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    let weight_type: EdgeWeightType = weight_type.ok_or("missing EDGE_WEIGHT_TYPE")?;
+    let metric = match TsplibMetric::new(weight_type) {
+        Some(_) if asymmetric => {
+            return Err("ATSP instances need EXPLICIT edge weights".to_string())
+        }
+        Some(metric) => {
+            if !coordinates {
+                return Err("missing NODE_COORD_SECTION".to_string());
+            }
+            metric
+        }
+        None => {
+            let (n, _) = dimension.ok_or("EXPLICIT instances need a DIMENSION")?;
+            let (format, line) = weight_format.ok_or("missing EDGE_WEIGHT_FORMAT")?;
+            if asymmetric && format != "FULL_MATRIX" {
+                return Err(format!(
+                    "line {}: ATSP weights have to be a FULL_MATRIX, got {}",
+                    line, format
+                ));
+            }
+            let positions =
+                weight_positions(&format, n).map_err(|err| format!("line {}: {}", line, err))?;
+            if positions.len() != weights.len() {
+                return Err(format!(
+                    "{} of {} nodes needs {} weights, got {}",
+                    format,
+                    n,
+                    positions.len(),
+                    weights.len()
+                ));
+            }
+            let mut rows = vec![vec![0.0; n]; n];
+            for ((i, j), weight) in positions.into_iter().zip(weights) {
+                rows[i][j] = weight;
+                if format != "FULL_MATRIX" {
+                    rows[j][i] = weight;
+                }
+            }
+            // tours never go from a node to itself, whatever the file says about it
+            for (i, row) in rows.iter_mut().enumerate() {
+                row[i] = 0.0;
+            }
+            let matrix = if asymmetric {
+                ExplicitMatrix::directed(&rows)?
+            } else {
+                ExplicitMatrix::new(&rows)?
+            };
+            nodes = if display.is_empty() {
+                matrix.nodes()
+            } else {
+                display
+            };
+            TsplibMetric::explicit(matrix)
+        }
+    };
+    if let Some((dimension, line)) = dimension {
+        if dimension != nodes.len() {
+            return Err(format!(
+                "line {}: dimension is {} but there are {} nodes",
+                line,
+                dimension,
+                nodes.len()
+            ));
+        }
+    }
+    if weight_type == EdgeWeightType::Explicit {
+        if let Some(node) = nodes.iter().find(|node| node.id >= nodes.len()) {
+            return Err(format!(
+                "node {} of {} is out of range",
+                node.id + 1,
+                nodes.len()
+            ));
+        }
+    }
+    Ok(TsplibInstance {
+        name,
+        metric,
+        nodes,
+    })
+}
+
+/// that `path` is a tsplib instance by its extension, `.tsp` or `.atsp`
+pub fn is_instance_path(path: &str) -> bool {
+    path.ends_with(".tsp") || path.ends_with(".atsp")
+}
+
+/// `parse_tsp` of a file, e.g. one of the tsplib benchmarks like berlin52.tsp or br17.atsp
+pub fn read_tsp(path: &str) -> Result<TsplibInstance, String> {
+    let text = std::fs::read_to_string(path).map_err(|err| format!("{}: {}", path, err))?;
+    parse_tsp(&text).map_err(|err| format!("{}: {}", path, err))
+}
+
+pub fn format_tsp(name: &str, metric: MetricKind, nodes: &[Node]) -> String {
+    // writing to a String never fails
+    let mut text = String::new();
+    let _ = writeln!(text, "NAME : {}", name);
+    let _ = writeln!(text, "TYPE : TSP");
+    let _ = writeln!(text, "DIMENSION : {}", nodes.len());
+    let _ = writeln!(text, "EDGE_WEIGHT_TYPE : {}", edge_weight_type(metric));
+    let _ = writeln!(text, "NODE_COORD_SECTION");
+    for node in nodes {
+        let _ = writeln!(text, "{} {} {}", node.id + 1, node.x, node.y);
+    }
+    text.push_str("EOF\n");
+    text
+}
+
+/// ids of the TOUR_SECTION of a `.tour` or `.opt.tour` file, up to its -1
+pub fn parse_tour(text: &str) -> Result<Vec<usize>, String> {
+    let mut ids = Vec::new();
+    let mut in_tour = false;
+    // with the line it's on
+    let mut dimension = None;
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if !in_tour {
+            in_tour = line == "TOUR_SECTION";
+            if let Some(("DIMENSION", value)) = header(line) {
+                let value = value
+                    .parse::<usize>()
+                    .map_err(|err| format!("line {}: invalid dimension: {}", number + 1, err))?;
+                dimension = Some((value, number + 1));
+            }
+            continue;
+        }
+        for token in line.split_whitespace() {
+            if token == "-1" {
+                if let Some((dimension, line)) = dimension.filter(|&(d, _)| d != ids.len()) {
+                    return Err(format!(
+                        "line {}: dimension is {} but the tour has {} nodes",
+                        line,
+                        dimension,
+                        ids.len()
+                    ));
+                }
+                return Ok(ids);
+            }
+            match token.parse::<usize>() {
+                Ok(id) if id > 0 => ids.push(id - 1),
+                _ => return Err(format!("line {}: invalid node '{}'", number + 1, token)),
+            }
+        }
+    }
+    if in_tour {
+        Err("TOUR_SECTION doesn't end with -1".to_string())
+    } else {
+        Err("missing TOUR_SECTION".to_string())
+    }
+}
+
+/// `parse_tour` of a file, e.g. the optimal tours like berlin52.opt.tour
+pub fn read_tour(path: &str) -> Result<Vec<usize>, String> {
+    let text = std::fs::read_to_string(path).map_err(|err| format!("{}: {}", path, err))?;
+    parse_tour(&text).map_err(|err| format!("{}: {}", path, err))
+}
+
+/// length of the tour under the metric of the instance, if it visits every node once
+pub fn verify_tour(instance: &TsplibInstance, ids: &[usize]) -> Result<f64, String> {
+    let tour = Tour::from_ids(ids, &instance.nodes)?;
+    Ok(instance.metric.tour_length(tour.nodes()))
+}
+
+pub fn format_tour(name: &str, ids: &[usize]) -> String {
+    let mut text = String::new();
+    let _ = writeln!(text, "NAME : {}", name);
+    let _ = writeln!(text, "TYPE : TOUR");
+    let _ = writeln!(text, "DIMENSION : {}", ids.len());
+    let _ = writeln!(text, "TOUR_SECTION");
+    for id in ids {
+        let _ = writeln!(text, "{}", id + 1);
+    }
+    text.push_str("-1\nEOF\n");
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tsplib::*;
+
+    #[test]
+    fn test_tsp_round_trip() {
+        let text = "NAME : tiny\nCOMMENT : three nodes\nTYPE : TSP\nDIMENSION : 3\n\
+                    EDGE_WEIGHT_TYPE : EUC_2D\nNODE_COORD_SECTION\n1 0 0\n2 3 0\n3 3 4\nEOF\n";
+        let instance = parse_tsp(text).unwrap();
+        assert_eq!(instance.name, "tiny");
+        assert_eq!(instance.metric.kind(), Some(MetricKind::Integer));
+        assert_eq!(instance.nodes[2].id, 2);
+        assert_eq!(instance.nodes[2].y, 4.0);
+        let written = format_tsp(&instance.name, MetricKind::Integer, &instance.nodes);
+        assert!(written.contains("NODE_COORD_SECTION\n1 0 0\n"));
+        assert_eq!(parse_tsp(&written).unwrap().nodes, instance.nodes);
+
+        assert!(parse_tsp(&text.replace("DIMENSION : 3", "DIMENSION : 4")).is_err());
+        assert_eq!(
+            parse_tsp(&text.replace("2 3 0", "2 3")).unwrap_err(),
+            "line 8: expected 'id x y', got '2 3'"
+        );
+    }
+
+    #[test]
+    fn test_tsplib_headers_and_errors() {
+        // the way the tsplib files are written, e.g. berlin52
+        let text = "NAME: berlin3\nTYPE: TSP\nCOMMENT: 3 locations in Berlin (Groetschel)\n\
+                    DIMENSION: 3\nEDGE_WEIGHT_TYPE: EUC_2D\nNODE_COORD_SECTION\n\
+                    1 565.0 575.0\n2 25.0 185.0\n3 3.45e2 750.0\n\
+                    DISPLAY_DATA_SECTION\n1 565.0 575.0\nEOF\n";
+        let instance = parse_tsp(text).unwrap();
+        assert_eq!(instance.name, "berlin3");
+        assert_eq!(instance.nodes.len(), 3);
+        assert_eq!(instance.nodes[2].x, 345.0);
+
+        let errors = [
+            (
+                text.replace("2 25.0", "1 25.0"),
+                "line 8: node 1 appears twice",
+            ),
+            (
+                text.replace("DIMENSION: 3", "DIMENSION: 2"),
+                "line 4: dimension is 2 but there are 3 nodes",
+            ),
+            (
+                text.replace("3 3.45e2", "3 x"),
+                "line 9: invalid x: invalid float literal",
+            ),
+            (
+                text.replace("DISPLAY_DATA", "FIXED_EDGES"),
+                "line 10: unsupported section 'FIXED_EDGES_SECTION'",
+            ),
+            (
+                text.replace("NODE_COORD_SECTION\n", ""),
+                "line 6: expected 'KEY : value', got '1 565.0 575.0'",
+            ),
+            (
+                "NAME: empty\nEDGE_WEIGHT_TYPE: EUC_2D\nEOF\n".to_string(),
+                "missing NODE_COORD_SECTION",
+            ),
+        ];
+        for (text, error) in errors {
+            assert_eq!(parse_tsp(&text).unwrap_err(), error);
+        }
+    }
+
+    /// burma14, its optimal tour is 3323
+    const BURMA14: &str = "NAME: burma14\nTYPE: TSP\nDIMENSION: 14\nEDGE_WEIGHT_TYPE: GEO\n\
+                           NODE_COORD_SECTION\n1 16.47 96.10\n2 16.47 94.44\n3 20.09 92.54\n\
+                           4 22.39 93.37\n5 25.23 97.24\n6 22.00 96.05\n7 20.47 97.02\n\
+                           8 17.20 96.29\n9 16.30 97.38\n10 14.05 98.12\n11 16.53 97.38\n\
+                           12 21.52 95.59\n13 19.41 97.13\n14 20.09 94.55\nEOF\n";
+
+    #[test]
+    fn test_edge_weights_match_published_optima() {
+        let instance = parse_tsp(BURMA14).unwrap();
+        let tour: Vec<Node> = [1, 2, 14, 3, 4, 5, 6, 12, 7, 13, 8, 11, 9, 10]
+            .iter()
+            .map(|&id| instance.nodes[id - 1].clone())
+            .collect();
+        assert_eq!(instance.metric.tour_length(&tour), 3323.0);
+        assert_eq!(instance.metric.distance(&tour[0], &tour[0]), 0.0);
+
+        let node = |id, x, y| Node { id, x, y };
+        let (a, b) = (node(0, 0.0, 0.0), node(1, 1.5, 2.0));
+        let distance = |weight_type| TsplibMetric::new(weight_type).unwrap().distance(&a, &b);
+        assert_eq!(distance(EdgeWeightType::Exact2d), 2.5);
+        assert_eq!(distance(EdgeWeightType::Euc2d), 3.0);
+        // sqrt(6.25 / 10) = 0.79 rounds to 1, sqrt(1000 / 10) = 10 stays
+        assert_eq!(distance(EdgeWeightType::Att), 1.0);
+        let far = node(1, 30.0, 10.0);
+        let att = TsplibMetric::new(EdgeWeightType::Att).unwrap();
+        assert_eq!(att.distance(&a, &far), 10.0);
+        assert_eq!(att.distance(&a, &node(1, 31.0, 10.0)), 11.0);
+    }
+
+    #[test]
+    fn test_explicit_formats_agree() {
+        // 4 nodes, d(i, j) = 10 i + j for i < j
+        let weights = [
+            ("FULL_MATRIX", "0 1 2 3\n1 0 12 13\n2 12 0 23\n3 13 23 0"),
+            ("UPPER_ROW", "1 2 3\n12 13\n23"),
+            ("LOWER_ROW", "1\n2 12\n3 13 23"),
+            ("UPPER_DIAG_ROW", "0 1 2 3 0 12 13 0 23 0"),
+            ("LOWER_DIAG_ROW", "0 1 0 2 12 0 3 13 23 0"),
+            ("UPPER_COL", "1 2 12 3 13 23"),
+        ];
+        for (format, section) in weights {
+            let text = format!(
+                "NAME: four\nTYPE: TSP\nDIMENSION: 4\nEDGE_WEIGHT_TYPE: EXPLICIT\n\
+                 EDGE_WEIGHT_FORMAT: {}\nEDGE_WEIGHT_SECTION\n{}\nEOF\n",
+                format, section
+            );
+            let instance = parse_tsp(&text).unwrap();
+            assert_eq!(instance.nodes.len(), 4);
+            let nodes = &instance.nodes;
+            assert_eq!(
+                instance.metric.tour_length(nodes),
+                1.0 + 12.0 + 23.0 + 3.0,
+                "{}",
+                format
+            );
+            assert_eq!(
+                instance.metric.distance(&nodes[3], &nodes[1]),
+                13.0,
+                "{}",
+                format
+            );
+            assert!(instance.metric.name().starts_with("tsplib explicit "));
+
+            let extra = text.replace("\nEOF", " 7\nEOF");
+            assert!(parse_tsp(&extra).unwrap_err().contains("weights, got"));
+        }
+    }
+
+    #[test]
+    fn test_atsp_instances() {
+        // the diagonal of atsp files is a large number rather than 0
+        let text = "NAME: one-way\nTYPE: ATSP\nDIMENSION: 4\nEDGE_WEIGHT_TYPE: EXPLICIT\n\
+                    EDGE_WEIGHT_FORMAT: FULL_MATRIX\nEDGE_WEIGHT_SECTION\n\
+                    9999 1 8 8\n9 9999 1 8\n9 9 9999 1\n1 9 9 9999\nEOF\n";
+        let instance = parse_tsp(text).unwrap();
+        let (metric, nodes) = (&instance.metric, &instance.nodes);
+        assert!(!metric.is_symmetric());
+        assert_eq!(metric.tour_length(nodes), 4.0);
+        let backwards: Vec<Node> = nodes.iter().rev().cloned().collect();
+        assert_eq!(metric.tour_length(&backwards), 35.0);
+        assert_eq!(verify_tour(&instance, &[0, 3, 2, 1]), Ok(35.0));
+
+        let triangle = text.replace("FULL_MATRIX", "UPPER_ROW").replace(
+            "9999 1 8 8\n9 9999 1 8\n9 9 9999 1\n1 9 9 9999",
+            "1 8 8 1 8 1",
+        );
+        assert_eq!(
+            parse_tsp(&triangle).unwrap_err(),
+            "line 5: ATSP weights have to be a FULL_MATRIX, got UPPER_ROW"
+        );
+        let coordinates = "TYPE: ATSP\nEDGE_WEIGHT_TYPE: EUC_2D\nNODE_COORD_SECTION\n1 0 0\nEOF\n";
+        assert!(parse_tsp(coordinates)
+            .unwrap_err()
+            .contains("need EXPLICIT"));
+        // an asymmetric matrix in a TSP file is a broken file
+        assert!(parse_tsp(&text.replace("TYPE: ATSP", "TYPE: TSP"))
+            .unwrap_err()
+            .contains("has to be symmetric"));
+
+        let path = std::env::temp_dir().join("tsp_test_one_way.atsp");
+        std::fs::write(&path, text).unwrap();
+        let path = path.to_str().unwrap();
+        assert!(is_instance_path(path) && is_instance_path("data/berlin52.tsp"));
+        assert!(!is_instance_path("data/berlin52.opt.tour"));
+        let read = read_tsp(path).unwrap();
+        assert_eq!(read.metric.tour_length(&read.nodes), 4.0);
+        assert!(!read.metric.is_symmetric());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_heuristics_near_the_optimal_tour() {
+        let instance = parse_tsp(BURMA14).unwrap();
+        let optimal =
+            "NAME : burma14.opt.tour\nCOMMENT : Optimum solution for burma14\nTYPE : TOUR\n\
+                       DIMENSION : 14\nTOUR_SECTION\n1\n2\n14\n3\n4\n5\n6\n12\n7\n13\n8\n11\n\
+                       9\n10\n-1\nEOF\n";
+        let ids = parse_tour(optimal).unwrap();
+        let optimum = verify_tour(&instance, &ids).unwrap();
+        assert_eq!(optimum, 3323.0);
+
+        let tour = crate::solvers::tsp_two_opt(
+            &crate::solvers::tsp_nearest_neighbor(&instance.nodes, &instance.metric),
+            &instance.metric,
+        );
+        let ids: Vec<usize> = tour.iter().map(|node| node.id).collect();
+        let length = verify_tour(&instance, &ids).unwrap();
+        assert!(length <= 1.05 * optimum, "{} against {}", length, optimum);
+
+        assert!(verify_tour(&instance, &ids[1..]).is_err());
+        assert_eq!(
+            parse_tour(&optimal.replace("\n10\n", "\n")).unwrap_err(),
+            "line 4: dimension is 14 but the tour has 13 nodes"
+        );
+    }
+
+    #[test]
+    fn test_tour_round_trip() {
+        let text = format_tour("tiny", &[0, 2, 1]);
+        assert!(text.contains("TOUR_SECTION\n1\n3\n2\n-1\n"));
+        assert_eq!(parse_tour(&text).unwrap(), vec![0, 2, 1]);
+        assert!(parse_tour("TOUR_SECTION\n1 2").is_err());
+    }
+}
@@ -0,0 +1,93 @@
+//! variable neighborhood search over the `local_search` neighborhoods, weakest to strongest:
+//! swap, 2-opt, or-opt and 3-opt
+//!
+//! every iteration shakes the tour with a random move of the current neighborhood and descends
+//! to a local optimum again. a shorter tour is kept and the shaking starts over at the weakest
+//! neighborhood, otherwise it moves on to the next one, wrapping around after 3-opt.
+//! the descent cycles through swap, 2-opt and or-opt until none of them improves the tour,
+//! a cubic 3-opt sweep after every shake would take too long
+
+use rand::rngs::StdRng;
+
+use crate::cancel;
+use crate::local_search::{local_search, MoveOperator, OrOpt, Swap, ThreeOpt, TwoOpt};
+use crate::metric::Metric;
+use crate::Node;
+
+/// the neighborhoods shaking goes through, in order
+fn neighborhoods() -> Vec<Box<dyn MoveOperator>> {
+    vec![
+        Box::new(Swap),
+        Box::new(TwoOpt),
+        Box::new(OrOpt::default()),
+        Box::new(ThreeOpt::default()),
+    ]
+}
+
+/// variable neighborhood descent: local search in the first neighborhood until it's stuck,
+/// then the next, back to the first whenever one of the later ones improved the tour
+fn descent(tour: &mut [Node], descents: &[Box<dyn MoveOperator>], metric: &dyn Metric) {
+    let mut k = 0;
+    while k < descents.len() && !cancel::is_cancelled() {
+        if local_search(tour, descents[k].as_ref(), metric) > 0 && k > 0 {
+            k = 0;
+        } else {
+            k += 1;
+        }
+    }
+}
+
+/// the shortest tour `iterations` shakes from the given one found
+pub fn tsp_vns(
+    nodes: &[Node],
+    metric: &dyn Metric,
+    iterations: usize,
+    rng: &mut StdRng,
+) -> Vec<Node> {
+    let neighborhoods = neighborhoods();
+    let descents = &neighborhoods[..3];
+    let mut current = nodes.to_vec();
+    descent(&mut current, descents, metric);
+    let mut length = metric.tour_length(&current);
+    let mut k = 0;
+    for _ in 0..iterations {
+        if cancel::is_cancelled() {
+            break;
+        }
+        let mut candidate = current.clone();
+        neighborhoods[k].random_move(&mut candidate, rng);
+        descent(&mut candidate, descents, metric);
+        let candidate_length = metric.tour_length(&candidate);
+        if candidate_length < length {
+            current = candidate;
+            length = candidate_length;
+            k = 0;
+        } else {
+            k = (k + 1) % neighborhoods.len();
+        }
+    }
+    current
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::metric::Euclidean;
+    use crate::random_nodes;
+    use crate::vns::*;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_shaking_beats_descent() {
+        let nodes = random_nodes(60, &mut StdRng::seed_from_u64(26));
+        let mut descended = nodes.clone();
+        descent(&mut descended, &neighborhoods()[..3], &Euclidean);
+        // a local optimum of all three
+        assert_eq!(local_search(&mut descended, &TwoOpt, &Euclidean), 0);
+        assert_eq!(local_search(&mut descended, &Swap, &Euclidean), 0);
+
+        let tour = tsp_vns(&descended, &Euclidean, 100, &mut StdRng::seed_from_u64(27));
+        let ids: Vec<usize> = tour.iter().map(|n| n.id).collect();
+        assert!(crate::tour::Tour::from_ids(&ids, &nodes).is_ok());
+        assert!(Euclidean.tour_length(&tour) < Euclidean.tour_length(&descended));
+    }
+}